@@ -240,6 +240,15 @@ impl<K: Clone + PartialEq, V> VecMap<K, V> {
         None
     }
 
+    /// Removes the entry inserted longest ago, if there is one
+    pub fn pop_oldest(&mut self) -> Option<(K, V)> {
+        if self.inner.is_empty() {
+            None
+        } else {
+            Some(self.inner.remove(0))
+        }
+    }
+
     pub fn len(&self) -> usize {
         self.inner.len()
     }
@@ -1,7 +1,7 @@
 use std::collections::{HashMap, HashSet};
 
 use abstutil::MultiMap;
-use connectivity::Spot;
+use connectivity::{AccessMode, Spot};
 use geom::Duration;
 use map_gui::tools::draw_isochrone;
 use map_model::{
@@ -63,17 +63,18 @@ impl MovementOptions {
     /// Calculate the quickest time to reach buildings across the map from any of the starting
     /// points, subject to the walking/biking settings configured in these Options.
     pub fn times_from(self, map: &Map, starts: Vec<Spot>) -> HashMap<BuildingID, Duration> {
-        match self {
-            MovementOptions::Walking(opts) => {
-                connectivity::all_walking_costs_from(map, starts, Duration::minutes(15), opts)
-            }
-            MovementOptions::Biking => connectivity::all_vehicle_costs_from(
-                map,
-                starts,
-                Duration::minutes(15),
-                PathConstraints::Bike,
-            ),
-        }
+        let (mode, walking) = match self {
+            MovementOptions::Walking(opts) => (AccessMode::Walk, opts),
+            MovementOptions::Biking => (AccessMode::Bike, connectivity::WalkingOptions::default()),
+        };
+        connectivity::times_from_spots(
+            map,
+            starts,
+            mode,
+            Duration::minutes(15),
+            walking,
+            map.routing_params(),
+        )
     }
 }
 
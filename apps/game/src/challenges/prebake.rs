@@ -33,8 +33,8 @@ pub fn prebake_all() {
         //MapName::seattle("wallingford"),
     ] {
         let map = map_model::Map::load_synchronously(name.path(), &mut timer);
-        let scenario: Scenario =
-            abstio::read_binary(abstio::path_scenario(map.get_name(), "weekday"), &mut timer);
+        let scenario =
+            Scenario::must_load(abstio::path_scenario(map.get_name(), "weekday"), &mut timer);
         summaries.push(prebake(&map, scenario, &mut timer));
     }
 
@@ -45,7 +45,7 @@ pub fn prebake_all() {
             &mut timer,
         );
         for scenario_name in ["base", "go_active", "base_with_bg", "go_active_with_bg"] {
-            let scenario: Scenario = abstio::read_binary(
+            let scenario = Scenario::must_load(
                 abstio::path_scenario(pbury_map.get_name(), scenario_name),
                 &mut timer,
             );
@@ -72,8 +72,8 @@ pub fn prebake_all() {
             MapName::new("br", "sao_paulo", "sao_miguel_paulista").path(),
             &mut timer,
         );
-        let scenario: Scenario =
-            abstio::read_binary(abstio::path_scenario(map.get_name(), "Full"), &mut timer);
+        let scenario =
+            Scenario::must_load(abstio::path_scenario(map.get_name(), "Full"), &mut timer);
         summaries.push(prebake(&map, scenario, &mut timer));
     }
 
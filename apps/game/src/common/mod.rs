@@ -373,7 +373,7 @@ pub fn color_for_agent_type(app: &App, a: AgentType) -> Color {
         AgentType::Bike => app.cs.unzoomed_bike,
         AgentType::Bus | AgentType::Train => app.cs.unzoomed_bus,
         AgentType::TransitRider => app.cs.bus_trip,
-        AgentType::Car => app.cs.unzoomed_car,
+        AgentType::Car | AgentType::CarPassenger => app.cs.unzoomed_car,
     }
 }
 
@@ -385,6 +385,10 @@ pub fn color_for_trip_phase(app: &App, tpt: TripPhaseType) -> Color {
         TripPhaseType::Parking => app.cs.parking_trip,
         TripPhaseType::WaitingForBus(_, _) => app.cs.bus_layer,
         TripPhaseType::RidingBus(_, _, _) => app.cs.bus_trip,
+        TripPhaseType::WaitingForCarpool | TripPhaseType::WaitingForRideHail => {
+            app.cs.unzoomed_pedestrian
+        }
+        TripPhaseType::RidingInCar(_) => app.cs.unzoomed_car,
         TripPhaseType::Cancelled | TripPhaseType::Finished => unreachable!(),
         TripPhaseType::DelayedStart => Color::YELLOW,
     }
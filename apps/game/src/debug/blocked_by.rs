@@ -138,7 +138,9 @@ impl Viewer {
                         AgentID::Pedestrian(p) => format!("p{}", p.0),
                         // There's always that ONE passenger lugging some inappropriate amount of
                         // furniture, somehow causing gridlock, right?
-                        AgentID::BusPassenger(_, c) => format!("c{}", c.id),
+                        AgentID::BusPassenger(_, c) | AgentID::CarPassenger(_, c) => {
+                            format!("c{}", c.id)
+                        }
                     };
                     col.push(
                         ctx.style()
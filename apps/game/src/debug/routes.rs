@@ -6,8 +6,8 @@ use map_gui::colors::ColorSchemeChoice;
 use map_gui::tools::{cmp_count, ColorNetwork};
 use map_gui::{AppLike, ID};
 use map_model::{
-    DirectedRoadID, Direction, PathConstraints, PathRequest, PathStepV2, Pathfinder, RoadID,
-    RoutingParams, NORMAL_LANE_THICKNESS,
+    DirectedRoadID, Direction, JourneyPlanner, Map, PathConstraints, PathRequest, PathStepV2,
+    Pathfinder, Position, RoadID, RoutingParams, NORMAL_LANE_THICKNESS,
 };
 use synthpop::{TripEndpoint, TripMode};
 use widgetry::mapspace::ToggleZoomed;
@@ -25,6 +25,8 @@ pub struct RouteExplorer {
     start: TripEndpoint,
     // (endpoint, confirmed, render the paths to it)
     goal: Option<(TripEndpoint, bool, Drawable)>,
+    // Only built once transit is first explored
+    journey_planner: Option<JourneyPlanner>,
 }
 
 impl RouteExplorer {
@@ -32,6 +34,7 @@ impl RouteExplorer {
         Box::new(RouteExplorer {
             start,
             goal: None,
+            journey_planner: None,
             panel: Panel::new_builder(Widget::col(vec![
                 Widget::row(vec![
                     Line("Route explorer").small_heading().into_widget(ctx),
@@ -44,6 +47,7 @@ impl RouteExplorer {
                     .build_def(ctx),
                 params_to_controls(ctx, TripMode::Bike, app.primary.map.routing_params())
                     .named("params"),
+                Text::new().into_widget(ctx).named("journey"),
             ]))
             .aligned(HorizontalAlignment::Right, VerticalAlignment::Top)
             .build(ctx),
@@ -53,25 +57,36 @@ impl RouteExplorer {
     fn recalc_paths(&mut self, ctx: &mut EventCtx, app: &App) {
         let (mode, params) = controls_to_params(&self.panel);
 
+        let mut summary = Text::new();
         if let Some((ref goal, _, ref mut preview)) = self.goal {
             *preview = Drawable::empty(ctx);
-            if let Some(polygon) = TripEndpoint::path_req(self.start, *goal, mode, &app.primary.map)
-                .and_then(|req| {
-                    Pathfinder::new_dijkstra(
-                        &app.primary.map,
-                        params,
-                        vec![req.constraints],
-                        &mut Timer::throwaway(),
-                    )
-                    .pathfind_v2(req, &app.primary.map)
-                })
-                .and_then(|path| path.into_v1(&app.primary.map).ok())
-                .and_then(|path| path.trace(&app.primary.map))
-                .map(|pl| pl.make_polygons(NORMAL_LANE_THICKNESS))
+            if mode == TripMode::Transit {
+                let map = &app.primary.map;
+                let planner = self
+                    .journey_planner
+                    .get_or_insert_with(|| JourneyPlanner::new(map));
+                let (batch, txt) = draw_journey(app, planner, self.start, *goal);
+                *preview = batch.upload(ctx);
+                summary = txt;
+            } else if let Some(polygon) =
+                TripEndpoint::path_req(self.start, *goal, mode, &app.primary.map)
+                    .and_then(|req| {
+                        Pathfinder::new_dijkstra(
+                            &app.primary.map,
+                            params,
+                            vec![req.constraints],
+                            &mut Timer::throwaway(),
+                        )
+                        .pathfind_v2(req, &app.primary.map)
+                    })
+                    .and_then(|path| path.into_v1(&app.primary.map).ok())
+                    .and_then(|path| path.trace(&app.primary.map))
+                    .map(|pl| pl.make_polygons(NORMAL_LANE_THICKNESS))
             {
                 *preview = GeomBatch::from(vec![(Color::PURPLE, polygon)]).upload(ctx);
             }
         }
+        self.panel.replace(ctx, "journey", summary.into_widget(ctx));
     }
 }
 
@@ -102,6 +117,15 @@ impl State<App> for RouteExplorer {
                     self.panel.replace(ctx, "params", controls);
                     self.recalc_paths(ctx, app);
                 }
+                "transit" => {
+                    let controls = params_to_controls(
+                        ctx,
+                        TripMode::Transit,
+                        app.primary.map.routing_params(),
+                    );
+                    self.panel.replace(ctx, "params", controls);
+                    self.recalc_paths(ctx, app);
+                }
                 "All routes" => {
                     return Transition::Replace(AllRoutesExplorer::new_state(ctx, app));
                 }
@@ -208,6 +232,11 @@ fn params_to_controls(ctx: &mut EventCtx, mode: TripMode, params: &RoutingParams
             .icon("system/assets/meters/pedestrian.svg")
             .disabled(mode == TripMode::Walk)
             .build_widget(ctx, "pedestrians"),
+        ctx.style()
+            .btn_plain
+            .icon("system/assets/meters/bus.svg")
+            .disabled(mode == TripMode::Transit)
+            .build_widget(ctx, "transit"),
     ])
     .evenly_spaced()];
     if mode == TripMode::Drive || mode == TripMode::Bike {
@@ -290,6 +319,9 @@ fn controls_to_params(panel: &Panel) -> (TripMode, RoutingParams) {
     if !panel.is_button_enabled("pedestrians") {
         return (TripMode::Walk, params);
     }
+    if !panel.is_button_enabled("transit") {
+        return (TripMode::Transit, params);
+    }
     params.unprotected_turn_penalty = panel.spinner("unprotected_turn_penalty");
     params.bike_lane_penalty = panel.spinner::<RoundedF64>("bike_lane_penalty").0;
     params.bus_lane_penalty = panel.spinner::<RoundedF64>("bus_lane_penalty").0;
@@ -300,6 +332,85 @@ fn controls_to_params(panel: &Panel) -> (TripMode, RoutingParams) {
     (TripMode::Bike, params)
 }
 
+/// Plan the fastest journey riding transit between two endpoints, leaving now. Walking legs are
+/// drawn in purple and rides in cyan, along with a description of the journey.
+fn draw_journey(
+    app: &App,
+    planner: &JourneyPlanner,
+    from: TripEndpoint,
+    to: TripEndpoint,
+) -> (GeomBatch, Text) {
+    let map = &app.primary.map;
+    let mut batch = GeomBatch::new();
+    let req = match TripEndpoint::path_req(from, to, TripMode::Transit, map) {
+        Some(req) => req,
+        None => {
+            return (batch, Text::from("Can't walk between these"));
+        }
+    };
+    let journey = match planner.plan(
+        map,
+        req.start,
+        req.end,
+        app.primary.sim.time(),
+        PathConstraints::Pedestrian,
+    ) {
+        Some(journey) => journey,
+        None => {
+            draw_walk(&mut batch, req.start, req.end, map);
+            return (batch, Text::from("Walking is faster than riding transit"));
+        }
+    };
+
+    let mut txt = Text::new();
+    let mut walk_from = req.start;
+    for ride in &journey.rides {
+        draw_walk(
+            &mut batch,
+            walk_from,
+            map.get_ts(ride.board).sidewalk_pos,
+            map,
+        );
+        walk_from = map.get_ts(ride.alight).sidewalk_pos;
+
+        let route = map.get_tr(ride.route);
+        txt.add_line(format!(
+            "Ride {} from {} to {}",
+            route.short_name,
+            map.get_ts(ride.board).name,
+            map.get_ts(ride.alight).name
+        ));
+        // Entry i is the path to stop i
+        let board = route.stops.iter().position(|s| *s == ride.board);
+        let alight = board.and_then(|board| {
+            route.stops[board..]
+                .iter()
+                .position(|s| *s == ride.alight)
+                .map(|i| board + i)
+        });
+        if let (Some(board), Some(alight), Ok(paths)) = (board, alight, route.all_paths(map)) {
+            for path in paths.iter().take(alight + 1).skip(board + 1) {
+                if let Some(pl) = path.trace(map) {
+                    batch.push(Color::CYAN, pl.make_polygons(NORMAL_LANE_THICKNESS));
+                }
+            }
+        }
+    }
+    draw_walk(&mut batch, walk_from, req.end, map);
+    txt.add_line(format!("Arrive around {}", journey.arrival.ampm_tostring()));
+    (batch, txt)
+}
+
+fn draw_walk(batch: &mut GeomBatch, start: Position, end: Position, map: &Map) {
+    if let Some(pl) = map
+        .pathfind(PathRequest::walking(start, end))
+        .ok()
+        .and_then(|path| path.trace(map))
+    {
+        batch.push(Color::PURPLE, pl.make_polygons(NORMAL_LANE_THICKNESS));
+    }
+}
+
 /// See how live-tuned routing parameters affect all requests for the current scenario.
 struct AllRoutesExplorer {
     panel: Panel,
@@ -6,6 +6,7 @@ use geom::{LonLat, Percent};
 use map_gui::colors::ColorSchemeChoice;
 use map_gui::tools::{ChooseSomething, CityPicker};
 use map_gui::AppLike;
+use synthpop::Scenario;
 use widgetry::{Choice, EventCtx, Key, Line, Panel, SimpleState, State, Widget};
 
 use crate::app::{App, Transition};
@@ -149,7 +150,7 @@ impl SimpleState<App> for DevToolsMode {
                     app.primary.map.get_name(),
                 ))),
                 Box::new(|s, ctx, app| {
-                    let scenario = abstio::read_binary(
+                    let scenario = Scenario::must_load(
                         abstio::path_scenario(app.primary.map.get_name(), &s),
                         &mut Timer::throwaway(),
                     );
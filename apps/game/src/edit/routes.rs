@@ -1,8 +1,10 @@
 use geom::{Duration, Time};
+use map_gui::tools::PromptInput;
 use map_model::{EditCmd, TransitRouteID};
+use widgetry::tools::PopupMsg;
 use widgetry::{
     EventCtx, GfxCtx, HorizontalAlignment, Key, Line, Outcome, Panel, Spinner, State, TextExt,
-    VerticalAlignment, Widget,
+    Toggle, VerticalAlignment, Widget,
 };
 
 use crate::app::App;
@@ -37,11 +39,27 @@ impl RouteEditor {
                         Duration::minutes(1),
                     ),
                 ]),
-                ctx.style()
-                    .btn_solid_primary
-                    .text("Apply")
-                    .hotkey(Key::Enter)
-                    .build_def(ctx),
+                Toggle::checkbox(
+                    ctx,
+                    "Transit signal priority",
+                    None,
+                    app.primary
+                        .map
+                        .get_edits()
+                        .transit_signal_priority
+                        .contains(&id),
+                ),
+                Widget::row(vec![
+                    ctx.style()
+                        .btn_solid_primary
+                        .text("Apply")
+                        .hotkey(Key::Enter)
+                        .build_def(ctx),
+                    ctx.style()
+                        .btn_outline
+                        .text("Export all routes to GTFS")
+                        .build_def(ctx),
+                ]),
             ]))
             .aligned(HorizontalAlignment::Center, VerticalAlignment::Top)
             .build(ctx),
@@ -74,10 +92,47 @@ impl State<App> for RouteEditor {
                         old: app.primary.map.get_tr(self.route).spawn_times.clone(),
                         new: hourly_times,
                     });
+                    let old = edits.transit_signal_priority.contains(&self.route);
+                    let new = self.panel.is_checked("Transit signal priority");
+                    if old != new {
+                        edits.commands.push(EditCmd::ChangeTransitSignalPriority {
+                            id: self.route,
+                            old,
+                            new,
+                        });
+                    }
                     apply_map_edits(ctx, app, edits);
 
                     return Transition::Pop;
                 }
+                "Export all routes to GTFS" => {
+                    return Transition::Push(PromptInput::new_state(
+                        ctx,
+                        "What time zone is this map in? (like America/Los_Angeles)",
+                        String::new(),
+                        Box::new(|timezone, ctx, app| {
+                            let name = app.primary.map.get_name();
+                            let dir = abstio::path_player(format!(
+                                "gtfs/{}/{}/{}",
+                                name.city.country, name.city.city, name.map
+                            ));
+                            Transition::Replace(
+                                match map_model::gtfs::write(&app.primary.map, &timezone, dir) {
+                                    Ok(dir) => PopupMsg::new_state(
+                                        ctx,
+                                        "GTFS exported",
+                                        vec![format!("Feed written to {}", dir)],
+                                    ),
+                                    Err(err) => PopupMsg::new_state(
+                                        ctx,
+                                        "Export failed",
+                                        vec![err.to_string()],
+                                    ),
+                                },
+                            )
+                        }),
+                    ));
+                }
                 _ => unreachable!(),
             }
         }
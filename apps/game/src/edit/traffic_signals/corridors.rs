@@ -0,0 +1,381 @@
+use std::collections::BTreeSet;
+
+use geom::{Distance, Duration, Polygon, Pt2D, Speed};
+use map_model::{
+    ControlTrafficSignal, CorridorDirection, CorridorLayout, EditCmd, EditIntersection,
+    IntersectionID, SignalCorridor,
+};
+use widgetry::tools::PopupMsg;
+use widgetry::{
+    Choice, Color, Drawable, EventCtx, GeomBatch, GfxCtx, HorizontalAlignment, Key, Line, Panel,
+    RewriteColor, SimpleState, Spinner, State, Text, TextExt, VerticalAlignment, Widget,
+};
+
+use crate::app::{App, Transition};
+use crate::common::CommonState;
+use crate::edit::traffic_signals::fade_irrelevant;
+use crate::edit::{apply_map_edits, speed_limit_choices};
+
+// Dimensions of the time-space diagram, in pixels
+const DIAGRAM_WIDTH: f64 = 600.0;
+const DIAGRAM_HEIGHT: f64 = 300.0;
+const BAR_THICKNESS: f64 = 5.0;
+// Room on the left for labeling each signal
+const LABEL_MARGIN: f64 = 30.0;
+
+/// Coordinate a group of signals along an arterial, choosing a common cycle length and offsets
+/// that produce a green wave. A time-space diagram previews the result.
+pub struct CorridorEditor {
+    corridor: SignalCorridor,
+    labels: Drawable,
+}
+
+impl CorridorEditor {
+    pub fn new_state(
+        ctx: &mut EventCtx,
+        app: &App,
+        members: BTreeSet<IntersectionID>,
+    ) -> Box<dyn State<App>> {
+        let corridor = initial_corridor(app, &members);
+
+        let panel = Panel::new_builder(Widget::col(vec![
+            Widget::row(vec![
+                Line(format!("Coordinating {} signals", members.len()))
+                    .small_heading()
+                    .into_widget(ctx),
+                ctx.style().btn_close_widget(ctx),
+            ]),
+            Widget::row(vec![
+                "Cycle length:".text_widget(ctx).centered_vert(),
+                Spinner::widget(
+                    ctx,
+                    "cycle length",
+                    (Duration::seconds(30.0), Duration::seconds(240.0)),
+                    corridor.cycle_length,
+                    Duration::seconds(5.0),
+                ),
+            ]),
+            Widget::row(vec![
+                "Design speed forwards:".text_widget(ctx).centered_vert(),
+                Widget::dropdown(
+                    ctx,
+                    "design speed forwards",
+                    corridor.design_speed_fwd,
+                    speed_limit_choices(app, Some(corridor.design_speed_fwd)),
+                ),
+            ]),
+            Widget::row(vec![
+                "Design speed backwards:".text_widget(ctx).centered_vert(),
+                Widget::dropdown(
+                    ctx,
+                    "design speed backwards",
+                    corridor.design_speed_back,
+                    speed_limit_choices(app, Some(corridor.design_speed_back)),
+                ),
+            ]),
+            Widget::row(vec![
+                "Green wave favors:".text_widget(ctx).centered_vert(),
+                Widget::dropdown(
+                    ctx,
+                    "favor",
+                    corridor.favor,
+                    vec![
+                        Choice::new("forwards", CorridorDirection::Forwards),
+                        Choice::new("backwards", CorridorDirection::Backwards),
+                        Choice::new("both directions", CorridorDirection::Balanced),
+                    ],
+                ),
+                ctx.style()
+                    .btn_outline
+                    .text("Reverse direction")
+                    .build_def(ctx),
+            ]),
+            draw_diagram(ctx, app, &corridor),
+            ctx.style()
+                .btn_solid_primary
+                .text("Apply")
+                .hotkey(Key::Enter)
+                .build_def(ctx),
+        ]))
+        .aligned(HorizontalAlignment::Right, VerticalAlignment::Top)
+        .build(ctx);
+
+        <dyn SimpleState<_>>::new_state(
+            panel,
+            Box::new(CorridorEditor {
+                labels: draw_labels(ctx, app, &corridor),
+                corridor,
+            }),
+        )
+    }
+}
+
+impl SimpleState<App> for CorridorEditor {
+    fn on_click(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &mut App,
+        x: &str,
+        panel: &mut Panel,
+    ) -> Transition {
+        match x {
+            "close" => Transition::Pop,
+            "Reverse direction" => {
+                self.corridor.signals.reverse();
+                self.labels = draw_labels(ctx, app, &self.corridor);
+                panel.replace(ctx, "diagram", draw_diagram(ctx, app, &self.corridor));
+                Transition::Keep
+            }
+            "Apply" => {
+                let map = &app.primary.map;
+                let signals = match self.corridor.retime(map) {
+                    Ok(signals) => signals,
+                    Err(err) => {
+                        return Transition::Push(PopupMsg::new_state(
+                            ctx,
+                            "Error",
+                            vec![err.to_string()],
+                        ));
+                    }
+                };
+
+                let mut edits = map.get_edits().clone();
+                // A signal can only belong to one corridor
+                edits
+                    .signal_corridors
+                    .retain(|c| !c.signals.iter().any(|i| self.corridor.signals.contains(i)));
+                edits.signal_corridors.push(self.corridor.clone());
+                for signal in signals {
+                    edits.commands.push(EditCmd::ChangeIntersection {
+                        i: signal.id,
+                        old: map.get_i_edit(signal.id),
+                        new: EditIntersection::TrafficSignal(signal.export(map)),
+                    });
+                }
+                apply_map_edits(ctx, app, edits);
+                Transition::Pop
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn panel_changed(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &mut App,
+        panel: &mut Panel,
+    ) -> Option<Transition> {
+        self.corridor.cycle_length = panel.spinner("cycle length");
+        self.corridor.design_speed_fwd = panel.dropdown_value("design speed forwards");
+        self.corridor.design_speed_back = panel.dropdown_value("design speed backwards");
+        self.corridor.favor = panel.dropdown_value("favor");
+        panel.replace(ctx, "diagram", draw_diagram(ctx, app, &self.corridor));
+        None
+    }
+
+    fn other_event(&mut self, ctx: &mut EventCtx, _: &mut App) -> Transition {
+        ctx.canvas_movement();
+        Transition::Keep
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        CommonState::draw_osd(g, app);
+
+        g.redraw(&self.labels);
+    }
+}
+
+/// If these signals were already coordinated, start with that setup. Otherwise guess the order
+/// along the arterial.
+fn initial_corridor(app: &App, members: &BTreeSet<IntersectionID>) -> SignalCorridor {
+    let map = &app.primary.map;
+    for corridor in &map.get_edits().signal_corridors {
+        if corridor.signals.iter().cloned().collect::<BTreeSet<_>>() == *members {
+            return corridor.clone();
+        }
+    }
+
+    // Start from one of the two signals farthest apart, then visit the others by straight-line
+    // distance from it. This works for arterials that don't curve too much.
+    let center = |i: IntersectionID| map.get_i(i).polygon.center();
+    let mut start = *members.iter().next().unwrap();
+    let mut farthest = Distance::ZERO;
+    for i1 in members {
+        for i2 in members {
+            let dist = center(*i1).dist_to(center(*i2));
+            if dist > farthest {
+                farthest = dist;
+                start = *i1;
+            }
+        }
+    }
+    let mut signals: Vec<IntersectionID> = members.iter().cloned().collect();
+    signals.sort_by_key(|i| center(start).dist_to(center(*i)));
+
+    // The longest existing cycle is usually the critical intersection, so use it for everyone
+    let cycle_length = signals
+        .iter()
+        .map(|i| map.get_traffic_signal(*i).simple_cycle_duration())
+        .max()
+        .unwrap()
+        .round_up(Duration::seconds(5.0));
+    SignalCorridor::new(signals, cycle_length, Speed::miles_per_hour(30.0))
+}
+
+/// Number the signals in order along the corridor
+fn draw_labels(ctx: &mut EventCtx, app: &App, corridor: &SignalCorridor) -> Drawable {
+    let mut batch = fade_irrelevant(app, &corridor.signals.iter().cloned().collect());
+    for (idx, i) in corridor.signals.iter().enumerate() {
+        batch.append(
+            Text::from(format!("{}", idx + 1))
+                .bg(Color::PURPLE)
+                .render_autocropped(ctx)
+                .color(RewriteColor::ChangeAlpha(0.8))
+                .scale(0.3)
+                .centered_on(app.primary.map.get_i(*i).polygon.center()),
+        );
+    }
+    ctx.upload(batch)
+}
+
+/// Plot distance along the corridor against time. Each signal gets two bars, showing when through
+/// traffic in each direction has a green. The diagonal bands show a platoon traveling at the design
+/// speed.
+fn draw_diagram(ctx: &mut EventCtx, app: &App, corridor: &SignalCorridor) -> Widget {
+    let map = &app.primary.map;
+    let (layout, signals) = match corridor
+        .layout(map)
+        .and_then(|layout| Ok((layout, corridor.retime(map)?)))
+    {
+        Ok(pair) => pair,
+        Err(err) => {
+            return Text::from(
+                Line(format!("Can't coordinate these signals: {}", err)).fg(Color::RED),
+            )
+            .wrap_to_pct(ctx, 30)
+            .into_widget(ctx)
+            .named("diagram");
+        }
+    };
+
+    let cycle = corridor.cycle_length;
+    let total_dist = *layout.distances.last().unwrap();
+    let travel_fwd = total_dist / corridor.design_speed_fwd;
+    let travel_back = total_dist / corridor.design_speed_back;
+    // Show two full cycles, plus enough time for the last wave to finish
+    let horizon = cycle * 2.0 + travel_fwd.max(travel_back);
+    let to_x = |t: Duration| LABEL_MARGIN + t / horizon * DIAGRAM_WIDTH;
+    // Leave room for the bars at the top and bottom
+    let to_y = |d: Distance| BAR_THICKNESS + DIAGRAM_HEIGHT - d / total_dist * DIAGRAM_HEIGHT;
+
+    let mut batch = GeomBatch::new();
+    batch.push(
+        app.cs.inner_panel_bg,
+        Polygon::rectangle(DIAGRAM_WIDTH, DIAGRAM_HEIGHT + 2.0 * BAR_THICKNESS)
+            .translate(LABEL_MARGIN, 0.0),
+    );
+
+    for (idx, signal) in signals.iter().enumerate() {
+        let y = to_y(layout.distances[idx]);
+        for (forwards, dy) in [(true, -BAR_THICKNESS), (false, 0.0)] {
+            batch.push(
+                app.cs.signal_banned_turn,
+                Polygon::rectangle(DIAGRAM_WIDTH, BAR_THICKNESS).translate(LABEL_MARGIN, y + dy),
+            );
+            for (start, end) in
+                absolute_green_windows(corridor, signal, &layout, idx, forwards, horizon)
+            {
+                if end - start < Duration::seconds(0.1) {
+                    continue;
+                }
+                batch.push(
+                    app.cs.signal_protected_turn,
+                    Polygon::rectangle(to_x(end) - to_x(start), BAR_THICKNESS)
+                        .translate(to_x(start), y + dy),
+                );
+            }
+        }
+        batch.append(
+            Text::from(format!("{}", idx + 1))
+                .render(ctx)
+                .centered_on(Pt2D::new(LABEL_MARGIN / 2.0, y)),
+        );
+    }
+
+    // Draw the bands starting from the first green of each cycle at either end
+    for (forwards, travel_time) in [(true, travel_fwd), (false, travel_back)] {
+        let end_idx = if forwards { 0 } else { signals.len() - 1 };
+        let first_green = match absolute_green_windows(
+            corridor,
+            &signals[end_idx],
+            &layout,
+            end_idx,
+            forwards,
+            cycle,
+        )
+        .into_iter()
+        .map(|(start, _)| start)
+        .min()
+        {
+            Some(t) => t,
+            None => continue,
+        };
+        let (d1, d2) = if forwards {
+            (Distance::ZERO, total_dist)
+        } else {
+            (total_dist, Distance::ZERO)
+        };
+        for start in [first_green, first_green + cycle] {
+            if let Ok(line) = geom::Line::new(
+                Pt2D::new(to_x(start), to_y(d1)),
+                Pt2D::new(to_x(start + travel_time), to_y(d2)),
+            ) {
+                batch.push(
+                    if forwards {
+                        Color::BLUE.alpha(0.8)
+                    } else {
+                        Color::ORANGE.alpha(0.8)
+                    },
+                    line.make_polygons(Distance::meters(2.0)),
+                );
+            }
+        }
+    }
+
+    Widget::col(vec![
+        batch.into_widget(ctx),
+        Text::from_multiline(vec![
+            Line("Blue: green wave traveling forwards").small(),
+            Line("Orange: green wave traveling backwards").small(),
+            Line(format!("Showing {} from midnight", horizon)).small(),
+        ])
+        .into_widget(ctx),
+    ])
+    .named("diagram")
+}
+
+/// Expresses a signal's green windows in time since midnight, up to the horizon.
+fn absolute_green_windows(
+    corridor: &SignalCorridor,
+    signal: &ControlTrafficSignal,
+    layout: &CorridorLayout,
+    idx: usize,
+    forwards: bool,
+    horizon: Duration,
+) -> Vec<(Duration, Duration)> {
+    let cycle = corridor.cycle_length;
+    let mut windows = Vec::new();
+    for (start, end) in corridor.green_windows(signal, layout, idx, forwards) {
+        // At midnight, the signal is already offset into its cycle
+        let mut shift = Duration::ZERO - signal.offset;
+        while shift < horizon {
+            let a = (start + shift).max(Duration::ZERO);
+            let b = (end + shift).min(horizon);
+            if a < b {
+                windows.push((a, b));
+            }
+            shift += cycle;
+        }
+    }
+    windows
+}
@@ -6,7 +6,7 @@ use map_model::{
 use widgetry::tools::PopupMsg;
 use widgetry::{
     Choice, DrawBaselayer, EventCtx, Key, Line, Panel, SimpleState, Spinner, State, Text, TextExt,
-    Widget,
+    Toggle, Widget,
 };
 
 use crate::app::{App, Transition};
@@ -47,18 +47,15 @@ impl ChangeDuration {
                 .secondary()
                 .into_widget(ctx),
             Widget::col(vec![
-                Text::from_all(match signal.stages[idx].stage_type {
-                    StageType::Fixed(_) => vec![
-                        Line("Fixed timing").small_heading(),
-                        Line(" (Adjust both values below to enable variable timing)"),
-                    ],
-                    StageType::Variable(_, _, _) => vec![
-                        Line("Variable timing").small_heading(),
-                        Line(" (Set either values below to 0 to use fixed timing."),
-                    ],
-                })
-                .into_widget(ctx)
-                .named("timing type"),
+                describe_timing(&signal.stages[idx].stage_type)
+                    .into_widget(ctx)
+                    .named("timing type"),
+                Toggle::checkbox(
+                    ctx,
+                    "actuated by detectors and pedestrian call buttons",
+                    None,
+                    matches!(signal.stages[idx].stage_type, StageType::Actuated(_, _, _)),
+                ),
                 Widget::row(vec![
                     "How much additional time can this stage last?"
                         .text_widget(ctx)
@@ -70,6 +67,7 @@ impl ChangeDuration {
                         match signal.stages[idx].stage_type {
                             StageType::Fixed(_) => Duration::ZERO,
                             StageType::Variable(_, _, additional) => additional,
+                            StageType::Actuated(min, max, _) => max - min,
                         },
                         Duration::seconds(1.0),
                     ),
@@ -85,6 +83,7 @@ impl ChangeDuration {
                         match signal.stages[idx].stage_type {
                             StageType::Fixed(_) => Duration::ZERO,
                             StageType::Variable(_, delay, _) => delay,
+                            StageType::Actuated(_, _, passage) => passage,
                         },
                         Duration::seconds(1.0),
                     ),
@@ -118,11 +117,12 @@ impl SimpleState<App> for ChangeDuration {
                 let dt = panel.spinner("duration");
                 let delay = panel.spinner("delay");
                 let additional = panel.spinner("additional");
-                let new_type = if delay == Duration::ZERO || additional == Duration::ZERO {
-                    StageType::Fixed(dt)
-                } else {
-                    StageType::Variable(dt, delay, additional)
-                };
+                let new_type = pick_timing(
+                    dt,
+                    delay,
+                    additional,
+                    panel.is_checked("actuated by detectors and pedestrian call buttons"),
+                );
                 let idx = self.idx;
                 Transition::Multi(vec![
                     Transition::Pop,
@@ -144,21 +144,12 @@ impl SimpleState<App> for ChangeDuration {
         _: &mut App,
         panel: &mut Panel,
     ) -> Option<Transition> {
-        let new_label = Text::from_all(
-            if panel.spinner::<Duration>("delay") == Duration::ZERO
-                || panel.spinner::<Duration>("additional") == Duration::ZERO
-            {
-                vec![
-                    Line("Fixed timing").small_heading(),
-                    Line(" (Adjust both values below to enable variable timing)"),
-                ]
-            } else {
-                vec![
-                    Line("Variable timing").small_heading(),
-                    Line(" (Set either values below to 0 to use fixed timing."),
-                ]
-            },
-        )
+        let new_label = describe_timing(&pick_timing(
+            panel.spinner("duration"),
+            panel.spinner("delay"),
+            panel.spinner("additional"),
+            panel.is_checked("actuated by detectors and pedestrian call buttons"),
+        ))
         .into_widget(ctx);
         panel.replace(ctx, "timing type", new_label);
         None
@@ -176,6 +167,35 @@ impl SimpleState<App> for ChangeDuration {
     }
 }
 
+/// For actuated stages, the delay is the passage time, and the additional time is on top of the
+/// minimum green.
+fn pick_timing(dt: Duration, delay: Duration, additional: Duration, actuated: bool) -> StageType {
+    if delay == Duration::ZERO || additional == Duration::ZERO {
+        StageType::Fixed(dt)
+    } else if actuated {
+        StageType::Actuated(dt, dt + additional, delay)
+    } else {
+        StageType::Variable(dt, delay, additional)
+    }
+}
+
+fn describe_timing(stage_type: &StageType) -> Text {
+    Text::from_all(match stage_type {
+        StageType::Fixed(_) => vec![
+            Line("Fixed timing").small_heading(),
+            Line(" (Adjust both values below to enable variable timing)"),
+        ],
+        StageType::Variable(_, _, _) => vec![
+            Line("Variable timing").small_heading(),
+            Line(" (Set either values below to 0 to use fixed timing."),
+        ],
+        StageType::Actuated(_, _, _) => vec![
+            Line("Actuated timing").small_heading(),
+            Line(" (Set either values below to 0 to use fixed timing."),
+        ],
+    })
+}
+
 pub fn edit_entire_signal(
     ctx: &mut EventCtx,
     app: &App,
@@ -22,6 +22,7 @@ use crate::common::CommonState;
 use crate::edit::{apply_map_edits, ConfirmDiscard};
 use crate::sandbox::GameplayMode;
 
+mod corridors;
 mod edits;
 mod gmns;
 mod offsets;
@@ -228,6 +229,27 @@ impl State<App> for TrafficSignalEditor {
                         self.members.clone(),
                     ));
                 }
+                "Coordinate as a corridor" => {
+                    if let Err(err) = self.validate_all_members(app) {
+                        error!("{}", err);
+                        return Transition::Push(PopupMsg::new_state(
+                            ctx,
+                            "Error",
+                            vec!["This signal configuration is somehow invalid; check the console logs"]
+                        ));
+                    }
+
+                    // Like editing multiple signals, commit the current changes first
+                    let changes = check_for_missing_turns(app, &self.members)
+                        .unwrap_or_else(|| BundleEdits::get_current(app, &self.members));
+                    self.original.apply(app);
+                    changes.commit(ctx, app);
+                    return Transition::Replace(corridors::CorridorEditor::new_state(
+                        ctx,
+                        app,
+                        self.members.clone(),
+                    ));
+                }
                 "Add a new stage" => {
                     self.add_new_edit(ctx, app, num_stages, |ts| {
                         ts.stages.push(Stage::new());
@@ -680,6 +702,9 @@ fn make_side_panel(
                     "Stage duration: {}, {}, {} (variable)",
                     min, delay, additional
                 ),
+                StageType::Actuated(min, max, passage) => {
+                    format!("Stage duration: {}, {}, {} (actuated)", min, max, passage)
+                }
             }
             .text_widget(ctx)
             .centered_vert(),
@@ -721,6 +746,7 @@ fn make_side_panel(
                 match canonical_signal.stages[idx].stage_type {
                     StageType::Fixed(d) => format!("{}", d),
                     StageType::Variable(min, _, _) => format!("{} (v)", min),
+                    StageType::Actuated(min, _, _) => format!("{} (a)", min),
                 },
             )))
             .render(ctx),
@@ -771,11 +797,17 @@ fn make_side_panel(
                 .hotkey(Key::E)
                 .build_def(ctx)
         } else {
-            ctx.style()
-                .btn_outline
-                .text("Tune offsets between signals")
-                .hotkey(Key::O)
-                .build_def(ctx)
+            Widget::row(vec![
+                ctx.style()
+                    .btn_outline
+                    .text("Tune offsets between signals")
+                    .hotkey(Key::O)
+                    .build_def(ctx),
+                ctx.style()
+                    .btn_outline
+                    .text("Coordinate as a corridor")
+                    .build_def(ctx),
+            ])
         },
     ]));
 
@@ -4,11 +4,12 @@ use enumset::EnumSet;
 use maplit::btreeset;
 
 use map_gui::tools::{checkbox_per_mode, intersections_from_roads, ColorDiscrete};
-use map_model::{AccessRestrictions, CommonEndpoint, PathConstraints, RoadID};
+use map_model::{CommonEndpoint, ConditionalAccess, PathConstraints, RoadID, TimeWindow};
 use synthpop::TripMode;
 use widgetry::mapspace::ToggleZoomed;
+use widgetry::tools::PopupMsg;
 use widgetry::{
-    Color, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, Outcome, Panel, State, Text,
+    Color, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, Outcome, Panel, State, Text, TextBox,
     VerticalAlignment, Widget,
 };
 
@@ -35,12 +36,22 @@ impl ZoneEditor {
             // Starting a new zone
             btreeset! { start.id }
         };
-        let allow_through_traffic = start
-            .access_restrictions
-            .allow_through_traffic
+        // The editor only handles one set of conditional restrictions per zone
+        let (allow_through_traffic, windows) =
+            if let Some(c) = start.access_restrictions.conditional.first() {
+                (c.allow_through_traffic, c.windows.clone())
+            } else {
+                (start.access_restrictions.allow_through_traffic, Vec::new())
+            };
+        let allow_through_traffic = allow_through_traffic
             .into_iter()
             .map(TripMode::from_constraints)
             .collect();
+        let hours = windows
+            .iter()
+            .map(|w| w.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
 
         let (draw, legend) = draw_zone(ctx, app, &members);
         let orig_members = members.clone();
@@ -55,6 +66,13 @@ impl ZoneEditor {
                 legend,
                 make_instructions(ctx, &allow_through_traffic).named("instructions"),
                 checkbox_per_mode(ctx, app, &allow_through_traffic),
+                Text::from(
+                    "Only restrict during these hours, like 07:30-09:00, 15:00-16:30. Leave blank \
+                     to restrict all day.",
+                )
+                .wrap_to_pct(ctx, 30)
+                .into_widget(ctx),
+                TextBox::default_widget(ctx, "hours", hours),
                 Widget::custom_row(vec![
                     ctx.style()
                         .btn_solid_primary
@@ -85,14 +103,27 @@ impl State<App> for ZoneEditor {
         match self.panel.event(ctx) {
             Outcome::Clicked(x) => match x.as_ref() {
                 "Apply" => {
+                    let windows = match TimeWindow::parse_all(&self.panel.text_box("hours")) {
+                        Ok(windows) => windows,
+                        Err(err) => {
+                            return Transition::Push(PopupMsg::new_state(
+                                ctx,
+                                "Invalid hours",
+                                vec![err.to_string()],
+                            ));
+                        }
+                    };
+
                     let mut edits = app.primary.map.get_edits().clone();
 
-                    // Roads deleted from the zone
+                    // Roads deleted from the zone. Peak-hour bus lanes aren't part of the zone, so
+                    // leave them alone.
                     for r in self.orig_members.difference(&self.selector.roads) {
                         edits
                             .commands
                             .push(app.primary.map.edit_road_cmd(*r, |new| {
-                                new.access_restrictions = AccessRestrictions::new();
+                                new.access_restrictions.allow_through_traffic = EnumSet::all();
+                                new.access_restrictions.conditional.clear();
                             }));
                     }
 
@@ -104,12 +135,24 @@ impl State<App> for ZoneEditor {
                     // The original allow_through_traffic always includes this, and there's no way
                     // to exclude it, so stay consistent.
                     allow_through_traffic.insert(PathConstraints::Train);
-                    let new_access_restrictions = AccessRestrictions {
-                        allow_through_traffic,
-                    };
+                    // Trucks count as driving
+                    if allow_through_traffic.contains(PathConstraints::Car) {
+                        allow_through_traffic.insert(PathConstraints::Truck);
+                    }
                     for r in &self.selector.roads {
                         let old_access_restrictions =
                             app.primary.map.get_r(*r).access_restrictions.clone();
+                        let mut new_access_restrictions = old_access_restrictions.clone();
+                        if windows.is_empty() {
+                            new_access_restrictions.allow_through_traffic = allow_through_traffic;
+                            new_access_restrictions.conditional.clear();
+                        } else {
+                            new_access_restrictions.allow_through_traffic = EnumSet::all();
+                            new_access_restrictions.conditional = vec![ConditionalAccess {
+                                allow_through_traffic,
+                                windows: windows.clone(),
+                            }];
+                        }
                         if old_access_restrictions != new_access_restrictions {
                             edits
                                 .commands
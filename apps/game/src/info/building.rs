@@ -3,7 +3,7 @@ use std::collections::BTreeMap;
 use geom::{Angle, Circle, Distance, Speed, Time};
 use map_gui::render::DrawPedestrian;
 use map_model::{BuildingID, LaneID, OffstreetParking, Traversable, SIDEWALK_THICKNESS};
-use sim::{DrawPedestrianInput, PedestrianID, PersonID, TripResult, VehicleType};
+use sim::{DrawPedestrianInput, PedestrianID, PersonID, TripResult};
 use synthpop::TripMode;
 use widgetry::{Color, EventCtx, Line, Text, TextExt, Widget};
 
@@ -162,9 +162,9 @@ fn people_body(ctx: &mut EventCtx, app: &App, details: &mut Details, id: Buildin
         let mut has_car = false;
         let mut has_bike = false;
         for vehicle in &person.vehicles {
-            if vehicle.vehicle_type == VehicleType::Car {
+            if vehicle.vehicle_type.is_car_like() {
                 has_car = true;
-            } else if vehicle.vehicle_type == VehicleType::Bike {
+            } else if vehicle.vehicle_type.is_bike_like() {
                 has_bike = true;
             }
         }
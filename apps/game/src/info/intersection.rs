@@ -342,6 +342,13 @@ fn traffic_signal_body(ctx: &mut EventCtx, app: &App, id: IntersectionID) -> Wid
                     delay,
                     additional
                 )),
+                StageType::Actuated(min, max, passage) => Line(format!(
+                    "Stage {}: {}, {}, {} (actuated)",
+                    idx + 1,
+                    min,
+                    max,
+                    passage
+                )),
             }
             .into_widget(ctx),
         );
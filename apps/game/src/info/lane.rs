@@ -1,5 +1,5 @@
 use abstutil::prettyprint_usize;
-use map_model::{LaneID, PathConstraints};
+use map_model::{LaneID, PathConstraints, TimeWindow};
 use widgetry::{EventCtx, Line, LinePlot, PlotOptions, Series, Text, TextExt, Widget};
 
 use crate::app::App;
@@ -37,6 +37,27 @@ fn info_body(ctx: &EventCtx, app: &App, id: LaneID) -> Widget {
         if !ban.is_empty() {
             kv.push(("No through-traffic for", ban.join(", ")));
         }
+        for c in &r.access_restrictions.conditional {
+            let mut ban = Vec::new();
+            for p in PathConstraints::all() {
+                if !c.allow_through_traffic.contains(p) {
+                    ban.push(format!("{:?}", p).to_ascii_lowercase());
+                }
+            }
+            kv.push((
+                "Timed restrictions",
+                format!(
+                    "no through-traffic for {} during {}",
+                    ban.join(", "),
+                    describe_windows(&c.windows)
+                ),
+            ));
+        }
+    }
+    for (dir, windows) in &r.access_restrictions.peak_bus_lanes {
+        if *dir == l.dir && l.is_driving() {
+            kv.push(("Peak-hour bus lane", describe_windows(windows)));
+        }
     }
 
     if l.is_parking() {
@@ -328,3 +349,11 @@ fn header(ctx: &EventCtx, app: &App, details: &mut Details, id: LaneID, tab: Tab
 
     Widget::custom_col(rows)
 }
+
+fn describe_windows(windows: &[TimeWindow]) -> String {
+    windows
+        .iter()
+        .map(|w| w.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -432,7 +432,7 @@ impl InfoPanel {
                     // Some objects are much wider/taller than others
                     let multiplier = match id {
                         ID::Car(c) => {
-                            if c.vehicle_type == VehicleType::Bike {
+                            if c.vehicle_type.is_bike_like() {
                                 3.0
                             } else {
                                 0.75
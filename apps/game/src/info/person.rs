@@ -148,7 +148,7 @@ fn trips_body(
                     match trip.mode {
                         TripMode::Walk => "system/assets/meters/pedestrian.svg",
                         TripMode::Bike => "system/assets/meters/bike.svg",
-                        TripMode::Drive => "system/assets/meters/car.svg",
+                        TripMode::Drive | TripMode::RideHail => "system/assets/meters/car.svg",
                         TripMode::Transit => "system/assets/meters/bus.svg",
                     },
                 )
@@ -342,7 +342,7 @@ fn bio_body(ctx: &mut EventCtx, app: &App, details: &mut Details, id: PersonID)
 
     let mut has_bike = false;
     for v in &person.vehicles {
-        if v.vehicle_type == VehicleType::Bike {
+        if v.vehicle_type.is_bike_like() {
             has_bike = true;
         } else if app.primary.sim.lookup_parked_car(v.id).is_some() {
             rows.push(
@@ -554,17 +554,20 @@ fn parked_car_body(ctx: &mut EventCtx, app: &App, details: &mut Details, id: Car
     // TODO prev trips, next trips, etc
     let mut rows = vec![];
 
-    let p = app.primary.sim.get_owner_of_car(id).unwrap();
-    rows.push(
-        ctx.style()
-            .btn_outline
-            .text(format!("Owned by {}", p))
-            .build_def(ctx),
-    );
-    details.hyperlinks.insert(
-        format!("Owned by {}", p),
-        Tab::PersonTrips(p, BTreeMap::new()),
-    );
+    if let Some(p) = app.primary.sim.get_owner_of_car(id) {
+        rows.push(
+            ctx.style()
+                .btn_outline
+                .text(format!("Owned by {}", p))
+                .build_def(ctx),
+        );
+        details.hyperlinks.insert(
+            format!("Owned by {}", p),
+            Tab::PersonTrips(p, BTreeMap::new()),
+        );
+    } else {
+        rows.push("Part of a ride-hail fleet".text_widget(ctx));
+    }
 
     if let Some(p) = app.primary.sim.lookup_parked_car(id) {
         match p.spot {
@@ -627,10 +630,21 @@ fn header(
                         ("walking", Some("system/assets/meters/pedestrian.svg"))
                     }
                     AgentID::Car(c) => match c.vehicle_type {
-                        VehicleType::Car => ("driving", Some("system/assets/meters/car.svg")),
+                        VehicleType::Car
+                        | VehicleType::Truck
+                        | VehicleType::Van
+                        | VehicleType::Motorcycle => {
+                            ("driving", Some("system/assets/meters/car.svg"))
+                        }
                         VehicleType::Bike => ("biking", Some("system/assets/meters/bike.svg")),
+                        VehicleType::Scooter => {
+                            ("riding a scooter", Some("system/assets/meters/bike.svg"))
+                        }
                         VehicleType::Bus | VehicleType::Train => unreachable!(),
                     },
+                    AgentID::CarPassenger(_, _) => {
+                        ("riding in a car", Some("system/assets/meters/car.svg"))
+                    }
                     AgentID::BusPassenger(_, _) => {
                         ("riding a bus", Some("system/assets/meters/bus.svg"))
                     }
@@ -318,6 +318,44 @@ fn route_body(ctx: &mut EventCtx, app: &App, details: &mut Details, id: TransitR
         rows.push(describe_schedule(route).into_widget(ctx));
     }
 
+    {
+        let now = app.primary.sim.time();
+        let (cnt, total) = app
+            .primary
+            .sim
+            .get_analytics()
+            .transit_signal_delay(id, now);
+        let mut txt = Text::new();
+        if map.get_edits().transit_signal_priority.contains(&id) {
+            txt.add_line("Transit signal priority is enabled");
+            let (extended, truncated) = app
+                .primary
+                .sim
+                .get_analytics()
+                .transit_signal_priority_actions(id, now);
+            txt.add_line(format!(
+                "Signals held a green {} times and cut a red short {} times",
+                prettyprint_usize(extended),
+                prettyprint_usize(truncated)
+            ));
+        }
+        if cnt > 0 {
+            txt.add_line(Line(format!(
+                "Average delay at traffic signals: {}",
+                total / (cnt as f64)
+            )));
+            if app.has_prebaked().is_some() {
+                let (cnt0, total0) = app.prebaked().transit_signal_delay(id, now);
+                if cnt0 > 0 {
+                    txt.append(Line(format!(" (before: {})", total0 / (cnt0 as f64))).secondary());
+                }
+            }
+        }
+        if !txt.is_empty() {
+            rows.push(txt.into_widget(ctx));
+        }
+    }
+
     // Draw the route, label stops, and show location of buses
     {
         let mut colorer = ColorNetwork::new(app);
@@ -401,6 +401,7 @@ fn describe_problems(
         TripMode::Walk => {
             let mut arterial_intersection_crossings = 0;
             let mut overcrowding = 0;
+            let mut jaywalking = 0;
             let empty = Vec::new();
             for (_, problem) in analytics.problems_per_trip.get(&id).unwrap_or(&empty) {
                 match problem {
@@ -410,6 +411,9 @@ fn describe_problems(
                     Problem::PedestrianOvercrowding(_) => {
                         overcrowding += 1;
                     }
+                    Problem::Jaywalking(_) => {
+                        jaywalking += 1;
+                    }
                     _ => {}
                 }
             }
@@ -424,6 +428,11 @@ fn describe_problems(
                 .secondary(),
             ]);
             txt.add_line(Line(format!("{overcrowding} overcrowded sidewalks crossed")).secondary());
+            if jaywalking > 0 {
+                txt.add_line(
+                    Line(format!("{jaywalking} crossings against the signal")).secondary(),
+                );
+            }
 
             Widget::custom_row(vec![
                 Line("Risk Exposure")
@@ -437,6 +446,7 @@ fn describe_problems(
         TripMode::Bike => {
             let mut count_complex_intersections = 0;
             let mut count_overtakes = 0;
+            let mut count_close_passes = 0;
             let empty = Vec::new();
             for (_, problem) in analytics.problems_per_trip.get(&id).unwrap_or(&empty) {
                 match problem {
@@ -446,6 +456,9 @@ fn describe_problems(
                     Problem::OvertakeDesired(_) => {
                         count_overtakes += 1;
                     }
+                    Problem::ClosePass(_) => {
+                        count_close_passes += 1;
+                    }
                     _ => {}
                 }
             }
@@ -468,6 +481,11 @@ fn describe_problems(
                 }
                 .secondary(),
             ]);
+            if count_close_passes > 0 {
+                txt.add_line(
+                    Line(format!("{count_close_passes} close passes by vehicles")).secondary(),
+                );
+            }
 
             Widget::custom_row(vec![
                 Line("Risk Exposure")
@@ -551,7 +569,7 @@ fn draw_problems(
                     (id, *time),
                 ));
             }
-            Problem::OvertakeDesired(on) => {
+            Problem::OvertakeDesired(on) | Problem::ClosePass(on) => {
                 let pt = on.get_polyline(map).middle();
                 details.draw_extra.unzoomed.append(
                     GeomBatch::load_svg(ctx, "system/assets/tools/alert.svg")
@@ -569,7 +587,11 @@ fn draw_problems(
                         Traversable::Lane(l) => map.get_parent(*l).get_thick_polygon(),
                         Traversable::Turn(t) => map.get_i(t.parent).polygon.clone(),
                     },
-                    Text::from("A vehicle wanted to over-take this cyclist near here."),
+                    Text::from(if matches!(problem, Problem::OvertakeDesired(_)) {
+                        "A vehicle wanted to over-take this cyclist near here."
+                    } else {
+                        "A vehicle passed this cyclist too closely near here."
+                    }),
                     (id, *time),
                 ));
             }
@@ -597,7 +619,7 @@ fn draw_problems(
                     (id, *time)
                 ));
             }
-            Problem::PedestrianOvercrowding(on) => {
+            Problem::PedestrianOvercrowding(on) | Problem::BlockedByDelivery(on) => {
                 let pt = on.get_polyline(map).middle();
                 details.draw_extra.unzoomed.append(
                     GeomBatch::load_svg(ctx, "system/assets/tools/alert.svg")
@@ -615,7 +637,34 @@ fn draw_problems(
                         Traversable::Lane(l) => map.get_parent(*l).get_thick_polygon(),
                         Traversable::Turn(t) => map.get_i(t.parent).polygon.clone(),
                     },
-                    Text::from("Too many pedestrians are crowded together here."),
+                    Text::from(if matches!(problem, Problem::PedestrianOvercrowding(_)) {
+                        "Too many pedestrians are crowded together here."
+                    } else {
+                        "This vehicle was stuck behind a delivery unloading here."
+                    }),
+                    (id, *time),
+                ));
+            }
+            Problem::Jaywalking(t) | Problem::JaywalkerConflict(t) => {
+                let geom = map.get_t(*t).geom.make_polygons(Distance::meters(10.0));
+                details.draw_extra.unzoomed.append(
+                    GeomBatch::load_svg(ctx, "system/assets/tools/alert.svg")
+                        .centered_on(geom.center())
+                        .color(RewriteColor::ChangeAlpha(0.8)),
+                );
+                details.draw_extra.zoomed.append(
+                    GeomBatch::load_svg(ctx, "system/assets/tools/alert.svg")
+                        .scale(0.5)
+                        .color(RewriteColor::ChangeAlpha(0.5))
+                        .centered_on(geom.center()),
+                );
+                details.tooltips.push((
+                    geom,
+                    Text::from(if matches!(problem, Problem::Jaywalking(_)) {
+                        "This pedestrian crossed against the signal here."
+                    } else {
+                        "A vehicle had to stop for this pedestrian crossing against the signal."
+                    }),
                     (id, *time),
                 ));
             }
@@ -721,6 +770,10 @@ fn make_timeline(
                         "system/assets/timeline/waiting_for_bus.svg"
                     }
                     TripPhaseType::RidingBus(_, _, _) => "system/assets/timeline/riding_bus.svg",
+                    TripPhaseType::WaitingForCarpool | TripPhaseType::WaitingForRideHail => {
+                        "system/assets/timeline/waiting_for_bus.svg"
+                    }
+                    TripPhaseType::RidingInCar(_) => "system/assets/timeline/driving.svg",
                     TripPhaseType::Cancelled | TripPhaseType::Finished => unreachable!(),
                     TripPhaseType::DelayedStart => "system/assets/timeline/delayed_start.svg",
                 },
@@ -0,0 +1,130 @@
+use abstutil::Counter;
+use geom::Time;
+use map_gui::tools::{ColorLegend, ColorNetwork};
+use map_gui::ID;
+use sim::{Emissions, Pollutant};
+use widgetry::mapspace::ToggleZoomed;
+use widgetry::{Choice, EventCtx, GfxCtx, Line, Outcome, Panel, Text, TextExt, Widget};
+
+use crate::app::App;
+use crate::layer::{header, Layer, LayerOutcome, PANEL_PLACEMENT};
+
+// Shows where vehicles have used the most energy or emitted the most of some pollutant.
+pub struct EmissionsMap {
+    time: Time,
+    pollutant: Pollutant,
+    tooltip: Option<Text>,
+    draw: ToggleZoomed,
+    panel: Panel,
+}
+
+impl Layer for EmissionsMap {
+    fn name(&self) -> Option<&'static str> {
+        Some("emissions")
+    }
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Option<LayerOutcome> {
+        let mut recalc_tooltip = false;
+        if app.primary.sim.time() != self.time {
+            *self = EmissionsMap::new(ctx, app, self.pollutant);
+            recalc_tooltip = true;
+        }
+
+        if ctx.canvas.is_unzoomed() {
+            if ctx.redo_mouseover() || recalc_tooltip {
+                let analytics = app.primary.sim.get_analytics();
+                self.tooltip = match app.mouseover_unzoomed_roads_and_intersections(ctx) {
+                    Some(ID::Road(r)) => analytics.road_emissions.get(&r),
+                    Some(ID::Intersection(i)) => analytics.intersection_emissions.get(&i),
+                    _ => None,
+                }
+                .map(|emissions| {
+                    Text::from(describe(emissions.get(self.pollutant), self.pollutant))
+                });
+            }
+        } else {
+            self.tooltip = None;
+        }
+
+        match self.panel.event(ctx) {
+            Outcome::Clicked(x) => match x.as_ref() {
+                "close" => {
+                    return Some(LayerOutcome::Close);
+                }
+                _ => unreachable!(),
+            },
+            Outcome::Changed(_) => {
+                *self = EmissionsMap::new(ctx, app, self.panel.dropdown_value("pollutant"));
+            }
+            _ => {}
+        }
+        None
+    }
+    fn draw(&self, g: &mut GfxCtx, _: &App) {
+        self.panel.draw(g);
+        self.draw.draw(g);
+        if let Some(ref txt) = self.tooltip {
+            g.draw_mouse_tooltip(txt.clone());
+        }
+    }
+    fn draw_minimap(&self, g: &mut GfxCtx) {
+        g.redraw(&self.draw.unzoomed);
+    }
+}
+
+impl EmissionsMap {
+    pub fn new(ctx: &mut EventCtx, app: &App, pollutant: Pollutant) -> EmissionsMap {
+        let analytics = app.primary.sim.get_analytics();
+        // Counters need whole numbers, so use thousandths of the unit. Electric vehicles going
+        // downhill may recover energy, but just show that as nothing.
+        let count = |emissions: &Emissions| (emissions.get(pollutant) * 1000.0).round() as usize;
+        let mut roads = Counter::new();
+        for (r, emissions) in &analytics.road_emissions {
+            if count(emissions) > 0 {
+                roads.add(*r, count(emissions));
+            }
+        }
+        let mut intersections = Counter::new();
+        for (i, emissions) in &analytics.intersection_emissions {
+            if count(emissions) > 0 {
+                intersections.add(*i, count(emissions));
+            }
+        }
+        let total = app.primary.sim.emissions_summary().total.get(pollutant);
+
+        let panel = Panel::new_builder(Widget::col(vec![
+            header(ctx, "Emissions"),
+            Text::from(Line("This counts all vehicles since midnight").secondary())
+                .wrap_to_pct(ctx, 15)
+                .into_widget(ctx),
+            Widget::dropdown(
+                ctx,
+                "pollutant",
+                pollutant,
+                Pollutant::all()
+                    .into_iter()
+                    .map(|p| Choice::new(p.name(), p))
+                    .collect(),
+            ),
+            format!("Total: {}", describe(total, pollutant)).text_widget(ctx),
+            ColorLegend::gradient(ctx, &app.cs.good_to_bad_red, vec!["0", "highest"]),
+        ]))
+        .aligned_pair(PANEL_PLACEMENT)
+        .build(ctx);
+
+        let mut colorer = ColorNetwork::new(app);
+        colorer.pct_roads(roads, &app.cs.good_to_bad_red);
+        colorer.pct_intersections(intersections, &app.cs.good_to_bad_red);
+
+        EmissionsMap {
+            time: app.primary.sim.time(),
+            pollutant,
+            tooltip: None,
+            draw: colorer.build(ctx),
+            panel,
+        }
+    }
+}
+
+fn describe(amount: f64, pollutant: Pollutant) -> String {
+    format!("{:.1} {} of {}", amount, pollutant.unit(), pollutant.name())
+}
@@ -1,5 +1,5 @@
 use map_gui::tools::{grey_out_map, HeatmapOptions};
-use sim::AgentType;
+use sim::{AgentType, Pollutant};
 use widgetry::{
     DrawBaselayer, EventCtx, GfxCtx, HorizontalAlignment, Image, Key, Line, Outcome, Panel, State,
     TextExt, VerticalAlignment, Widget,
@@ -9,6 +9,7 @@ use crate::app::{App, Transition};
 use crate::sandbox::dashboards;
 
 pub mod elevation;
+mod emissions;
 pub mod favorites;
 pub mod map;
 mod pandemic;
@@ -110,6 +111,11 @@ impl PickLayer {
                     btn("traffic jams", Key::J),
                     btn("cycling activity", Key::B),
                     btn("pedestrian crowding", Key::C),
+                    if app.primary.sim.is_estimating_emissions() {
+                        btn("emissions", Key::I)
+                    } else {
+                        Widget::nothing()
+                    },
                 ]),
                 Widget::col(vec![
                     "Map".text_widget(ctx),
@@ -185,6 +191,13 @@ impl State<App> for PickLayer {
                 "steep streets" => {
                     app.primary.layer = Some(Box::new(elevation::SteepStreets::new(ctx, app)));
                 }
+                "emissions" => {
+                    app.primary.layer = Some(Box::new(emissions::EmissionsMap::new(
+                        ctx,
+                        app,
+                        Pollutant::CO2,
+                    )));
+                }
                 "elevation" => {
                     app.primary.layer = Some(Box::new(elevation::ElevationContours::new(ctx, app)));
                 }
@@ -85,10 +85,7 @@ impl Occupancy {
         let mut has_car = 0;
         for p in app.primary.sim.get_all_people() {
             total_ppl += 1;
-            if p.vehicles
-                .iter()
-                .any(|v| v.vehicle_type == VehicleType::Car)
-            {
+            if p.vehicles.iter().any(|v| v.vehicle_type.is_car_like()) {
                 has_car += 1;
             }
         }
@@ -57,6 +57,20 @@ impl Layer for ProblemMap {
                         },
                     )));
                 }
+                "Export safety conflicts" => {
+                    return Some(LayerOutcome::Transition(Transition::Push(
+                        match export_safety_conflicts(app) {
+                            Ok(path) => PopupMsg::new_state(
+                                ctx,
+                                "Data exported",
+                                vec![format!("Data exported to {path}")],
+                            ),
+                            Err(err) => {
+                                PopupMsg::new_state(ctx, "Export failed", vec![err.to_string()])
+                            }
+                        },
+                    )));
+                }
                 _ => unreachable!(),
             },
             Outcome::Changed(x) => {
@@ -232,7 +246,13 @@ fn make_controls(
         col.push(Line("Heatmap Options").small_heading().into_widget(ctx));
         col.extend(o.to_controls(ctx, legend.unwrap()));
     }
-    col.push(ctx.style().btn_plain.text("Export to CSV").build_def(ctx));
+    col.push(Widget::row(vec![
+        ctx.style().btn_plain.text("Export to CSV").build_def(ctx),
+        ctx.style()
+            .btn_plain
+            .text("Export safety conflicts")
+            .build_def(ctx),
+    ]));
 
     Panel::new_builder(Widget::col(col))
         .aligned_pair(PANEL_PLACEMENT)
@@ -260,11 +280,16 @@ fn export_raw_problems(app: &App) -> Result<String> {
                 Problem::IntersectionDelay(i, _) | Problem::ComplexIntersectionCrossing(i) => {
                     map.get_i(*i).orig_id.to_string()
                 }
-                Problem::OvertakeDesired(on) | Problem::PedestrianOvercrowding(on) => match on {
+                Problem::OvertakeDesired(on)
+                | Problem::ClosePass(on)
+                | Problem::PedestrianOvercrowding(on)
+                | Problem::BlockedByDelivery(on) => match on {
                     Traversable::Lane(l) => map.get_r(l.road).orig_id.to_string(),
                     Traversable::Turn(t) => map.get_i(t.parent).orig_id.to_string(),
                 },
-                Problem::ArterialIntersectionCrossing(t) => map.get_i(t.parent).orig_id.to_string(),
+                Problem::ArterialIntersectionCrossing(t)
+                | Problem::Jaywalking(t)
+                | Problem::JaywalkerConflict(t) => map.get_i(t.parent).orig_id.to_string(),
             };
             writeln!(
                 out,
@@ -281,3 +306,17 @@ fn export_raw_problems(app: &App) -> Result<String> {
 
     abstio::write_file(path, out)
 }
+
+/// Near-misses measured so far, in a format comparable to real collision data.
+fn export_safety_conflicts(app: &App) -> Result<String> {
+    let map = &app.primary.map;
+    let path = format!(
+        "safety_conflicts_{}_{}.json",
+        map.get_name().as_filename(),
+        app.primary.sim.time().as_filename()
+    );
+    abstio::write_file(
+        path,
+        abstutil::to_json(&app.primary.sim.safety_conflict_dataset(map)),
+    )
+}
@@ -121,17 +121,20 @@ impl RelativeProblemMap {
                     Problem::IntersectionDelay(i, _) | Problem::ComplexIntersectionCrossing(i) => {
                         intersections.inc(*i);
                     }
-                    Problem::OvertakeDesired(on) | Problem::PedestrianOvercrowding(on) => {
-                        match on {
-                            Traversable::Lane(l) => {
-                                roads.inc(l.road);
-                            }
-                            Traversable::Turn(t) => {
-                                intersections.inc(t.parent);
-                            }
+                    Problem::OvertakeDesired(on)
+                    | Problem::ClosePass(on)
+                    | Problem::PedestrianOvercrowding(on)
+                    | Problem::BlockedByDelivery(on) => match on {
+                        Traversable::Lane(l) => {
+                            roads.inc(l.road);
                         }
-                    }
-                    Problem::ArterialIntersectionCrossing(t) => {
+                        Traversable::Turn(t) => {
+                            intersections.inc(t.parent);
+                        }
+                    },
+                    Problem::ArterialIntersectionCrossing(t)
+                    | Problem::Jaywalking(t)
+                    | Problem::JaywalkerConflict(t) => {
                         intersections.inc(t.parent);
                     }
                 }
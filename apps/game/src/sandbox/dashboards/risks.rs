@@ -134,6 +134,18 @@ impl RiskSummaries {
                             ),
                         ])
                         .section(ctx),
+                        Widget::col(vec![
+                            Line("Cyclists passed too closely")
+                                .small_heading()
+                                .into_widget(ctx)
+                                .centered_horiz(),
+                            problem_matrix(
+                                ctx,
+                                app,
+                                bike_filter.trip_problems(app, ProblemType::ClosePass),
+                            ),
+                        ])
+                        .section(ctx),
                     ],
                 )
                 .margin_above(30),
@@ -197,7 +197,13 @@ impl GameplayMode {
                         return false;
                     }
                 }
-                EditCmd::ChangeRouteSchedule { .. } => {}
+                EditCmd::ChangeRouteSchedule { .. }
+                | EditCmd::ChangeTransitSignalPriority { .. } => {}
+                EditCmd::ChangeDiagonalFilter { .. } => {
+                    if !self.can_edit_roads() {
+                        return false;
+                    }
+                }
             }
         }
         true
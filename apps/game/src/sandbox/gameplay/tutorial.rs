@@ -1326,7 +1326,7 @@ pub fn execute(ctx: &mut EventCtx, app: &mut App, id: ID, action: &str) -> Trans
                         ],
                     )
                 }
-            } else if c.vehicle_type == VehicleType::Bike {
+            } else if c.vehicle_type.is_bike_like() {
                 PopupMsg::new_state(
                     ctx,
                     "That's a bike",
@@ -10,7 +10,7 @@ use map_gui::tools::{ChooseSomething, Minimap, TurnExplorer};
 use map_gui::{AppLike, ID};
 use sim::Analytics;
 use synthpop::Scenario;
-use widgetry::tools::{FileLoader, FutureLoader, URLManager};
+use widgetry::tools::{FileLoader, FutureLoader, RawBytes, URLManager};
 use widgetry::{lctrl, Choice, EventCtx, GfxCtx, Key, Outcome, Panel, State, UpdateType};
 
 pub use self::gameplay::{spawn_agents_around, GameplayMode, TutorialPointer, TutorialState};
@@ -525,12 +525,13 @@ impl State<App> for SandboxLoader {
                                 }
                             }
 
-                            return Transition::Push(FileLoader::<App, Scenario>::new_state(
+                            return Transition::Push(FileLoader::<App, RawBytes>::new_state(
                                 ctx,
                                 path,
-                                Box::new(|_, _, _, scenario| {
+                                Box::new(|_, _, _, bytes| {
                                     // TODO Handle corrupt files
-                                    let scenario = scenario.unwrap();
+                                    let scenario =
+                                        Scenario::from_binary(&bytes.unwrap().0).unwrap();
                                     Transition::Multi(vec![
                                         Transition::Pop,
                                         Transition::ModifyState(Box::new(|state, _, _| {
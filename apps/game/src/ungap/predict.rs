@@ -8,7 +8,7 @@ use map_gui::ID;
 use map_model::{PathRequest, PathStepV2, RoadID};
 use synthpop::{Scenario, TripEndpoint, TripMode};
 use widgetry::mapspace::ToggleZoomed;
-use widgetry::tools::{open_browser, FileLoader};
+use widgetry::tools::{open_browser, FileLoader, RawBytes};
 use widgetry::{EventCtx, GfxCtx, Line, Outcome, Panel, Spinner, State, Text, TextExt, Widget};
 
 use crate::app::{App, Transition};
@@ -68,12 +68,12 @@ impl State<App> for ShowGaps {
                     let change_key = app.primary.map.get_edits_change_key();
                     let map_name = app.primary.map.get_name().clone();
                     let scenario_name = Scenario::default_scenario_for_map(&map_name);
-                    return Transition::Push(FileLoader::<App, Scenario>::new_state(
+                    return Transition::Push(FileLoader::<App, RawBytes>::new_state(
                         ctx,
                         abstio::path_scenario(&map_name, &scenario_name),
-                        Box::new(move |ctx, app, timer, maybe_scenario| {
+                        Box::new(move |ctx, app, timer, maybe_bytes| {
                             // TODO Handle corrupt files
-                            let scenario = maybe_scenario.unwrap();
+                            let scenario = Scenario::from_binary(&maybe_bytes.unwrap().0).unwrap();
                             let data = ModeShiftData::from_scenario(ctx, app, scenario, timer);
                             app.session.mode_shift.set((map_name, change_key), data);
 
@@ -5,7 +5,7 @@ use map_model::{IntersectionID, Perimeter};
 use widgetry::tools::PolyLineLasso;
 use widgetry::{DrawBaselayer, EventCtx, GfxCtx, Key, Line, ScreenPt, State, Text, Widget};
 
-use crate::{after_edit, App, DiagonalFilter, Neighbourhood, Transition};
+use crate::{after_edit, App, Neighbourhood, Transition};
 
 pub struct FreehandFilters {
     lasso: PolyLineLasso,
@@ -67,7 +67,7 @@ impl FreehandFilters {
         for i in &self.interior_intersections {
             if app.map.get_i(*i).polygon.intersects_polyline(&path) {
                 // We probably won't guess the right one, but make an attempt
-                DiagonalFilter::cycle_through_alternatives(app, *i);
+                crate::filters::cycle_through_alternatives(app, *i);
             }
         }
         after_edit(ctx, app);
@@ -40,6 +40,12 @@ impl TopPanel {
                         .build_def(ctx)
                         .centered_vert()
                         .hide(consultation),
+                    ctx.style()
+                        .btn_plain
+                        .text("Simulate in A/B Street")
+                        .build_def(ctx)
+                        .centered_vert()
+                        .hide(consultation),
                     ctx.style()
                         .btn_plain
                         .icon("system/assets/tools/search.svg")
@@ -113,6 +119,18 @@ impl TopPanel {
                         }
                     }))
                 }
+                "Simulate in A/B Street" => {
+                    let name = crate::export::write_map_edits(app);
+                    Some(Transition::Push(PopupMsg::new_state(
+                        ctx,
+                        "Map edits saved",
+                        vec![
+                            format!("The modal filters were saved as map edits called \"{}\"", name),
+                            "Load these edits in the A/B Street game to simulate traffic with them."
+                                .to_string(),
+                        ],
+                    )))
+                }
                 _ => unreachable!(),
             }
         } else {
@@ -5,7 +5,7 @@ use widgetry::{lctrl, EventCtx, Image, Key, Line, Text, TextExt, Widget};
 
 use super::{EditOutcome, Obj};
 use crate::shortcuts::Shortcuts;
-use crate::{after_edit, colors, App, DiagonalFilter, Neighbourhood};
+use crate::{after_edit, colors, App, Neighbourhood};
 
 pub fn widget(ctx: &mut EventCtx, app: &App) -> Widget {
     Widget::col(vec![
@@ -119,7 +119,7 @@ pub fn handle_world_outcome(
         }
         WorldOutcome::ClickedObject(Obj::InteriorIntersection(i)) => {
             app.session.modal_filters.before_edit();
-            DiagonalFilter::cycle_through_alternatives(app, i);
+            crate::filters::cycle_through_alternatives(app, i);
             after_edit(ctx, app);
             EditOutcome::Recalculate
         }
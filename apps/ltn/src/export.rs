@@ -12,6 +12,24 @@ pub fn write_geojson_file(ctx: &EventCtx, app: &App) -> Result<String> {
     abstio::write_file(path, contents)
 }
 
+/// Saves the modal filters as map edits, so the main A/B Street game can simulate them. Returns
+/// the name of the edits.
+pub fn write_map_edits(app: &App) -> String {
+    let mut edits = app.session.modal_filters.to_map_edits(&app.map);
+    edits.edits_name = format!(
+        "LTN {}",
+        app.session
+            .proposal_name
+            .as_ref()
+            .unwrap_or(&String::from("existing LTNs"))
+    );
+    abstio::write_json(
+        abstio::path_edits(app.map.get_name(), &edits.edits_name),
+        &edits.to_permanent(&app.map),
+    );
+    edits.edits_name
+}
+
 fn geojson_string(ctx: &EventCtx, app: &App) -> Result<String> {
     use geo::MapCoordsInPlace;
     use geojson::{Feature, FeatureCollection, GeoJson, Geometry, Value};
@@ -1,13 +1,16 @@
 pub mod auto;
 mod existing;
 
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::BTreeMap;
 
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_btreemap, serialize_btreemap};
 use geom::{Circle, Distance, Line};
-use map_model::{IntersectionID, Map, PathConstraints, RoadID, RoutingParams, TurnID};
+use map_model::{
+    DiagonalFilter, EditCmd, FilterType, IntersectionID, Map, MapEdits, PathConstraints,
+    RoadFilter, RoadID, RoutingParams, TurnID,
+};
 use widgetry::mapspace::{DrawUnzoomedShapes, ToggleZoomed};
 use widgetry::{EventCtx, GeomBatch, GfxCtx};
 
@@ -42,23 +45,6 @@ pub struct ChangeKey {
     intersections: BTreeMap<IntersectionID, DiagonalFilter>,
 }
 
-/// A diagonal filter exists in an intersection. It's defined by two roads (the order is
-/// arbitrary). When all of the intersection's roads are sorted in clockwise order, this pair of
-/// roads splits the ordering into two groups. Turns in each group are still possible, but not
-/// across groups.
-///
-/// TODO Be careful with PartialEq! At a 4-way intersection, the same filter can be expressed as a
-/// different pair of two roads. And the (r1, r2) ordering is also arbitrary.
-#[derive(Clone, PartialEq, Serialize, Deserialize)]
-pub struct DiagonalFilter {
-    r1: RoadID,
-    r2: RoadID,
-    i: IntersectionID,
-
-    group1: BTreeSet<RoadID>,
-    group2: BTreeSet<RoadID>,
-}
-
 impl ModalFilters {
     /// Call before making any changes to preserve edit history
     pub fn before_edit(&mut self) {
@@ -91,6 +77,28 @@ impl ModalFilters {
         }
     }
 
+    /// Express these filters as map edits, so the main A/B Street game can load and simulate them.
+    /// Filters along roads are treated as bollards.
+    pub fn to_map_edits(&self, map: &Map) -> MapEdits {
+        let mut edits = map.new_edits();
+        for (r, dist) in &self.roads {
+            edits.commands.push(map.edit_road_cmd(*r, |new| {
+                new.modal_filter = Some(RoadFilter {
+                    dist: *dist,
+                    filter_type: FilterType::WalkCycleOnly,
+                });
+            }));
+        }
+        for (i, filter) in &self.intersections {
+            edits.commands.push(EditCmd::ChangeDiagonalFilter {
+                i: *i,
+                old: map.get_i(*i).modal_filter.clone(),
+                new: Some(filter.clone()),
+            });
+        }
+        edits
+    }
+
     pub fn allows_turn(&self, t: TurnID) -> bool {
         if let Some(filter) = self.intersections.get(&t.parent) {
             return filter.allows_turn(t.src.road, t.dst.road);
@@ -196,126 +204,66 @@ impl ModalFilters {
     }
 }
 
-impl DiagonalFilter {
-    /// The caller must call this in a `before_edit` / `after_edit` "transaction."
-    pub fn cycle_through_alternatives(app: &mut App, i: IntersectionID) {
-        let map = &app.map;
-        let mut roads = map.get_i(i).get_roads_sorted_by_incoming_angle(map);
-
-        if roads.len() == 4 {
-            // 4-way intersections are the only place where true diagonal filters can be placed
-            let alt1 = DiagonalFilter::new(map, i, roads[0], roads[1]);
-            let alt2 = DiagonalFilter::new(map, i, roads[1], roads[2]);
-
-            match app.session.modal_filters.intersections.get(&i) {
-                Some(prev) => {
-                    if prev == &alt1 {
-                        app.session.modal_filters.intersections.insert(i, alt2);
-                    } else if prev == &alt2 {
-                        app.session.modal_filters.intersections.remove(&i);
-                    } else {
-                        unreachable!()
-                    }
-                }
-                None => {
-                    app.session.modal_filters.intersections.insert(i, alt1);
-                }
-            }
-        } else if roads.len() > 1 {
-            // Diagonal filters elsewhere don't really make sense. They're equivalent to filtering
-            // one road. Just cycle through those.
-
-            // But skip roads that're aren't filterable
-            roads.retain(|r| {
-                let road = app.map.get_r(*r);
-                // Include non-driveable roads in this check, since we haven't filtered those out yet
-                road.oneway_for_driving().is_none()
-                    && !road.is_deadend_for_driving(&app.map)
-                    && PathConstraints::Car.can_use_road(road, &app.map)
-            });
-
-            let mut add_filter_to = None;
-            if let Some(idx) = roads
-                .iter()
-                .position(|r| app.session.modal_filters.roads.contains_key(r))
-            {
-                app.session.modal_filters.roads.remove(&roads[idx]);
-                if idx != roads.len() - 1 {
-                    add_filter_to = Some(roads[idx + 1]);
+/// The caller must call this in a `before_edit` / `after_edit` "transaction."
+pub fn cycle_through_alternatives(app: &mut App, i: IntersectionID) {
+    let map = &app.map;
+    let mut roads = map.get_i(i).get_roads_sorted_by_incoming_angle(map);
+
+    if roads.len() == 4 {
+        // 4-way intersections are the only place where true diagonal filters can be placed
+        let alt1 =
+            DiagonalFilter::new(map, i, roads[0], roads[1], FilterType::WalkCycleOnly).unwrap();
+        let alt2 =
+            DiagonalFilter::new(map, i, roads[1], roads[2], FilterType::WalkCycleOnly).unwrap();
+
+        match app.session.modal_filters.intersections.get(&i) {
+            Some(prev) => {
+                if prev == &alt1 {
+                    app.session.modal_filters.intersections.insert(i, alt2);
+                } else if prev == &alt2 {
+                    app.session.modal_filters.intersections.remove(&i);
+                } else {
+                    unreachable!()
                 }
-            } else {
-                add_filter_to = Some(roads[0]);
             }
-            if let Some(r) = add_filter_to {
-                let road = map.get_r(r);
-                let dist = if i == road.src_i {
-                    Distance::ZERO
-                } else {
-                    road.length()
-                };
-                app.session.modal_filters.roads.insert(r, dist);
+            None => {
+                app.session.modal_filters.intersections.insert(i, alt1);
             }
         }
-    }
-
-    fn new(map: &Map, i: IntersectionID, r1: RoadID, r2: RoadID) -> DiagonalFilter {
-        let mut roads = map.get_i(i).get_roads_sorted_by_incoming_angle(map);
-        // Make self.r1 be the first entry
-        while roads[0] != r1 {
-            roads.rotate_right(1);
-        }
-
-        let mut group1 = BTreeSet::new();
-        group1.insert(roads.remove(0));
-        loop {
-            let next = roads.remove(0);
-            group1.insert(next);
-            if next == r2 {
-                break;
+    } else if roads.len() > 1 {
+        // Diagonal filters elsewhere don't really make sense. They're equivalent to filtering
+        // one road. Just cycle through those.
+
+        // But skip roads that're aren't filterable
+        roads.retain(|r| {
+            let road = app.map.get_r(*r);
+            // Include non-driveable roads in this check, since we haven't filtered those out yet
+            road.oneway_for_driving().is_none()
+                && !road.is_deadend_for_driving(&app.map)
+                && PathConstraints::Car.can_use_road(road, &app.map)
+        });
+
+        let mut add_filter_to = None;
+        if let Some(idx) = roads
+            .iter()
+            .position(|r| app.session.modal_filters.roads.contains_key(r))
+        {
+            app.session.modal_filters.roads.remove(&roads[idx]);
+            if idx != roads.len() - 1 {
+                add_filter_to = Some(roads[idx + 1]);
             }
+        } else {
+            add_filter_to = Some(roads[0]);
         }
-        // This is only true for 4-ways...
-        assert_eq!(group1.len(), 2);
-        assert_eq!(roads.len(), 2);
-
-        DiagonalFilter {
-            r1,
-            r2,
-            i,
-            group1,
-            group2: roads.into_iter().collect(),
-        }
-    }
-
-    /// Physically where is the filter placed?
-    pub fn geometry(&self, map: &Map) -> Line {
-        let r1 = map.get_r(self.r1);
-        let r2 = map.get_r(self.r2);
-
-        // Orient the road to face the intersection
-        let pl1 = r1.center_pts.maybe_reverse(r1.src_i == self.i);
-        let pl2 = r2.center_pts.maybe_reverse(r2.src_i == self.i);
-
-        // The other combinations of left/right here would produce points or a line across just one
-        // road
-        let pt1 = pl1.must_shift_right(r1.get_half_width()).last_pt();
-        let pt2 = pl2.must_shift_left(r2.get_half_width()).last_pt();
-        Line::must_new(pt1, pt2)
-    }
-
-    pub fn allows_turn(&self, from: RoadID, to: RoadID) -> bool {
-        self.group1.contains(&from) == self.group1.contains(&to)
-    }
-
-    fn avoid_movements_between_roads(&self) -> Vec<(RoadID, RoadID)> {
-        let mut pairs = Vec::new();
-        for from in &self.group1 {
-            for to in &self.group2 {
-                pairs.push((*from, *to));
-                pairs.push((*to, *from));
-            }
+        if let Some(r) = add_filter_to {
+            let road = map.get_r(r);
+            let dist = if i == road.src_i {
+                Distance::ZERO
+            } else {
+                road.length()
+            };
+            app.session.modal_filters.roads.insert(r, dist);
         }
-        pairs
     }
 }
 
@@ -6,7 +6,7 @@ use abstio::MapName;
 use abstutil::Timer;
 use geom::{Duration, Time};
 use map_gui::tools::compare_counts::CompareCounts;
-use map_model::{Path, PathConstraints, PathRequest, Pathfinder, RoadID};
+use map_model::{Path, PathConstraints, PathRequest, RoadID};
 use synthpop::{Scenario, TrafficCounts, TripEndpoint, TripMode};
 use widgetry::EventCtx;
 
@@ -142,9 +142,11 @@ impl Impact {
         let mut params = map.routing_params().clone();
         app.session.modal_filters.update_routing_params(&mut params);
         // Since we're making so many requests, it's worth it to rebuild a contraction hierarchy.
-        // This depends on the current map edits, so no need to cache
+        // Reusing the map's node ordering makes this much faster. This depends on the current map
+        // edits, so no need to cache
         let pathfinder_after =
-            Pathfinder::new_ch(map, params, constraints.into_iter().collect(), timer);
+            map.get_pathfinder()
+                .reweight(map, params, constraints.into_iter().collect(), timer);
 
         // We can't simply use TrafficCounts::from_path_requests. Due to spurious diffs with paths,
         // we need to skip cases where the path before and after have the same cost. It's easiest
@@ -198,7 +200,8 @@ impl Impact {
                 .collect();
             let mut params = map.routing_params().clone();
             app.session.modal_filters.update_routing_params(&mut params);
-            Pathfinder::new_ch(map, params, constraints.into_iter().collect(), timer)
+            map.get_pathfinder()
+                .reweight(map, params, constraints.into_iter().collect(), timer)
         };
 
         let mut changed = Vec::new();
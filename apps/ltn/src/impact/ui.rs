@@ -3,7 +3,7 @@ use std::collections::BTreeSet;
 use map_gui::tools::checkbox_per_mode;
 use map_model::{Path, NORMAL_LANE_THICKNESS};
 use synthpop::{Scenario, TripMode};
-use widgetry::tools::{FileLoader, PopupMsg};
+use widgetry::tools::{FileLoader, PopupMsg, RawBytes};
 use widgetry::{
     Color, DrawBaselayer, Drawable, EventCtx, GeomBatch, GfxCtx, HorizontalAlignment, Key, Line,
     Outcome, Panel, Slider, State, Text, TextExt, Toggle, VerticalAlignment, Widget,
@@ -25,12 +25,12 @@ impl ShowResults {
         let map_name = app.map.get_name().clone();
         if app.session.impact.map != map_name {
             let scenario_name = Scenario::default_scenario_for_map(&map_name);
-            return FileLoader::<App, Scenario>::new_state(
+            return FileLoader::<App, RawBytes>::new_state(
                 ctx,
                 abstio::path_scenario(&map_name, &scenario_name),
-                Box::new(move |ctx, app, timer, maybe_scenario| {
+                Box::new(move |ctx, app, timer, maybe_bytes| {
                     // TODO Handle corrupt files
-                    let scenario = maybe_scenario.unwrap();
+                    let scenario = Scenario::from_binary(&maybe_bytes.unwrap().0).unwrap();
                     app.session.impact = Impact::from_scenario(ctx, app, scenario, timer);
                     Transition::Replace(ShowResults::new_state(ctx, app))
                 }),
@@ -7,8 +7,8 @@ use abstutil::Timer;
 use widgetry::{EventCtx, GfxCtx, Settings};
 
 pub use browse::BrowseNeighbourhoods;
+pub use filters::ModalFilters;
 use filters::Toggle3Zoomed;
-pub use filters::{DiagonalFilter, ModalFilters};
 pub use neighbourhood::{Cell, DistanceInterval, Neighbourhood};
 pub use partition::{NeighbourhoodID, Partitioning};
 
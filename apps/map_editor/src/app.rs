@@ -248,6 +248,13 @@ impl State<App> for MainState {
                     WorldOutcome::Keypress("mark/unmark as a junction", ID::Road(r)) => {
                         app.model.toggle_junction(ctx, r);
                     }
+                    WorldOutcome::Keypress("add a mid-block crossing here", ID::Road(r)) => {
+                        if let Some(pt) = ctx.canvas.get_cursor_in_map_space() {
+                            app.model.add_crossing(ctx, r, pt);
+                            app.model.world.initialize_hover(ctx);
+                            self.update_instructions(ctx, app);
+                        }
+                    }
                     WorldOutcome::Keypress("debug in OSM", ID::Road(r)) => {
                         open_browser(r.osm_way_id.to_string());
                     }
@@ -41,6 +41,12 @@ impl EditRoad {
         for (via, to) in &road.complicated_turn_restrictions {
             info!("Complicated turn restriction via {} to {}", via, to);
         }
+        for (to, windows) in &road.conditional_turn_restrictions {
+            info!(
+                "Conditional turn restriction to {} during {:?}",
+                to, windows
+            );
+        }
         let info = txt.into_widget(ctx);
 
         let controls = Widget::col(vec![
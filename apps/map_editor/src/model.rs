@@ -3,8 +3,13 @@ use std::io::Write;
 
 use abstio::{CityName, MapName};
 use abstutil::{Tags, Timer};
-use geom::{Bounds, Circle, Distance, FindClosest, GPSBounds, HashablePt2D, LonLat, Polygon, Pt2D};
-use raw_map::{osm, IntersectionType, OriginalRoad, RawBuilding, RawIntersection, RawMap, RawRoad};
+use geom::{
+    Bounds, Circle, Distance, FindClosest, GPSBounds, HashablePt2D, LonLat, PolyLine, Polygon, Pt2D,
+};
+use raw_map::{
+    osm, CrossingType, IntersectionType, OriginalRoad, RawBuilding, RawIntersection, RawMap,
+    RawRoad,
+};
 use widgetry::mapspace::{ObjectID, World};
 use widgetry::{Color, Drawable, EventCtx, GeomBatch, Key, Line, Text};
 
@@ -150,6 +155,7 @@ impl Model {
         let i = &self.map.intersections[&id];
         let color = match i.intersection_type {
             IntersectionType::TrafficSignal => Color::GREEN,
+            IntersectionType::StopSign if i.mid_block_crossing => Color::PURPLE,
             IntersectionType::StopSign => Color::RED,
             IntersectionType::Border => Color::BLUE,
             IntersectionType::Construction => Color::ORANGE,
@@ -289,6 +295,7 @@ impl Model {
             .hotkey(Key::X, "remove interior points")
             .hotkey(Key::M, "merge")
             .hotkey(Key::J, "mark/unmark as a junction")
+            .hotkey(Key::C, "add a mid-block crossing here")
             .hotkey(Key::D, "debug in OSM")
             .build(ctx);
     }
@@ -504,6 +511,42 @@ impl Model {
         info!("Merged {}", id.as_string_code());
     }
 
+    pub fn add_crossing(&mut self, ctx: &EventCtx, id: OriginalRoad, pt: Pt2D) {
+        let pl = PolyLine::unchecked_new(self.map.roads[&id].osm_center_points.clone());
+        let dist = match pl.dist_along_of_point(pl.project_pt(pt)) {
+            Some((dist, _)) => dist,
+            None => {
+                warn!("Couldn't figure out where along {} to add a crossing", id);
+                return;
+            }
+        };
+
+        self.stop_showing_pts(id);
+        let crossing = self.map.new_osm_node_id(time_to_id());
+        let result = self
+            .map
+            .add_mid_block_crossing(id, dist, CrossingType::Marked, crossing);
+        let (r1, r2) = match result {
+            Ok(pair) => pair,
+            Err(err) => {
+                warn!("Can't add a crossing here: {}", err);
+                self.show_r_points(ctx, id);
+                return;
+            }
+        };
+
+        self.road_deleted(id);
+        for i in [id.i1, id.i2] {
+            self.world.delete_before_replacement(ID::Intersection(i));
+            self.intersection_added(ctx, i);
+        }
+        self.intersection_added(ctx, crossing);
+        self.road_added(ctx, r1);
+        self.road_added(ctx, r2);
+
+        info!("Added crossing {} along {}", crossing, id.as_string_code());
+    }
+
     pub fn toggle_junction(&mut self, ctx: &EventCtx, id: OriginalRoad) {
         self.road_deleted(id);
 
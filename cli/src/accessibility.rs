@@ -0,0 +1,347 @@
+use std::io::Write;
+
+use anyhow::{bail, Result};
+use geojson::{Feature, FeatureCollection, GeoJson};
+
+use abstutil::Timer;
+use geom::{Duration, Time};
+use map_model::connectivity::{AccessMode, Accessibility, AccessibilityOptions, WalkingOptions};
+use map_model::{AmenityType, Building, BuildingID, BuildingType, Map};
+
+pub fn run(
+    map: String,
+    departure: Time,
+    time_limit: Duration,
+    from: Option<usize>,
+    output: String,
+) -> Result<()> {
+    let geojson = if output.ends_with(".geojson") {
+        true
+    } else if output.ends_with(".csv") {
+        false
+    } else {
+        bail!("--output={} must end in .geojson or .csv", output);
+    };
+
+    let mut timer = Timer::new("measure accessibility");
+    let map = Map::load_synchronously(map, &mut timer);
+    timer.start("prepare transit schedule");
+    let accessibility = Accessibility::new(
+        &map,
+        AccessibilityOptions {
+            departure,
+            time_limit,
+            walking: WalkingOptions::default(),
+        },
+    );
+    timer.stop("prepare transit schedule");
+
+    let (columns, rows) = measure(
+        &map,
+        &accessibility,
+        from.map(BuildingID),
+        time_limit,
+        &mut timer,
+    )?;
+    if geojson {
+        write_geojson(&map, columns, rows, output);
+    } else {
+        write_csv(fs_err::File::create(&output)?, columns, rows)?;
+        println!("Wrote {}", output);
+    }
+    Ok(())
+}
+
+/// Each row has a building, and then a value per column
+type Row<'a> = (&'a Building, Vec<Option<usize>>);
+
+/// Measures the time to reach every building from one building or, if there's no starting
+/// building, the opportunities reachable from every home. Returns the columns and rows.
+fn measure<'a>(
+    map: &'a Map,
+    accessibility: &Accessibility,
+    from: Option<BuildingID>,
+    time_limit: Duration,
+    timer: &mut Timer,
+) -> Result<(Vec<String>, Vec<Row<'a>>)> {
+    let mut columns = Vec::new();
+    let mut rows = Vec::new();
+    if let Some(from) = from {
+        if from.0 >= map.all_buildings().len() {
+            bail!("{} doesn't exist", from);
+        }
+        let mut times_per_mode = Vec::new();
+        for mode in AccessMode::all() {
+            columns.push(format!("{}_seconds", mode.noun()));
+            times_per_mode.push(accessibility.times_from(from, mode));
+        }
+        for b in map.all_buildings() {
+            let values: Vec<Option<usize>> = times_per_mode
+                .iter()
+                .map(|times| times.get(&b.id).map(|t| t.inner_seconds() as usize))
+                .collect();
+            if values.iter().any(|x| x.is_some()) {
+                rows.push((b, values));
+            }
+        }
+    } else {
+        for mode in AccessMode::all() {
+            for amenity in AmenityType::all() {
+                columns.push(format!("{}_{}", mode.noun(), amenity));
+            }
+        }
+        // Equity analyses care about where people live
+        let homes: Vec<&Building> = map
+            .all_buildings()
+            .iter()
+            .filter(|b| {
+                matches!(
+                    b.bldg_type,
+                    BuildingType::Residential { .. } | BuildingType::ResidentialCommercial(_, _)
+                )
+            })
+            .collect();
+        timer.start_iter("measure from homes", homes.len());
+        for b in homes {
+            timer.next();
+            let mut values = Vec::new();
+            for mode in AccessMode::all() {
+                let times = accessibility.times_from(b.id, mode);
+                let counts = accessibility.opportunities(&times, time_limit);
+                for amenity in AmenityType::all() {
+                    values.push(Some(counts.get(&amenity).cloned().unwrap_or(0)));
+                }
+            }
+            rows.push((b, values));
+        }
+    }
+
+    Ok((columns, rows))
+}
+
+fn write_geojson(map: &Map, columns: Vec<String>, rows: Vec<Row>, output: String) {
+    let mut features = Vec::new();
+    for (b, values) in rows {
+        let mut feature = Feature {
+            bbox: None,
+            geometry: Some(b.polygon.to_geojson(Some(map.get_gps_bounds()))),
+            id: None,
+            properties: None,
+            foreign_members: None,
+        };
+        feature.set_property("id", b.id.0);
+        feature.set_property("osm_id", b.orig_id.to_string());
+        for (column, value) in columns.iter().zip(values) {
+            if let Some(value) = value {
+                feature.set_property(column.clone(), value);
+            }
+        }
+        features.push(feature);
+    }
+    let geojson = GeoJson::from(FeatureCollection {
+        bbox: None,
+        features,
+        foreign_members: None,
+    });
+    abstio::write_json(output, &geojson);
+}
+
+fn write_csv<W: Write>(writer: W, columns: Vec<String>, rows: Vec<Row>) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    let mut header = vec!["id".to_string(), "osm_id".to_string()];
+    header.extend(columns);
+    writer.write_record(&header)?;
+    for (b, values) in rows {
+        let mut record = vec![b.id.0.to_string(), b.orig_id.to_string()];
+        // Leave unreachable buildings blank
+        record.extend(
+            values
+                .into_iter()
+                .map(|x| x.map(|x| x.to_string()).unwrap_or_default()),
+        );
+        writer.write_record(&record)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use abstio::MapName;
+    use geom::{LonLat, PolyLine};
+    use map_model::raw::{RawTransitRoute, RawTransitStop, RawTransitType};
+
+    use super::*;
+
+    /// A long street with a home and supermarket at one end and a cafe at the other. A bus runs
+    /// along the street every 5 minutes in the morning.
+    fn fixture() -> Map {
+        let mut timer = Timer::throwaway();
+        let mut raw = convert_osm::convert(
+            format!(
+                "{}/../tests/input/accessibility.osm",
+                env!("CARGO_MANIFEST_DIR")
+            ),
+            MapName::new("zz", "oneshot", "accessibility"),
+            None,
+            convert_osm::Options::default_for_side(map_model::DrivingSide::Right),
+            &mut timer,
+        );
+        // Stops are on the south side of the street, so the bus heads east
+        let mut shape = Vec::new();
+        for (gtfs_id, lon) in [("west", -122.4688), ("east", -122.4415)] {
+            let position = LonLat::new(lon, 47.72195).to_pt(&raw.gps_bounds);
+            raw.transit_stops.insert(
+                gtfs_id.to_string(),
+                RawTransitStop {
+                    gtfs_id: gtfs_id.to_string(),
+                    position,
+                    name: gtfs_id.to_string(),
+                },
+            );
+            shape.push(position);
+        }
+        raw.transit_routes.push(RawTransitRoute {
+            long_name: "Main Street shuttle".to_string(),
+            short_name: "1".to_string(),
+            gtfs_id: "shuttle".to_string(),
+            shape: PolyLine::must_new(shape),
+            stops: vec!["west".to_string(), "east".to_string()],
+            route_type: RawTransitType::Bus,
+            schedule: (0..36)
+                .map(|i| {
+                    let depart = Time::START_OF_DAY + Duration::hours(6) + Duration::minutes(5 * i);
+                    vec![depart, depart + Duration::minutes(4)]
+                })
+                .collect(),
+        });
+        Map::create_from_raw(raw, map_model::RawToMapOptions::default(), &mut timer)
+    }
+
+    fn accessibility(map: &Map) -> Accessibility {
+        Accessibility::new(
+            map,
+            AccessibilityOptions {
+                departure: Time::START_OF_DAY + Duration::hours(7),
+                time_limit: Duration::hours(1),
+                walking: WalkingOptions::default(),
+            },
+        )
+    }
+
+    /// Finds the building with some amenity, or the one without any
+    fn building(map: &Map, amenity: Option<&str>) -> BuildingID {
+        map.all_buildings()
+            .iter()
+            .find(|b| b.amenities.iter().map(|a| a.amenity_type.as_str()).next() == amenity)
+            .unwrap()
+            .id
+    }
+
+    #[test]
+    fn test_times_and_opportunities() {
+        let map = fixture();
+        assert_eq!(map.all_transit_routes().len(), 1);
+        let accessibility = accessibility(&map);
+        let home = building(&map, None);
+        let cafe = building(&map, Some("cafe"));
+
+        let walk = accessibility.times_from(home, AccessMode::Walk);
+        let transit = accessibility.times_from(home, AccessMode::Transit);
+        assert_eq!(walk.len(), 3);
+        // Transit riders can always just walk
+        for (b, time) in &walk {
+            assert!(transit[b] <= *time, "{} is slower to reach by transit", b);
+        }
+        // Riding the bus down the street beats walking it
+        assert!(transit[&cafe] + Duration::minutes(5) < walk[&cafe]);
+
+        // Only count amenities reachable in time
+        let count = |times, within, amenity| {
+            accessibility
+                .opportunities(times, within)
+                .get(&amenity)
+                .cloned()
+                .unwrap_or(0)
+        };
+        let next_door = Duration::minutes(5);
+        assert_eq!(count(&walk, next_door, AmenityType::Supermarket), 1);
+        assert_eq!(count(&walk, next_door, AmenityType::Cafe), 0);
+        assert_eq!(count(&walk, Duration::minutes(20), AmenityType::Cafe), 0);
+        assert_eq!(count(&transit, Duration::minutes(20), AmenityType::Cafe), 1);
+        assert_eq!(count(&walk, Duration::hours(1), AmenityType::Cafe), 1);
+    }
+
+    #[test]
+    fn test_csv() {
+        let map = fixture();
+        let accessibility = accessibility(&map);
+        let home = building(&map, None);
+        let cafe = building(&map, Some("cafe"));
+        let mut timer = Timer::throwaway();
+
+        // Times from one building
+        let (columns, rows) = measure(
+            &map,
+            &accessibility,
+            Some(home),
+            Duration::hours(1),
+            &mut timer,
+        )
+        .unwrap();
+        let mut output = Vec::new();
+        write_csv(&mut output, columns, rows).unwrap();
+        let mut reader = csv::Reader::from_reader(output.as_slice());
+        assert_eq!(
+            reader.headers().unwrap().iter().collect::<Vec<_>>(),
+            vec![
+                "id",
+                "osm_id",
+                "walk_seconds",
+                "bike_seconds",
+                "drive_seconds",
+                "transit_seconds"
+            ]
+        );
+        let mut num_rows = 0;
+        for rec in reader.records() {
+            let rec = rec.unwrap();
+            let seconds = |idx: usize| rec[idx].parse::<usize>().unwrap();
+            assert!(seconds(5) <= seconds(2));
+            if rec[0] == cafe.0.to_string() {
+                assert!(seconds(5) + 5 * 60 < seconds(2));
+            }
+            num_rows += 1;
+        }
+        assert_eq!(num_rows, 3);
+
+        // Opportunities from every home, counting what's reachable within 20 minutes
+        let (columns, rows) = measure(
+            &map,
+            &accessibility,
+            None,
+            Duration::minutes(20),
+            &mut timer,
+        )
+        .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].0.id, home);
+        let value = |column: &str| {
+            let idx = columns.iter().position(|c| c == column).unwrap();
+            rows[0].1[idx]
+        };
+        assert_eq!(value("walk_Supermarket"), Some(1));
+        assert_eq!(value("walk_Cafe"), Some(0));
+        assert_eq!(value("transit_Cafe"), Some(1));
+
+        // Buildings that don't exist are an error
+        assert!(measure(
+            &map,
+            &accessibility,
+            Some(BuildingID(100)),
+            Duration::hours(1),
+            &mut timer
+        )
+        .is_err());
+    }
+}
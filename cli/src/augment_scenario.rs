@@ -18,7 +18,7 @@ pub fn run(
     let mut rng = XorShiftRng::seed_from_u64(rng_seed);
     let mut timer = Timer::new("augment scenario");
 
-    let mut scenario: Scenario = abstio::must_read_object(input_scenario, &mut timer);
+    let mut scenario = Scenario::must_load(input_scenario, &mut timer);
     let map = Map::load_synchronously(scenario.map_name.path(), &mut timer);
 
     if should_add_return_trips {
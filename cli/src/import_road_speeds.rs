@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use serde::Deserialize;
+
+use abstutil::{prettyprint_usize, Timer};
+use geom::{Duration, Speed, Time};
+use map_model::raw::OriginalRoad;
+use map_model::{osm, DirectedRoadID, Direction, Map, RoadTravelTimes};
+
+pub fn run(csv_path: String, map: String, output: String) -> Result<()> {
+    let mut timer = Timer::new("import road speeds");
+    let map = Map::load_synchronously(map, &mut timer);
+
+    // Each road in the map corresponds to one segment of an OSM way between two intersections
+    let mut roads: HashMap<OriginalRoad, DirectedRoadID> = HashMap::new();
+    for r in map.all_roads() {
+        let id = r.orig_id;
+        roads.insert(
+            id,
+            DirectedRoadID {
+                road: r.id,
+                dir: Direction::Fwd,
+            },
+        );
+        roads.insert(
+            OriginalRoad {
+                osm_way_id: id.osm_way_id,
+                i1: id.i2,
+                i2: id.i1,
+            },
+            DirectedRoadID {
+                road: r.id,
+                dir: Direction::Back,
+            },
+        );
+    }
+
+    let mut times = RoadTravelTimes::new(Duration::hours(1));
+    let mut matched = 0;
+    let mut unmatched = 0;
+    for rec in csv::Reader::from_reader(fs_err::File::open(csv_path)?).deserialize() {
+        let rec: Record = rec?;
+        if rec.hour >= 24 {
+            bail!("hour {} isn't between 0 and 23", rec.hour);
+        }
+        if rec.speed_kmph <= 0.0 {
+            bail!("speed {} isn't positive", rec.speed_kmph);
+        }
+        let id = OriginalRoad {
+            osm_way_id: osm::WayID(rec.osm_way_id),
+            i1: osm::NodeID(rec.osm_node1),
+            i2: osm::NodeID(rec.osm_node2),
+        };
+        match roads.get(&id) {
+            Some(dr) => {
+                let travel_time = map.get_r(dr.road).length() / Speed::km_per_hour(rec.speed_kmph);
+                times.set(
+                    *dr,
+                    Time::START_OF_DAY + Duration::hours(rec.hour),
+                    travel_time,
+                );
+                matched += 1;
+            }
+            None => {
+                unmatched += 1;
+            }
+        }
+    }
+    println!(
+        "Matched {} speeds to roads, skipped {} that don't match any road in the map",
+        prettyprint_usize(matched),
+        prettyprint_usize(unmatched)
+    );
+    abstio::write_json(output, &times);
+
+    Ok(())
+}
+
+/// The speed observed along one segment of an OSM way, between two intersections, in one
+/// direction, during one hour of the day.
+#[derive(Debug, Deserialize)]
+struct Record {
+    osm_way_id: i64,
+    /// The segment is measured traveling from this node to the next one.
+    osm_node1: i64,
+    osm_node2: i64,
+    /// Between 0 and 23
+    hour: usize,
+    speed_kmph: f64,
+}
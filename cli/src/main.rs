@@ -4,23 +4,26 @@
 #[macro_use]
 extern crate log;
 
+mod accessibility;
 mod augment_scenario;
 mod clip_osm;
 mod generate_houses;
 mod geojson_to_osmosis;
 mod import_grid2demand;
+mod import_road_speeds;
 mod import_scenario;
 mod one_step_import;
 
 use std::io::Write;
 
 use abstio::CityName;
-use anyhow::Result;
+use anyhow::{bail, Result};
 use fs_err::File;
 use importer::Job;
 use structopt::StructOpt;
 
 use abstutil::Timer;
+use geom::{Duration, Time};
 
 #[derive(StructOpt)]
 #[structopt(name = "abcli", about = "The A/B Street multi-tool")]
@@ -42,6 +45,25 @@ enum Command {
         #[structopt(long)]
         scenario_name: String,
     },
+    /// Generates delivery tours from every border to businesses and back. Add these to another
+    /// scenario with the "add extra trips" modifier.
+    FreightScenario {
+        /// A seed for generating random numbers
+        #[structopt(long)]
+        rng_seed: u64,
+        /// The path to a map to generate a scenario for
+        #[structopt(long)]
+        map: String,
+        /// The name of the scenario to generate
+        #[structopt(long)]
+        scenario_name: String,
+        /// How many delivery tours to generate
+        #[structopt(long, default_value = "100")]
+        num_tours: usize,
+        /// The fraction of tours using heavy goods vehicles instead of vans, between 0 and 1
+        #[structopt(long, default_value = "0.2")]
+        fraction_trucks: f64,
+    },
     /// Modifies the schedule of every person in an existing scenario.
     AugmentScenario {
         /// The path to a scenario to augment. This will be modified in-place.
@@ -98,6 +120,53 @@ enum Command {
         #[structopt(long)]
         map: String,
     },
+    /// Import speeds observed on each road by hour of the day, for routing to use instead of
+    /// speed limits. The CSV needs `osm_way_id`, `osm_node1`, `osm_node2`, `hour`, and
+    /// `speed_kmph` columns, one row per segment of an OSM way between two intersections, per
+    /// direction and hour.
+    ImportRoadSpeeds {
+        /// The path to a CSV file
+        #[structopt(long)]
+        input: String,
+        /// The path to a map matching the speed data
+        #[structopt(long)]
+        map: String,
+        /// The path to write travel times as JSON
+        #[structopt(long)]
+        output: String,
+    },
+    /// Extract the time cars took to cross each road by hour of the day from prebaked results, so
+    /// routing can use it instead of speed limits.
+    ExtractRoadTravelTimes {
+        /// The path to prebaked results, written by `prebake-scenario`. Other simulations only
+        /// record these times with `--record-road-crossing-times`.
+        #[structopt(long)]
+        prebaked: String,
+        /// The path to write travel times as JSON
+        #[structopt(long)]
+        output: String,
+    },
+    /// Measure how long it takes to reach places by walking, biking, driving, and transit. With
+    /// `--from`, writes the time to reach every building from one building. Otherwise, for every
+    /// residential building, counts how many amenities of each type are within the time limit by
+    /// each mode.
+    Accessibility {
+        /// The path to a map
+        #[structopt(long)]
+        map: String,
+        /// The time of day to leave, like "8:00"
+        #[structopt(long, parse(try_from_str = Time::parse), default_value = "8:00")]
+        departure: Time,
+        /// Ignore places further away than this, like "15:00" for 15 minutes
+        #[structopt(long, parse(try_from_str = Duration::parse), default_value = "15:00")]
+        time_limit: Duration,
+        /// The ID of a building to measure from
+        #[structopt(long)]
+        from: Option<usize>,
+        /// The path to write, ending in .geojson or .csv
+        #[structopt(long)]
+        output: String,
+    },
     /// Import a JSON scenario in the
     /// https://a-b-street.github.io/docs/tech/dev/formats/scenarios.html format
     ImportScenario {
@@ -122,6 +191,23 @@ enum Command {
         #[structopt(long)]
         output: String,
     },
+    /// Writes a GTFS feed with a map's public transit routes, including any edits to their
+    /// schedules.
+    #[structopt(name = "export-gtfs")]
+    ExportGTFS {
+        /// The path to a map
+        #[structopt(long)]
+        map: String,
+        /// The path to edits to apply to the map first
+        #[structopt(long)]
+        edits: Option<String>,
+        /// The timezone of the map, like America/Los_Angeles
+        #[structopt(long)]
+        timezone: String,
+        /// The directory to write the feed's files into
+        #[structopt(long)]
+        output: String,
+    },
     /// Removes nonessential parts of a Map, for the bike network tool.
     MinifyMap {
         /// The path to a map to shrink. The map is modified in-place.
@@ -172,6 +258,10 @@ enum Command {
         /// Downgrade crosswalks not matching a `highway=crossing` OSM node into unmarked crossings.
         #[structopt(long)]
         filter_crosswalks: bool,
+        /// Split roads at `highway=crossing` OSM nodes in the middle of a block, so pedestrians
+        /// can cross there.
+        #[structopt(long)]
+        mid_block_crossings: bool,
         /// Generate a simple travel demand model based on 2011 UK commuting data. This will only
         /// work if the boundary is in the UK.
         #[structopt(long)]
@@ -191,6 +281,10 @@ enum Command {
         /// Downgrade crosswalks not matching a `highway=crossing` OSM node into unmarked crossings.
         #[structopt(long)]
         filter_crosswalks: bool,
+        /// Split roads at `highway=crossing` OSM nodes in the middle of a block, so pedestrians
+        /// can cross there.
+        #[structopt(long)]
+        mid_block_crossings: bool,
         /// Generate a simple travel demand model based on 2011 UK commuting data. This will only
         /// work if the boundary is in the UK.
         #[structopt(long)]
@@ -223,6 +317,23 @@ enum Command {
         /// The path to a scenario file
         #[structopt()]
         scenario_path: String,
+        /// Before saving results, iterate traffic assignment, so drivers pick routes that account
+        /// for congestion.
+        #[structopt(long)]
+        assign_traffic: bool,
+        #[structopt(flatten)]
+        assignment: sim::assignment::AssignmentOptions,
+    },
+    /// Repeatedly simulate a full day of a scenario. After each run, some drivers switch to
+    /// routes that are faster given the travel times observed, until the routes reach an
+    /// equilibrium. Prints a JSON summary of each iteration.
+    #[structopt(name = "assign-traffic")]
+    AssignTraffic {
+        /// The path to a scenario file
+        #[structopt()]
+        scenario_path: String,
+        #[structopt(flatten)]
+        opts: sim::assignment::AssignmentOptions,
     },
 }
 
@@ -254,6 +365,13 @@ async fn main() -> Result<()> {
             map,
             scenario_name,
         } => random_scenario(rng_seed, map, scenario_name),
+        Command::FreightScenario {
+            rng_seed,
+            map,
+            scenario_name,
+            num_tours,
+            fraction_trucks,
+        } => freight_scenario(rng_seed, map, scenario_name, num_tours, fraction_trucks)?,
         Command::AugmentScenario {
             input_scenario,
             add_return_trips,
@@ -276,12 +394,31 @@ async fn main() -> Result<()> {
         } => clip_osm::run(pbf_path, clip_path, out_path)?,
         Command::GeoJSONToOsmosis { input } => geojson_to_osmosis::run(input)?,
         Command::ImportGrid2Demand { input, map } => import_grid2demand::run(input, map)?,
+        Command::ImportRoadSpeeds { input, map, output } => {
+            import_road_speeds::run(input, map, output)?
+        }
+        Command::ExtractRoadTravelTimes { prebaked, output } => {
+            extract_road_travel_times(prebaked, output)
+        }
+        Command::Accessibility {
+            map,
+            departure,
+            time_limit,
+            from,
+            output,
+        } => accessibility::run(map, departure, time_limit, from, output)?,
         Command::ImportScenario {
             input,
             map,
             skip_problems,
         } => import_scenario::run(input, map, skip_problems),
         Command::ImportJSONMap { input, output } => import_json_map(input, output),
+        Command::ExportGTFS {
+            map,
+            edits,
+            timezone,
+            output,
+        } => export_gtfs(map, edits, timezone, output)?,
         Command::MinifyMap { map } => minify_map(map),
         Command::GenerateHouses {
             map,
@@ -298,6 +435,7 @@ async fn main() -> Result<()> {
             drive_on_left,
             use_geofabrik,
             filter_crosswalks,
+            mid_block_crossings,
             create_uk_travel_demand_model,
         } => {
             one_step_import::run(
@@ -306,6 +444,7 @@ async fn main() -> Result<()> {
                 driving_side(drive_on_left),
                 use_geofabrik,
                 filter_crosswalks,
+                mid_block_crossings,
                 create_uk_travel_demand_model,
             )
             .await?
@@ -315,6 +454,7 @@ async fn main() -> Result<()> {
             clip_path,
             drive_on_left,
             filter_crosswalks,
+            mid_block_crossings,
             create_uk_travel_demand_model,
             opts,
         } => {
@@ -323,6 +463,7 @@ async fn main() -> Result<()> {
                 clip_path,
                 driving_side(drive_on_left),
                 filter_crosswalks,
+                mid_block_crossings,
                 create_uk_travel_demand_model,
                 opts,
             )
@@ -334,7 +475,15 @@ async fn main() -> Result<()> {
         } => importer::regenerate_everything(shard_num, num_shards).await,
         Command::RegenerateEverythingExternally => regenerate_everything_externally()?,
         Command::Import { job } => job.run(&mut Timer::new("import one city")).await,
-        Command::PrebakeScenario { scenario_path } => prebake_scenario(scenario_path),
+        Command::PrebakeScenario {
+            scenario_path,
+            assign_traffic,
+            assignment,
+        } => prebake_scenario(scenario_path, assign_traffic, assignment),
+        Command::AssignTraffic {
+            scenario_path,
+            opts,
+        } => assign_traffic(scenario_path, opts),
     }
     Ok(())
 }
@@ -347,9 +496,7 @@ fn dump_json(path: String) {
         println!("{}", abstutil::to_json(&map));
         return;
     }
-    if let Ok(scenario) =
-        abstio::maybe_read_binary::<synthpop::Scenario>(path.clone(), &mut Timer::throwaway())
-    {
+    if let Ok(scenario) = synthpop::Scenario::load(path.clone(), &mut Timer::throwaway()) {
         println!("{}", abstutil::to_json(&scenario));
         return;
     }
@@ -375,6 +522,35 @@ fn random_scenario(rng_seed: u64, map: String, scenario_name: String) {
     );
 }
 
+fn freight_scenario(
+    rng_seed: u64,
+    map: String,
+    scenario_name: String,
+    num_tours: usize,
+    fraction_trucks: f64,
+) -> Result<()> {
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    if !(0.0..=1.0).contains(&fraction_trucks) {
+        bail!(
+            "--fraction-trucks={} must be between 0 and 1",
+            fraction_trucks
+        );
+    }
+    let mut rng = XorShiftRng::seed_from_u64(rng_seed);
+    let map = map_model::Map::load_synchronously(map, &mut Timer::throwaway());
+    let mut generator = sim::FreightGenerator::from_borders(&map, &scenario_name, num_tours);
+    generator.fraction_trucks = fraction_trucks;
+    let scenario = generator.generate(&map, &mut rng, &mut Timer::throwaway())?;
+    scenario.save();
+    println!(
+        "Wrote {}",
+        abstio::path_scenario(&scenario.map_name, &scenario.scenario_name)
+    );
+    Ok(())
+}
+
 fn import_json_map(input: String, output: String) {
     // TODO This can't handle the output of dump_map! What?!
     let mut map: map_model::Map = abstio::read_json(input, &mut Timer::throwaway());
@@ -382,6 +558,23 @@ fn import_json_map(input: String, output: String) {
     abstio::write_binary(output, &map);
 }
 
+fn extract_road_travel_times(prebaked: String, output: String) {
+    let analytics: sim::Analytics = abstio::must_read_object(prebaked, &mut Timer::throwaway());
+    abstio::write_json(output, &analytics.road_travel_times());
+}
+
+fn export_gtfs(map: String, edits: Option<String>, timezone: String, output: String) -> Result<()> {
+    let mut timer = Timer::new("export GTFS");
+    let mut map = map_model::Map::load_synchronously(map, &mut timer);
+    if let Some(path) = edits {
+        let edits = map_model::MapEdits::load_from_file(&map, path, &mut timer)?;
+        map.must_apply_edits(edits, &mut timer);
+    }
+    let dir = map_model::gtfs::write(&map, &timezone, output)?;
+    println!("Wrote GTFS feed to {}", dir);
+    Ok(())
+}
+
 fn minify_map(path: String) {
     let mut timer = Timer::new("minify map");
     let mut map = map_model::Map::load_synchronously(path, &mut timer);
@@ -411,11 +604,27 @@ fn regenerate_everything_externally() -> Result<()> {
     Ok(())
 }
 
-fn prebake_scenario(path: String) {
+fn prebake_scenario(
+    path: String,
+    assign_traffic: bool,
+    assignment: sim::assignment::AssignmentOptions,
+) {
     let mut timer = Timer::new("prebake scenario");
-    let scenario: synthpop::Scenario = abstio::must_read_object(path, &mut timer);
+    let scenario = synthpop::Scenario::must_load(path, &mut timer);
+    let map = map_model::Map::load_synchronously(scenario.map_name.path(), &mut timer);
+    if assign_traffic {
+        sim::prebake::prebake_with_assignment(&map, scenario, &assignment, &mut timer);
+    } else {
+        sim::prebake::prebake(&map, scenario, &mut timer);
+    }
+}
+
+fn assign_traffic(path: String, opts: sim::assignment::AssignmentOptions) {
+    let mut timer = Timer::new("assign traffic");
+    let scenario = synthpop::Scenario::must_load(path, &mut timer);
     let map = map_model::Map::load_synchronously(scenario.map_name.path(), &mut timer);
-    sim::prebake::prebake(&map, scenario, &mut timer);
+    let (_, iterations) = sim::assignment::assign_traffic(&map, &scenario, &opts, &mut timer);
+    println!("{}", abstutil::to_json(&iterations));
 }
 
 fn driving_side(drive_on_left: bool) -> map_model::DrivingSide {
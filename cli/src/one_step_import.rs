@@ -11,6 +11,7 @@ pub async fn run(
     driving_side: map_model::DrivingSide,
     use_geofabrik: bool,
     filter_crosswalks: bool,
+    mid_block_crossings: bool,
     create_uk_travel_demand_model: bool,
 ) -> Result<()> {
     if name.contains(' ') || name.is_empty() {
@@ -84,6 +85,7 @@ pub async fn run(
         Some("boundary0.poly".to_string()),
         driving_side,
         filter_crosswalks,
+        mid_block_crossings,
         create_uk_travel_demand_model,
         map_model::RawToMapOptions::default(),
     )
@@ -42,6 +42,69 @@ pub enum Severity {
     Fatal,
 }
 
+/// Near-misses measured in a traffic simulation, in a form comparable to a `CollisionDataset`.
+#[derive(Serialize, Deserialize)]
+pub struct ConflictDataset {
+    /// Describes where the conflicts came from, like the map and scenario simulated.
+    pub source: String,
+    /// The near-misses measured.
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Two road users came close to colliding, measured by surrogate safety measures. At least one of
+/// `ttc` and `pet` is set.
+#[derive(Serialize, Deserialize)]
+pub struct Conflict {
+    /// A single point describing where the conflict occurred.
+    pub location: LonLat,
+    /// The local time the conflict occurred.
+    pub time: Duration,
+    /// Time-to-collision: when the second road user entered the conflict area, how long until the
+    /// two would've collided if both kept going at the same speed.
+    pub ttc: Option<Duration>,
+    /// Post-encroachment time: how long after the first road user left the conflict area the
+    /// second entered it.
+    pub pet: Option<Duration>,
+    /// Who was involved, like "Car (Left) vs Pedestrian (Crosswalk)".
+    pub description: String,
+}
+
+impl Conflict {
+    /// A very rough stand-in for how bad a collision would've been. Shorter times leave less room
+    /// to brake, but this ignores speed and who was involved.
+    pub fn severity(&self) -> Severity {
+        let min = match (self.ttc, self.pet) {
+            (Some(ttc), Some(pet)) => ttc.min(pet),
+            (Some(x), None) | (None, Some(x)) => x,
+            (None, None) => Duration::ZERO,
+        };
+        if min < Duration::seconds(0.5) {
+            Severity::Serious
+        } else {
+            Severity::Slight
+        }
+    }
+}
+
+impl ConflictDataset {
+    /// Treat every near-miss as a collision, so simulated risk can be compared against the same
+    /// tools as real collision data.
+    pub fn to_collisions(&self) -> CollisionDataset {
+        CollisionDataset {
+            source_url: self.source.clone(),
+            collisions: self
+                .conflicts
+                .iter()
+                .map(|c| Collision {
+                    location: c.location,
+                    time: c.time,
+                    severity: c.severity(),
+                })
+                .collect(),
+        }
+    }
+}
+
 /// Import data from the UK STATS19 dataset. See https://github.com/ropensci/stats19. Any parsing
 /// errors will skip the row and log a warning.
 pub fn import_stats19(input: ExtraShapes, source_url: &str) -> CollisionDataset {
@@ -0,0 +1,777 @@
+//! Read elevation directly from digital elevation model (DEM) files, without any external tools.
+//! Two formats are supported:
+//!
+//! - SRTM `.hgt` tiles, named like `N47W122.hgt`, covering one degree square each
+//! - Single-band, uncompressed GeoTIFFs in WGS84 longitude/latitude. Compressed or projected
+//!   files can be converted with something like
+//!   `gdalwarp -t_srs EPSG:4326 -co COMPRESS=NONE in.tif out.tif`.
+
+use std::cell::RefCell;
+use std::io::{Read, Seek, SeekFrom};
+
+use anyhow::{Context, Result};
+
+use geom::{Distance, GPSBounds, LonLat};
+
+/// All of the elevation tiles overlapping a map.
+pub struct Dem {
+    tiles: Vec<Tile>,
+}
+
+impl Dem {
+    /// Load every tile in a directory that overlaps the bounds. Files that can't be read are
+    /// skipped with a warning. Fails if no tile overlaps.
+    pub fn load(dir: &str, bounds: &GPSBounds) -> Result<Dem> {
+        let mut tiles = Vec::new();
+        for path in abstio::list_dir(dir.to_string()) {
+            let lower = path.to_lowercase();
+            let tile = if lower.ends_with(".hgt") {
+                Tile::load_hgt(&path, bounds)
+            } else if lower.ends_with(".tif") || lower.ends_with(".tiff") {
+                Tile::load_geotiff(&path, bounds)
+            } else {
+                continue;
+            };
+            match tile {
+                Ok(Some(tile)) => {
+                    info!("Using elevation data from {}", path);
+                    tiles.push(tile);
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    warn!("Skipping elevation data from {}: {}", path, err);
+                }
+            }
+        }
+        if tiles.is_empty() {
+            bail!("No .hgt or GeoTIFF files in {} cover the map", dir);
+        }
+        Ok(Dem { tiles })
+    }
+
+    /// The elevation at a point, interpolated from the nearest samples. None if no tile covers
+    /// the point.
+    pub fn elevation(&self, pt: LonLat) -> Option<Distance> {
+        self.tiles
+            .iter()
+            .find_map(|tile| tile.sample(pt))
+            .map(Distance::meters)
+    }
+}
+
+/// A regular grid of elevation samples, in meters. Rows go from north to south.
+struct Tile {
+    /// The longitude and latitude of the center of the first sample
+    lon0: f64,
+    lat0: f64,
+    /// Degrees between samples
+    dx: f64,
+    dy: f64,
+    width: usize,
+    height: usize,
+    values: Vec<f32>,
+    nodata: Option<f32>,
+}
+
+impl Tile {
+    /// The area covered, extending half a sample past the outermost sample centers
+    fn bounds(&self) -> GPSBounds {
+        GPSBounds {
+            min_lon: self.lon0 - self.dx / 2.0,
+            max_lon: self.lon0 + (self.width as f64 - 0.5) * self.dx,
+            min_lat: self.lat0 - (self.height as f64 - 0.5) * self.dy,
+            max_lat: self.lat0 + self.dy / 2.0,
+        }
+    }
+
+    fn overlaps(&self, other: &GPSBounds) -> bool {
+        let b = self.bounds();
+        b.min_lon <= other.max_lon
+            && other.min_lon <= b.max_lon
+            && b.min_lat <= other.max_lat
+            && other.min_lat <= b.max_lat
+    }
+
+    fn get(&self, col: usize, row: usize) -> Option<f64> {
+        let value = self.values[row * self.width + col];
+        if !value.is_finite() || Some(value) == self.nodata {
+            return None;
+        }
+        Some(value as f64)
+    }
+
+    /// Bilinear interpolation between the four nearest samples, ignoring missing ones
+    fn sample(&self, pt: LonLat) -> Option<f64> {
+        if !self.bounds().contains(pt) {
+            return None;
+        }
+        // Within half a sample of the edge, just use the edge
+        let col = ((pt.x() - self.lon0) / self.dx).clamp(0.0, (self.width - 1) as f64);
+        let row = ((self.lat0 - pt.y()) / self.dy).clamp(0.0, (self.height - 1) as f64);
+        let (c0, r0) = (col.floor() as usize, row.floor() as usize);
+        let (c1, r1) = ((c0 + 1).min(self.width - 1), (r0 + 1).min(self.height - 1));
+        let (fx, fy) = (col - c0 as f64, row - r0 as f64);
+
+        let mut total = 0.0;
+        let mut total_weight = 0.0;
+        for (c, r, weight) in [
+            (c0, r0, (1.0 - fx) * (1.0 - fy)),
+            (c1, r0, fx * (1.0 - fy)),
+            (c0, r1, (1.0 - fx) * fy),
+            (c1, r1, fx * fy),
+        ] {
+            if let Some(value) = self.get(c, r) {
+                total += weight * value;
+                total_weight += weight;
+            }
+        }
+        if total_weight == 0.0 {
+            return None;
+        }
+        Some(total / total_weight)
+    }
+
+    /// Returns None if the tile doesn't overlap the bounds.
+    fn load_hgt(path: &str, bounds: &GPSBounds) -> Result<Option<Tile>> {
+        let name = std::path::Path::new(path)
+            .file_stem()
+            .and_then(|x| x.to_str())
+            .unwrap_or("");
+        let (lon, lat) = parse_hgt_name(name)
+            .with_context(|| format!("{} isn't named like N47W122.hgt", path))?;
+        // Each file names its southwest corner and spans one degree
+        if (lon as f64) > bounds.max_lon
+            || ((lon + 1) as f64) < bounds.min_lon
+            || (lat as f64) > bounds.max_lat
+            || ((lat + 1) as f64) < bounds.min_lat
+        {
+            return Ok(None);
+        }
+        Tile::from_hgt(lon, lat, &abstio::slurp_file(path)?).map(Some)
+    }
+
+    /// SRTM tiles are square grids of big-endian 16-bit integers, in meters. Samples on the edges
+    /// are shared with neighboring tiles.
+    fn from_hgt(lon: i32, lat: i32, bytes: &[u8]) -> Result<Tile> {
+        let samples = bytes.len() / 2;
+        let size = (samples as f64).sqrt() as usize;
+        if size < 2 || size * size * 2 != bytes.len() {
+            bail!("{} bytes isn't a square grid of samples", bytes.len());
+        }
+        let values = bytes
+            .chunks_exact(2)
+            .map(|pair| i16::from_be_bytes([pair[0], pair[1]]) as f32)
+            .collect();
+        let spacing = 1.0 / (size - 1) as f64;
+        Ok(Tile {
+            lon0: lon as f64,
+            lat0: (lat + 1) as f64,
+            dx: spacing,
+            dy: spacing,
+            width: size,
+            height: size,
+            values,
+            nodata: Some(-32768.0),
+        })
+    }
+
+    /// Returns None if the tile doesn't overlap the bounds.
+    fn load_geotiff(path: &str, bounds: &GPSBounds) -> Result<Option<Tile>> {
+        let file = std::fs::File::open(path)?;
+        Tile::from_geotiff(std::io::BufReader::new(file), bounds)
+    }
+
+    /// Only reads the samples after the header shows the tile overlaps the bounds, since GeoTIFFs
+    /// can be huge.
+    fn from_geotiff<R: Read + Seek>(reader: R, bounds: &GPSBounds) -> Result<Option<Tile>> {
+        let tiff = Tiff::new(reader)?;
+        let mut tile = tiff.georeference()?;
+        if !tile.overlaps(bounds) {
+            return Ok(None);
+        }
+        tile.values = tiff.read_values(tile.width, tile.height)?;
+        Ok(Some(tile))
+    }
+}
+
+// Parses "N47W122" into (-122, 47)
+fn parse_hgt_name(name: &str) -> Option<(i32, i32)> {
+    let name = name.to_uppercase();
+    let lat_sign = match name.get(0..1)? {
+        "N" => 1,
+        "S" => -1,
+        _ => {
+            return None;
+        }
+    };
+    let lon_sign = match name.get(3..4)? {
+        "E" => 1,
+        "W" => -1,
+        _ => {
+            return None;
+        }
+    };
+    let lat = name.get(1..3)?.parse::<i32>().ok()?;
+    let lon = name.get(4..7)?.parse::<i32>().ok()?;
+    Some((lon_sign * lon, lat_sign * lat))
+}
+
+// Just enough of the TIFF spec to read one band of samples. Tags are numbered per
+// https://www.awaresystems.be/imaging/tiff/tifftags.html
+const IMAGE_WIDTH: u16 = 256;
+const IMAGE_LENGTH: u16 = 257;
+const BITS_PER_SAMPLE: u16 = 258;
+const COMPRESSION: u16 = 259;
+const STRIP_OFFSETS: u16 = 273;
+const SAMPLES_PER_PIXEL: u16 = 277;
+const ROWS_PER_STRIP: u16 = 278;
+const TILE_WIDTH: u16 = 322;
+const TILE_LENGTH: u16 = 323;
+const TILE_OFFSETS: u16 = 324;
+const SAMPLE_FORMAT: u16 = 339;
+const MODEL_PIXEL_SCALE: u16 = 33550;
+const MODEL_TIEPOINT: u16 = 33922;
+const GEO_KEY_DIRECTORY: u16 = 34735;
+const GDAL_NODATA: u16 = 42113;
+
+/// How many bytes each value of a TIFF field type takes
+fn field_size(field_type: u16) -> Option<usize> {
+    match field_type {
+        1 | 2 | 6 | 7 => Some(1),
+        3 | 8 => Some(2),
+        4 | 9 | 11 => Some(4),
+        5 | 10 | 12 => Some(8),
+        _ => None,
+    }
+}
+
+// GeoKeys, from the GeoTIFF spec
+const GT_RASTER_TYPE: u16 = 1025;
+const RASTER_PIXEL_IS_POINT: u16 = 2;
+const PROJECTED_CS_TYPE: u16 = 3072;
+
+struct Tiff<R> {
+    reader: RefCell<R>,
+    big_endian: bool,
+    // The first image file directory: (tag, field type, count, offset of the value)
+    entries: Vec<(u16, u16, usize, usize)>,
+}
+
+impl<R: Read + Seek> Tiff<R> {
+    /// Only reads the header and the directory of tags.
+    fn new(reader: R) -> Result<Tiff<R>> {
+        let mut tiff = Tiff {
+            reader: RefCell::new(reader),
+            big_endian: false,
+            entries: Vec::new(),
+        };
+        tiff.big_endian = match tiff.read(0, 2)?.as_slice() {
+            b"II" => false,
+            b"MM" => true,
+            _ => bail!("Not a TIFF file"),
+        };
+        if tiff.u16(2)? != 42 {
+            bail!("Only classic TIFF is supported, not BigTIFF");
+        }
+        let ifd = tiff.u32(4)? as usize;
+        for i in 0..tiff.u16(ifd)? as usize {
+            let entry = ifd + 2 + 12 * i;
+            let tag = tiff.u16(entry)?;
+            let field_type = tiff.u16(entry + 2)?;
+            let count = tiff.u32(entry + 4)? as usize;
+            let size = count
+                * field_size(field_type)
+                    .ok_or_else(|| anyhow!("Tag {} has unknown type {}", tag, field_type))?;
+            // Small values are stored inline
+            let offset = if size <= 4 {
+                entry + 8
+            } else {
+                tiff.u32(entry + 8)? as usize
+            };
+            tiff.entries.push((tag, field_type, count, offset));
+        }
+        Ok(tiff)
+    }
+
+    fn read(&self, offset: usize, len: usize) -> Result<Vec<u8>> {
+        let mut reader = self.reader.borrow_mut();
+        let mut buf = vec![0; len];
+        reader
+            .seek(SeekFrom::Start(offset as u64))
+            .and_then(|_| reader.read_exact(&mut buf))
+            .with_context(|| format!("TIFF is truncated at byte {}", offset))?;
+        Ok(buf)
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16> {
+        Ok(self.decode_u16(&self.read(offset, 2)?))
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32> {
+        Ok(self.decode_u32(&self.read(offset, 4)?))
+    }
+
+    fn decode_u16(&self, raw: &[u8]) -> u16 {
+        let b: [u8; 2] = raw[0..2].try_into().unwrap();
+        if self.big_endian {
+            u16::from_be_bytes(b)
+        } else {
+            u16::from_le_bytes(b)
+        }
+    }
+
+    fn decode_u32(&self, raw: &[u8]) -> u32 {
+        let b: [u8; 4] = raw[0..4].try_into().unwrap();
+        if self.big_endian {
+            u32::from_be_bytes(b)
+        } else {
+            u32::from_le_bytes(b)
+        }
+    }
+
+    fn decode_f64(&self, raw: &[u8]) -> f64 {
+        let b: [u8; 8] = raw[0..8].try_into().unwrap();
+        if self.big_endian {
+            f64::from_be_bytes(b)
+        } else {
+            f64::from_le_bytes(b)
+        }
+    }
+
+    /// Read a numeric tag. Returns an empty list if the tag is missing.
+    fn tag(&self, tag: u16) -> Result<Vec<f64>> {
+        let (_, field_type, count, offset) = match self.entries.iter().find(|e| e.0 == tag) {
+            Some(entry) => *entry,
+            None => {
+                return Ok(Vec::new());
+            }
+        };
+        // Read all of the values at once; there may be one per strip or tile
+        let size = field_size(field_type).unwrap();
+        let raw = self.read(offset, count * size)?;
+        let mut values = Vec::new();
+        for i in 0..count {
+            let raw = &raw[size * i..];
+            values.push(match field_type {
+                1 => raw[0] as f64,
+                3 => self.decode_u16(raw) as f64,
+                4 => self.decode_u32(raw) as f64,
+                12 => self.decode_f64(raw),
+                _ => bail!("Tag {} has unsupported type {}", tag, field_type),
+            });
+        }
+        Ok(values)
+    }
+
+    fn single_tag(&self, tag: u16) -> Result<Option<usize>> {
+        Ok(self.tag(tag)?.first().map(|x| *x as usize))
+    }
+
+    fn required_tag(&self, tag: u16) -> Result<usize> {
+        self.single_tag(tag)?
+            .ok_or_else(|| anyhow!("Missing TIFF tag {}", tag))
+    }
+
+    fn ascii_tag(&self, tag: u16) -> Result<Option<String>> {
+        match self.entries.iter().find(|e| e.0 == tag) {
+            Some((_, 2, count, offset)) => {
+                let raw = self.read(*offset, *count)?;
+                Ok(Some(
+                    String::from_utf8_lossy(&raw)
+                        .trim_end_matches('\0')
+                        .trim()
+                        .to_string(),
+                ))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Figure out where the samples are, but don't read them yet.
+    fn georeference(&self) -> Result<Tile> {
+        let width = self.required_tag(IMAGE_WIDTH)?;
+        let height = self.required_tag(IMAGE_LENGTH)?;
+        if width == 0 || height == 0 {
+            bail!("TIFF has no samples");
+        }
+        let scale = self.tag(MODEL_PIXEL_SCALE)?;
+        let tiepoint = self.tag(MODEL_TIEPOINT)?;
+        if scale.len() < 2 || tiepoint.len() < 6 {
+            bail!("Missing GeoTIFF pixel scale or tiepoint");
+        }
+        if !(scale[0] > 0.0 && scale[1] > 0.0) {
+            bail!("GeoTIFF pixel scale must be positive");
+        }
+
+        let mut pixel_is_point = false;
+        // The key directory is a header of 4 values, then 4 values per key
+        let keys = self.tag(GEO_KEY_DIRECTORY)?;
+        for key in keys.chunks_exact(4).skip(1) {
+            let id = key[0] as u16;
+            if id == PROJECTED_CS_TYPE {
+                bail!("Projected coordinate systems aren't supported; reproject to EPSG:4326");
+            }
+            // Short values are stored directly in the last field
+            if id == GT_RASTER_TYPE && key[1] == 0.0 && key[3] as u16 == RASTER_PIXEL_IS_POINT {
+                pixel_is_point = true;
+            }
+        }
+
+        let (dx, dy) = (scale[0], scale[1]);
+        // The tiepoint maps raster (i, j) to the world (x, y). With the default pixel-is-area
+        // interpretation, raster coordinates refer to the corner of a pixel, not its center.
+        let center = if pixel_is_point { 0.0 } else { 0.5 };
+        let lon0 = tiepoint[3] + (center - tiepoint[0]) * dx;
+        let lat0 = tiepoint[4] - (center - tiepoint[1]) * dy;
+        if !(-180.0..=180.0).contains(&lon0) || !(-90.0..=90.0).contains(&lat0) {
+            bail!("GeoTIFF doesn't seem to use longitude and latitude");
+        }
+
+        let nodata = match self.ascii_tag(GDAL_NODATA)? {
+            Some(x) => Some(x.parse::<f32>()?),
+            None => None,
+        };
+        Ok(Tile {
+            lon0,
+            lat0,
+            dx,
+            dy,
+            width,
+            height,
+            values: Vec::new(),
+            nodata,
+        })
+    }
+
+    fn read_values(&self, width: usize, height: usize) -> Result<Vec<f32>> {
+        if self.single_tag(COMPRESSION)?.unwrap_or(1) != 1 {
+            bail!("Only uncompressed GeoTIFFs are supported");
+        }
+        if self.single_tag(SAMPLES_PER_PIXEL)?.unwrap_or(1) != 1 {
+            bail!("Only single-band GeoTIFFs are supported");
+        }
+        let bits = self.single_tag(BITS_PER_SAMPLE)?.unwrap_or(1);
+        let format = self.single_tag(SAMPLE_FORMAT)?.unwrap_or(1);
+        let bytes_per_sample = bits / 8;
+        let decode = |raw: &[u8]| -> Result<f32> {
+            let b = |n: usize| -> Vec<u8> {
+                if self.big_endian {
+                    raw[0..n].iter().rev().cloned().collect()
+                } else {
+                    raw[0..n].to_vec()
+                }
+            };
+            Ok(match (format, bits) {
+                (1, 8) => raw[0] as f32,
+                (2, 8) => raw[0] as i8 as f32,
+                (1, 16) => u16::from_le_bytes(b(2).try_into().unwrap()) as f32,
+                (2, 16) => i16::from_le_bytes(b(2).try_into().unwrap()) as f32,
+                (1, 32) => u32::from_le_bytes(b(4).try_into().unwrap()) as f32,
+                (2, 32) => i32::from_le_bytes(b(4).try_into().unwrap()) as f32,
+                (3, 32) => f32::from_le_bytes(b(4).try_into().unwrap()),
+                (3, 64) => f64::from_le_bytes(b(8).try_into().unwrap()) as f32,
+                _ => bail!("Unsupported sample format {} with {} bits", format, bits),
+            })
+        };
+
+        let mut values = vec![f32::NAN; width * height];
+        // The samples are split into either horizontal strips or rectangular tiles, stored
+        // anywhere in the file
+        let (block_width, block_height, offsets) = if self.single_tag(TILE_WIDTH)?.is_some() {
+            (
+                self.required_tag(TILE_WIDTH)?,
+                self.required_tag(TILE_LENGTH)?,
+                self.tag(TILE_OFFSETS)?,
+            )
+        } else {
+            (
+                width,
+                self.single_tag(ROWS_PER_STRIP)?.unwrap_or(height),
+                self.tag(STRIP_OFFSETS)?,
+            )
+        };
+        if block_width == 0 || block_height == 0 {
+            bail!("TIFF has empty strips or tiles");
+        }
+        let blocks_across = width.div_ceil(block_width);
+        for (idx, offset) in offsets.into_iter().enumerate() {
+            let (block_col, block_row) = (idx % blocks_across, idx / blocks_across);
+            let first_row = block_row * block_height;
+            if first_row >= height {
+                break;
+            }
+            // The last strip may be shorter. Tiles are padded past the bottom of the image, but
+            // those rows aren't needed.
+            let rows = block_height.min(height - first_row);
+            let raw = self.read(offset as usize, rows * block_width * bytes_per_sample)?;
+            for y in 0..rows {
+                for x in 0..block_width {
+                    let col = block_col * block_width + x;
+                    if col >= width {
+                        // Tiles are padded past the edge of the image
+                        continue;
+                    }
+                    values[(first_row + y) * width + col] =
+                        decode(&raw[(y * block_width + x) * bytes_per_sample..])?;
+                }
+            }
+        }
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hgt() {
+        assert_eq!(parse_hgt_name("N47W122"), Some((-122, 47)));
+        assert_eq!(parse_hgt_name("s01e009"), Some((9, -1)));
+        assert_eq!(parse_hgt_name("elevation"), None);
+
+        // A 3x3 grid, with one void
+        let mut bytes = Vec::new();
+        for value in [10, 20, 30, 40, 50, 60, 70, 80, -32768_i16] {
+            bytes.extend(value.to_be_bytes());
+        }
+        let tile = Tile::from_hgt(-122, 47, &bytes).unwrap();
+        // The northwest corner
+        assert_eq!(tile.sample(LonLat::new(-122.0, 48.0)), Some(10.0));
+        // The center
+        assert_eq!(tile.sample(LonLat::new(-121.5, 47.5)), Some(50.0));
+        // Halfway between two samples
+        assert_eq!(tile.sample(LonLat::new(-121.75, 48.0)), Some(15.0));
+        // Only valid samples count
+        assert_eq!(tile.sample(LonLat::new(-121.0, 47.0)), None);
+        assert_eq!(tile.sample(LonLat::new(-121.0, 47.25)), Some(60.0));
+        // Outside the tile entirely
+        assert_eq!(tile.sample(LonLat::new(-120.0, 47.5)), None);
+    }
+
+    enum Value {
+        Short(Vec<u16>),
+        Long(Vec<u32>),
+        Double(Vec<f64>),
+        Ascii(&'static str),
+    }
+
+    /// Lay out a TIFF with `data` right after the header, followed by one image file directory
+    /// with these tags. Values too big to fit in the directory go after it.
+    fn encode_tiff(big_endian: bool, data: &[u8], tags: Vec<(u16, Value)>) -> Vec<u8> {
+        let u16b = |x: u16| {
+            if big_endian {
+                x.to_be_bytes()
+            } else {
+                x.to_le_bytes()
+            }
+        };
+        let u32b = |x: u32| {
+            if big_endian {
+                x.to_be_bytes()
+            } else {
+                x.to_le_bytes()
+            }
+        };
+        let f64b = |x: f64| {
+            if big_endian {
+                x.to_be_bytes()
+            } else {
+                x.to_le_bytes()
+            }
+        };
+
+        let ifd = 8 + data.len();
+        let mut out = Vec::new();
+        out.extend(if big_endian { b"MM" } else { b"II" });
+        out.extend(u16b(42));
+        out.extend(u32b(ifd as u32));
+        out.extend(data);
+
+        let extra_start = ifd + 2 + 12 * tags.len() + 4;
+        let mut extra = Vec::new();
+        out.extend(u16b(tags.len() as u16));
+        for (tag, value) in tags {
+            let (field_type, count, bytes): (u16, usize, Vec<u8>) = match value {
+                Value::Short(v) => (3, v.len(), v.into_iter().flat_map(u16b).collect()),
+                Value::Long(v) => (4, v.len(), v.into_iter().flat_map(u32b).collect()),
+                Value::Double(v) => (12, v.len(), v.into_iter().flat_map(f64b).collect()),
+                Value::Ascii(x) => (2, x.len() + 1, format!("{}\0", x).into_bytes()),
+            };
+            out.extend(u16b(tag));
+            out.extend(u16b(field_type));
+            out.extend(u32b(count as u32));
+            if bytes.len() <= 4 {
+                let mut inline = bytes;
+                inline.resize(4, 0);
+                out.extend(inline);
+            } else {
+                out.extend(u32b((extra_start + extra.len()) as u32));
+                extra.extend(bytes);
+            }
+        }
+        out.extend(u32b(0));
+        out.extend(extra);
+        out
+    }
+
+    fn everywhere() -> GPSBounds {
+        GPSBounds {
+            min_lon: -180.0,
+            max_lon: 180.0,
+            min_lat: -90.0,
+            max_lat: 90.0,
+        }
+    }
+
+    fn load(bytes: Vec<u8>, bounds: &GPSBounds) -> Result<Option<Tile>> {
+        Tile::from_geotiff(std::io::Cursor::new(bytes), bounds)
+    }
+
+    /// A little-endian grid of signed 16-bit samples, 2 rows tall, split into strips
+    fn strips(width: u32, rows_per_strip: u16, strip_offsets: Vec<u32>) -> Vec<u8> {
+        let mut data = Vec::new();
+        for value in [100_i16, 200, 300, 400, 500, -9999] {
+            data.extend(value.to_le_bytes());
+        }
+        encode_tiff(
+            false,
+            &data,
+            vec![
+                (IMAGE_WIDTH, Value::Long(vec![width])),
+                (IMAGE_LENGTH, Value::Long(vec![2])),
+                (BITS_PER_SAMPLE, Value::Short(vec![16])),
+                (STRIP_OFFSETS, Value::Long(strip_offsets)),
+                (ROWS_PER_STRIP, Value::Short(vec![rows_per_strip])),
+                (SAMPLE_FORMAT, Value::Short(vec![2])),
+                (MODEL_PIXEL_SCALE, Value::Double(vec![0.25, 0.25, 0.0])),
+                (
+                    MODEL_TIEPOINT,
+                    Value::Double(vec![0.0, 0.0, 0.0, -122.0, 48.0, 0.0]),
+                ),
+                (GDAL_NODATA, Value::Ascii("-9999")),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_geotiff_strips() {
+        let tile = load(strips(3, 1, vec![8, 14]), &everywhere())
+            .unwrap()
+            .unwrap();
+        // Pixels are areas by default, so the first sample is in the middle of the first pixel
+        assert_eq!((tile.lon0, tile.lat0), (-121.875, 47.875));
+        assert_eq!(tile.nodata, Some(-9999.0));
+        assert_eq!(
+            tile.values,
+            vec![100.0, 200.0, 300.0, 400.0, 500.0, -9999.0]
+        );
+
+        assert_eq!(tile.sample(LonLat::new(-121.875, 47.875)), Some(100.0));
+        assert_eq!(tile.sample(LonLat::new(-121.75, 47.875)), Some(150.0));
+        // The missing sample
+        assert_eq!(tile.sample(LonLat::new(-121.375, 47.625)), None);
+
+        // The same samples in one strip
+        let tile = load(strips(3, 2, vec![8]), &everywhere()).unwrap().unwrap();
+        assert_eq!(
+            tile.values,
+            vec![100.0, 200.0, 300.0, 400.0, 500.0, -9999.0]
+        );
+    }
+
+    #[test]
+    fn test_geotiff_tiles() {
+        // A big-endian 3x3 grid of unsigned samples, split into 2x2 tiles padded past the edges
+        let mut data = Vec::new();
+        for value in [1_u16, 2, 4, 5, 3, 0, 6, 0, 7, 8, 0, 0, 9, 0, 0, 0] {
+            data.extend(value.to_be_bytes());
+        }
+        let bytes = encode_tiff(
+            true,
+            &data,
+            vec![
+                (IMAGE_WIDTH, Value::Short(vec![3])),
+                (IMAGE_LENGTH, Value::Short(vec![3])),
+                (BITS_PER_SAMPLE, Value::Short(vec![16])),
+                (TILE_WIDTH, Value::Short(vec![2])),
+                (TILE_LENGTH, Value::Short(vec![2])),
+                (TILE_OFFSETS, Value::Long(vec![8, 16, 24, 32])),
+                (MODEL_PIXEL_SCALE, Value::Double(vec![0.5, 0.5, 0.0])),
+                (
+                    MODEL_TIEPOINT,
+                    Value::Double(vec![0.0, 0.0, 0.0, 10.0, 20.0, 0.0]),
+                ),
+                // Pixels are points
+                (
+                    GEO_KEY_DIRECTORY,
+                    Value::Short(vec![
+                        1,
+                        1,
+                        0,
+                        1,
+                        GT_RASTER_TYPE,
+                        0,
+                        1,
+                        RASTER_PIXEL_IS_POINT,
+                    ]),
+                ),
+            ],
+        );
+        let tile = load(bytes, &everywhere()).unwrap().unwrap();
+        assert_eq!((tile.lon0, tile.lat0), (10.0, 20.0));
+        assert_eq!(
+            tile.values,
+            vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0]
+        );
+        assert_eq!(tile.sample(LonLat::new(11.0, 19.0)), Some(9.0));
+    }
+
+    #[test]
+    fn test_geotiff_header_first() {
+        // The strips are past the end of the file, but the header shows the tile is somewhere
+        // else, so they're never read
+        let missing = vec![1000, 1006];
+        let elsewhere = GPSBounds {
+            min_lon: 0.0,
+            max_lon: 1.0,
+            min_lat: 0.0,
+            max_lat: 1.0,
+        };
+        assert!(load(strips(3, 1, missing.clone()), &elsewhere)
+            .unwrap()
+            .is_none());
+        // But reading them fails
+        assert!(load(strips(3, 1, missing), &everywhere()).is_err());
+    }
+
+    #[test]
+    fn test_geotiff_errors() {
+        assert!(load(b"not a tiff".to_vec(), &everywhere()).is_err());
+        assert!(load(Vec::new(), &everywhere()).is_err());
+        // No samples
+        assert!(load(strips(0, 1, vec![8, 14]), &everywhere()).is_err());
+        // Strips with no rows
+        assert!(load(strips(3, 0, vec![8, 14]), &everywhere()).is_err());
+
+        // A projected coordinate system
+        let bytes = encode_tiff(
+            false,
+            &[],
+            vec![
+                (IMAGE_WIDTH, Value::Short(vec![1])),
+                (IMAGE_LENGTH, Value::Short(vec![1])),
+                (MODEL_PIXEL_SCALE, Value::Double(vec![30.0, 30.0, 0.0])),
+                (
+                    MODEL_TIEPOINT,
+                    Value::Double(vec![0.0, 0.0, 0.0, 500000.0, 5000000.0, 0.0]),
+                ),
+                (
+                    GEO_KEY_DIRECTORY,
+                    Value::Short(vec![1, 1, 0, 1, PROJECTED_CS_TYPE, 0, 1, 32610]),
+                ),
+            ],
+        );
+        assert!(load(bytes, &everywhere()).is_err());
+    }
+}
@@ -7,7 +7,61 @@ use fs_err::File;
 use geom::{Distance, PolyLine};
 use raw_map::{OriginalRoad, RawMap};
 
+use crate::dem::Dem;
+
+/// Fill out the elevation of every intersection and the incline of every road. If .hgt or GeoTIFF
+/// files in data/input/shared/elevation cover the map, read them directly. Otherwise, run the
+/// elevation_lookups tool through Docker.
 pub fn add_data(map: &mut RawMap) -> Result<()> {
+    match Dem::load(&abstio::path_shared_input("elevation"), &map.gps_bounds) {
+        Ok(dem) => add_data_from_dem(map, &dem),
+        Err(err) => {
+            info!("{}, so using elevation_lookups through Docker", err);
+            add_data_from_docker(map)
+        }
+    }
+}
+
+fn add_data_from_dem(map: &mut RawMap, dem: &Dem) -> Result<()> {
+    let mut missing = 0;
+    let mut found = 0;
+    // Like the Docker path, take the elevation of each intersection from the ends of the road
+    // geometry, before it's trimmed.
+    let mut updates = Vec::new();
+    for (id, r) in &map.roads {
+        for (i, pt) in [
+            (id.i1, r.osm_center_points[0]),
+            (id.i2, *r.osm_center_points.last().unwrap()),
+        ] {
+            match dem.elevation(pt.to_gps(&map.gps_bounds)) {
+                Some(elevation) => {
+                    found += 1;
+                    updates.push((i, elevation));
+                }
+                None => {
+                    missing += 1;
+                }
+            }
+        }
+    }
+    if found == 0 {
+        bail!("The elevation data doesn't cover any roads");
+    }
+    if missing > 0 {
+        warn!(
+            "The elevation data doesn't cover {} road endpoints; they'll be flat",
+            missing
+        );
+    }
+    for (i, elevation) in updates {
+        map.intersections.get_mut(&i).unwrap().elevation = elevation;
+    }
+
+    calculate_inclines(map);
+    Ok(())
+}
+
+fn add_data_from_docker(map: &mut RawMap) -> Result<()> {
     let input = format!("elevation_input_{}", map.name.as_filename());
     let output = format!("elevation_output_{}", map.name.as_filename());
 
@@ -134,6 +188,11 @@ fn scrape_output(output: &str, map: &mut RawMap, ids: Vec<OriginalRoad>) -> Resu
         bail!("Output had {} lines, but we made {} queries", cnt, num_ids);
     }
 
+    calculate_inclines(map);
+    Ok(())
+}
+
+fn calculate_inclines(map: &mut RawMap) {
     // Calculate the incline for each road here, before the road gets trimmed for intersection
     // geometry. If we did this after trimming, we'd miss some of the horizontal distance.
     for (id, road) in &mut map.roads {
@@ -155,6 +214,4 @@ fn scrape_output(output: &str, map: &mut RawMap, ids: Vec<OriginalRoad>) -> Resu
             );
         }
     }
-
-    Ok(())
 }
@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 
 use osm::{NodeID, OsmID, RelationID, WayID};
 
@@ -7,8 +7,8 @@ use abstutil::{Tags, Timer};
 use geom::{Distance, FindClosest, HashablePt2D, Polygon, Pt2D, Ring};
 use kml::{ExtraShape, ExtraShapes};
 use raw_map::{
-    osm, Amenity, AreaType, Direction, DrivingSide, NamePerLanguage, RawArea, RawBuilding, RawMap,
-    RawParkingLot, RestrictionType,
+    osm, Amenity, AreaType, CrossingType, Direction, DrivingSide, NamePerLanguage, RawArea,
+    RawBuilding, RawMap, RawParkingLot, RestrictionType, TimeWindow,
 };
 
 use crate::osm_geom::{get_multipolygon_members, glue_multipolygon, multipoly_geometry};
@@ -24,10 +24,12 @@ pub struct OsmExtract {
     pub simple_turn_restrictions: Vec<(RestrictionType, WayID, NodeID, WayID)>,
     /// (relation ID, from way ID, via way ID, to way ID)
     pub complicated_turn_restrictions: Vec<(RelationID, WayID, WayID, WayID)>,
+    /// (from way ID, via node ID, to way ID, windows). Turns banned only at some times of day.
+    pub conditional_turn_restrictions: Vec<(WayID, NodeID, WayID, Vec<TimeWindow>)>,
     /// (location, amenity)
     pub amenities: Vec<(Pt2D, Amenity)>,
     /// Crosswalks located at these points, which should be on a RawRoad's center line
-    pub crosswalks: HashSet<HashablePt2D>,
+    pub crosswalks: HashMap<HashablePt2D, (NodeID, CrossingType)>,
 }
 
 pub fn extract_osm(
@@ -64,8 +66,9 @@ pub fn extract_osm(
         osm_node_ids: HashMap::new(),
         simple_turn_restrictions: Vec::new(),
         complicated_turn_restrictions: Vec::new(),
+        conditional_turn_restrictions: Vec::new(),
         amenities: Vec::new(),
-        crosswalks: HashSet::new(),
+        crosswalks: HashMap::new(),
     };
 
     timer.start_iter("processing OSM nodes", doc.nodes.len());
@@ -82,7 +85,15 @@ pub fn extract_osm(
             out.traffic_signals.insert(node.pt.to_hashable(), dir);
         }
         if node.tags.is(osm::HIGHWAY, "crossing") {
-            out.crosswalks.insert(node.pt.to_hashable());
+            let crossing = if node.tags.is("crossing", "traffic_signals") {
+                CrossingType::Signalized
+            } else if node.tags.is("crossing", "unmarked") {
+                CrossingType::Unmarked
+            } else {
+                CrossingType::Marked
+            };
+            out.crosswalks
+                .insert(node.pt.to_hashable(), (*id, crossing));
         }
         for amenity in get_bldg_amenities(&node.tags) {
             out.amenities.push((node.pt, amenity));
@@ -246,6 +257,17 @@ pub fn extract_osm(
                     }
                 }
             }
+            // Like "no_left_turn @ (Mo-Fr 07:00-09:00)". Only handle bans via a node for now.
+            if let Some(conditional) = rel.tags.get("restriction:conditional") {
+                if let (Some(from), Some(via), Some(to)) = (from_way_id, via_node_id, to_way_id) {
+                    for (restriction, windows) in TimeWindow::parse_osm_conditional(conditional) {
+                        if RestrictionType::new(&restriction) == Some(RestrictionType::BanTurns) {
+                            out.conditional_turn_restrictions
+                                .push((from, via, to, windows));
+                        }
+                    }
+                }
+            }
         } else if is_bldg(&rel.tags) {
             match multipoly_geometry(id, rel, &doc) {
                 Ok(polygon) => {
@@ -5,11 +5,13 @@ use fs_err::File;
 use serde::Deserialize;
 
 use abstutil::MultiMap;
-use geom::{LonLat, PolyLine, Pt2D};
+use geom::{Duration, LonLat, PolyLine, Pt2D, Time};
 use kml::{ExtraShape, ExtraShapes};
 use raw_map::{RawMap, RawTransitRoute, RawTransitStop, RawTransitType};
 
-pub fn import(map: &mut RawMap) -> Result<()> {
+/// Imports routes, stops, and the timetable for one service day. If `service_day` is unspecified,
+/// use trips that normally run on Wednesdays.
+pub fn import(map: &mut RawMap, service_day: Option<&str>) -> Result<()> {
     // Collect metadata about routes
     for rec in csv::Reader::from_reader(File::open(map.name.city.input_path("gtfs/routes.txt"))?)
         .deserialize()
@@ -34,9 +36,12 @@ pub fn import(map: &mut RawMap) -> Result<()> {
             shape: PolyLine::dummy(),
             stops: Vec::new(),
             route_type,
+            schedule: Vec::new(),
         });
     }
 
+    let active_services = active_services(map, service_day)?;
+
     // Map route_id to shape_id
     let mut route_to_shapes = MultiMap::new();
     // Map (route_id, shape_id) to trip_id
@@ -45,6 +50,11 @@ pub fn import(map: &mut RawMap) -> Result<()> {
         .deserialize()
     {
         let rec: Trip = rec?;
+        if let Some(ref services) = active_services {
+            if !services.contains(&rec.service_id) {
+                continue;
+            }
+        }
         route_to_shapes.insert(rec.route_id.clone(), rec.shape_id.clone());
         route_and_shape_to_trips.insert((rec.route_id, rec.shape_id), rec.trip_id);
     }
@@ -72,7 +82,10 @@ pub fn import(map: &mut RawMap) -> Result<()> {
     for mut route in map.transit_routes.drain(..) {
         let shape_ids = route_to_shapes.get(RouteID(route.gtfs_id.clone()));
         if shape_ids.is_empty() {
-            warn!("Route {} has no shape", route.gtfs_id);
+            warn!(
+                "Route {} has no shape, or doesn't run on the service day",
+                route.gtfs_id
+            );
             continue;
         }
         if shape_ids.len() > 1 {
@@ -107,8 +120,7 @@ pub fn import(map: &mut RawMap) -> Result<()> {
     }
     map.transit_routes = transit_routes;
 
-    // For now, every route uses exactly one trip ID, and there's no schedule. Just pick an
-    // arbitrary trip per route.
+    // Every route has one sequence of stops. Pick an arbitrary trip per route to define it.
     let mut route_to_trip = HashMap::new();
     for (route_id, shape_id) in &route_to_shape {
         let trips = route_and_shape_to_trips.get((route_id.clone(), shape_id.clone()));
@@ -117,28 +129,98 @@ pub fn import(map: &mut RawMap) -> Result<()> {
         }
     }
 
-    // Scrape the trip ID -> (stop ID, sequence number)
-    let mut trip_to_stops: HashMap<TripID, Vec<(StopID, usize)>> = HashMap::new();
+    // Scrape the trip ID -> (stop ID, sequence number, departure time)
+    let mut trip_to_stops: HashMap<TripID, Vec<(StopID, usize, Option<Time>)>> = HashMap::new();
     for rec in
         csv::Reader::from_reader(File::open(map.name.city.input_path("gtfs/stop_times.txt"))?)
             .deserialize()
     {
         let rec: StopTime = rec?;
+        let time = if !rec.departure_time.trim().is_empty() {
+            Some(Time::parse(rec.departure_time.trim())?)
+        } else if !rec.arrival_time.trim().is_empty() {
+            Some(Time::parse(rec.arrival_time.trim())?)
+        } else {
+            None
+        };
         trip_to_stops
             .entry(rec.trip_id)
             .or_insert_with(Vec::new)
-            .push((rec.stop_id, rec.stop_sequence));
+            .push((rec.stop_id, rec.stop_sequence, time));
+    }
+    for stops in trip_to_stops.values_mut() {
+        stops.sort_by_key(|(_, seq, _)| *seq);
     }
 
-    // Assign the stops for every route
+    // Some trips are repeated at a fixed headway, instead of listed individually. The stop times
+    // for these trips just describe the pattern. (start time, end time, headway)
+    let mut frequencies: HashMap<TripID, Vec<(Time, Time, Duration)>> = HashMap::new();
+    let frequencies_path = map.name.city.input_path("gtfs/frequencies.txt");
+    if abstio::file_exists(&frequencies_path) {
+        for rec in csv::Reader::from_reader(File::open(frequencies_path)?).deserialize() {
+            let rec: Frequency = rec?;
+            if rec.headway_secs == 0 {
+                continue;
+            }
+            frequencies
+                .entry(rec.trip_id)
+                .or_insert_with(Vec::new)
+                .push((
+                    Time::parse(rec.start_time.trim())?,
+                    Time::parse(rec.end_time.trim())?,
+                    Duration::seconds(rec.headway_secs as f64),
+                ));
+        }
+    }
+
+    // Assign the stops and timetable for every route
     let mut stop_ids = HashSet::new();
     for route in &mut map.transit_routes {
-        let trip_id = route_to_trip[&RouteID(route.gtfs_id.clone())];
-        let mut stops = trip_to_stops.remove(&trip_id).unwrap_or_else(Vec::new);
-        stops.sort_by_key(|(_, seq)| *seq);
-        for (stop_id, _) in stops {
+        let route_id = RouteID(route.gtfs_id.clone());
+        let trip_id = route_to_trip[&route_id];
+        let pattern: Vec<StopID> = trip_to_stops
+            .get(trip_id)
+            .map(|stops| stops.iter().map(|(id, _, _)| id.clone()).collect())
+            .unwrap_or_else(Vec::new);
+        for stop_id in &pattern {
             route.stops.push(stop_id.0.clone());
-            stop_ids.insert(stop_id);
+            stop_ids.insert(stop_id.clone());
+        }
+
+        let mut different_stops = 0;
+        for trip_id in
+            route_and_shape_to_trips.get((route_id.clone(), route_to_shape[&route_id].clone()))
+        {
+            let stops = match trip_to_stops.get(trip_id) {
+                Some(stops) => stops,
+                None => continue,
+            };
+            // A route only has one sequence of stops, so skip trips that short-turn or take a
+            // variant
+            if stops.len() != pattern.len()
+                || stops.iter().zip(pattern.iter()).any(|(a, b)| a.0 != *b)
+            {
+                different_stops += 1;
+                continue;
+            }
+            let times = match interpolate_times(stops.iter().map(|(_, _, t)| *t).collect()) {
+                Some(times) => times,
+                None => {
+                    warn!("Trip {:?} doesn't have a time at both ends", trip_id);
+                    continue;
+                }
+            };
+            if let Some(list) = frequencies.get(trip_id) {
+                route.schedule.extend(expand_frequencies(&times, list));
+            } else {
+                route.schedule.push(times);
+            }
+        }
+        if different_stops > 0 {
+            info!(
+                "Skipped {} trips on route {} that serve different stops",
+                different_stops, route.gtfs_id
+            );
         }
     }
 
@@ -165,10 +247,20 @@ pub fn import(map: &mut RawMap) -> Result<()> {
     // Make sure all of the stops are valid and used by some route
     let mut used_stops = HashSet::new();
     for route in &mut map.transit_routes {
-        route.stops.retain(|stop_id| {
-            used_stops.insert(stop_id.clone());
-            map.transit_stops.contains_key(stop_id)
-        });
+        let keep: Vec<bool> = route
+            .stops
+            .iter()
+            .map(|stop_id| {
+                used_stops.insert(stop_id.clone());
+                map.transit_stops.contains_key(stop_id)
+            })
+            .collect();
+        let mut iter = keep.iter();
+        route.stops.retain(|_| *iter.next().unwrap());
+        for stop_times in &mut route.schedule {
+            let mut iter = keep.iter();
+            stop_times.retain(|_| *iter.next().unwrap());
+        }
     }
     map.transit_routes.retain(|route| !route.stops.is_empty());
     map.transit_stops
@@ -181,6 +273,136 @@ pub fn import(map: &mut RawMap) -> Result<()> {
     Ok(())
 }
 
+/// Returns the services running on the chosen day, or None if the feed has no usable calendar, so
+/// every trip should be used.
+fn active_services(map: &RawMap, service_day: Option<&str>) -> Result<Option<HashSet<ServiceID>>> {
+    let calendar_path = map.name.city.input_path("gtfs/calendar.txt");
+    let dates_path = map.name.city.input_path("gtfs/calendar_dates.txt");
+    let day = if let Some(day) = service_day {
+        let date = day.parse::<u32>()?;
+        if day.len() != 8 || !(1..=12).contains(&((date / 100) % 100)) {
+            bail!("GTFS service day {} isn't formatted like 20210421", day);
+        }
+        Some(date)
+    } else {
+        None
+    };
+    if !abstio::file_exists(&calendar_path) && (day.is_none() || !abstio::file_exists(&dates_path))
+    {
+        warn!("No GTFS calendar, so using trips from every service");
+        return Ok(None);
+    }
+
+    let mut calendar = Vec::new();
+    if abstio::file_exists(&calendar_path) {
+        for rec in csv::Reader::from_reader(File::open(calendar_path)?).deserialize() {
+            calendar.push(rec?);
+        }
+    }
+    let mut dates = Vec::new();
+    if day.is_some() && abstio::file_exists(&dates_path) {
+        for rec in csv::Reader::from_reader(File::open(dates_path)?).deserialize() {
+            dates.push(rec?);
+        }
+    }
+    let services = services_running(calendar, dates, day);
+    if services.is_empty() {
+        warn!("No GTFS services run on the chosen day");
+    }
+    Ok(Some(services))
+}
+
+/// Applies the regular calendar, then any exceptions for the chosen date. With no date, services
+/// that normally run on Wednesdays are used.
+fn services_running(
+    calendar: Vec<Calendar>,
+    dates: Vec<CalendarDate>,
+    day: Option<u32>,
+) -> HashSet<ServiceID> {
+    let mut services = HashSet::new();
+    for rec in calendar {
+        let runs = if let Some(day) = day {
+            rec.start_date <= day && day <= rec.end_date && rec.runs_on(day_of_week(day))
+        } else {
+            rec.wednesday == 1
+        };
+        if runs {
+            services.insert(rec.service_id);
+        }
+    }
+    // Exceptions to the regular calendar only make sense for a specific date
+    if let Some(day) = day {
+        for rec in dates {
+            if rec.date != day {
+                continue;
+            }
+            // See https://developers.google.com/transit/gtfs/reference#calendar_datestxt
+            match rec.exception_type {
+                1 => {
+                    services.insert(rec.service_id);
+                }
+                2 => {
+                    services.remove(&rec.service_id);
+                }
+                _ => {}
+            }
+        }
+    }
+    services
+}
+
+/// For a date like 20210421, returns 0 for Sunday, 1 for Monday, etc.
+fn day_of_week(date: u32) -> usize {
+    // Sakamoto's method
+    const OFFSETS: [u32; 12] = [0, 3, 2, 5, 0, 3, 5, 1, 4, 6, 2, 4];
+    let (mut year, month, day) = (date / 10000, (date / 100) % 100, date % 100);
+    if month < 3 {
+        year -= 1;
+    }
+    ((year + year / 4 - year / 100 + year / 400 + OFFSETS[(month - 1) as usize] + day) % 7) as usize
+}
+
+/// GTFS only requires times at some stops along a trip. Fill in the others, assuming the vehicle
+/// takes the same time between every stop. None if the first or last stop is missing a time.
+fn interpolate_times(times: Vec<Option<Time>>) -> Option<Vec<Time>> {
+    if times.first()?.is_none() || times.last()?.is_none() {
+        return None;
+    }
+
+    let mut results = Vec::new();
+    let mut prev = (0, times[0].unwrap());
+    for (idx, time) in times.iter().enumerate() {
+        if let Some(t) = time {
+            prev = (idx, *t);
+            results.push(*t);
+            continue;
+        }
+        let (next_idx, next_time) = times
+            .iter()
+            .enumerate()
+            .skip(idx)
+            .find_map(|(i, t)| t.map(|t| (i, t)))
+            .unwrap();
+        let pct = ((idx - prev.0) as f64) / ((next_idx - prev.0) as f64);
+        results.push(prev.1 + pct * (next_time - prev.1));
+    }
+    Some(results)
+}
+
+/// Repeats the pattern of stop times for a trip, departing the first stop every headway within each
+/// (start time, end time, headway) window. The end time is exclusive.
+fn expand_frequencies(times: &[Time], windows: &[(Time, Time, Duration)]) -> Vec<Vec<Time>> {
+    let mut trips = Vec::new();
+    for (start, end, headway) in windows {
+        let mut departure = *start;
+        while departure < *end {
+            trips.push(times.iter().map(|t| departure + (*t - times[0])).collect());
+            departure += *headway;
+        }
+    }
+    trips
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
 struct ShapeID(String);
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
@@ -189,6 +411,8 @@ struct TripID(String);
 struct StopID(String);
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
 struct RouteID(String);
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+struct ServiceID(String);
 
 #[derive(Deserialize)]
 struct Route {
@@ -206,6 +430,7 @@ struct Trip {
     route_id: RouteID,
     shape_id: ShapeID,
     trip_id: TripID,
+    service_id: ServiceID,
 }
 
 #[derive(Deserialize)]
@@ -229,6 +454,55 @@ struct StopTime {
     trip_id: TripID,
     stop_id: StopID,
     stop_sequence: usize,
+    // These may be blank, except for the first and last stop of a trip
+    #[serde(default)]
+    arrival_time: String,
+    #[serde(default)]
+    departure_time: String,
+}
+
+#[derive(Deserialize)]
+struct Frequency {
+    trip_id: TripID,
+    start_time: String,
+    end_time: String,
+    headway_secs: usize,
+}
+
+#[derive(Deserialize)]
+struct Calendar {
+    service_id: ServiceID,
+    monday: u8,
+    tuesday: u8,
+    wednesday: u8,
+    thursday: u8,
+    friday: u8,
+    saturday: u8,
+    sunday: u8,
+    start_date: u32,
+    end_date: u32,
+}
+
+impl Calendar {
+    fn runs_on(&self, day_of_week: usize) -> bool {
+        let flag = match day_of_week {
+            0 => self.sunday,
+            1 => self.monday,
+            2 => self.tuesday,
+            3 => self.wednesday,
+            4 => self.thursday,
+            5 => self.friday,
+            _ => self.saturday,
+        };
+        flag == 1
+    }
+}
+
+#[derive(Deserialize)]
+struct CalendarDate {
+    service_id: ServiceID,
+    date: u32,
+    exception_type: usize,
 }
 
 fn dump_kml(map: &RawMap) {
@@ -262,3 +536,163 @@ fn dump_kml(map: &RawMap) {
         &ExtraShapes { shapes },
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calendar(service_id: &str, weekdays: u8, weekends: u8) -> Calendar {
+        Calendar {
+            service_id: ServiceID(service_id.to_string()),
+            monday: weekdays,
+            tuesday: weekdays,
+            wednesday: weekdays,
+            thursday: weekdays,
+            friday: weekdays,
+            saturday: weekends,
+            sunday: weekends,
+            start_date: 20210101,
+            end_date: 20211231,
+        }
+    }
+
+    fn exception(service_id: &str, date: u32, exception_type: usize) -> CalendarDate {
+        CalendarDate {
+            service_id: ServiceID(service_id.to_string()),
+            date,
+            exception_type,
+        }
+    }
+
+    fn ids(services: HashSet<ServiceID>) -> Vec<String> {
+        let mut ids: Vec<String> = services.into_iter().map(|id| id.0).collect();
+        ids.sort();
+        ids
+    }
+
+    fn time(hours: usize, minutes: usize) -> Time {
+        Time::START_OF_DAY + Duration::hours(hours) + Duration::minutes(minutes)
+    }
+
+    #[test]
+    fn test_day_of_week() {
+        // Wednesday
+        assert_eq!(day_of_week(20210421), 3);
+        // Saturday, in a leap year before March
+        assert_eq!(day_of_week(20000101), 6);
+        assert_eq!(day_of_week(20000229), 2);
+        // Sunday
+        assert_eq!(day_of_week(20211226), 0);
+    }
+
+    #[test]
+    fn test_services_running() {
+        let regular = || vec![calendar("weekday", 1, 0), calendar("weekend", 0, 1)];
+
+        // Without a date, use whatever normally runs on Wednesdays, ignoring exceptions
+        assert_eq!(
+            ids(services_running(
+                regular(),
+                vec![exception("weekend", 20210421, 1)],
+                None
+            )),
+            vec!["weekday"]
+        );
+        // A regular Saturday
+        assert_eq!(
+            ids(services_running(regular(), Vec::new(), Some(20210424))),
+            vec!["weekend"]
+        );
+        // Outside the calendar's date range
+        assert!(services_running(regular(), Vec::new(), Some(20220421)).is_empty());
+
+        // A holiday on a Monday: the weekday service is removed and the weekend one added
+        let holiday = vec![
+            exception("weekday", 20210531, 2),
+            exception("weekend", 20210531, 1),
+            // Exceptions for other dates don't matter
+            exception("weekday", 20210601, 2),
+            exception("special", 20210601, 1),
+        ];
+        assert_eq!(
+            ids(services_running(regular(), holiday, Some(20210531))),
+            vec!["weekend"]
+        );
+
+        // Feeds may only have calendar_dates
+        assert_eq!(
+            ids(services_running(
+                Vec::new(),
+                vec![exception("special", 20210601, 1)],
+                Some(20210601)
+            )),
+            vec!["special"]
+        );
+    }
+
+    #[test]
+    fn test_interpolate_times() {
+        assert_eq!(
+            interpolate_times(vec![Some(time(8, 0)), Some(time(8, 10))]),
+            Some(vec![time(8, 0), time(8, 10)])
+        );
+        // Missing stops in the middle are spread evenly between the known times
+        assert_eq!(
+            interpolate_times(vec![
+                Some(time(8, 0)),
+                None,
+                None,
+                Some(time(8, 30)),
+                None,
+                Some(time(8, 40)),
+            ]),
+            Some(vec![
+                time(8, 0),
+                time(8, 10),
+                time(8, 20),
+                time(8, 30),
+                time(8, 35),
+                time(8, 40)
+            ])
+        );
+        // A missing time at either end can't be filled in
+        assert_eq!(
+            interpolate_times(vec![None, Some(time(8, 0)), Some(time(8, 10))]),
+            None
+        );
+        assert_eq!(
+            interpolate_times(vec![Some(time(8, 0)), Some(time(8, 10)), None]),
+            None
+        );
+        assert_eq!(interpolate_times(vec![None]), None);
+        assert_eq!(interpolate_times(Vec::new()), None);
+    }
+
+    #[test]
+    fn test_expand_frequencies() {
+        let times = vec![time(0, 0), time(0, 5), time(0, 12)];
+        // The window doesn't divide evenly by the headway, and the end is exclusive
+        let trips = expand_frequencies(
+            &times,
+            &[
+                (time(8, 0), time(8, 25), Duration::minutes(10)),
+                (time(9, 0), time(9, 20), Duration::minutes(10)),
+            ],
+        );
+        assert_eq!(
+            trips,
+            vec![
+                vec![time(8, 0), time(8, 5), time(8, 12)],
+                vec![time(8, 10), time(8, 15), time(8, 22)],
+                vec![time(8, 20), time(8, 25), time(8, 32)],
+                vec![time(9, 0), time(9, 5), time(9, 12)],
+                vec![time(9, 10), time(9, 15), time(9, 22)],
+            ]
+        );
+        // An empty window runs no trips
+        assert!(
+            expand_frequencies(&times, &[(time(8, 0), time(8, 0), Duration::minutes(10))])
+                .is_empty()
+        );
+    }
+}
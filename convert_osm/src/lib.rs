@@ -3,7 +3,7 @@ extern crate anyhow;
 #[macro_use]
 extern crate log;
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap};
 
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
@@ -11,9 +11,10 @@ use serde::{Deserialize, Serialize};
 use abstio::MapName;
 use abstutil::{Tags, Timer};
 use geom::{Distance, FindClosest, GPSBounds, HashablePt2D, LonLat, PolyLine, Polygon, Pt2D, Ring};
-use raw_map::{osm, Amenity, MapConfig, OriginalRoad, RawMap, RawRoad};
+use raw_map::{osm, Amenity, CrossingType, MapConfig, OriginalRoad, RawMap, RawRoad};
 
 mod clip;
+mod dem;
 mod elevation;
 mod extract;
 mod gtfs;
@@ -38,8 +39,16 @@ pub struct Options {
     pub skip_local_roads: bool,
     /// Only include crosswalks that match a `highway=crossing` OSM node.
     pub filter_crosswalks: bool,
+    /// Split roads at `highway=crossing` OSM nodes in the middle of a block, so pedestrians can
+    /// cross there, instead of snapping the crossing to the nearest intersection.
+    pub mid_block_crossings: bool,
     /// Configure public transit using this URL to a static GTFS feed in .zip format.
     pub gtfs_url: Option<String>,
+    /// Only import GTFS trips running on this date, formatted like 20210421. If unspecified, use
+    /// trips that normally run on Wednesdays.
+    pub gtfs_service_day: Option<String>,
+    /// Fill out elevation and road inclines from DEM tiles in data/input/shared/elevation, or by
+    /// running elevation_lookups through Docker if there are none.
     pub elevation: bool,
 }
 
@@ -60,7 +69,9 @@ impl Options {
             extra_buildings: None,
             skip_local_roads: false,
             filter_crosswalks: false,
+            mid_block_crossings: false,
             gtfs_url: None,
+            gtfs_service_day: None,
             elevation: false,
         }
     }
@@ -121,7 +132,7 @@ pub fn convert(
     }
 
     let extract = extract::extract_osm(&mut map, &osm_input_path, clip_path, &opts, timer);
-    let split_output = split_ways::split_up_roads(&mut map, extract, timer);
+    let mut split_output = split_ways::split_up_roads(&mut map, extract, timer);
     clip::clip_map(&mut map, timer);
 
     // Need to do a first pass of removing cul-de-sacs here, or we wind up with loop PolyLines when
@@ -143,6 +154,14 @@ pub fn convert(
         add_extra_buildings(&mut map, path).unwrap();
     }
 
+    if opts.mid_block_crossings {
+        add_mid_block_crossings(
+            &mut map,
+            &mut split_output.crosswalks,
+            &mut split_output.pt_to_road,
+            timer,
+        );
+    }
     if opts.filter_crosswalks {
         filter_crosswalks(
             &mut map,
@@ -153,7 +172,7 @@ pub fn convert(
     }
 
     if opts.gtfs_url.is_some() {
-        gtfs::import(&mut map).unwrap();
+        gtfs::import(&mut map, opts.gtfs_service_day.as_deref()).unwrap();
     }
 
     if map.name == MapName::new("gb", "bristol", "east") {
@@ -206,22 +225,94 @@ fn add_extra_buildings(map: &mut RawMap, path: &str) -> Result<()> {
     Ok(())
 }
 
+/// Split roads at crossing nodes that aren't near an intersection. Crossings that're handled are
+/// removed from `crosswalks`.
+fn add_mid_block_crossings(
+    map: &mut RawMap,
+    crosswalks: &mut HashMap<HashablePt2D, (osm::NodeID, CrossingType)>,
+    pt_to_road: &mut HashMap<HashablePt2D, OriginalRoad>,
+    timer: &mut Timer,
+) {
+    // Group the crossings by road, and find where along the road each one is
+    let mut per_road: BTreeMap<OriginalRoad, Vec<(Distance, HashablePt2D)>> = BTreeMap::new();
+    for pt in crosswalks.keys() {
+        if let Some((r, road)) = pt_to_road
+            .get(pt)
+            .and_then(|r| map.roads.get(r).map(|road| (*r, road)))
+        {
+            // Crossing a footway or a railway doesn't need an intersection
+            if road.is_footway() || road.is_light_rail() {
+                continue;
+            }
+            if let Ok(pl) = PolyLine::new(road.osm_center_points.clone()) {
+                if let Some((dist, _)) = pl.dist_along_of_point(pt.to_pt2d()) {
+                    per_road.entry(r).or_insert_with(Vec::new).push((dist, *pt));
+                }
+            }
+        }
+    }
+
+    timer.start_iter("add mid-block crossings", per_road.len());
+    for (r, mut crossings) in per_road {
+        timer.next();
+        // Split from the far end first. The first piece keeps the same start, so the distances of
+        // the remaining crossings along it don't change.
+        crossings.sort_by_key(|(dist, _)| *dist);
+        crossings.reverse();
+
+        let mut first_piece = r;
+        // (where the split happened, the piece of road starting there)
+        let mut pieces = Vec::new();
+        let mut leftovers = Vec::new();
+        for (dist, pt) in crossings {
+            let (id, crossing) = crosswalks[&pt];
+            match map.add_mid_block_crossing(first_piece, dist, crossing, id) {
+                Ok((r1, r2)) => {
+                    crosswalks.remove(&pt);
+                    first_piece = r1;
+                    pieces.push((dist, r2));
+                }
+                Err(err) => {
+                    debug!("Not adding a mid-block crossing at {}: {}", id, err);
+                    leftovers.push((dist, pt));
+                }
+            }
+        }
+
+        // Crossings too close to an intersection might still get snapped to it later, so point
+        // them at the right piece of the road
+        for (dist, pt) in leftovers {
+            let piece = pieces
+                .iter()
+                .find(|(split, _)| *split <= dist)
+                .map(|(_, piece)| *piece)
+                .unwrap_or(first_piece);
+            pt_to_road.insert(pt, piece);
+        }
+    }
+}
+
 fn filter_crosswalks(
     map: &mut RawMap,
-    crosswalks: HashSet<HashablePt2D>,
+    crosswalks: HashMap<HashablePt2D, (osm::NodeID, CrossingType)>,
     pt_to_road: HashMap<HashablePt2D, OriginalRoad>,
     timer: &mut Timer,
 ) {
     // Normally we assume every road has a crosswalk, but since this map is configured to use OSM
-    // crossing nodes, let's reverse that assumption.
-    for road in map.roads.values_mut() {
-        road.crosswalk_forward = false;
-        road.crosswalk_backward = false;
+    // crossing nodes, let's reverse that assumption. Mid-block crossings were already configured
+    // from the node they came from.
+    for (id, road) in &mut map.roads {
+        if !map.intersections[&id.i2].mid_block_crossing {
+            road.crosswalk_forward = false;
+        }
+        if !map.intersections[&id.i1].mid_block_crossing {
+            road.crosswalk_backward = false;
+        }
     }
 
     // Match each crosswalk node to a road
     timer.start_iter("filter crosswalks", crosswalks.len());
-    for pt in crosswalks {
+    for pt in crosswalks.into_keys() {
         timer.next();
         // Some crossing nodes are outside the map boundary or otherwise not on a road that we
         // retained
@@ -235,7 +326,7 @@ fn filter_crosswalks(
                     // Don't throw away any crossings. If it occurs in the first half of the road,
                     // snap to the first intersection. If there's a mid-block crossing mapped,
                     // that'll likely not be correctly interpreted, unless an intersection is there
-                    // anyway.
+                    // anyway or `mid_block_crossings` is enabled.
                     if pct <= 0.5 {
                         road.crosswalk_backward = true;
                     } else {
@@ -1,16 +1,17 @@
-use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::collections::{hash_map::Entry, HashMap};
 
 use abstutil::{Counter, Tags, Timer};
 use geom::{Distance, HashablePt2D, PolyLine, Pt2D};
 use raw_map::{
-    osm, Amenity, Direction, IntersectionType, OriginalRoad, RawIntersection, RawMap, RawRoad,
+    osm, Amenity, CrossingType, Direction, IntersectionType, OriginalRoad, RawIntersection, RawMap,
+    RawRoad,
 };
 
 use crate::extract::OsmExtract;
 
 pub struct Output {
     pub amenities: Vec<(Pt2D, Amenity)>,
-    pub crosswalks: HashSet<HashablePt2D>,
+    pub crosswalks: HashMap<HashablePt2D, (osm::NodeID, CrossingType)>,
     /// A mapping of all points to the split road. Some internal points on roads get removed in
     /// `split_up_roads`, so this mapping isn't redundant.
     pub pt_to_road: HashMap<HashablePt2D, OriginalRoad>,
@@ -203,6 +204,25 @@ pub fn split_up_roads(map: &mut RawMap, mut input: OsmExtract, timer: &mut Timer
             .push((via, to));
     }
 
+    // Resolve conditional turn restrictions, the same way as simple ones
+    let mut conditional_restrictions = Vec::new();
+    for (from_osm, via_osm, to_osm, windows) in input.conditional_turn_restrictions {
+        let roads = map.roads_per_intersection(via_osm);
+        if let (Some(from), Some(to)) = (
+            roads.iter().find(|r| r.osm_way_id == from_osm),
+            roads.iter().find(|r| r.osm_way_id == to_osm),
+        ) {
+            conditional_restrictions.push((*from, *to, windows));
+        }
+    }
+    for (from, to, windows) in conditional_restrictions {
+        map.roads
+            .get_mut(&from)
+            .unwrap()
+            .conditional_turn_restrictions
+            .push((to, windows));
+    }
+
     timer.start("match traffic signals to intersections");
     // Handle traffic signals tagged on incoming ways and not at intersections
     // (https://wiki.openstreetmap.org/wiki/Tag:highway=traffic%20signals?uselang=en#Tag_all_incoming_ways).
@@ -354,6 +354,10 @@ fn handle_command(
                 })
                 .collect(),
         })),
+        "/data/get-ride-hail-summary" => Ok(abstutil::to_json(&sim.ride_hail_summary())),
+        "/data/get-transit-summaries" => Ok(abstutil::to_json(&sim.transit_route_summaries(map))),
+        "/data/get-emissions" => Ok(abstutil::to_json(&sim.emissions_summary())),
+        "/data/get-safety-conflicts" => Ok(abstutil::to_json(&sim.safety_conflict_dataset(map))),
         "/data/trip-time-lower-bound" => {
             let id = TripID(get("id")?.parse::<usize>()?);
             let duration = sim.get_trip_time_lower_bound(map, id)?;
@@ -501,7 +505,7 @@ struct LoadSim {
 
 impl LoadSim {
     fn setup(&self, timer: &mut Timer) -> (Map, Sim) {
-        let mut scenario: Scenario = abstio::must_read_object(self.scenario.clone(), timer);
+        let mut scenario = Scenario::must_load(self.scenario.clone(), timer);
 
         let mut map = Map::load_synchronously(scenario.map_name.path(), timer);
         if let Some(perma) = self.edits.clone() {
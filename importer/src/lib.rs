@@ -51,6 +51,7 @@ pub async fn oneshot(
     clip: Option<String>,
     driving_side: map_model::DrivingSide,
     filter_crosswalks: bool,
+    mid_block_crossings: bool,
     create_uk_travel_demand_model: bool,
     opts: RawToMapOptions,
 ) {
@@ -59,6 +60,7 @@ pub async fn oneshot(
     let name = abstutil::basename(&osm_path);
     let mut options = convert_osm::Options::default_for_side(driving_side);
     options.filter_crosswalks = filter_crosswalks;
+    options.mid_block_crossings = mid_block_crossings;
     let raw = convert_osm::convert(
         osm_path,
         MapName::new("zz", "oneshot", &name),
@@ -72,6 +72,7 @@ pub fn config_for_map(name: &MapName) -> convert_osm::Options {
         extra_buildings,
         skip_local_roads: name == &MapName::new("us", "phoenix", "loop101"),
         filter_crosswalks: false,
+        mid_block_crossings: false,
         // https://www.transit.land is a great place to find the static GTFS URLs
         gtfs_url: if name == &MapName::new("us", "seattle", "arboretum") {
             Some("http://metro.kingcounty.gov/GTFS/google_transit.zip".to_string())
@@ -84,6 +85,7 @@ pub fn config_for_map(name: &MapName) -> convert_osm::Options {
         } else {
             None
         },
+        gtfs_service_day: None,
         // Our underlying elevation source is quite unvalidated outside of Seattle. We should
         // consider disabling it in most places until resolved, but for the moment, just for one
         // map of particular importance.
@@ -112,18 +112,13 @@ pub async fn generate_scenario(
             );
 
             // Create two scenarios, merging the background traffic with the base/active scenarios.
-            let mut base: Scenario = abstio::maybe_read_binary::<Scenario>(
-                abstio::path_scenario(map.get_name(), "base"),
-                timer,
-            )?;
+            let mut base = Scenario::load(abstio::path_scenario(map.get_name(), "base"), timer)?;
             base.people.extend(scenario.people.clone());
             base.scenario_name = "base_with_bg".to_string();
             base.save();
 
-            let mut go_active: Scenario = abstio::maybe_read_binary(
-                abstio::path_scenario(map.get_name(), "go_active"),
-                timer,
-            )?;
+            let mut go_active =
+                Scenario::load(abstio::path_scenario(map.get_name(), "go_active"), timer)?;
             go_active.people.extend(scenario.people);
             go_active.scenario_name = "go_active_with_bg".to_string();
             go_active.save();
@@ -203,14 +203,16 @@ impl UnzoomedAgents {
 
     fn color(&self, agent: &UnzoomedAgent, color_scheme: &ColorScheme) -> Option<Color> {
         match agent.id.to_vehicle_type() {
-            Some(VehicleType::Car) => {
+            Some(
+                VehicleType::Car | VehicleType::Truck | VehicleType::Van | VehicleType::Motorcycle,
+            ) => {
                 if self.cars {
                     Some(color_scheme.unzoomed_car)
                 } else {
                     None
                 }
             }
-            Some(VehicleType::Bike) => {
+            Some(VehicleType::Bike | VehicleType::Scooter) => {
                 if self.bikes {
                     Some(color_scheme.unzoomed_bike)
                 } else {
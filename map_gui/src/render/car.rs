@@ -13,6 +13,7 @@ pub struct DrawCar {
     pub id: CarID,
     body: PolyLine,
     body_polygon: Polygon,
+    width: Distance,
     zorder: isize,
 
     draw_default: Drawable,
@@ -27,11 +28,12 @@ impl DrawCar {
         cs: &ColorScheme,
     ) -> DrawCar {
         let mut draw_default = GeomBatch::new();
+        let width = car_width(input.id.vehicle_type);
 
         // Wheels
         for side in vec![
-            input.body.shift_right(CAR_WIDTH / 2.0),
-            input.body.shift_left(CAR_WIDTH / 2.0),
+            input.body.shift_right(width / 2.0),
+            input.body.shift_left(width / 2.0),
         ]
         .into_iter()
         .flatten()
@@ -57,24 +59,24 @@ impl DrawCar {
 
         let body_polygon = if input.body.length() < Distance::meters(1.1) {
             // Simpler shape while appearing from a border
-            input.body.make_polygons(CAR_WIDTH)
+            input.body.make_polygons(width)
         } else {
             let front_corner = input.body.length() - Distance::meters(1.0);
             let thick_line = input
                 .body
                 .exact_slice(Distance::ZERO, front_corner)
-                .make_polygons(CAR_WIDTH);
+                .make_polygons(width);
 
             let (corner_pt, corner_angle) = input.body.must_dist_along(front_corner);
             let tip_pt = input.body.last_pt();
             let tip_angle = input.body.last_line().angle();
             // If this fails for any reason, just fallback to the simple shape
             match Ring::new(vec![
-                corner_pt.project_away(CAR_WIDTH / 2.0, corner_angle.rotate_degs(90.0)),
-                corner_pt.project_away(CAR_WIDTH / 2.0, corner_angle.rotate_degs(-90.0)),
-                tip_pt.project_away(CAR_WIDTH / 4.0, tip_angle.rotate_degs(-90.0)),
-                tip_pt.project_away(CAR_WIDTH / 4.0, tip_angle.rotate_degs(90.0)),
-                corner_pt.project_away(CAR_WIDTH / 2.0, corner_angle.rotate_degs(90.0)),
+                corner_pt.project_away(width / 2.0, corner_angle.rotate_degs(90.0)),
+                corner_pt.project_away(width / 2.0, corner_angle.rotate_degs(-90.0)),
+                tip_pt.project_away(width / 4.0, tip_angle.rotate_degs(-90.0)),
+                tip_pt.project_away(width / 4.0, tip_angle.rotate_degs(90.0)),
+                corner_pt.project_away(width / 2.0, corner_angle.rotate_degs(90.0)),
             ]) {
                 Ok(front) => front.into_polygon().union(thick_line),
                 Err(_) => thick_line,
@@ -113,7 +115,7 @@ impl DrawCar {
 
         // If the vehicle is temporarily too short for anything, just omit.
         if input.body.length() >= Distance::meters(2.5) {
-            let arrow_len = 0.8 * CAR_WIDTH;
+            let arrow_len = 0.8 * width;
             let arrow_thickness = Distance::meters(0.5);
 
             if let Some(t) = input.waiting_for_turn {
@@ -161,11 +163,8 @@ impl DrawCar {
                     cs.brake_light,
                     thick_line_from_angle(
                         window_thickness,
-                        CAR_WIDTH - window_length_gap * 2.0,
-                        pos.project_away(
-                            CAR_WIDTH / 2.0 - window_length_gap,
-                            angle.rotate_degs(-90.0),
-                        ),
+                        width - window_length_gap * 2.0,
+                        pos.project_away(width / 2.0 - window_length_gap, angle.rotate_degs(-90.0)),
                         angle.rotate_degs(90.0),
                     ),
                 );
@@ -201,6 +200,7 @@ impl DrawCar {
             id: input.id,
             body: input.body,
             body_polygon,
+            width,
             zorder,
             draw_default: prerender.upload(draw_default),
         }
@@ -218,7 +218,7 @@ impl Renderable for DrawCar {
 
     fn get_outline(&self, _: &Map) -> Polygon {
         self.body
-            .to_thick_boundary(CAR_WIDTH, OUTLINE_THICKNESS)
+            .to_thick_boundary(self.width, OUTLINE_THICKNESS)
             .unwrap_or_else(|| self.body_polygon.clone())
     }
 
@@ -242,6 +242,15 @@ fn thick_line_from_angle(
     PolyLine::must_new(vec![pt, pt2]).make_polygons(thickness)
 }
 
+fn car_width(vehicle_type: VehicleType) -> Distance {
+    match vehicle_type {
+        VehicleType::Truck => Distance::meters(2.4),
+        VehicleType::Van => Distance::meters(2.0),
+        VehicleType::Motorcycle => Distance::meters(0.8),
+        _ => CAR_WIDTH,
+    }
+}
+
 fn zoomed_color_car(input: &DrawCarInput, sim: &Sim, cs: &ColorScheme) -> Color {
     if input.id.vehicle_type == VehicleType::Bus {
         cs.bus_body
@@ -63,7 +63,7 @@ fn draw_vehicle(
     prerender: &Prerender,
     cs: &ColorScheme,
 ) -> Box<dyn Renderable> {
-    if input.id.vehicle_type == VehicleType::Bike {
+    if input.id.vehicle_type.is_bike_like() {
         Box::new(DrawBike::new(input, map, sim, prerender, cs))
     } else {
         Box::new(DrawCar::new(input, map, sim, prerender, cs))
@@ -86,6 +86,7 @@ impl<A: AppLike + 'static> ImportCity<A> {
                         ),
                     ]),
                     Toggle::switch(ctx, "Filter crosswalks", None, false),
+                    Toggle::switch(ctx, "Mid-block crossings", None, false),
                     Toggle::switch(ctx, "Generate travel demand model (UK only)", None, false),
                 ])
                 .section(ctx),
@@ -131,6 +132,9 @@ impl<A: AppLike + 'static> State<A> for ImportCity<A> {
                     if self.panel.is_checked("Filter crosswalks") {
                         args.push("--filter-crosswalks".to_string());
                     }
+                    if self.panel.is_checked("Mid-block crossings") {
+                        args.push("--mid-block-crossings".to_string());
+                    }
                     if self
                         .panel
                         .is_checked("Generate travel demand model (UK only)")
@@ -283,7 +283,7 @@ pub fn color_for_mode(app: &dyn AppLike, m: TripMode) -> Color {
         TripMode::Walk => app.cs().unzoomed_pedestrian,
         TripMode::Bike => app.cs().unzoomed_bike,
         TripMode::Transit => app.cs().unzoomed_bus,
-        TripMode::Drive => app.cs().unzoomed_car,
+        TripMode::Drive | TripMode::RideHail => app.cs().unzoomed_car,
     }
 }
 
@@ -0,0 +1,157 @@
+use std::collections::{BTreeMap, HashMap};
+
+use geom::{Duration, Time};
+
+use super::walking::all_walking_costs_from_positions;
+use super::{all_walking_costs_from, vehicle_costs_from, Spot, WalkingOptions};
+use crate::{AmenityType, BuildingID, JourneyPlanner, Map, PathConstraints, RoutingParams};
+
+/// How somebody gets around to reach places
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AccessMode {
+    Walk,
+    Bike,
+    Drive,
+    /// Walk and ride public transit, following the schedule
+    Transit,
+}
+
+impl AccessMode {
+    pub fn all() -> Vec<AccessMode> {
+        vec![
+            AccessMode::Walk,
+            AccessMode::Bike,
+            AccessMode::Drive,
+            AccessMode::Transit,
+        ]
+    }
+
+    pub fn noun(self) -> &'static str {
+        match self {
+            AccessMode::Walk => "walk",
+            AccessMode::Bike => "bike",
+            AccessMode::Drive => "drive",
+            AccessMode::Transit => "transit",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AccessibilityOptions {
+    /// Access restrictions, road travel times, and transit schedules depend on the time of day.
+    pub departure: Time,
+    /// Ignore buildings further away than this
+    pub time_limit: Duration,
+    pub walking: WalkingOptions,
+}
+
+/// Measures how long it takes to reach buildings by different modes, without any UI, so that
+/// whole cities can be analyzed in batch. Preparing the transit schedule is expensive, so create
+/// this once and reuse it for many starting buildings.
+pub struct Accessibility<'a> {
+    map: &'a Map,
+    opts: AccessibilityOptions,
+    routing_params: RoutingParams,
+    journey_planner: JourneyPlanner,
+}
+
+impl<'a> Accessibility<'a> {
+    pub fn new(map: &'a Map, opts: AccessibilityOptions) -> Accessibility<'a> {
+        let mut routing_params = map.routing_params().clone();
+        routing_params.time = Some(opts.departure);
+        Accessibility {
+            map,
+            opts,
+            routing_params,
+            journey_planner: JourneyPlanner::new(map),
+        }
+    }
+
+    /// How long it takes to reach every building from a starting building. Buildings that can't
+    /// be reached within the time limit are omitted.
+    pub fn times_from(&self, start: BuildingID, mode: AccessMode) -> HashMap<BuildingID, Duration> {
+        let time_limit = self.opts.time_limit;
+        match mode {
+            AccessMode::Walk | AccessMode::Bike | AccessMode::Drive => times_from_spots(
+                self.map,
+                vec![Spot::Building(start)],
+                mode,
+                time_limit,
+                self.opts.walking.clone(),
+                &self.routing_params,
+            ),
+            AccessMode::Transit => {
+                // Walk from the start, or from every stop after riding there
+                let start_pos = self.map.get_b(start).sidewalk_pos;
+                let mut starts = vec![(start_pos, Duration::ZERO)];
+                for (stop, arrival) in
+                    self.journey_planner
+                        .earliest_arrivals(self.map, start_pos, self.opts.departure)
+                {
+                    starts.push((
+                        self.map.get_ts(stop).sidewalk_pos,
+                        arrival - self.opts.departure,
+                    ));
+                }
+                all_walking_costs_from_positions(
+                    self.map,
+                    starts,
+                    time_limit,
+                    self.opts.walking.clone(),
+                )
+            }
+        }
+    }
+
+    /// A cumulative opportunity score: how many amenities of each type can be reached within some
+    /// time, given the result of `times_from`.
+    pub fn opportunities(
+        &self,
+        times: &HashMap<BuildingID, Duration>,
+        within: Duration,
+    ) -> BTreeMap<AmenityType, usize> {
+        let mut counts = BTreeMap::new();
+        for (b, time) in times {
+            if *time > within {
+                continue;
+            }
+            for amenity in &self.map.get_b(*b).amenities {
+                if let Some(category) = AmenityType::categorize(&amenity.amenity_type) {
+                    *counts.entry(category).or_insert(0) += 1;
+                }
+            }
+        }
+        counts
+    }
+}
+
+/// How long it takes to reach every building from any of the starting spots by walking, biking, or
+/// driving. Buildings that can't be reached within the time limit are omitted. Transit depends on
+/// a prepared schedule, so use `Accessibility` for that.
+pub fn times_from_spots(
+    map: &Map,
+    starts: Vec<Spot>,
+    mode: AccessMode,
+    time_limit: Duration,
+    walking: WalkingOptions,
+    routing_params: &RoutingParams,
+) -> HashMap<BuildingID, Duration> {
+    match mode {
+        AccessMode::Walk => all_walking_costs_from(map, starts, time_limit, walking),
+        AccessMode::Bike => vehicle_costs_from(
+            map,
+            starts,
+            time_limit,
+            PathConstraints::Bike,
+            routing_params,
+        ),
+        AccessMode::Drive => vehicle_costs_from(
+            map,
+            starts,
+            time_limit,
+            PathConstraints::Car,
+            routing_params,
+        ),
+        AccessMode::Transit => panic!("times_from_spots can't measure transit; use Accessibility"),
+    }
+}
@@ -8,10 +8,14 @@ use petgraph::graphmap::DiGraphMap;
 
 use geom::Duration;
 
+pub use self::accessibility::{times_from_spots, AccessMode, Accessibility, AccessibilityOptions};
 pub use self::walking::{all_walking_costs_from, WalkingOptions};
 pub use crate::pathfind::{vehicle_cost, WalkingNode};
-use crate::{BuildingID, DirectedRoadID, IntersectionID, LaneID, Map, PathConstraints};
+use crate::{
+    BuildingID, DirectedRoadID, IntersectionID, LaneID, Map, PathConstraints, RoutingParams,
+};
 
+mod accessibility;
 mod walking;
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
@@ -65,6 +69,17 @@ pub fn all_vehicle_costs_from(
     starts: Vec<Spot>,
     time_limit: Duration,
     constraints: PathConstraints,
+) -> HashMap<BuildingID, Duration> {
+    vehicle_costs_from(map, starts, time_limit, constraints, map.routing_params())
+}
+
+/// Like `all_vehicle_costs_from`, but using different routing params.
+pub(crate) fn vehicle_costs_from(
+    map: &Map,
+    starts: Vec<Spot>,
+    time_limit: Duration,
+    constraints: PathConstraints,
+    params: &RoutingParams,
 ) -> HashMap<BuildingID, Duration> {
     assert!(constraints != PathConstraints::Pedestrian);
     // TODO We have a graph of DirectedRoadIDs, but mapping a building to one isn't
@@ -73,7 +88,7 @@ pub fn all_vehicle_costs_from(
 
     let mut bldg_to_road = HashMap::new();
     for b in map.all_buildings() {
-        if constraints == PathConstraints::Car {
+        if matches!(constraints, PathConstraints::Car | PathConstraints::Truck) {
             if let Some((pos, _)) = b.driving_connection(map) {
                 bldg_to_road.insert(b.id, map.get_l(pos.lane()).get_directed_parent());
             }
@@ -131,9 +146,7 @@ pub fn all_vehicle_costs_from(
         cost_per_node.insert(current.node, current.cost);
 
         for mvmnt in map.get_movements_for(current.node, constraints) {
-            if let Some(cost) =
-                vehicle_cost(mvmnt.from, mvmnt, constraints, map.routing_params(), map)
-            {
+            if let Some(cost) = vehicle_cost(mvmnt.from, mvmnt, constraints, params, map) {
                 queue.push(Item {
                     cost: current.cost + cost,
                     node: mvmnt.to,
@@ -6,7 +6,7 @@ use geom::{Duration, Speed};
 
 use crate::connectivity::Spot;
 use crate::pathfind::{zone_cost, WalkingNode};
-use crate::{BuildingID, Lane, LaneType, Map, PathConstraints, PathStep};
+use crate::{BuildingID, Lane, LaneType, Map, PathConstraints, PathStep, Position};
 
 #[derive(Clone)]
 pub struct WalkingOptions {
@@ -115,6 +115,35 @@ pub fn all_walking_costs_from(
         }
     }
 
+    walking_costs(map, queue, time_limit, opts)
+}
+
+/// Like `all_walking_costs_from`, but start from sidewalk positions, each only reached after some
+/// delay. This is useful for continuing after riding transit to some stops.
+pub(crate) fn all_walking_costs_from_positions(
+    map: &Map,
+    starts: Vec<(Position, Duration)>,
+    time_limit: Duration,
+    opts: WalkingOptions,
+) -> HashMap<BuildingID, Duration> {
+    let mut queue: BinaryHeap<Item> = BinaryHeap::new();
+    for (pos, cost) in starts {
+        if cost <= time_limit {
+            queue.push(Item {
+                cost,
+                node: WalkingNode::closest(pos, map),
+            });
+        }
+    }
+    walking_costs(map, queue, time_limit, opts)
+}
+
+fn walking_costs(
+    map: &Map,
+    mut queue: BinaryHeap<Item>,
+    time_limit: Duration,
+    opts: WalkingOptions,
+) -> HashMap<BuildingID, Duration> {
     if !opts.allow_shoulders {
         let mut shoulder_endpoint = Vec::new();
         for q in &queue {
@@ -202,7 +231,12 @@ pub fn all_walking_costs_from(
                             PathConstraints::Pedestrian,
                             map,
                         )
-                    + zone_cost(turn.id.to_movement(map), PathConstraints::Pedestrian, map),
+                    + zone_cost(
+                        turn.id.to_movement(map),
+                        PathConstraints::Pedestrian,
+                        None,
+                        map,
+                    ),
                 node: WalkingNode::SidewalkEndpoint(
                     map.get_l(turn.id.dst).get_directed_parent(),
                     map.get_l(turn.id.dst).dst_i == turn.id.parent,
@@ -111,6 +111,34 @@ pub fn upgrade(mut value: Value, map: &Map) -> Result<PermanentMapEdits> {
             .unwrap()
             .insert("version".to_string(), Value::Number(11.into()));
     }
+    if value["version"] == Value::Number(11.into()) {
+        fix_signal_corridors(&mut value);
+        value
+            .as_object_mut()
+            .unwrap()
+            .insert("version".to_string(), Value::Number(12.into()));
+    }
+    if value["version"] == Value::Number(12.into()) {
+        fix_transit_signal_priority(&mut value);
+        value
+            .as_object_mut()
+            .unwrap()
+            .insert("version".to_string(), Value::Number(13.into()));
+    }
+    if value["version"] == Value::Number(13.into()) {
+        fix_modal_filters(&mut value);
+        value
+            .as_object_mut()
+            .unwrap()
+            .insert("version".to_string(), Value::Number(14.into()));
+    }
+    if value["version"] == Value::Number(14.into()) {
+        transit_signal_priority_cmds(&mut value);
+        value
+            .as_object_mut()
+            .unwrap()
+            .insert("version".to_string(), Value::Number(15.into()));
+    }
 
     abstutil::from_json(&value.to_string().into_bytes())
 }
@@ -442,6 +470,52 @@ fn remove_vehicle_caps(value: &mut Value) {
     });
 }
 
+// Version 12 added coordinated signal corridors
+fn fix_signal_corridors(value: &mut Value) {
+    let obj = value.as_object_mut().unwrap();
+    if !obj.contains_key("signal_corridors") {
+        obj.insert("signal_corridors".to_string(), Value::Array(Vec::new()));
+    }
+}
+
+// Version 13 added per-route transit signal priority
+fn fix_transit_signal_priority(value: &mut Value) {
+    let obj = value.as_object_mut().unwrap();
+    if !obj.contains_key("transit_signal_priority") {
+        obj.insert(
+            "transit_signal_priority".to_string(),
+            Value::Array(Vec::new()),
+        );
+    }
+}
+
+// Version 14 added modal filters to EditRoad
+fn fix_modal_filters(value: &mut Value) {
+    walk(value, &|map| {
+        if map.contains_key("lanes_ltr") && !map.contains_key("modal_filter") {
+            map.insert("modal_filter".to_string(), Value::Null);
+        }
+        false
+    });
+}
+
+// Version 15 made transit signal priority a command, so it can be undone like other edits
+fn transit_signal_priority_cmds(value: &mut Value) {
+    let obj = value.as_object_mut().unwrap();
+    if let Some(Value::Array(routes)) = obj.remove("transit_signal_priority") {
+        let commands = obj.get_mut("commands").unwrap().as_array_mut().unwrap();
+        for gtfs_id in routes {
+            commands.push(serde_json::json!({
+                "ChangeTransitSignalPriority": {
+                    "gtfs_id": gtfs_id,
+                    "old": false,
+                    "new": true,
+                }
+            }));
+        }
+    }
+}
+
 // These're old structs used in fix_old_lane_cmds.
 #[derive(Debug, Deserialize)]
 struct OriginalLane {
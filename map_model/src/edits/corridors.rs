@@ -0,0 +1,310 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use geom::{Distance, Duration, Speed};
+
+use crate::{ControlTrafficSignal, IntersectionID, Map, MovementID, RoadID, StageType};
+
+/// A sequence of traffic signals along one arterial, timed together. Every member signal shares a
+/// common cycle length, and the offsets are chosen so that a platoon traveling at the design speed
+/// meets a green wave.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignalCorridor {
+    /// Ordered from one end of the arterial to the other. Traveling "forwards" visits the signals
+    /// in this order.
+    pub signals: Vec<IntersectionID>,
+    /// Every member signal is retimed to last exactly this long.
+    pub cycle_length: Duration,
+    pub design_speed_fwd: Speed,
+    pub design_speed_back: Speed,
+    /// Which direction's green wave the offsets favor
+    pub favor: CorridorDirection,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CorridorDirection {
+    Forwards,
+    Backwards,
+    /// Split the difference between the ideal offsets in both directions
+    Balanced,
+}
+
+/// How the members of a corridor are connected. Calculating this requires pathfinding, so callers
+/// that need it repeatedly should hang onto it.
+pub struct CorridorLayout {
+    /// The distance of each signal from the first one, along the arterial
+    pub distances: Vec<Distance>,
+    /// For each signal, the road arriving from the previous signal and the road leaving towards
+    /// the next one
+    links: Vec<(Option<RoadID>, Option<RoadID>)>,
+}
+
+impl SignalCorridor {
+    pub fn new(signals: Vec<IntersectionID>, cycle_length: Duration, speed: Speed) -> Self {
+        SignalCorridor {
+            signals,
+            cycle_length,
+            design_speed_fwd: speed,
+            design_speed_back: speed,
+            favor: CorridorDirection::Forwards,
+        }
+    }
+
+    pub fn layout(&self, map: &Map) -> Result<CorridorLayout> {
+        if self.signals.len() < 2 {
+            bail!("A corridor needs at least 2 signals");
+        }
+        let mut distances = vec![Distance::ZERO];
+        let mut links = vec![(None, None); self.signals.len()];
+        for (idx, pair) in self.signals.windows(2).enumerate() {
+            let (roads, _) = map
+                .simple_path_btwn(pair[0], pair[1])
+                .ok_or_else(|| anyhow!("No path between {} and {}", pair[0], pair[1]))?;
+            if roads.is_empty() {
+                bail!("{} appears twice in a row in the corridor", pair[0]);
+            }
+            let dist: Distance = roads.iter().map(|r| map.get_r(*r).length()).sum();
+            distances.push(distances[idx] + dist);
+            links[idx].1 = Some(roads[0]);
+            links[idx + 1].0 = Some(*roads.last().unwrap());
+        }
+        Ok(CorridorLayout { distances, links })
+    }
+
+    /// Calculate the new configuration of every member signal: stages are scaled to fit the
+    /// common cycle length, and offsets are set to produce a green wave. Doesn't modify the map.
+    pub fn retime(&self, map: &Map) -> Result<Vec<ControlTrafficSignal>> {
+        let layout = self.layout(map)?;
+        let mut signals = Vec::new();
+        for i in &self.signals {
+            let mut signal = map.get_traffic_signal(*i).clone();
+            scale_to_cycle(&mut signal, self.cycle_length)?;
+            signal.validate(map.get_i(*i))?;
+            signals.push(signal);
+        }
+
+        let fwd = self.ideal_offsets(&signals, &layout, true);
+        let back = self.ideal_offsets(&signals, &layout, false);
+        for (idx, signal) in signals.iter_mut().enumerate() {
+            let offset = match self.favor {
+                CorridorDirection::Forwards => fwd[idx],
+                CorridorDirection::Backwards => back[idx],
+                CorridorDirection::Balanced => {
+                    balanced_offset(fwd[idx], back[idx], self.cycle_length)
+                }
+            };
+            // Offsets are stored as whole seconds
+            signal.offset = wrap(
+                Duration::seconds(offset.inner_seconds().round()),
+                self.cycle_length,
+            );
+        }
+        Ok(signals)
+    }
+
+    /// For each signal, the offset that would make the green for through traffic in one direction
+    /// begin exactly when a platoon traveling at the design speed arrives.
+    pub fn ideal_offsets(
+        &self,
+        signals: &[ControlTrafficSignal],
+        layout: &CorridorLayout,
+        forwards: bool,
+    ) -> Vec<Duration> {
+        let total = *layout.distances.last().unwrap();
+        signals
+            .iter()
+            .enumerate()
+            .map(|(idx, signal)| {
+                let arrival = if forwards {
+                    layout.distances[idx] / self.design_speed_fwd
+                } else {
+                    (total - layout.distances[idx]) / self.design_speed_back
+                };
+                let green_starts = self
+                    .green_windows(signal, layout, idx, forwards)
+                    .first()
+                    .map(|(start, _)| *start)
+                    .unwrap_or(Duration::ZERO);
+                // At midnight, the signal is already offset into its cycle.
+                wrap(green_starts - arrival, signal.simple_cycle_duration())
+            })
+            .collect()
+    }
+
+    /// Returns the intervals of one cycle (relative to the start of the cycle, ignoring the
+    /// offset) when through traffic in one direction gets a green. Stages with the through
+    /// movement protected come first.
+    pub fn green_windows(
+        &self,
+        signal: &ControlTrafficSignal,
+        layout: &CorridorLayout,
+        idx: usize,
+        forwards: bool,
+    ) -> Vec<(Duration, Duration)> {
+        let (from, to) = if forwards {
+            layout.links[idx]
+        } else {
+            (layout.links[idx].1, layout.links[idx].0)
+        };
+        let through = |m: &MovementID| {
+            !m.crosswalk
+                && from.map(|r| m.from.road == r).unwrap_or(true)
+                && to.map(|r| m.to.road == r).unwrap_or(true)
+        };
+
+        let mut protected = Vec::new();
+        let mut permitted = Vec::new();
+        let mut start = Duration::ZERO;
+        for stage in &signal.stages {
+            let end = start + stage.stage_type.simple_duration();
+            if stage.protected_movements.iter().any(through) {
+                protected.push((start, end));
+            } else if stage.yield_movements.iter().any(through) {
+                permitted.push((start, end));
+            }
+            start = end;
+        }
+        protected.extend(permitted);
+        protected
+    }
+}
+
+/// Scale all stages proportionally so the cycle lasts exactly `cycle_length`, rounding to whole
+/// seconds. Only fixed timing can be coordinated.
+fn scale_to_cycle(signal: &mut ControlTrafficSignal, cycle_length: Duration) -> Result<()> {
+    let current = signal.simple_cycle_duration();
+    if current == Duration::ZERO {
+        bail!("{} has no stages", signal.id);
+    }
+    let ratio = cycle_length / current;
+    let num_stages = signal.stages.len();
+    let mut remaining = cycle_length;
+    for (idx, stage) in signal.stages.iter_mut().enumerate() {
+        let dt = match stage.stage_type {
+            StageType::Fixed(dt) => dt,
+            _ => bail!(
+                "{} doesn't use fixed timing, so it can't be part of a corridor",
+                signal.id
+            ),
+        };
+        let new_dt = if idx == num_stages - 1 {
+            remaining
+        } else {
+            Duration::seconds((dt * ratio).inner_seconds().round())
+        };
+        if new_dt <= Duration::ZERO {
+            bail!(
+                "A cycle length of {} is too short for {}",
+                cycle_length,
+                signal.id
+            );
+        }
+        remaining -= new_dt;
+        stage.stage_type = StageType::Fixed(new_dt);
+    }
+    Ok(())
+}
+
+/// The midpoint between two offsets, along the shorter arc of the cycle. May be negative or past
+/// the end of the cycle; callers should wrap the result.
+fn balanced_offset(fwd: Duration, back: Duration, cycle_length: Duration) -> Duration {
+    let diff = wrap(back - fwd, cycle_length);
+    if diff <= cycle_length / 2.0 {
+        fwd + diff / 2.0
+    } else {
+        fwd + (diff - cycle_length) / 2.0
+    }
+}
+
+/// Wrap a possibly negative duration into [0, cycle_length).
+fn wrap(dt: Duration, cycle_length: Duration) -> Duration {
+    let x = dt % cycle_length;
+    if x < Duration::ZERO {
+        x + cycle_length
+    } else {
+        x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Stage;
+
+    fn secs(s: f64) -> Duration {
+        Duration::seconds(s)
+    }
+
+    fn fixed_signal(stages: Vec<Duration>) -> ControlTrafficSignal {
+        ControlTrafficSignal {
+            id: IntersectionID(0),
+            stages: stages
+                .into_iter()
+                .map(|dt| {
+                    let mut stage = Stage::new();
+                    stage.stage_type = StageType::Fixed(dt);
+                    stage
+                })
+                .collect(),
+            offset: Duration::ZERO,
+        }
+    }
+
+    fn durations(signal: &ControlTrafficSignal) -> Vec<Duration> {
+        signal
+            .stages
+            .iter()
+            .map(|s| s.stage_type.simple_duration())
+            .collect()
+    }
+
+    #[test]
+    fn test_wrap() {
+        let cycle = secs(90.0);
+        assert_eq!(wrap(secs(30.0), cycle), secs(30.0));
+        assert_eq!(wrap(secs(100.0), cycle), secs(10.0));
+        assert_eq!(wrap(secs(-10.0), cycle), secs(80.0));
+        assert_eq!(wrap(secs(-190.0), cycle), secs(80.0));
+        assert_eq!(wrap(cycle, cycle), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_balanced_offset() {
+        let cycle = secs(100.0);
+        assert_eq!(balanced_offset(secs(10.0), secs(30.0), cycle), secs(20.0));
+        assert_eq!(balanced_offset(secs(30.0), secs(10.0), cycle), secs(20.0));
+        // The shorter arc crosses the start of the cycle
+        assert_eq!(
+            wrap(balanced_offset(secs(90.0), secs(10.0), cycle), cycle),
+            Duration::ZERO
+        );
+        assert_eq!(
+            wrap(balanced_offset(secs(10.0), secs(90.0), cycle), cycle),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn test_scale_to_cycle() {
+        // Stages keep their proportions, and the last one absorbs the rounding
+        let mut signal = fixed_signal(vec![secs(30.0), secs(20.0), secs(10.0)]);
+        scale_to_cycle(&mut signal, secs(90.0)).unwrap();
+        assert_eq!(durations(&signal), vec![secs(45.0), secs(30.0), secs(15.0)]);
+
+        let mut signal = fixed_signal(vec![secs(10.0), secs(10.0), secs(10.0)]);
+        scale_to_cycle(&mut signal, secs(100.0)).unwrap();
+        assert_eq!(durations(&signal), vec![secs(33.0), secs(33.0), secs(34.0)]);
+        assert_eq!(signal.simple_cycle_duration(), secs(100.0));
+
+        // Too short to give every stage a whole second
+        let mut signal = fixed_signal(vec![secs(30.0), secs(30.0), secs(1.0)]);
+        assert!(scale_to_cycle(&mut signal, secs(2.0)).is_err());
+
+        // Only fixed timing can be coordinated
+        let mut signal = fixed_signal(vec![secs(30.0), secs(30.0)]);
+        signal.stages[1].stage_type = StageType::Variable(secs(10.0), secs(2.0), secs(10.0));
+        assert!(scale_to_cycle(&mut signal, secs(60.0)).is_err());
+
+        assert!(scale_to_cycle(&mut fixed_signal(Vec::new()), secs(60.0)).is_err());
+    }
+}
@@ -11,15 +11,18 @@ use abstutil::Timer;
 use geom::{Distance, HashablePt2D, Line, Speed, Time};
 use raw_map::{get_lane_specs_ltr, InputRoad};
 
+pub use self::corridors::{CorridorDirection, CorridorLayout, SignalCorridor};
 pub use self::perma::PermanentMapEdits;
 use crate::make::{match_points_to_lanes, snap_driveway, trim_path};
 use crate::{
     connectivity, AccessRestrictions, BuildingID, ControlStopSign, ControlTrafficSignal,
-    IntersectionID, IntersectionType, LaneID, LaneSpec, Map, MapConfig, Movement, ParkingLotID,
-    PathConstraints, Pathfinder, Road, RoadID, TransitRouteID, TurnID, TurnType, Zone,
+    DiagonalFilter, IntersectionID, IntersectionType, LaneID, LaneSpec, Map, MapConfig, Movement,
+    ParkingLotID, PathConstraints, Pathfinder, Road, RoadFilter, RoadID, TransitRouteID, TurnID,
+    TurnType, Zone,
 };
 
 mod compat;
+mod corridors;
 mod perma;
 
 /// Represents changes to a map. Note this isn't serializable -- that's what `PermanentMapEdits`
@@ -34,12 +37,21 @@ pub struct MapEdits {
     /// Zone; every Road will be its own Zone. This is used to experiment with a per-road cap. Note
     /// this is a map-wide setting.
     pub merge_zones: bool,
+    /// Groups of traffic signals that're timed together. The signal timing itself is changed
+    /// through commands; this just remembers how the groups were set up, so they can be retimed
+    /// later.
+    pub signal_corridors: Vec<SignalCorridor>,
 
     /// Derived from commands, kept up to date by update_derived
     pub changed_roads: BTreeSet<RoadID>,
     pub original_intersections: BTreeMap<IntersectionID, EditIntersection>,
     pub original_crosswalks: BTreeMap<IntersectionID, EditCrosswalks>,
     pub changed_routes: BTreeSet<TransitRouteID>,
+    /// Intersections with a diagonal filter. OSM never has these, so there's no original to track.
+    pub changed_diagonal_filters: BTreeSet<IntersectionID>,
+    /// Transit vehicles on these routes request priority at traffic signals, extending a green or
+    /// cutting short a red as they approach.
+    pub transit_signal_priority: BTreeSet<TransitRouteID>,
 
     /// Some edits are included in the game by default, in data/system/proposals, as "community
     /// proposals." They require a description and may have a link to a write-up.
@@ -61,6 +73,7 @@ pub struct EditRoad {
     pub lanes_ltr: Vec<LaneSpec>,
     pub speed_limit: Speed,
     pub access_restrictions: AccessRestrictions,
+    pub modal_filter: Option<RoadFilter>,
 }
 
 /// This must contain all crossing turns at one intersection, each mapped either to Crosswalk or
@@ -73,7 +86,9 @@ impl EditRoad {
         EditRoad {
             lanes_ltr: get_lane_specs_ltr(&r.osm_tags, cfg),
             speed_limit: r.speed_limit_from_osm(),
-            access_restrictions: r.access_restrictions_from_osm(),
+            access_restrictions: r.access_restrictions_from_osm(cfg),
+            // TODO Detect existing bollards and bus gates from OSM
+            modal_filter: None,
         }
     }
 
@@ -116,6 +131,9 @@ impl EditRoad {
         if self.access_restrictions != other.access_restrictions {
             changes.push("access restrictions".to_string());
         }
+        if self.modal_filter != other.modal_filter {
+            changes.push("modal filter".to_string());
+        }
         changes
     }
 }
@@ -142,6 +160,16 @@ pub enum EditCmd {
         old: EditCrosswalks,
         new: EditCrosswalks,
     },
+    ChangeDiagonalFilter {
+        i: IntersectionID,
+        old: Option<DiagonalFilter>,
+        new: Option<DiagonalFilter>,
+    },
+    ChangeTransitSignalPriority {
+        id: TransitRouteID,
+        old: bool,
+        new: bool,
+    },
 }
 
 pub struct EditEffects {
@@ -163,11 +191,14 @@ impl MapEdits {
             proposal_link: None,
             commands: Vec::new(),
             merge_zones: true,
+            signal_corridors: Vec::new(),
 
             changed_roads: BTreeSet::new(),
             original_intersections: BTreeMap::new(),
             original_crosswalks: BTreeMap::new(),
             changed_routes: BTreeSet::new(),
+            changed_diagonal_filters: BTreeSet::new(),
+            transit_signal_priority: BTreeSet::new(),
         }
     }
 
@@ -240,6 +271,8 @@ impl MapEdits {
         self.original_intersections.clear();
         self.original_crosswalks.clear();
         self.changed_routes.clear();
+        self.changed_diagonal_filters.clear();
+        self.transit_signal_priority.clear();
 
         for cmd in &self.commands {
             match cmd {
@@ -259,6 +292,16 @@ impl MapEdits {
                 EditCmd::ChangeRouteSchedule { id, .. } => {
                     self.changed_routes.insert(*id);
                 }
+                EditCmd::ChangeDiagonalFilter { i, .. } => {
+                    self.changed_diagonal_filters.insert(*i);
+                }
+                EditCmd::ChangeTransitSignalPriority { id, new, .. } => {
+                    if *new {
+                        self.transit_signal_priority.insert(*id);
+                    } else {
+                        self.transit_signal_priority.remove(id);
+                    }
+                }
             }
         }
 
@@ -273,6 +316,8 @@ impl MapEdits {
             let r = map.get_tr(*br);
             r.spawn_times != r.orig_spawn_times
         });
+        self.changed_diagonal_filters
+            .retain(|i| map.get_i(*i).modal_filter.is_some());
     }
 
     /// Assumes update_derived has been called.
@@ -306,6 +351,20 @@ impl MapEdits {
                 old: r.orig_spawn_times.clone(),
             });
         }
+        for i in &self.changed_diagonal_filters {
+            self.commands.push(EditCmd::ChangeDiagonalFilter {
+                i: *i,
+                old: None,
+                new: map.get_i(*i).modal_filter.clone(),
+            });
+        }
+        for id in &self.transit_signal_priority {
+            self.commands.push(EditCmd::ChangeTransitSignalPriority {
+                id: *id,
+                old: false,
+                new: true,
+            });
+        }
     }
 
     /// Pick apart changed_roads and figure out if an entire road was edited, or just a few lanes.
@@ -319,6 +378,7 @@ impl MapEdits {
             // What exactly changed?
             if r.speed_limit != orig.speed_limit
                 || r.access_restrictions != orig.access_restrictions
+                || r.modal_filter != orig.modal_filter
                 // If a lane was added or deleted, figuring out if any were modified is kind of
                 // unclear -- just mark the entire road.
                 || r.lanes.len() != orig.lanes_ltr.len()
@@ -376,9 +436,26 @@ impl EditCmd {
                 EditIntersection::Closed => format!("close {}", i),
             },
             EditCmd::ChangeCrosswalks { i, .. } => format!("crosswalks at {}", i),
+            EditCmd::ChangeDiagonalFilter { i, new, .. } => match new {
+                Some(_) => format!("diagonal filter at {}", i),
+                None => format!("remove diagonal filter at {}", i),
+            },
             EditCmd::ChangeRouteSchedule { id, .. } => {
                 format!("reschedule route {}", map.get_tr(*id).short_name)
             }
+            EditCmd::ChangeTransitSignalPriority { id, new, .. } => {
+                if *new {
+                    format!(
+                        "transit signal priority for route {}",
+                        map.get_tr(*id).short_name
+                    )
+                } else {
+                    format!(
+                        "no transit signal priority for route {}",
+                        map.get_tr(*id).short_name
+                    )
+                }
+            }
         };
         (summary, details)
     }
@@ -395,6 +472,7 @@ impl EditCmd {
                 let road = &mut map.roads[r.0];
                 road.speed_limit = new.speed_limit;
                 road.access_restrictions = new.access_restrictions.clone();
+                road.modal_filter = new.modal_filter.clone();
 
                 effects.changed_roads.insert(road.id);
                 for i in [road.src_i, road.dst_i] {
@@ -464,6 +542,15 @@ impl EditCmd {
             EditCmd::ChangeRouteSchedule { id, new, .. } => {
                 map.transit_routes[id.0].spawn_times = new.clone();
             }
+            EditCmd::ChangeDiagonalFilter { i, ref new, .. } => {
+                if &map.get_i(*i).modal_filter == new {
+                    return;
+                }
+                effects.changed_intersections.insert(*i);
+                map.intersections[i.0].modal_filter = new.clone();
+            }
+            // Nothing in the map changes; the simulation looks at the edits directly
+            EditCmd::ChangeTransitSignalPriority { .. } => {}
         }
     }
 
@@ -489,6 +576,18 @@ impl EditCmd {
                 old: new,
                 new: old,
             },
+            EditCmd::ChangeDiagonalFilter { i, old, new } => EditCmd::ChangeDiagonalFilter {
+                i,
+                old: new,
+                new: old,
+            },
+            EditCmd::ChangeTransitSignalPriority { id, old, new } => {
+                EditCmd::ChangeTransitSignalPriority {
+                    id,
+                    old: new,
+                    new: old,
+                }
+            }
         }
     }
 }
@@ -769,6 +868,7 @@ impl Map {
             lanes_ltr: r.lane_specs(),
             speed_limit: r.speed_limit,
             access_restrictions: r.access_restrictions.clone(),
+            modal_filter: r.modal_filter.clone(),
         }
     }
 
@@ -924,6 +1024,10 @@ impl Map {
 
         let merge_zones_changed = self.edits.merge_zones != new_edits.merge_zones;
 
+        // If a member stopped being a traffic signal, the corridor no longer makes sense
+        new_edits
+            .signal_corridors
+            .retain(|c| c.signals.iter().all(|i| self.get_i(*i).is_traffic_signal()));
         new_edits.update_derived(self);
         self.edits = new_edits;
         self.pathfinder_dirty = true;
@@ -951,6 +1055,7 @@ impl Map {
             .extend(more_changed_intersections);
 
         self.recalculate_road_to_buildings();
+        self.recalculate_conditional_restriction_changes();
 
         effects
     }
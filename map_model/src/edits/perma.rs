@@ -5,11 +5,16 @@ use serde::{Deserialize, Serialize};
 
 use abstio::MapName;
 use abstutil::{deserialize_btreemap, serialize_btreemap};
-use geom::Time;
+use geom::{Duration, Speed, Time};
 
-use crate::edits::{EditCmd, EditCrosswalks, EditIntersection, EditRoad, MapEdits};
+use crate::edits::{
+    CorridorDirection, EditCmd, EditCrosswalks, EditIntersection, EditRoad, MapEdits,
+    SignalCorridor,
+};
 use crate::raw::OriginalRoad;
-use crate::{osm, ControlStopSign, IntersectionID, Map, MovementID, TurnType};
+use crate::{
+    osm, ControlStopSign, DiagonalFilter, FilterType, IntersectionID, Map, MovementID, TurnType,
+};
 
 // Manually change this to attempt to preserve edits after major OSM updates.
 const IGNORE_OLD_LANES: bool = false;
@@ -27,6 +32,7 @@ pub struct PermanentMapEdits {
     /// Zone; every Road will be its own Zone. This is used to experiment with a per-road cap. Note
     /// this is a map-wide setting.
     merge_zones: bool,
+    signal_corridors: Vec<PermanentSignalCorridor>,
 
     /// Edits without these are player generated.
     pub proposal_description: Vec<String>,
@@ -56,6 +62,22 @@ pub struct PermanentEditCrosswalks {
     turns: BTreeMap<traffic_signal_data::Turn, TurnType>,
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PermanentDiagonalFilter {
+    r1: OriginalRoad,
+    r2: OriginalRoad,
+    filter_type: FilterType,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PermanentSignalCorridor {
+    signals: Vec<osm::NodeID>,
+    cycle_length: Duration,
+    design_speed_fwd: Speed,
+    design_speed_back: Speed,
+    favor: CorridorDirection,
+}
+
 #[allow(clippy::enum_variant_names)]
 #[derive(Serialize, Deserialize, Clone)]
 pub enum PermanentEditCmd {
@@ -79,6 +101,16 @@ pub enum PermanentEditCmd {
         old: Vec<Time>,
         new: Vec<Time>,
     },
+    ChangeDiagonalFilter {
+        i: osm::NodeID,
+        old: Option<PermanentDiagonalFilter>,
+        new: Option<PermanentDiagonalFilter>,
+    },
+    ChangeTransitSignalPriority {
+        gtfs_id: String,
+        old: bool,
+        new: bool,
+    },
 }
 
 impl EditCmd {
@@ -106,6 +138,20 @@ impl EditCmd {
                     new: new.clone(),
                 }
             }
+            EditCmd::ChangeDiagonalFilter { i, old, new } => {
+                PermanentEditCmd::ChangeDiagonalFilter {
+                    i: map.get_i(*i).orig_id,
+                    old: old.as_ref().map(|f| f.to_permanent(map)),
+                    new: new.as_ref().map(|f| f.to_permanent(map)),
+                }
+            }
+            EditCmd::ChangeTransitSignalPriority { id, old, new } => {
+                PermanentEditCmd::ChangeTransitSignalPriority {
+                    gtfs_id: map.get_tr(*id).gtfs_id.clone(),
+                    old: *old,
+                    new: *new,
+                }
+            }
         }
     }
 }
@@ -167,6 +213,26 @@ impl PermanentEditCmd {
                     .ok_or_else(|| anyhow!("can't find {}", gtfs_id))?;
                 Ok(EditCmd::ChangeRouteSchedule { id, old, new })
             }
+            PermanentEditCmd::ChangeDiagonalFilter { i, old, new } => {
+                let id = map.find_i_by_osm_id(i)?;
+                Ok(EditCmd::ChangeDiagonalFilter {
+                    i: id,
+                    old: old
+                        .map(|f| f.with_permanent(id, map))
+                        .transpose()
+                        .with_context(|| format!("old ChangeDiagonalFilter of {} invalid", i))?,
+                    new: new
+                        .map(|f| f.with_permanent(id, map))
+                        .transpose()
+                        .with_context(|| format!("new ChangeDiagonalFilter of {} invalid", i))?,
+                })
+            }
+            PermanentEditCmd::ChangeTransitSignalPriority { gtfs_id, old, new } => {
+                let id = map
+                    .find_tr_by_gtfs(&gtfs_id)
+                    .ok_or_else(|| anyhow!("can't find {}", gtfs_id))?;
+                Ok(EditCmd::ChangeTransitSignalPriority { id, old, new })
+            }
         }
     }
 }
@@ -178,11 +244,16 @@ impl MapEdits {
             map_name: map.get_name().clone(),
             edits_name: self.edits_name.clone(),
             // Increase this every time there's a schema change
-            version: 11,
+            version: 15,
             proposal_description: self.proposal_description.clone(),
             proposal_link: self.proposal_link.clone(),
             commands: self.commands.iter().map(|cmd| cmd.to_perma(map)).collect(),
             merge_zones: self.merge_zones,
+            signal_corridors: self
+                .signal_corridors
+                .iter()
+                .map(|c| c.to_permanent(map))
+                .collect(),
         }
     }
 }
@@ -201,11 +272,18 @@ impl PermanentMapEdits {
                 .map(|cmd| cmd.into_cmd(map))
                 .collect::<Result<Vec<EditCmd>>>()?,
             merge_zones: self.merge_zones,
+            signal_corridors: self
+                .signal_corridors
+                .into_iter()
+                .map(|c| c.with_permanent(map))
+                .collect::<Result<Vec<SignalCorridor>>>()?,
 
             changed_roads: BTreeSet::new(),
             original_intersections: BTreeMap::new(),
             original_crosswalks: BTreeMap::new(),
             changed_routes: BTreeSet::new(),
+            changed_diagonal_filters: BTreeSet::new(),
+            transit_signal_priority: BTreeSet::new(),
         };
         edits.update_derived(map);
         Ok(edits)
@@ -230,11 +308,24 @@ impl PermanentMapEdits {
                 })
                 .collect(),
             merge_zones: self.merge_zones,
+            signal_corridors: self
+                .signal_corridors
+                .into_iter()
+                .filter_map(|c| match c.with_permanent(map) {
+                    Ok(c) => Some(c),
+                    Err(err) => {
+                        warn!("Skipping broken signal corridor: {}", err);
+                        None
+                    }
+                })
+                .collect(),
 
             changed_roads: BTreeSet::new(),
             original_intersections: BTreeMap::new(),
             original_crosswalks: BTreeMap::new(),
             changed_routes: BTreeSet::new(),
+            changed_diagonal_filters: BTreeSet::new(),
+            transit_signal_priority: BTreeSet::new(),
         };
         edits.update_derived(map);
         edits
@@ -339,3 +430,53 @@ impl PermanentEditCrosswalks {
         Ok(EditCrosswalks(turns))
     }
 }
+
+impl DiagonalFilter {
+    fn to_permanent(&self, map: &Map) -> PermanentDiagonalFilter {
+        PermanentDiagonalFilter {
+            r1: map.get_r(self.r1).orig_id,
+            r2: map.get_r(self.r2).orig_id,
+            filter_type: self.filter_type,
+        }
+    }
+}
+
+impl PermanentDiagonalFilter {
+    fn with_permanent(self, i: IntersectionID, map: &Map) -> Result<DiagonalFilter> {
+        DiagonalFilter::new(
+            map,
+            i,
+            map.find_r_by_osm_id(self.r1)?,
+            map.find_r_by_osm_id(self.r2)?,
+            self.filter_type,
+        )
+    }
+}
+
+impl SignalCorridor {
+    fn to_permanent(&self, map: &Map) -> PermanentSignalCorridor {
+        PermanentSignalCorridor {
+            signals: self.signals.iter().map(|i| map.get_i(*i).orig_id).collect(),
+            cycle_length: self.cycle_length,
+            design_speed_fwd: self.design_speed_fwd,
+            design_speed_back: self.design_speed_back,
+            favor: self.favor,
+        }
+    }
+}
+
+impl PermanentSignalCorridor {
+    fn with_permanent(self, map: &Map) -> Result<SignalCorridor> {
+        let mut signals = Vec::new();
+        for i in self.signals {
+            signals.push(map.find_i_by_osm_id(i)?);
+        }
+        Ok(SignalCorridor {
+            signals,
+            cycle_length: self.cycle_length,
+            design_speed_fwd: self.design_speed_fwd,
+            design_speed_back: self.design_speed_back,
+            favor: self.favor,
+        })
+    }
+}
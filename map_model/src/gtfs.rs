@@ -0,0 +1,261 @@
+//! Exports the map's public transit as a static GTFS feed, so edits to routes can be used in other
+//! transit tools. See <https://developers.google.com/transit/gtfs/reference>. This is the inverse
+//! of the GTFS import in convert_osm.
+
+use std::fmt::Write;
+
+use anyhow::Result;
+
+use geom::{Duration, Pt2D, Time};
+
+use crate::{Map, PathConstraints, TransitRoute, TransitStopID};
+
+// A/B Street only simulates one generic day, so the exported service runs every day.
+const SERVICE_ID: &str = "everyday";
+const AGENCY_ID: &str = "abstreet";
+
+/// Returns the filename and contents of every file in the feed. Routes use their current
+/// schedule, including edits. Trips imported from a timetable keep their original times at each
+/// stop; other trips assume free-flow driving between stops.
+///
+/// The map doesn't know its own timezone, so the caller must specify it, like
+/// "America/Los_Angeles".
+pub fn export(map: &Map, timezone: &str) -> Result<Vec<(&'static str, String)>> {
+    if timezone.trim().is_empty() {
+        bail!("A GTFS feed needs a timezone");
+    }
+    let timezone = timezone.trim();
+
+    let mut agency = String::new();
+    writeln!(agency, "agency_id,agency_name,agency_url,agency_timezone")?;
+    writeln!(
+        agency,
+        "{},A/B Street,https://abstreet.org,{}",
+        AGENCY_ID,
+        escape(timezone)
+    )?;
+
+    let mut calendar = String::new();
+    writeln!(
+        calendar,
+        "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date"
+    )?;
+    writeln!(calendar, "{},1,1,1,1,1,1,1,20200101,20991231", SERVICE_ID)?;
+
+    let mut stops = String::new();
+    writeln!(stops, "stop_id,stop_name,stop_lat,stop_lon")?;
+    for ts in map.all_transit_stops().values() {
+        let gps = ts.sidewalk_pos.pt(map).to_gps(map.get_gps_bounds());
+        writeln!(
+            stops,
+            "{},{},{},{}",
+            escape(&stop_id(map, ts.id)),
+            escape(&ts.name),
+            gps.y(),
+            gps.x()
+        )?;
+    }
+
+    let mut routes = String::new();
+    writeln!(
+        routes,
+        "route_id,agency_id,route_short_name,route_long_name,route_type"
+    )?;
+    let mut trips = String::new();
+    writeln!(trips, "route_id,service_id,trip_id,shape_id")?;
+    let mut stop_times = String::new();
+    writeln!(
+        stop_times,
+        "trip_id,arrival_time,departure_time,stop_id,stop_sequence"
+    )?;
+    let mut shapes = String::new();
+    writeln!(
+        shapes,
+        "shape_id,shape_pt_lat,shape_pt_lon,shape_pt_sequence"
+    )?;
+
+    for route in map.all_transit_routes() {
+        let paths = match route.all_paths(map) {
+            Ok(paths) => paths,
+            Err(err) => {
+                warn!("Not exporting {}: {}", route.long_name, err);
+                continue;
+            }
+        };
+        let id = route_id(route);
+        writeln!(
+            routes,
+            "{},{},{},{},{}",
+            escape(&id),
+            AGENCY_ID,
+            escape(&route.short_name),
+            escape(&route.long_name),
+            // Light rail is the closest type to a train. 3 is a bus.
+            if route.route_type == PathConstraints::Bus {
+                3
+            } else {
+                0
+            }
+        )?;
+
+        let mut pts: Vec<Pt2D> = Vec::new();
+        for path in &paths {
+            if let Some(pl) = path.trace(map) {
+                for pt in pl.into_points() {
+                    if pts.last() != Some(&pt) {
+                        pts.push(pt);
+                    }
+                }
+            }
+        }
+        for (idx, gps) in map
+            .get_gps_bounds()
+            .convert_back(&pts)
+            .into_iter()
+            .enumerate()
+        {
+            writeln!(shapes, "{},{},{},{}", escape(&id), gps.y(), gps.x(), idx)?;
+        }
+
+        // When to depart each stop, relative to the vehicle starting the route
+        let mut free_flow = Vec::new();
+        let mut total = Duration::ZERO;
+        for path in &paths[0..route.stops.len()] {
+            total += path.estimate_duration(map, None);
+            free_flow.push(total);
+        }
+
+        for (trip_idx, spawn_time) in route.spawn_times.iter().enumerate() {
+            let trip_id = format!("{}_{}", id, trip_idx);
+            writeln!(
+                trips,
+                "{},{},{},{}",
+                escape(&id),
+                SERVICE_ID,
+                escape(&trip_id),
+                escape(&id)
+            )?;
+            let times: Vec<Time> = match route.scheduled_stop_times(*spawn_time) {
+                Some(times) => times.clone(),
+                None => free_flow.iter().map(|dt| *spawn_time + *dt).collect(),
+            };
+            for (seq, (ts, t)) in route.stops.iter().zip(times).enumerate() {
+                writeln!(
+                    stop_times,
+                    "{},{},{},{},{}",
+                    escape(&trip_id),
+                    gtfs_time(t),
+                    gtfs_time(t),
+                    escape(&stop_id(map, *ts)),
+                    seq
+                )?;
+            }
+        }
+    }
+
+    Ok(vec![
+        ("agency.txt", agency),
+        ("calendar.txt", calendar),
+        ("stops.txt", stops),
+        ("routes.txt", routes),
+        ("trips.txt", trips),
+        ("stop_times.txt", stop_times),
+        ("shapes.txt", shapes),
+    ])
+}
+
+/// Writes every file in the feed to a directory, returning the directory.
+pub fn write(map: &Map, timezone: &str, dir: String) -> Result<String> {
+    for (filename, contents) in export(map, timezone)? {
+        abstio::write_raw(format!("{}/{}", dir, filename), contents.as_bytes())?;
+    }
+    Ok(dir)
+}
+
+fn route_id(route: &TransitRoute) -> String {
+    if route.gtfs_id.is_empty() {
+        format!("route{}", route.id.0)
+    } else {
+        route.gtfs_id.clone()
+    }
+}
+
+fn stop_id(map: &Map, id: TransitStopID) -> String {
+    let ts = map.get_ts(id);
+    if ts.gtfs_id.is_empty() {
+        format!("stop{}_{}", id.road.0, id.idx)
+    } else {
+        ts.gtfs_id.clone()
+    }
+}
+
+/// GTFS times may go past 24 hours for service after midnight.
+fn gtfs_time(t: Time) -> String {
+    let secs = t.inner_seconds().round() as usize;
+    format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600,
+        (secs / 60) % 60,
+        secs % 60
+    )
+}
+
+fn escape(value: &str) -> String {
+    if value.contains(',') || value.contains('"') || value.contains('\n') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gtfs_time() {
+        assert_eq!(gtfs_time(Time::START_OF_DAY), "00:00:00");
+        assert_eq!(
+            gtfs_time(Time::START_OF_DAY + Duration::hours(7) + Duration::seconds(65.4)),
+            "07:01:05"
+        );
+        // Service after midnight keeps counting hours
+        assert_eq!(
+            gtfs_time(Time::START_OF_DAY + Duration::hours(25) + Duration::minutes(30)),
+            "25:30:00"
+        );
+        // The importer reads these back
+        let t = Time::START_OF_DAY + Duration::hours(26) + Duration::seconds(59.0);
+        assert_eq!(Time::parse(&gtfs_time(t)).unwrap(), t);
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("Route 44"), "Route 44");
+        assert_eq!(escape(""), "");
+        assert_eq!(escape("Ballard, Fremont"), "\"Ballard, Fremont\"");
+        assert_eq!(escape("the \"express\""), "\"the \"\"express\"\"\"");
+        assert_eq!(escape("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn test_export_needs_timezone() {
+        let map = Map::blank();
+        assert!(export(&map, " ").is_err());
+
+        let files = export(&map, "America/Los_Angeles").unwrap();
+        let agency = &files
+            .iter()
+            .find(|(name, _)| *name == "agency.txt")
+            .unwrap()
+            .1;
+        assert!(agency.ends_with(",America/Los_Angeles\n"));
+        // No routes means only headers
+        let stop_times = &files
+            .iter()
+            .find(|(name, _)| *name == "stop_times.txt")
+            .unwrap()
+            .1;
+        assert_eq!(stop_times.lines().count(), 1);
+    }
+}
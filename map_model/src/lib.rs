@@ -34,16 +34,18 @@ use serde::{Deserialize, Serialize};
 
 use abstio::MapName;
 use abstutil::{deserialize_btreemap, serialize_btreemap, MultiMap};
-use geom::{Bounds, GPSBounds, Polygon};
+use geom::{Bounds, Duration, GPSBounds, Polygon};
 pub use raw_map as raw;
 pub use raw_map::{
     osm, Amenity, AmenityType, AreaType, BufferType, Direction, DrivingSide, IntersectionType,
-    LaneSpec, LaneType, MapConfig, NamePerLanguage, NORMAL_LANE_THICKNESS, SIDEWALK_THICKNESS,
+    LaneSpec, LaneType, MapConfig, NamePerLanguage, TimeWindow, NORMAL_LANE_THICKNESS,
+    SIDEWALK_THICKNESS,
 };
 
 pub use crate::city::City;
 pub use crate::edits::{
-    EditCmd, EditEffects, EditIntersection, EditRoad, MapEdits, PermanentMapEdits,
+    CorridorDirection, CorridorLayout, EditCmd, EditEffects, EditIntersection, EditRoad, MapEdits,
+    PermanentMapEdits, SignalCorridor,
 };
 pub use crate::make::RawToMapOptions;
 pub use crate::objects::area::{Area, AreaID};
@@ -51,24 +53,31 @@ pub use crate::objects::block::{Block, Perimeter};
 pub use crate::objects::building::{Building, BuildingID, BuildingType, OffstreetParking};
 pub use crate::objects::intersection::{Intersection, IntersectionID};
 pub use crate::objects::lane::{CommonEndpoint, Lane, LaneID, PARKING_LOT_SPOT_LENGTH};
+pub use crate::objects::modal_filter::{DiagonalFilter, FilterType, RoadFilter};
 pub use crate::objects::movement::{CompressedMovementID, Movement, MovementID};
 pub use crate::objects::parking_lot::{ParkingLot, ParkingLotID};
 pub use crate::objects::road::{DirectedRoadID, Road, RoadID, RoadSideID, SideOfRoad};
 pub use crate::objects::stop_signs::{ControlStopSign, RoadWithStopSign};
 pub use crate::objects::traffic_signals::{ControlTrafficSignal, Stage, StageType};
-pub use crate::objects::transit::{TransitRoute, TransitRouteID, TransitStop, TransitStopID};
+pub use crate::objects::transit::{
+    ScheduledTrip, TransitRoute, TransitRouteID, TransitStop, TransitStopID,
+};
 pub use crate::objects::turn::{Turn, TurnID, TurnPriority, TurnType};
-pub use crate::objects::zone::{AccessRestrictions, Zone};
+pub use crate::objects::zone::{AccessRestrictions, ConditionalAccess, Zone};
 pub use crate::pathfind::uber_turns::{IntersectionCluster, UberTurn};
 pub use crate::pathfind::{
-    Path, PathConstraints, PathRequest, PathStep, PathStepV2, PathV2, Pathfinder, PathfinderCache,
-    PathfinderCaching, RoutingParams,
+    vehicle_cost, Journey, JourneyAccess, JourneyPlanner, Path, PathConstraints, PathRequest,
+    PathStep, PathStepV2, PathV2, Pathfinder, PathfinderCache, PathfinderCaching, RoadTravelTimes,
+    RoutingParams, TransitRide,
+};
+pub use crate::traversable::{
+    Position, Traversable, MAX_BIKE_SPEED, MAX_TRUCK_SPEED, MAX_WALKING_SPEED,
 };
-pub use crate::traversable::{Position, Traversable, MAX_BIKE_SPEED, MAX_WALKING_SPEED};
 
 mod city;
 pub mod connectivity;
 mod edits;
+pub mod gtfs;
 mod make;
 mod map;
 mod objects;
@@ -114,4 +123,8 @@ pub struct Map {
     edits_generation: usize,
     #[serde(skip_serializing, skip_deserializing)]
     road_to_buildings: MultiMap<RoadID, BuildingID>,
+    /// Sorted times of day when some conditional restriction starts or stops, and whether any
+    /// restriction is in effect afterwards
+    #[serde(skip_serializing, skip_deserializing)]
+    conditional_restriction_changes: Vec<(Duration, bool)>,
 }
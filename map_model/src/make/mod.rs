@@ -73,6 +73,7 @@ impl Map {
             edits: MapEdits::new(),
             edits_generation: 0,
             road_to_buildings: MultiMap::new(),
+            conditional_restriction_changes: Vec::new(),
         };
         map.edits = map.new_edits();
 
@@ -98,6 +99,7 @@ impl Map {
                 outgoing_lanes: Vec::new(),
                 roads: i.roads.iter().map(|id| road_id_mapping[id]).collect(),
                 merged: !raw.intersections[&i.id].trim_roads_for_merging.is_empty(),
+                modal_filter: None,
             });
             intersection_id_mapping.insert(i.id, id);
         }
@@ -139,6 +141,13 @@ impl Map {
                         }
                     })
                     .collect(),
+                conditional_turn_restrictions: raw_road
+                    .conditional_turn_restrictions
+                    .iter()
+                    .filter_map(|(to, windows)| {
+                        road_id_mapping.get(to).map(|to| (*to, windows.clone()))
+                    })
+                    .collect(),
                 orig_id: r.id,
                 lanes: Vec::new(),
                 center_pts: r.trimmed_center_pts,
@@ -148,13 +157,14 @@ impl Map {
                 speed_limit: Speed::ZERO,
                 zorder: raw_road.get_zorder(),
                 access_restrictions: AccessRestrictions::new(),
+                modal_filter: None,
                 percent_incline: raw_road.percent_incline,
                 crosswalk_forward: raw_road.crosswalk_forward,
                 crosswalk_backward: raw_road.crosswalk_backward,
                 transit_stops: BTreeSet::new(),
             };
             road.speed_limit = road.speed_limit_from_osm();
-            road.access_restrictions = road.access_restrictions_from_osm();
+            road.access_restrictions = road.access_restrictions_from_osm(&map.config);
 
             road.recreate_lanes(raw_road.lane_specs_ltr.clone());
             for lane in &road.lanes {
@@ -239,6 +249,7 @@ impl Map {
         );
 
         map.zones = Zone::make_all(&map);
+        map.recalculate_conditional_restriction_changes();
 
         for a in &raw.areas {
             map.areas.push(Area {
@@ -8,8 +8,8 @@ use geom::{Distance, Duration, FindClosest, HashablePt2D, Time};
 use crate::make::match_points_to_lanes;
 use crate::raw::{RawMap, RawTransitRoute, RawTransitStop, RawTransitType};
 use crate::{
-    LaneID, Map, PathConstraints, Position, TransitRoute, TransitRouteID, TransitStop,
-    TransitStopID,
+    LaneID, Map, PathConstraints, Position, ScheduledTrip, TransitRoute, TransitRouteID,
+    TransitStop, TransitStopID,
 };
 
 pub fn finalize_transit(map: &mut Map, raw: &RawMap, timer: &mut Timer) {
@@ -148,11 +148,16 @@ fn create_route(
     snapper: &BorderSnapper,
 ) -> Result<()> {
     // TODO At least warn about stops that failed to snap
-    let stops: Vec<TransitStopID> = route
-        .stops
-        .iter()
-        .filter_map(|gtfs_id| gtfs_to_stop_id.get(gtfs_id).cloned())
-        .collect();
+    let mut stops = Vec::new();
+    let mut schedule: Vec<Vec<Time>> = vec![Vec::new(); route.schedule.len()];
+    for (idx, gtfs_id) in route.stops.iter().enumerate() {
+        if let Some(id) = gtfs_to_stop_id.get(gtfs_id) {
+            stops.push(*id);
+            for (trip, stop_times) in schedule.iter_mut().zip(route.schedule.iter()) {
+                trip.push(stop_times[idx]);
+            }
+        }
+    }
     if stops.is_empty() {
         bail!("No valid stops");
     }
@@ -221,12 +226,7 @@ fn create_route(
         }
     };
 
-    // TODO This'll come from the RawTransitRoute eventually. For now, every 30 minutes.
-    let spawn_times: Vec<Time> = (0..48)
-        .map(|i| Time::START_OF_DAY + (i as f64) * Duration::minutes(30))
-        .collect();
-
-    let result = TransitRoute {
+    let mut result = TransitRoute {
         id: TransitRouteID(map.transit_routes.len()),
         long_name: route.long_name.clone(),
         short_name: route.short_name.clone(),
@@ -238,12 +238,65 @@ fn create_route(
             RawTransitType::Bus => PathConstraints::Bus,
             RawTransitType::Train => PathConstraints::Train,
         },
-        spawn_times: spawn_times.clone(),
-        orig_spawn_times: spawn_times,
+        spawn_times: Vec::new(),
+        orig_spawn_times: Vec::new(),
+        timetable: Vec::new(),
     };
 
     // Check that the paths are valid
-    result.all_paths(map)?;
+    let paths = result.all_paths(map)?;
+
+    if schedule.is_empty() {
+        // Without a timetable, just run every 30 minutes.
+        result.orig_spawn_times = (0..48)
+            .map(|i| Time::START_OF_DAY + (i as f64) * Duration::minutes(30))
+            .collect();
+    } else {
+        // The vehicle has to leave the route's start early enough to reach the first stop on time
+        let time_to_first_stop = paths[0].estimate_duration(map, None);
+        for stop_times in schedule {
+            let spawn_time = if stop_times[0] - Time::START_OF_DAY > time_to_first_stop {
+                stop_times[0] - time_to_first_stop
+            } else {
+                Time::START_OF_DAY
+            };
+            result.timetable.push(ScheduledTrip {
+                spawn_time,
+                stop_times,
+            });
+        }
+        result.timetable.sort_by(|a, b| {
+            a.spawn_time
+                .cmp(&b.spawn_time)
+                .then_with(|| a.stop_times.cmp(&b.stop_times))
+        });
+        // The same run might be listed under several services
+        let num_trips = result.timetable.len();
+        result
+            .timetable
+            .dedup_by(|a, b| a.stop_times == b.stop_times);
+        if result.timetable.len() != num_trips {
+            warn!(
+                "Route {} lists {} runs more than once; only keeping one copy of each",
+                route.gtfs_id,
+                num_trips - result.timetable.len()
+            );
+        }
+        // Different runs could start at the same time, but each vehicle needs a distinct spawn
+        // time. Stagger them by a second.
+        for idx in 1..result.timetable.len() {
+            let prev = result.timetable[idx - 1].spawn_time;
+            if result.timetable[idx].spawn_time <= prev {
+                result.timetable[idx].spawn_time = prev + Duration::seconds(1.0);
+            }
+        }
+        result.orig_spawn_times = result
+            .timetable
+            .iter()
+            .map(|trip| trip.spawn_time)
+            .collect();
+    }
+    result.spawn_times = result.orig_spawn_times.clone();
 
     map.transit_routes.push(result);
     Ok(())
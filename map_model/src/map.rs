@@ -67,6 +67,7 @@ impl Map {
 
         self.edits = self.new_edits();
         self.recalculate_road_to_buildings();
+        self.recalculate_conditional_restriction_changes();
         self.recalculate_all_movements(timer);
 
         // Enable to work on shrinking map file sizes. Never run this on the web though --
@@ -160,6 +161,7 @@ impl Map {
             edits: MapEdits::new(),
             edits_generation: 0,
             road_to_buildings: MultiMap::new(),
+            conditional_restriction_changes: Vec::new(),
         }
     }
 
@@ -364,6 +366,8 @@ impl Map {
             .collect()
     }
 
+    /// Turns from the end of a lane usable by some vehicle. Reaching them means driving the whole
+    /// lane, so a modal filter anywhere on it blocks every turn.
     pub fn get_next_turns_and_lanes_for(
         &self,
         from: LaneID,
@@ -371,7 +375,9 @@ impl Map {
     ) -> Vec<(&Turn, &Lane)> {
         self.get_next_turns_and_lanes(from)
             .into_iter()
-            .filter(|(_, l)| constraints.can_use(l, self))
+            .filter(|(t, l)| {
+                constraints.can_use(l, self) && constraints.can_cross_filters(t.id, self)
+            })
             .collect()
     }
 
@@ -391,9 +397,17 @@ impl Map {
         let mut result = BTreeSet::new();
         for t in &self.get_i(from.dst_i(self)).turns {
             let src = self.get_l(t.id.src);
+            // Filters along the road itself are handled by vehicle_cost, because a vehicle might
+            // start partway along the road, past the filter
             if src.get_directed_parent() == from
                 && constraints.can_use(src, self)
                 && constraints.can_use(self.get_l(t.id.dst), self)
+                && constraints.allowed_by_diagonal_filter(
+                    t.id.parent,
+                    t.id.src.road,
+                    t.id.dst.road,
+                    self,
+                )
             {
                 result.insert(t.id.to_movement(self));
             }
@@ -585,6 +599,34 @@ impl Map {
             .pathfind_with_params(req.clone(), params, cache_custom, self)
             .ok_or_else(|| anyhow!("can't fulfill {}", req))
     }
+    /// Like `pathfind`, but also respects conditional access restrictions, peak-hour bus lanes,
+    /// turn bans, and road travel times in effect at some time. When none apply, this is just as
+    /// fast as `pathfind`.
+    pub fn pathfind_at(&self, req: PathRequest, time: Time) -> Result<Path> {
+        self.pathfind_at_with_params(req, time, self.routing_params())
+    }
+    /// Like `pathfind_at`, but uses custom routing params for vehicles. A fast pathfinder is built
+    /// and cached for each distinct set of params, so only use this with a few of them.
+    pub fn pathfind_at_with_params(
+        &self,
+        req: PathRequest,
+        time: Time,
+        params: &RoutingParams,
+    ) -> Result<Path> {
+        if req.constraints == PathConstraints::Pedestrian {
+            return self.pathfind(req);
+        }
+        match self.pathfinding_period_start(time, params) {
+            Some(changed) => {
+                let mut params = params.clone();
+                params.time = Some(changed);
+                // Restrictions only change a few times per day, so it's worth keeping a fast
+                // pathfinder around for each period.
+                self.pathfind_with_params(req, &params, PathfinderCaching::CacheCH)
+            }
+            None => self.pathfind_with_params(req, params, PathfinderCaching::CacheCH),
+        }
+    }
     pub fn should_use_transit(
         &self,
         start: Position,
@@ -797,6 +839,74 @@ impl Map {
         &self.routing_params
     }
 
+    /// Pathfinding only changes when some conditional restriction starts or stops, or when a new
+    /// window of road travel times begins. Returns when the period containing some time started,
+    /// as a time on the first day, so the same pathfinder is used for that period every day. None
+    /// means nothing time-dependent applies right now.
+    fn pathfinding_period_start(&self, time: Time, params: &RoutingParams) -> Option<Time> {
+        let day = Duration::hours(24);
+        let time_of_day = Duration::seconds(time.inner_seconds() % day.inner_seconds());
+        // How long ago did things last change? Restrictions and travel times both repeat every
+        // day, so compare times of day. A restriction active since yesterday evening may have
+        // started "after" the current time of day.
+        let mut since_change = self
+            .conditional_restrictions_changed_at(time)
+            .map(|changed| (time_of_day - changed + day) % day);
+        if let Some(ref times) = params.road_travel_times {
+            let since_window = time - times.window_start(time);
+            since_change = Some(since_change.map_or(since_window, |d| d.min(since_window)));
+        }
+        since_change.map(|since| Time::START_OF_DAY + (time_of_day - since + day) % day)
+    }
+
+    /// If any conditional restrictions are in effect at this time, returns the most recent time of
+    /// day when some restriction started or stopped.
+    fn conditional_restrictions_changed_at(&self, time: Time) -> Option<Duration> {
+        let changes = &self.conditional_restriction_changes;
+        if changes.is_empty() {
+            return None;
+        }
+        let time_of_day = Duration::seconds(time.inner_seconds() % (24.0 * 3600.0));
+        // If nothing changed earlier today, the period in effect started yesterday
+        let (latest, active) = match changes.partition_point(|(t, _)| *t <= time_of_day) {
+            0 => changes.last().unwrap(),
+            idx => &changes[idx - 1],
+        };
+        if *active {
+            Some(*latest)
+        } else {
+            None
+        }
+    }
+
+    /// Find every time of day when some conditional restriction starts or stops, and whether any
+    /// restriction is in effect until the next change.
+    pub(crate) fn recalculate_conditional_restriction_changes(&mut self) {
+        let mut windows = Vec::new();
+        for r in self.all_roads() {
+            let turn_bans = r
+                .conditional_turn_restrictions
+                .iter()
+                .flat_map(|(_, windows)| windows.iter());
+            windows.extend(
+                r.access_restrictions
+                    .all_windows()
+                    .chain(turn_bans)
+                    .copied(),
+            );
+        }
+        let mut times: Vec<Duration> = windows.iter().flat_map(|w| [w.start, w.end]).collect();
+        times.sort();
+        times.dedup();
+        self.conditional_restriction_changes = times
+            .into_iter()
+            .map(|t| {
+                let active = windows.iter().any(|w| w.contains(Time::START_OF_DAY + t));
+                (t, active)
+            })
+            .collect();
+    }
+
     pub fn road_to_buildings(&self, r: RoadID) -> &BTreeSet<BuildingID> {
         self.road_to_buildings.get(r)
     }
@@ -900,3 +1010,86 @@ impl Map {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RoadTravelTimes;
+
+    fn at(day: usize, hours: usize, minutes: usize) -> Time {
+        Time::START_OF_DAY + Duration::hours(24 * day + hours) + Duration::minutes(minutes)
+    }
+
+    fn hourly_travel_times() -> RoutingParams {
+        RoutingParams {
+            road_travel_times: Some(RoadTravelTimes::new(Duration::hours(1))),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_pathfinding_period_across_days() {
+        let mut map = Map::blank();
+        // Something's restricted from 07:30 to 09:00
+        map.conditional_restriction_changes = vec![
+            (Duration::hours(7) + Duration::minutes(30), true),
+            (Duration::hours(9), false),
+        ];
+        let plain = RoutingParams::default();
+        let hourly = hourly_travel_times();
+
+        for day in 0..3 {
+            // The restriction started more recently than the travel time window
+            assert_eq!(
+                map.pathfinding_period_start(at(day, 7, 45), &hourly),
+                Some(at(0, 7, 30))
+            );
+            // And the other way around
+            assert_eq!(
+                map.pathfinding_period_start(at(day, 8, 20), &hourly),
+                Some(at(0, 8, 0))
+            );
+            // Only travel times matter after the restriction ends
+            assert_eq!(
+                map.pathfinding_period_start(at(day, 10, 0), &hourly),
+                Some(at(0, 10, 0))
+            );
+            assert_eq!(
+                map.pathfinding_period_start(at(day, 8, 20), &plain),
+                Some(at(0, 7, 30))
+            );
+            assert_eq!(map.pathfinding_period_start(at(day, 10, 0), &plain), None);
+        }
+    }
+
+    #[test]
+    fn test_pathfinding_period_overnight() {
+        let mut map = Map::blank();
+        // Something's restricted from 22:00 to 06:00
+        map.conditional_restriction_changes =
+            vec![(Duration::hours(6), false), (Duration::hours(22), true)];
+        let plain = RoutingParams::default();
+        let hourly = hourly_travel_times();
+
+        for day in 0..3 {
+            // The restriction started yesterday, so the travel time window is more recent
+            assert_eq!(
+                map.pathfinding_period_start(at(day, 3, 0), &hourly),
+                Some(at(0, 3, 0))
+            );
+            assert_eq!(
+                map.pathfinding_period_start(at(day, 3, 0), &plain),
+                Some(at(0, 22, 0))
+            );
+            assert_eq!(
+                map.pathfinding_period_start(at(day, 22, 30), &hourly),
+                Some(at(0, 22, 0))
+            );
+            assert_eq!(
+                map.pathfinding_period_start(at(day, 6, 30), &hourly),
+                Some(at(0, 6, 0))
+            );
+            assert_eq!(map.pathfinding_period_start(at(day, 6, 30), &plain), None);
+        }
+    }
+}
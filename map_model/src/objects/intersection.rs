@@ -9,8 +9,8 @@ use geom::{Distance, Polygon};
 use raw_map::IntersectionType;
 
 use crate::{
-    osm, CompressedMovementID, DirectedRoadID, LaneID, Map, Movement, MovementID, PathConstraints,
-    Road, RoadID, RoadSideID, SideOfRoad, Turn, TurnID,
+    osm, CompressedMovementID, DiagonalFilter, DirectedRoadID, LaneID, Map, Movement, MovementID,
+    PathConstraints, Road, RoadID, RoadSideID, SideOfRoad, Turn, TurnID,
 };
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
@@ -54,6 +54,8 @@ pub struct Intersection {
 
     /// Was a short road adjacent to this intersection merged?
     pub merged: bool,
+    /// Blocks some traffic from turning between certain roads
+    pub modal_filter: Option<DiagonalFilter>,
     // These increase the map file size, so instead, just use `recalculate_all_movements` after
     // deserializing.
     #[serde(skip_serializing, skip_deserializing)]
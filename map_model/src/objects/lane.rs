@@ -3,7 +3,7 @@ use std::fmt;
 
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-use geom::{Distance, Line, PolyLine, Polygon, Pt2D};
+use geom::{Distance, Line, PolyLine, Polygon, Pt2D, Time};
 use raw_map::LaneType;
 
 use crate::{
@@ -167,6 +167,33 @@ impl Lane {
         self.lane_type == LaneType::Bus
     }
 
+    /// Is this a bus lane, or a driving lane that's reserved for buses at this time of day?
+    pub fn is_bus_only_at(&self, time: Time, map: &Map) -> bool {
+        if self.is_bus() {
+            return true;
+        }
+        if !self.is_driving() {
+            return false;
+        }
+        let road = map.get_r(self.id.road);
+        if !road.access_restrictions.peak_bus_lane_at(self.dir, time) {
+            return false;
+        }
+        // The peak-hour bus lane is the driving lane closest to the curb
+        let mut driving = road
+            .lanes
+            .iter()
+            .filter(|l| l.dir == self.dir && l.is_driving());
+        let curbside = if (self.dir == Direction::Fwd)
+            == (map.get_config().driving_side == DrivingSide::Right)
+        {
+            driving.last()
+        } else {
+            driving.next()
+        };
+        curbside.map(|l| l.id) == Some(self.id)
+    }
+
     pub fn is_walkable(&self) -> bool {
         self.lane_type.is_walkable()
     }
@@ -3,6 +3,7 @@ pub mod block;
 pub mod building;
 pub mod intersection;
 pub mod lane;
+pub mod modal_filter;
 pub mod movement;
 pub mod parking_lot;
 pub mod road;
@@ -0,0 +1,139 @@
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use geom::{Distance, Line};
+
+use crate::{IntersectionID, Map, PathConstraints, RoadID};
+
+/// What kind of traffic can pass through a modal filter? Pedestrians can always get through.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FilterType {
+    /// Bollards, planters, and the like. Only cyclists and pedestrians can pass.
+    WalkCycleOnly,
+    /// Usually enforced by a camera. Buses, cyclists, and pedestrians can pass.
+    BusGate,
+}
+
+impl FilterType {
+    pub fn allows(self, constraints: PathConstraints) -> bool {
+        match constraints {
+            // Filters don't cover light rail tracks
+            PathConstraints::Pedestrian | PathConstraints::Bike | PathConstraints::Train => true,
+            PathConstraints::Bus => self == FilterType::BusGate,
+            PathConstraints::Car | PathConstraints::Truck => false,
+        }
+    }
+}
+
+/// A modal filter placed somewhere along a road. Vehicles that can't pass it can still reach
+/// anything on the road from either end; they just can't drive through.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RoadFilter {
+    /// Measured from the road's `src_i`
+    pub dist: Distance,
+    pub filter_type: FilterType,
+}
+
+/// A diagonal filter exists in an intersection. It's defined by two roads (the order is
+/// arbitrary). When all of the intersection's roads are sorted in clockwise order, this pair of
+/// roads splits the ordering into two groups. Turns in each group are still possible, but not
+/// across groups.
+///
+/// TODO Be careful with PartialEq! At a 4-way intersection, the same filter can be expressed as a
+/// different pair of two roads. And the (r1, r2) ordering is also arbitrary.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DiagonalFilter {
+    pub r1: RoadID,
+    pub r2: RoadID,
+    pub i: IntersectionID,
+    // Diagonal filters saved by the LTN tool before bus gates existed
+    #[serde(default = "walk_cycle_only")]
+    pub filter_type: FilterType,
+
+    group1: BTreeSet<RoadID>,
+    group2: BTreeSet<RoadID>,
+}
+
+impl DiagonalFilter {
+    /// Only 4-way intersections can have true diagonal filters, and the two roads must be
+    /// adjacent.
+    pub fn new(
+        map: &Map,
+        i: IntersectionID,
+        r1: RoadID,
+        r2: RoadID,
+        filter_type: FilterType,
+    ) -> Result<DiagonalFilter> {
+        let mut roads = map.get_i(i).get_roads_sorted_by_incoming_angle(map);
+        if roads.len() != 4 {
+            bail!("{} has {} roads, not 4", i, roads.len());
+        }
+        if !roads.contains(&r1) || !roads.contains(&r2) {
+            bail!("{} and {} don't both meet at {}", r1, r2, i);
+        }
+        // Make r1 be the first entry
+        while roads[0] != r1 {
+            roads.rotate_right(1);
+        }
+
+        let mut group1 = BTreeSet::new();
+        group1.insert(roads.remove(0));
+        loop {
+            let next = roads.remove(0);
+            group1.insert(next);
+            if next == r2 {
+                break;
+            }
+        }
+        if group1.len() != 2 {
+            bail!("{} and {} aren't adjacent at {}", r1, r2, i);
+        }
+
+        Ok(DiagonalFilter {
+            r1,
+            r2,
+            i,
+            filter_type,
+            group1,
+            group2: roads.into_iter().collect(),
+        })
+    }
+
+    /// Physically where is the filter placed?
+    pub fn geometry(&self, map: &Map) -> Line {
+        let r1 = map.get_r(self.r1);
+        let r2 = map.get_r(self.r2);
+
+        // Orient the road to face the intersection
+        let pl1 = r1.center_pts.maybe_reverse(r1.src_i == self.i);
+        let pl2 = r2.center_pts.maybe_reverse(r2.src_i == self.i);
+
+        // The other combinations of left/right here would produce points or a line across just one
+        // road
+        let pt1 = pl1.must_shift_right(r1.get_half_width()).last_pt();
+        let pt2 = pl2.must_shift_left(r2.get_half_width()).last_pt();
+        Line::must_new(pt1, pt2)
+    }
+
+    /// Ignoring the type of filter, can anything turn between these two roads?
+    pub fn allows_turn(&self, from: RoadID, to: RoadID) -> bool {
+        self.group1.contains(&from) == self.group1.contains(&to)
+    }
+
+    pub fn avoid_movements_between_roads(&self) -> Vec<(RoadID, RoadID)> {
+        let mut pairs = Vec::new();
+        for from in &self.group1 {
+            for to in &self.group2 {
+                pairs.push((*from, *to));
+                pairs.push((*to, *from));
+            }
+        }
+        pairs
+    }
+}
+
+fn walk_cycle_only() -> FilterType {
+    FilterType::WalkCycleOnly
+}
@@ -6,15 +6,21 @@ use enumset::EnumSet;
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_usize, serialize_usize, Tags};
-use geom::{Distance, PolyLine, Polygon, Speed};
+use geom::{Distance, PolyLine, Polygon, Speed, Time};
 use raw_map::Direction;
 
 use crate::raw::{OriginalRoad, RestrictionType};
 use crate::{
-    osm, AccessRestrictions, CommonEndpoint, DrivingSide, IntersectionID, Lane, LaneID, LaneSpec,
-    LaneType, Map, PathConstraints, TransitStopID, Zone,
+    osm, AccessRestrictions, CommonEndpoint, ConditionalAccess, DrivingSide, IntersectionID, Lane,
+    LaneID, LaneSpec, LaneType, Map, MapConfig, PathConstraints, RoadFilter, TimeWindow,
+    TransitStopID, Zone,
 };
 
+/// Heavy goods vehicles weigh more than this
+const MIN_TRUCK_WEIGHT_LIMIT_TONNES: f64 = 7.5;
+/// Heavy goods vehicles are taller than this
+const MIN_TRUCK_HEIGHT_LIMIT_METERS: f64 = 4.0;
+
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct RoadID(
     #[serde(
@@ -163,9 +169,13 @@ pub struct Road {
     pub turn_restrictions: Vec<(RestrictionType, RoadID)>,
     /// self is 'from'. (via, to). Only BanTurns.
     pub complicated_turn_restrictions: Vec<(RoadID, RoadID)>,
+    /// self is 'from'. (to, windows). These turns are only banned at some times of day.
+    pub conditional_turn_restrictions: Vec<(RoadID, Vec<TimeWindow>)>,
     pub orig_id: OriginalRoad,
     pub speed_limit: Speed,
     pub access_restrictions: AccessRestrictions,
+    /// A bollard or bus gate somewhere along the road, blocking some through-traffic
+    pub modal_filter: Option<RoadFilter>,
     pub zorder: isize,
     /// [-1.0, 1.0] theoretically, but in practice, about [-0.25, 0.25]. 0 is flat,
     /// positive is uphill from src_i -> dst_i, negative is downhill.
@@ -445,6 +455,25 @@ impl Road {
         self.lanes.iter().any(|l| l.is_driving())
     }
 
+    /// Can heavy goods vehicles use this road at all? Bans, weight limits and low clearances all
+    /// keep them out.
+    pub fn allows_trucks(&self) -> bool {
+        if self.osm_tags.is_any("hgv", vec!["no", "private"]) {
+            return false;
+        }
+        if let Some(tonnes) = self.osm_tags.get("maxweight").and_then(|x| parse_limit(x)) {
+            if tonnes < MIN_TRUCK_WEIGHT_LIMIT_TONNES {
+                return false;
+            }
+        }
+        if let Some(meters) = self.osm_tags.get("maxheight").and_then(|x| parse_limit(x)) {
+            if meters < MIN_TRUCK_HEIGHT_LIMIT_METERS {
+                return false;
+            }
+        }
+        true
+    }
+
     pub fn common_endpoint(&self, other: &Road) -> CommonEndpoint {
         CommonEndpoint::new((self.src_i, self.dst_i), (other.src_i, other.dst_i))
     }
@@ -463,26 +492,94 @@ impl Road {
     }
 
     pub fn is_private(&self) -> bool {
-        self.access_restrictions != AccessRestrictions::new() && !self.is_light_rail()
+        self.access_restrictions.restricts_through_traffic() && !self.is_light_rail()
     }
 
-    pub(crate) fn access_restrictions_from_osm(&self) -> AccessRestrictions {
+    pub(crate) fn access_restrictions_from_osm(&self, cfg: &MapConfig) -> AccessRestrictions {
+        let buses_allowed = self.osm_tags.is("psv", "yes") || self.osm_tags.is("bus", "yes");
         let allow_through_traffic = if self.osm_tags.is("access", "private") {
             EnumSet::new()
         } else if self.osm_tags.is(osm::HIGHWAY, "living_street") {
             let mut allow = PathConstraints::Pedestrian | PathConstraints::Bike;
-            if self.osm_tags.is("psv", "yes") || self.osm_tags.is("bus", "yes") {
+            if buses_allowed {
                 allow |= PathConstraints::Bus;
             }
             allow
+        } else if self.osm_tags.is("hgv", "destination") {
+            EnumSet::all() - PathConstraints::Truck
         } else {
             EnumSet::all()
         };
+
+        // School streets and other timed closures. Treat "destination" like "no", since trips
+        // beginning or ending inside a zone are always allowed.
+        let mut conditional = Vec::new();
+        for (key, mut allow) in [
+            ("access:conditional", EnumSet::new()),
+            (
+                "vehicle:conditional",
+                EnumSet::only(PathConstraints::Pedestrian),
+            ),
+            (
+                "motor_vehicle:conditional",
+                PathConstraints::Pedestrian | PathConstraints::Bike,
+            ),
+        ] {
+            if let Some(value) = self.osm_tags.get(key) {
+                if buses_allowed {
+                    allow |= PathConstraints::Bus;
+                }
+                for (restriction, windows) in TimeWindow::parse_osm_conditional(value) {
+                    if restriction == "no" || restriction == "destination" {
+                        conditional.push(ConditionalAccess {
+                            allow_through_traffic: allow,
+                            windows,
+                        });
+                    }
+                }
+            }
+        }
+
+        // Peak-hour bus lanes, like busway:right:conditional = "lane @ (Mo-Fr 07:00-10:00)"
+        let (right, left) = match cfg.driving_side {
+            DrivingSide::Right => (Direction::Fwd, Direction::Back),
+            DrivingSide::Left => (Direction::Back, Direction::Fwd),
+        };
+        let mut peak_bus_lanes = Vec::new();
+        for (key, dirs) in [
+            ("busway:conditional", vec![Direction::Fwd, Direction::Back]),
+            (
+                "busway:both:conditional",
+                vec![Direction::Fwd, Direction::Back],
+            ),
+            ("busway:right:conditional", vec![right]),
+            ("busway:left:conditional", vec![left]),
+        ] {
+            if let Some(value) = self.osm_tags.get(key) {
+                for (restriction, windows) in TimeWindow::parse_osm_conditional(value) {
+                    if restriction == "lane" {
+                        for dir in &dirs {
+                            peak_bus_lanes.push((*dir, windows.clone()));
+                        }
+                    }
+                }
+            }
+        }
+
         AccessRestrictions {
             allow_through_traffic,
+            conditional,
+            peak_bus_lanes,
         }
     }
 
+    /// Is the turn from this road onto another banned at this time of day?
+    pub fn turn_banned_at(&self, to: RoadID, time: Time) -> bool {
+        self.conditional_turn_restrictions
+            .iter()
+            .any(|(r, windows)| *r == to && windows.iter().any(|w| w.contains(time)))
+    }
+
     pub fn get_zone<'a>(&self, map: &'a Map) -> Option<&'a Zone> {
         if !self.is_private() {
             return None;
@@ -670,3 +767,14 @@ impl Road {
         }
     }
 }
+
+/// Parses an OSM maxweight or maxheight value, like "7.5" or "3.8 m". Imperial units aren't
+/// handled.
+fn parse_limit(value: &str) -> Option<f64> {
+    let mut parts = value.split_whitespace();
+    let number = parts.next()?.parse::<f64>().ok()?;
+    match parts.next() {
+        None | Some("t") | Some("m") => Some(number),
+        _ => None,
+    }
+}
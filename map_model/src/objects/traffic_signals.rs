@@ -40,6 +40,14 @@ pub enum StageType {
     /// Delay is the elapsed time with no demand that ends a cycle.
     /// Additional is the additional duration for an extended cycle.
     Variable(Duration, Duration, Duration),
+    /// Fully actuated control, driven by loop-detector style calls on each movement. The stage is
+    /// skipped entirely if no movement in it has a call. Otherwise, it lasts at least the minimum
+    /// green, then is extended by the passage time every time a vehicle arrives on one of the
+    /// protected movements, up to the maximum green. Crosswalks only get a walk interval when a
+    /// pedestrian has pushed the call button before the stage begins.
+    ///
+    /// Minimum green, maximum green, passage time
+    Actuated(Duration, Duration, Duration),
 }
 
 impl StageType {
@@ -48,6 +56,16 @@ impl StageType {
         match self {
             StageType::Fixed(d) => *d,
             StageType::Variable(duration, _, _) => *duration,
+            StageType::Actuated(min, _, _) => *min,
+        }
+    }
+
+    /// The longest this stage could possibly last.
+    pub fn max_duration(&self) -> Duration {
+        match self {
+            StageType::Fixed(d) => *d,
+            StageType::Variable(min, _, additional) => *min + *additional,
+            StageType::Actuated(_, max, _) => *max,
         }
     }
 }
@@ -135,16 +153,21 @@ impl ControlTrafficSignal {
                 // TODO Maybe make UnmarkedCrossing yield
                 assert!(!m.turn_type.pedestrian_crossing())
             }
-            // Is there enough time in each stage to walk across the crosswalk
+            // Is there enough time in each stage to walk across the crosswalk? Actuated stages
+            // extend the minimum green when a pedestrian calls, so only the maximum matters.
             let min_crossing_time = self.get_min_crossing_time(stage_index, i);
-            if stage.stage_type.simple_duration() < min_crossing_time {
+            let time_given = match stage.stage_type {
+                StageType::Actuated(_, max, _) => max,
+                _ => stage.stage_type.simple_duration(),
+            };
+            if time_given < min_crossing_time {
                 bail!(
                     "Traffic signal does not allow enough time in stage to complete the \
                      crosswalk\nStage Index{}\nStage : {:?}\nTime Required: {}\nTime Given: {}",
                     stage_index,
                     stage,
                     min_crossing_time,
-                    stage.stage_type.simple_duration()
+                    time_given
                 );
             }
         }
@@ -345,12 +368,21 @@ impl Stage {
                 .inner_seconds()
                 .ceil(),
         );
+        // Actuated stages only serve the crosswalk when a pedestrian calls, so just make sure the
+        // maximum green is long enough.
+        if let StageType::Actuated(min, max, passage) = self.stage_type {
+            if time > max {
+                self.stage_type = StageType::Actuated(min, time, passage);
+            }
+            return;
+        }
         if time > self.stage_type.simple_duration() {
             self.stage_type = match self.stage_type {
                 StageType::Fixed(_) => StageType::Fixed(time),
                 StageType::Variable(_, delay, additional) => {
                     StageType::Variable(time, delay, additional)
                 }
+                StageType::Actuated(_, _, _) => unreachable!(),
             };
         }
     }
@@ -407,6 +439,13 @@ impl ControlTrafficSignal {
                                     additional.inner_seconds() as usize,
                                 )
                             }
+                            StageType::Actuated(min, max, passage) => {
+                                traffic_signal_data::StageType::Actuated(
+                                    min.inner_seconds() as usize,
+                                    max.inner_seconds() as usize,
+                                    passage.inner_seconds() as usize,
+                                )
+                            }
                         },
                     })
                     .collect(),
@@ -462,6 +501,13 @@ impl ControlTrafficSignal {
                                 Duration::seconds(additional as f64),
                             )
                         }
+                        traffic_signal_data::StageType::Actuated(min, max, passage) => {
+                            StageType::Actuated(
+                                Duration::seconds(min as f64),
+                                Duration::seconds(max as f64),
+                                Duration::seconds(passage as f64),
+                            )
+                        }
                     },
                 });
             } else {
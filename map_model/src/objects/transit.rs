@@ -69,6 +69,18 @@ pub struct TransitRoute {
     /// Explicitly store whatever the original was, since this can't be reconstructed without side
     /// input.
     pub orig_spawn_times: Vec<Time>,
+    /// The trips from the imported timetable, in order of spawn time. `orig_spawn_times` comes
+    /// from this. Empty if the route had no timetable.
+    pub timetable: Vec<ScheduledTrip>,
+}
+
+/// One run of a route, as scheduled by the timetable.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ScheduledTrip {
+    /// When the vehicle should begin at the route's start
+    pub spawn_time: Time,
+    /// When the vehicle should depart each of the route's stops
+    pub stop_times: Vec<Time>,
 }
 
 impl TransitRoute {
@@ -138,6 +150,15 @@ impl TransitRoute {
         Ok(paths)
     }
 
+    /// If a vehicle beginning the route at some time follows a trip from the timetable, returns
+    /// when it should depart each stop.
+    pub fn scheduled_stop_times(&self, spawn_time: Time) -> Option<&Vec<Time>> {
+        self.timetable
+            .iter()
+            .find(|trip| trip.spawn_time == spawn_time)
+            .map(|trip| &trip.stop_times)
+    }
+
     pub fn plural_noun(&self) -> &'static str {
         if self.route_type == PathConstraints::Bus {
             "buses"
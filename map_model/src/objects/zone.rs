@@ -4,24 +4,77 @@
 //! 2) Stay Healthy Streets, where most car traffic is banned, except for trips beginning/ending in
 //!    the zone
 //! 3) Congestion capping, where only so many cars per hour can enter the zone
+//! 4) School streets and other timed closures, where the restrictions only apply at certain times
+//!    of day
 
 use std::collections::BTreeSet;
 
 use enumset::EnumSet;
 use serde::{Deserialize, Serialize};
 
-use crate::{CommonEndpoint, IntersectionID, Map, PathConstraints, RoadID};
+use geom::Time;
+
+use crate::{CommonEndpoint, Direction, IntersectionID, Map, PathConstraints, RoadID, TimeWindow};
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct AccessRestrictions {
     pub allow_through_traffic: EnumSet<PathConstraints>,
+    /// During these windows, `allow_through_traffic` is replaced. The first matching entry wins.
+    #[serde(default)]
+    pub conditional: Vec<ConditionalAccess>,
+    /// During these windows, the curbside driving lane in this direction is reserved for buses.
+    /// Outside of them, it's a normal driving lane.
+    #[serde(default)]
+    pub peak_bus_lanes: Vec<(Direction, Vec<TimeWindow>)>,
+}
+
+/// Restrictions on through-traffic that only apply at some times of day
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ConditionalAccess {
+    pub allow_through_traffic: EnumSet<PathConstraints>,
+    pub windows: Vec<TimeWindow>,
 }
 
 impl AccessRestrictions {
     pub fn new() -> AccessRestrictions {
         AccessRestrictions {
             allow_through_traffic: EnumSet::all(),
+            conditional: Vec::new(),
+            peak_bus_lanes: Vec::new(),
+        }
+    }
+
+    /// Who's allowed through at a certain time? If the time is unknown, conditional restrictions
+    /// are ignored.
+    pub fn allow_through_traffic_at(&self, time: Option<Time>) -> EnumSet<PathConstraints> {
+        if let Some(time) = time {
+            for c in &self.conditional {
+                if c.windows.iter().any(|w| w.contains(time)) {
+                    return c.allow_through_traffic;
+                }
+            }
         }
+        self.allow_through_traffic
+    }
+
+    /// Is through-traffic restricted for anybody, at any time?
+    pub fn restricts_through_traffic(&self) -> bool {
+        self.allow_through_traffic != EnumSet::all() || !self.conditional.is_empty()
+    }
+
+    /// Is there a peak-hour bus lane in this direction, in effect at this time?
+    pub fn peak_bus_lane_at(&self, dir: Direction, time: Time) -> bool {
+        self.peak_bus_lanes
+            .iter()
+            .any(|(d, windows)| *d == dir && windows.iter().any(|w| w.contains(time)))
+    }
+
+    /// Every window when some conditional restriction starts or stops
+    pub fn all_windows(&self) -> impl Iterator<Item = &TimeWindow> {
+        self.conditional
+            .iter()
+            .flat_map(|c| c.windows.iter())
+            .chain(self.peak_bus_lanes.iter().flat_map(|(_, w)| w.iter()))
     }
 }
 
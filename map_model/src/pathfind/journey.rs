@@ -0,0 +1,597 @@
+//! Plan journeys that ride public transit, maybe transferring between routes, and start by
+//! walking, biking, or driving to the first stop. This uses RAPTOR
+//! (<https://www.microsoft.com/en-us/research/publication/round-based-public-transit-routing/>)
+//! over the transit schedule: round k finds the earliest time to reach every stop using k rides.
+//!
+//! Walking and access legs are estimated from straight-line distances, so the results are a
+//! choice of which stops and routes to use, not exact paths.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use geom::{Distance, Duration, Speed, Time};
+
+use crate::{
+    BuildingID, Map, PathConstraints, Position, TransitRoute, TransitRouteID, TransitStopID,
+    MAX_BIKE_SPEED, MAX_WALKING_SPEED,
+};
+
+/// Don't walk further than this between the start or end and a stop
+const MAX_WALK: Distance = Distance::const_meters(1000.0);
+/// Don't walk further than this to transfer between stops
+const MAX_TRANSFER_WALK: Distance = Distance::const_meters(400.0);
+const MAX_BIKE_ACCESS: Distance = Distance::const_meters(5000.0);
+const MAX_DRIVE_ACCESS: Distance = Distance::const_meters(20_000.0);
+/// Roughly 40km/h, an average driving speed through a city
+const DRIVING_SPEED: Speed = Speed::const_meters_per_second(11.0);
+/// How long it takes to find parking and lock up a car
+const PARKING_TIME: Duration = Duration::const_seconds(300.0);
+const BIKE_PARKING_TIME: Duration = Duration::const_seconds(60.0);
+/// Straight-line distances are multiplied by this to guess the distance along streets
+const DETOUR_FACTOR: f64 = 1.3;
+/// When there's no timetable, assume vehicles wait this long at every stop
+const DWELL_TIME: Duration = Duration::const_seconds(30.0);
+/// Only board a vehicle scheduled to leave at least this long after reaching the stop, since the
+/// schedule and walking times are estimates.
+const BOARDING_SLACK: Duration = Duration::const_seconds(60.0);
+/// At most this many rides, so at most one fewer transfers
+const MAX_RIDES: usize = 4;
+
+/// The transit schedule, prepared for planning journeys. This is expensive to build, so create it
+/// once and rebuild it when transit routes are edited.
+#[derive(Clone, Debug)]
+pub struct JourneyPlanner {
+    routes: BTreeMap<TransitRouteID, RouteSchedule>,
+    /// Each route serving a stop, and the stop's index along the route
+    routes_at_stop: BTreeMap<TransitStopID, Vec<(TransitRouteID, usize)>>,
+    /// Other stops within walking distance, and how long the walk takes
+    transfers: BTreeMap<TransitStopID, Vec<(TransitStopID, Duration)>>,
+    /// Where to leave a car or bike to ride from a stop
+    park_near: BTreeMap<(TransitStopID, PathConstraints), BuildingID>,
+}
+
+#[derive(Clone, Debug)]
+struct RouteSchedule {
+    stops: Vec<TransitStopID>,
+    /// Each run of the route, with the time it departs each stop, in order
+    runs: Vec<Vec<Time>>,
+}
+
+/// A plan to ride public transit.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Journey {
+    pub access: JourneyAccess,
+    /// In order. Between rides, walk from where the last ride alighted to where the next boards.
+    pub rides: Vec<TransitRide>,
+    pub departure: Time,
+    /// Estimated from the schedule and straight-line walking
+    pub arrival: Time,
+}
+
+/// How to get to the first stop of a journey
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum JourneyAccess {
+    Walk,
+    /// Drive, park near this building, then walk to the stop
+    ParkAndRide(BuildingID),
+    /// Bike, leave the bike near this building, then walk to the stop
+    BikeAndRide(BuildingID),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TransitRide {
+    pub route: TransitRouteID,
+    pub board: TransitStopID,
+    pub alight: TransitStopID,
+}
+
+/// How a stop was reached in some round
+#[derive(Clone, Copy)]
+struct Label {
+    arrival: Time,
+    how: How,
+}
+
+#[derive(Clone, Copy)]
+enum How {
+    Access(JourneyAccess),
+    Ride {
+        route: TransitRouteID,
+        board: TransitStopID,
+        /// The round where the boarding stop was reached
+        board_round: usize,
+    },
+    /// Walked from another stop reached in the same round
+    Transfer(TransitStopID),
+}
+
+impl JourneyPlanner {
+    pub fn new(map: &Map) -> JourneyPlanner {
+        let mut planner = JourneyPlanner {
+            routes: BTreeMap::new(),
+            routes_at_stop: BTreeMap::new(),
+            transfers: BTreeMap::new(),
+            park_near: BTreeMap::new(),
+        };
+
+        for route in map.all_transit_routes() {
+            let runs = match route_runs(route, map) {
+                Ok(runs) => runs,
+                Err(err) => {
+                    warn!("Can't plan journeys using {}: {}", route.long_name, err);
+                    continue;
+                }
+            };
+            if runs.is_empty() {
+                continue;
+            }
+            for (idx, stop) in route.stops.iter().enumerate() {
+                planner
+                    .routes_at_stop
+                    .entry(*stop)
+                    .or_insert_with(Vec::new)
+                    .push((route.id, idx));
+            }
+            planner.routes.insert(
+                route.id,
+                RouteSchedule {
+                    stops: route.stops.clone(),
+                    runs,
+                },
+            );
+        }
+
+        let served: Vec<TransitStopID> = planner.routes_at_stop.keys().cloned().collect();
+        for stop1 in &served {
+            let pt1 = map.get_ts(*stop1).sidewalk_pos.pt(map);
+            for stop2 in &served {
+                if stop1 == stop2 {
+                    continue;
+                }
+                let dist = pt1.dist_to(map.get_ts(*stop2).sidewalk_pos.pt(map));
+                if dist <= MAX_TRANSFER_WALK {
+                    planner
+                        .transfers
+                        .entry(*stop1)
+                        .or_insert_with(Vec::new)
+                        .push((*stop2, walking_time(dist)));
+                }
+            }
+
+            for constraints in [PathConstraints::Car, PathConstraints::Bike] {
+                if let Some(b) = closest_building(*stop1, constraints, map) {
+                    planner.park_near.insert((*stop1, constraints), b);
+                }
+            }
+        }
+
+        planner
+    }
+
+    /// Plan the fastest journey from one sidewalk position to another, leaving at some time. The
+    /// first stop is reached by walking, or for `Car` and `Bike`, by whichever of walking and the
+    /// vehicle is faster. Returns `None` if no journey is faster than just walking.
+    pub fn plan(
+        &self,
+        map: &Map,
+        start: Position,
+        end: Position,
+        departure: Time,
+        access: PathConstraints,
+    ) -> Option<Journey> {
+        let start_pt = start.pt(map);
+        let end_pt = end.pt(map);
+        let rounds = self.rounds(map, start, departure, access);
+
+        // Walk from the last stop to the end. Prefer fewer rides when the arrival is the same.
+        let mut goal: Option<(Time, usize, TransitStopID)> = None;
+        for (round, labels) in rounds.iter().enumerate().skip(1) {
+            for (stop, label) in labels {
+                let dist = map.get_ts(*stop).sidewalk_pos.pt(map).dist_to(end_pt);
+                if dist > MAX_WALK {
+                    continue;
+                }
+                let arrival = label.arrival + walking_time(dist);
+                if goal.map(|(t, _, _)| arrival < t).unwrap_or(true) {
+                    goal = Some((arrival, round, *stop));
+                }
+            }
+        }
+        let (arrival, round, stop) = goal?;
+        if departure + walking_time(start_pt.dist_to(end_pt)) <= arrival {
+            return None;
+        }
+
+        let (access, rides) = reconstruct(&rounds, round, stop);
+
+        Some(Journey {
+            access,
+            rides,
+            departure,
+            arrival,
+        })
+    }
+
+    /// Walking from a sidewalk position and leaving at some time, when's the earliest each stop can
+    /// be reached by riding transit? Stops that can only be reached by walking aren't included.
+    pub fn earliest_arrivals(
+        &self,
+        map: &Map,
+        start: Position,
+        departure: Time,
+    ) -> BTreeMap<TransitStopID, Time> {
+        let mut arrivals = BTreeMap::new();
+        for labels in self
+            .rounds(map, start, departure, PathConstraints::Pedestrian)
+            .into_iter()
+            .skip(1)
+        {
+            // Later rounds only reach stops earlier
+            for (stop, label) in labels {
+                arrivals.insert(stop, label.arrival);
+            }
+        }
+        arrivals
+    }
+
+    /// The stops reached in each round of RAPTOR. Round k reaches stops using k rides.
+    fn rounds(
+        &self,
+        map: &Map,
+        start: Position,
+        departure: Time,
+        access: PathConstraints,
+    ) -> Vec<BTreeMap<TransitStopID, Label>> {
+        let start_pt = start.pt(map);
+
+        // Round 0 reaches stops without riding anything
+        let mut round0 = BTreeMap::new();
+        for stop in self.routes_at_stop.keys() {
+            let stop_pt = map.get_ts(*stop).sidewalk_pos.pt(map);
+            let mut reach = None;
+            let dist = start_pt.dist_to(stop_pt);
+            if dist <= MAX_WALK {
+                reach = Some((walking_time(dist), JourneyAccess::Walk));
+            }
+            if let Some(b) = self.park_near.get(&(*stop, access)) {
+                let park_pt = map.get_b(*b).sidewalk_pos.pt(map);
+                let dist = start_pt.dist_to(park_pt) * DETOUR_FACTOR;
+                let vehicle = if access == PathConstraints::Car && dist <= MAX_DRIVE_ACCESS {
+                    Some((
+                        dist / DRIVING_SPEED + PARKING_TIME,
+                        JourneyAccess::ParkAndRide(*b),
+                    ))
+                } else if access == PathConstraints::Bike && dist <= MAX_BIKE_ACCESS {
+                    Some((
+                        dist / MAX_BIKE_SPEED + BIKE_PARKING_TIME,
+                        JourneyAccess::BikeAndRide(*b),
+                    ))
+                } else {
+                    None
+                };
+                if let Some((time, how)) = vehicle {
+                    let time = time + walking_time(park_pt.dist_to(stop_pt));
+                    if reach.map(|(walk, _)| time < walk).unwrap_or(true) {
+                        reach = Some((time, how));
+                    }
+                }
+            }
+            if let Some((time, how)) = reach {
+                let arrival = departure + time;
+                round0.insert(
+                    *stop,
+                    Label {
+                        arrival,
+                        how: How::Access(how),
+                    },
+                );
+            }
+        }
+
+        self.raptor(round0)
+    }
+
+    /// Starting from the stops reached without riding anything, find the stops reached in each
+    /// later round.
+    fn raptor(
+        &self,
+        round0: BTreeMap<TransitStopID, Label>,
+    ) -> Vec<BTreeMap<TransitStopID, Label>> {
+        let mut best: BTreeMap<TransitStopID, Time> = round0
+            .iter()
+            .map(|(stop, label)| (*stop, label.arrival))
+            .collect();
+        let mut marked: Vec<TransitStopID> = round0.keys().cloned().collect();
+        let mut rounds = vec![round0];
+        while rounds.len() <= MAX_RIDES && !marked.is_empty() {
+            // Only scan routes from the earliest stop improved last round
+            let mut queue: BTreeMap<TransitRouteID, usize> = BTreeMap::new();
+            for stop in &marked {
+                for (route, idx) in &self.routes_at_stop[stop] {
+                    let first = queue.entry(*route).or_insert(*idx);
+                    *first = (*first).min(*idx);
+                }
+            }
+
+            let mut current: BTreeMap<TransitStopID, Label> = BTreeMap::new();
+            for (route, first_idx) in queue {
+                let schedule = &self.routes[&route];
+                // The run being ridden, and where it was boarded
+                let mut riding: Option<(usize, TransitStopID, usize)> = None;
+                for idx in first_idx..schedule.stops.len() {
+                    let stop = schedule.stops[idx];
+                    if let Some((run, board, board_round)) = riding {
+                        let arrival = schedule.runs[run][idx];
+                        if best.get(&stop).map(|t| arrival < *t).unwrap_or(true) {
+                            best.insert(stop, arrival);
+                            current.insert(
+                                stop,
+                                Label {
+                                    arrival,
+                                    how: How::Ride {
+                                        route,
+                                        board,
+                                        board_round,
+                                    },
+                                },
+                            );
+                        }
+                    }
+
+                    // Can we catch an earlier run here? The runs are in order.
+                    if let Some((round, label)) = latest_label(&rounds, stop) {
+                        let ready = label.arrival + BOARDING_SLACK;
+                        let run = schedule.runs.partition_point(|times| times[idx] < ready);
+                        if run < schedule.runs.len()
+                            && riding.map(|(r, _, _)| run < r).unwrap_or(true)
+                        {
+                            riding = Some((run, stop, round));
+                        }
+                    }
+                }
+            }
+
+            // Walk to nearby stops after getting off
+            let alighted: Vec<(TransitStopID, Time)> = current
+                .iter()
+                .map(|(stop, label)| (*stop, label.arrival))
+                .collect();
+            for (stop, arrival) in alighted {
+                for (other, walk) in self.transfers.get(&stop).into_iter().flatten() {
+                    let arrival = arrival + *walk;
+                    if best.get(other).map(|t| arrival < *t).unwrap_or(true) {
+                        best.insert(*other, arrival);
+                        current.insert(
+                            *other,
+                            Label {
+                                arrival,
+                                how: How::Transfer(stop),
+                            },
+                        );
+                    }
+                }
+            }
+
+            marked = current.keys().cloned().collect();
+            rounds.push(current);
+        }
+        rounds
+    }
+}
+
+/// When each run of a route departs each stop, using the timetable when possible
+fn route_runs(route: &TransitRoute, map: &Map) -> Result<Vec<Vec<Time>>> {
+    let mut estimated_offsets = None;
+    let mut runs = Vec::new();
+    for spawn_time in &route.spawn_times {
+        if let Some(times) = route.scheduled_stop_times(*spawn_time) {
+            if times.len() == route.stops.len() {
+                runs.push(times.clone());
+                continue;
+            }
+        }
+
+        if estimated_offsets.is_none() {
+            let mut offsets = Vec::new();
+            let mut total = Duration::ZERO;
+            // Entry i is the path to stop i
+            for path in route.all_paths(map)?.into_iter().take(route.stops.len()) {
+                total += path.estimate_duration(map, None);
+                offsets.push(total);
+                total += DWELL_TIME;
+            }
+            estimated_offsets = Some(offsets);
+        }
+        runs.push(
+            estimated_offsets
+                .as_ref()
+                .unwrap()
+                .iter()
+                .map(|offset| *spawn_time + *offset)
+                .collect(),
+        );
+    }
+    runs.sort_by_key(|times| times[0]);
+    Ok(runs)
+}
+
+/// The closest building on the same road as a stop that a vehicle can reach
+fn closest_building(
+    stop: TransitStopID,
+    constraints: PathConstraints,
+    map: &Map,
+) -> Option<BuildingID> {
+    let stop_pos = map.get_ts(stop).sidewalk_pos;
+    let stop_pt = stop_pos.pt(map);
+    map.road_to_buildings(stop_pos.lane().road)
+        .iter()
+        .filter(|b| {
+            let bldg = map.get_b(**b);
+            if constraints == PathConstraints::Bike {
+                bldg.biking_connection(map).is_some()
+            } else {
+                bldg.driving_connection(map).is_some()
+            }
+        })
+        .min_by_key(|b| map.get_b(**b).sidewalk_pos.pt(map).dist_to(stop_pt))
+        .cloned()
+}
+
+/// Work backwards from a stop reached in some round to find how it was reached
+fn reconstruct(
+    rounds: &[BTreeMap<TransitStopID, Label>],
+    mut round: usize,
+    mut stop: TransitStopID,
+) -> (JourneyAccess, Vec<TransitRide>) {
+    let mut rides = Vec::new();
+    let access = loop {
+        match rounds[round][&stop].how {
+            How::Access(access) => {
+                break access;
+            }
+            How::Ride {
+                route,
+                board,
+                board_round,
+            } => {
+                rides.push(TransitRide {
+                    route,
+                    board,
+                    alight: stop,
+                });
+                stop = board;
+                round = board_round;
+            }
+            How::Transfer(from) => {
+                stop = from;
+            }
+        }
+    };
+    rides.reverse();
+    (access, rides)
+}
+
+/// The latest round before the current one that reached a stop
+fn latest_label(
+    rounds: &[BTreeMap<TransitStopID, Label>],
+    stop: TransitStopID,
+) -> Option<(usize, &Label)> {
+    rounds
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(round, labels)| labels.get(&stop).map(|label| (round, label)))
+}
+
+fn walking_time(dist: Distance) -> Duration {
+    dist * DETOUR_FACTOR / MAX_WALKING_SPEED
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RoadID;
+
+    fn stop(road: usize) -> TransitStopID {
+        TransitStopID {
+            road: RoadID(road),
+            idx: 0,
+        }
+    }
+
+    fn t(seconds: f64) -> Time {
+        Time::START_OF_DAY + Duration::seconds(seconds)
+    }
+
+    /// Route 0 goes from A to B and route 1 from C to D, with a walk from B to C in between.
+    /// Route 2 goes straight from A to D, but slowly.
+    fn planner(transfer: Duration) -> JourneyPlanner {
+        let (a, b, c, d) = (stop(1), stop(2), stop(3), stop(4));
+        let mut planner = JourneyPlanner {
+            routes: BTreeMap::new(),
+            routes_at_stop: BTreeMap::new(),
+            transfers: BTreeMap::new(),
+            park_near: BTreeMap::new(),
+        };
+        for (id, stops, runs) in [
+            (
+                0,
+                vec![a, b],
+                vec![vec![t(100.0), t(400.0)], vec![t(700.0), t(1000.0)]],
+            ),
+            (
+                1,
+                vec![c, d],
+                vec![vec![t(500.0), t(800.0)], vec![t(900.0), t(1200.0)]],
+            ),
+            (2, vec![a, d], vec![vec![t(150.0), t(2000.0)]]),
+        ] {
+            let route = TransitRouteID(id);
+            for (idx, stop) in stops.iter().enumerate() {
+                planner
+                    .routes_at_stop
+                    .entry(*stop)
+                    .or_insert_with(Vec::new)
+                    .push((route, idx));
+            }
+            planner.routes.insert(route, RouteSchedule { stops, runs });
+        }
+        planner.transfers.insert(b, vec![(c, transfer)]);
+        planner
+    }
+
+    fn start_at_a() -> BTreeMap<TransitStopID, Label> {
+        let mut round0 = BTreeMap::new();
+        round0.insert(
+            stop(1),
+            Label {
+                arrival: t(0.0),
+                how: How::Access(JourneyAccess::Walk),
+            },
+        );
+        round0
+    }
+
+    #[test]
+    fn transfer_beats_direct_route() {
+        let rounds = planner(Duration::seconds(30.0)).raptor(start_at_a());
+
+        // With one ride, only the slow direct route reaches D
+        assert_eq!(rounds[1][&stop(4)].arrival, t(2000.0));
+        // Walking from B reaches C in the same round
+        assert_eq!(rounds[1][&stop(3)].arrival, t(430.0));
+        // Transferring to route 1 gets there earlier
+        assert_eq!(rounds[2][&stop(4)].arrival, t(800.0));
+
+        let (access, rides) = reconstruct(&rounds, 2, stop(4));
+        assert_eq!(access, JourneyAccess::Walk);
+        assert_eq!(
+            rides,
+            vec![
+                TransitRide {
+                    route: TransitRouteID(0),
+                    board: stop(1),
+                    alight: stop(2),
+                },
+                TransitRide {
+                    route: TransitRouteID(1),
+                    board: stop(3),
+                    alight: stop(4),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn boarding_slack_misses_a_run() {
+        // Reaching C at 445s isn't a full minute before the first run leaves at 500s, so take the
+        // next one
+        let rounds = planner(Duration::seconds(45.0)).raptor(start_at_a());
+        assert_eq!(rounds[2][&stop(4)].arrival, t(1200.0));
+
+        // The first run of route 0 still leaves A more than a minute after starting
+        assert_eq!(rounds[1][&stop(2)].arrival, t(400.0));
+    }
+}
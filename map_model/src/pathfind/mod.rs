@@ -1,23 +1,30 @@
 //! Everything related to pathfinding through a map for different types of agents.
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
 use enumset::EnumSetType;
 use serde::{Deserialize, Serialize};
 
-use geom::Duration;
+use abstutil::{deserialize_btreemap, serialize_btreemap};
+use geom::{Duration, Time};
 
 pub use self::engine::CreateEngine;
+pub use self::journey::{Journey, JourneyAccess, JourneyPlanner, TransitRide};
 pub use self::pathfinder::{Pathfinder, PathfinderCache, PathfinderCaching};
+pub use self::travel_times::RoadTravelTimes;
 pub use self::v1::{Path, PathRequest, PathStep};
 pub use self::v2::{PathStepV2, PathV2};
 pub use self::vehicles::vehicle_cost;
 pub use self::walking::WalkingNode;
-use crate::{osm, Lane, LaneID, LaneType, Map, MovementID, Road, RoadID, TurnType};
+use crate::{
+    osm, IntersectionID, Lane, LaneID, LaneType, Map, MovementID, Road, RoadID, TurnID, TurnType,
+};
 
 mod engine;
+mod journey;
 mod node_map;
 mod pathfinder;
+mod travel_times;
 // TODO tmp
 pub mod uber_turns;
 mod v1;
@@ -34,6 +41,8 @@ pub enum PathConstraints {
     Bike,
     Bus,
     Train,
+    /// Heavy goods vehicles, which can't use some roads or make tight turns
+    Truck,
 }
 
 impl PathConstraints {
@@ -44,6 +53,7 @@ impl PathConstraints {
             PathConstraints::Bike,
             PathConstraints::Bus,
             PathConstraints::Train,
+            PathConstraints::Truck,
         ]
     }
 
@@ -67,6 +77,7 @@ impl PathConstraints {
                 return lane.is_walkable();
             }
             PathConstraints::Car => lane.is_driving(),
+            PathConstraints::Truck => lane.is_driving() && map.get_r(lane.id.road).allows_trucks(),
             PathConstraints::Bike => {
                 if lane.is_biking() {
                     true
@@ -121,6 +132,36 @@ impl PathConstraints {
         road.lanes.iter().any(|lane| self.can_use(lane, map))
     }
 
+    /// Can an agent pass the modal filter somewhere along this road, if there is one?
+    pub fn can_cross_road_filter(self, road: &Road) -> bool {
+        road.modal_filter
+            .as_ref()
+            .map(|filter| filter.filter_type.allows(self))
+            .unwrap_or(true)
+    }
+
+    /// Can an agent turn between two roads, or does a diagonal filter in the intersection block
+    /// them?
+    pub fn allowed_by_diagonal_filter(
+        self,
+        i: IntersectionID,
+        from: RoadID,
+        to: RoadID,
+        map: &Map,
+    ) -> bool {
+        match map.get_i(i).modal_filter {
+            Some(ref filter) => filter.filter_type.allows(self) || filter.allows_turn(from, to),
+            None => true,
+        }
+    }
+
+    /// Can an agent make this turn without passing through any modal filter? Reaching the turn
+    /// means driving along its entire source road, so a filter anywhere on that road counts.
+    pub fn can_cross_filters(self, turn: TurnID, map: &Map) -> bool {
+        self.can_cross_road_filter(map.get_r(turn.src.road))
+            && self.allowed_by_diagonal_filter(turn.parent, turn.src.road, turn.dst.road, map)
+    }
+
     /// Strict for bikes. If there are bike lanes, not allowed to use other lanes.
     pub(crate) fn filter_lanes(self, mut choices: Vec<LaneID>, map: &Map) -> Vec<LaneID> {
         choices.retain(|l| self.can_use(map.get_l(*l), map));
@@ -138,18 +179,24 @@ impl PathConstraints {
     }
 }
 
-/// Heavily penalize crossing into an access-restricted zone that doesn't allow this mode.
-pub(crate) fn zone_cost(mvmnt: MovementID, constraints: PathConstraints, map: &Map) -> Duration {
+/// Heavily penalize crossing into an access-restricted zone that doesn't allow this mode. If the
+/// time is known, conditional restrictions in effect then also count.
+pub(crate) fn zone_cost(
+    mvmnt: MovementID,
+    constraints: PathConstraints,
+    time: Option<Time>,
+    map: &Map,
+) -> Duration {
     // Detect when we cross into a new zone that doesn't allow constraints.
     if map
         .get_r(mvmnt.from.road)
         .access_restrictions
-        .allow_through_traffic
+        .allow_through_traffic_at(time)
         .contains(constraints)
         && !map
             .get_r(mvmnt.to.road)
             .access_restrictions
-            .allow_through_traffic
+            .allow_through_traffic_at(time)
             .contains(constraints)
     {
         // This should be high enough to achieve the desired effect of somebody not entering
@@ -196,6 +243,26 @@ pub struct RoutingParams {
     /// Don't allow movements between these roads at all. Only affects vehicle routing, not
     /// pedestrian.
     pub avoid_movements_between: BTreeSet<(RoadID, RoadID)>,
+
+    /// Evaluate conditional access restrictions, peak-hour bus lanes, and turn bans at this time
+    /// of day. If this is `None`, only the restrictions that always apply matter. Callers should
+    /// use `Map::pathfind_at`, which rounds the time down to when the restrictions last changed,
+    /// so that pathfinders can be cached.
+    pub time: Option<Time>,
+
+    /// Extra delay for cars making a movement, on top of the usual cost of the road leading up to
+    /// it and the movement itself. Traffic assignment fills this out from travel times observed in
+    /// a previous simulation, so that drivers avoid congestion.
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub movement_delays: BTreeMap<MovementID, Duration>,
+
+    /// Instead of assuming cars and buses cross roads at the speed limit, use these travel times,
+    /// observed in a previous simulation or imported from real data. They vary by time of day, so
+    /// `time` should usually be set too.
+    pub road_travel_times: Option<RoadTravelTimes>,
 }
 
 impl Default for RoutingParams {
@@ -216,6 +283,12 @@ impl Default for RoutingParams {
 
             avoid_roads: BTreeSet::new(),
             avoid_movements_between: BTreeSet::new(),
+
+            time: None,
+
+            movement_delays: BTreeMap::new(),
+
+            road_travel_times: None,
         }
     }
 }
@@ -1,5 +1,6 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::sync::OnceLock;
 
 use serde::{Deserialize, Serialize};
 use thread_local::ThreadLocal;
@@ -7,7 +8,7 @@ use thread_local::ThreadLocal;
 use abstutil::{Timer, VecMap};
 use geom::Duration;
 
-use crate::pathfind::engine::CreateEngine;
+use crate::pathfind::engine::{CreateEngine, PathfindEngine};
 use crate::pathfind::vehicles::VehiclePathfinder;
 use crate::pathfind::walking::SidewalkPathfinder;
 use crate::{
@@ -15,9 +16,16 @@ use crate::{
     TransitRouteID, TransitStopID,
 };
 
+/// Each cached alternative holds an entire graph, so only keep a limited number around.
+const MAX_CACHED_ALTERNATIVES: usize = 16;
+
 #[derive(Serialize, Deserialize)]
 pub struct Pathfinder {
     car_graph: VehiclePathfinder,
+    // Most maps never see a truck, so don't build or save this until the first truck needs a
+    // path. The node ordering from car_graph makes this fairly quick.
+    #[serde(skip_serializing, skip_deserializing)]
+    truck_graph: OnceLock<VehiclePathfinder>,
     bike_graph: VehiclePathfinder,
     bus_graph: VehiclePathfinder,
     train_graph: VehiclePathfinder,
@@ -51,6 +59,7 @@ impl Clone for Pathfinder {
     fn clone(&self) -> Self {
         Self {
             car_graph: self.car_graph.clone(),
+            truck_graph: self.truck_graph.clone(),
             bike_graph: self.bike_graph.clone(),
             bus_graph: self.bus_graph.clone(),
             train_graph: self.train_graph.clone(),
@@ -68,6 +77,7 @@ impl Pathfinder {
     pub(crate) fn empty() -> Pathfinder {
         Pathfinder {
             car_graph: VehiclePathfinder::empty(),
+            truck_graph: OnceLock::new(),
             bike_graph: VehiclePathfinder::empty(),
             bus_graph: VehiclePathfinder::empty(),
             train_graph: VehiclePathfinder::empty(),
@@ -123,6 +133,7 @@ impl Pathfinder {
 
         Pathfinder {
             car_graph,
+            truck_graph: OnceLock::new(),
             bike_graph,
             bus_graph,
             train_graph,
@@ -156,6 +167,54 @@ impl Pathfinder {
         Self::new_limited(map, params, CreateEngine::CH, modes, timer)
     }
 
+    /// Create a new Pathfinder with custom routing params that can only serve some modes, such as
+    /// congested travel times. Contraction hierarchies reuse the node ordering from this
+    /// pathfinder, so this is much faster than `new_ch`. The ordering only affects speed, not
+    /// correctness, so this works best when the new costs aren't wildly different.
+    pub fn reweight(
+        &self,
+        map: &Map,
+        params: RoutingParams,
+        modes: Vec<PathConstraints>,
+        timer: &mut Timer,
+    ) -> Self {
+        let mut p = Pathfinder::empty();
+        for constraints in modes {
+            timer.start(format!("reweight pathfinding for just {:?}", constraints));
+            match constraints {
+                // Walking doesn't depend on the params
+                PathConstraints::Pedestrian => {
+                    p.walking_graph = self.walking_graph.clone();
+                }
+                PathConstraints::Car => {
+                    p.car_graph = reweight_graph(&self.car_graph, constraints, &params, map);
+                }
+                PathConstraints::Truck => {
+                    // Don't build the truck graph with the old params just to reweight it
+                    let graph = match self.truck_graph.get() {
+                        Some(graph) => reweight_graph(graph, constraints, &params, map),
+                        None => {
+                            VehiclePathfinder::new(map, constraints, &params, &self.truck_engine())
+                        }
+                    };
+                    p.truck_graph = OnceLock::from(graph);
+                }
+                PathConstraints::Bike => {
+                    p.bike_graph = reweight_graph(&self.bike_graph, constraints, &params, map);
+                }
+                PathConstraints::Bus => {
+                    p.bus_graph = reweight_graph(&self.bus_graph, constraints, &params, map);
+                }
+                PathConstraints::Train => {
+                    p.train_graph = reweight_graph(&self.train_graph, constraints, &params, map);
+                }
+            }
+            timer.stop(format!("reweight pathfinding for just {:?}", constraints));
+        }
+        p.params = params;
+        p
+    }
+
     /// Create a new Pathfinder with custom routing params that can only serve some modes.
     pub(crate) fn new_limited(
         map: &Map,
@@ -174,6 +233,10 @@ impl Pathfinder {
                 PathConstraints::Car => {
                     p.car_graph = VehiclePathfinder::new(map, constraints, &params, &engine);
                 }
+                PathConstraints::Truck => {
+                    let graph = VehiclePathfinder::new(map, constraints, &params, &engine);
+                    p.truck_graph = OnceLock::from(graph);
+                }
                 PathConstraints::Bike => {
                     p.bike_graph = VehiclePathfinder::new(map, constraints, &params, &engine);
                 }
@@ -206,6 +269,7 @@ impl Pathfinder {
         match req.constraints {
             PathConstraints::Pedestrian => self.walking_graph.pathfind(req, map),
             PathConstraints::Car => self.car_graph.pathfind(req, map),
+            PathConstraints::Truck => self.get_truck_graph(map).pathfind(req, map),
             PathConstraints::Bike => self.bike_graph.pathfind(req, map),
             PathConstraints::Bus => self.bus_graph.pathfind(req, map),
             PathConstraints::Train => self.train_graph.pathfind(req, map),
@@ -228,6 +292,7 @@ impl Pathfinder {
             return match constraints {
                 PathConstraints::Pedestrian => self.walking_graph.pathfind(req, map),
                 PathConstraints::Car => self.car_graph.pathfind(req, map),
+                PathConstraints::Truck => self.get_truck_graph(map).pathfind(req, map),
                 PathConstraints::Bike => self.bike_graph.pathfind(req, map),
                 PathConstraints::Bus => self.bus_graph.pathfind(req, map),
                 PathConstraints::Train => self.train_graph.pathfind(req, map),
@@ -247,26 +312,33 @@ impl Pathfinder {
 
         // If somebody's repeatedly calling this without caching, log very obnoxiously.
         let mut timer = Timer::new(format!("Pathfinding slowly for {} with custom params", req));
-        let tmp_pathfinder = Pathfinder::new_limited(
-            map,
-            params.clone(),
-            match cache_custom {
-                PathfinderCaching::NoCache | PathfinderCaching::CacheDijkstra => {
-                    CreateEngine::Dijkstra
-                }
-                // TODO Can we pick the right seed?
-                PathfinderCaching::CacheCH => CreateEngine::CH,
-            },
-            vec![constraints],
-            &mut timer,
-        );
+        let tmp_pathfinder = match cache_custom {
+            PathfinderCaching::NoCache | PathfinderCaching::CacheDijkstra => {
+                Pathfinder::new_limited(
+                    map,
+                    params.clone(),
+                    CreateEngine::Dijkstra,
+                    vec![constraints],
+                    &mut timer,
+                )
+            }
+            PathfinderCaching::CacheCH => {
+                self.reweight(map, params.clone(), vec![constraints], &mut timer)
+            }
+        };
         let result =
             tmp_pathfinder.pathfind_with_params(req, params, PathfinderCaching::NoCache, map);
         if cache_custom != PathfinderCaching::NoCache {
-            self.cached_alternatives
+            let mut cache = self
+                .cached_alternatives
                 .get_or(|| RefCell::new(VecMap::new()))
-                .borrow_mut()
-                .push((constraints, params.clone()), tmp_pathfinder);
+                .borrow_mut();
+            // Callers like traffic assignment keep producing new params, and the old ones are
+            // rarely used again
+            if cache.len() == MAX_CACHED_ALTERNATIVES {
+                cache.pop_oldest();
+            }
+            cache.push((constraints, params.clone()), tmp_pathfinder);
         }
         result
     }
@@ -280,12 +352,38 @@ impl Pathfinder {
         let all_costs = match req.constraints {
             PathConstraints::Pedestrian => self.walking_graph.all_costs_from(req.start, map),
             PathConstraints::Car => self.car_graph.all_costs_from(req.start, map),
+            PathConstraints::Truck => self.get_truck_graph(map).all_costs_from(req.start, map),
             PathConstraints::Bike => self.bike_graph.all_costs_from(req.start, map),
             PathConstraints::Bus | PathConstraints::Train => unreachable!(),
         };
         Some((req_cost, all_costs))
     }
 
+    fn get_truck_graph(&self, map: &Map) -> &VehiclePathfinder {
+        self.truck_graph.get_or_init(|| {
+            let mut timer = Timer::new("prepare pathfinding for trucks");
+            timer.start("prepare pathfinding for trucks");
+            let graph = VehiclePathfinder::new(
+                map,
+                PathConstraints::Truck,
+                &self.params,
+                &self.truck_engine(),
+            );
+            timer.stop("prepare pathfinding for trucks");
+            graph
+        })
+    }
+
+    // Trucks use the same roads as cars with a few exceptions, so the car graph's node ordering
+    // works well
+    fn truck_engine(&self) -> CreateEngine {
+        match self.car_graph.engine {
+            // A pathfinder from new_limited might not have a car graph
+            PathfindEngine::Empty => CreateEngine::CH,
+            ref engine => engine.reuse_ordering(),
+        }
+    }
+
     // TODO Consider returning the walking-only path in the failure case, to avoid wasting work
     pub fn should_use_transit(
         &self,
@@ -302,6 +400,13 @@ impl Pathfinder {
         self.car_graph.apply_edits(map);
         timer.stop("apply edits to car pathfinding");
 
+        // If the truck graph hasn't been built yet, it'll be built from the edited map later
+        if let Some(truck_graph) = self.truck_graph.get_mut() {
+            timer.start("apply edits to truck pathfinding");
+            truck_graph.apply_edits(map);
+            timer.stop("apply edits to truck pathfinding");
+        }
+
         timer.start("apply edits to bike pathfinding");
         self.bike_graph.apply_edits(map);
         timer.stop("apply edits to bike pathfinding");
@@ -325,6 +430,20 @@ impl Pathfinder {
     }
 }
 
+// If this pathfinder was never built for these constraints, there's no node ordering to reuse.
+fn reweight_graph(
+    graph: &VehiclePathfinder,
+    constraints: PathConstraints,
+    params: &RoutingParams,
+    map: &Map,
+) -> VehiclePathfinder {
+    if matches!(graph.engine, PathfindEngine::Empty) {
+        VehiclePathfinder::new(map, constraints, params, &CreateEngine::CH)
+    } else {
+        graph.reweight(params, map)
+    }
+}
+
 /// For callers needing to request paths with a variety of RoutingParams. The caller is in charge
 /// of the lifetime, so they can clear it out when appropriate.
 pub struct PathfinderCache {
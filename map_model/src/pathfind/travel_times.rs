@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use abstutil::{deserialize_btreemap, serialize_btreemap};
+use geom::{Duration, Time};
+
+use crate::DirectedRoadID;
+
+/// How long vehicles actually take to cross each road, varying over the day. This can come from a
+/// previous simulation or from real-world data, and lets routing reflect congestion instead of
+/// just speed limits.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RoadTravelTimes {
+    /// The day is split into windows of this length, starting at midnight.
+    window: Duration,
+    /// Keyed by road and window index. The time includes waiting to leave the road, but not the
+    /// movement through the next intersection.
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    times: BTreeMap<(DirectedRoadID, usize), Duration>,
+}
+
+impl RoadTravelTimes {
+    pub fn new(window: Duration) -> RoadTravelTimes {
+        assert!(window > Duration::ZERO);
+        RoadTravelTimes {
+            window,
+            times: BTreeMap::new(),
+        }
+    }
+
+    /// Record the travel time of a road during the window containing some time.
+    pub fn set(&mut self, dr: DirectedRoadID, time: Time, travel_time: Duration) {
+        let idx = self.window_idx(time);
+        self.times.insert((dr, idx), travel_time);
+    }
+
+    /// How long does it take to cross a road at some time? If the time isn't known, assume the
+    /// slowest time of day. Returns `None` for roads and windows without data.
+    pub fn get(&self, dr: DirectedRoadID, time: Option<Time>) -> Option<Duration> {
+        match time {
+            Some(time) => self.times.get(&(dr, self.window_idx(time))).cloned(),
+            None => self
+                .times
+                .range((dr, 0)..=(dr, usize::MAX))
+                .map(|(_, t)| *t)
+                .max(),
+        }
+    }
+
+    /// Travel times don't change within a window. Returns when the window containing some time
+    /// started.
+    pub fn window_start(&self, time: Time) -> Time {
+        time - self.time_of_day(time) % self.window
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.times.is_empty()
+    }
+
+    fn window_idx(&self, time: Time) -> usize {
+        (self.time_of_day(time) / self.window).floor() as usize
+    }
+
+    // Multi-day simulations reuse the same times every day
+    fn time_of_day(&self, time: Time) -> Duration {
+        Duration::seconds(time.inner_seconds() % (24.0 * 3600.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Direction, RoadID};
+
+    #[test]
+    fn windows() {
+        let dr = DirectedRoadID {
+            road: RoadID(0),
+            dir: Direction::Fwd,
+        };
+        let mut times = RoadTravelTimes::new(Duration::hours(1));
+        let morning = Time::START_OF_DAY + Duration::hours(8) + Duration::minutes(15);
+        let evening = Time::START_OF_DAY + Duration::hours(17) + Duration::minutes(30);
+        times.set(dr, morning, Duration::seconds(30.0));
+        times.set(dr, evening, Duration::seconds(45.0));
+
+        assert_eq!(
+            times.window_start(morning),
+            Time::START_OF_DAY + Duration::hours(8)
+        );
+        assert_eq!(
+            times.get(dr, Some(Time::START_OF_DAY + Duration::hours(8))),
+            Some(Duration::seconds(30.0))
+        );
+        assert_eq!(
+            times.get(dr, Some(evening + Duration::hours(24))),
+            Some(Duration::seconds(45.0))
+        );
+        assert_eq!(times.get(dr, Some(Time::START_OF_DAY)), None);
+        assert_eq!(times.get(dr, None), Some(Duration::seconds(45.0)));
+    }
+}
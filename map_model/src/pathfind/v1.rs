@@ -312,6 +312,36 @@ impl Path {
         // TODO Maybe need to amend uber_turns?
     }
 
+    /// Replace the rest of this path with a different route. The new path must start from the
+    /// current step and end at the same place.
+    pub fn reroute(&mut self, new_path: Path, map: &Map) -> Result<()> {
+        if self.currently_inside_ut.is_some() {
+            bail!("can't reroute in the middle of an uber-turn");
+        }
+        if new_path.steps[0] != self.steps[0] {
+            bail!(
+                "new path starts at {:?}, but the current step is {:?}",
+                new_path.steps[0],
+                self.steps[0]
+            );
+        }
+        if new_path.orig_req.end != self.orig_req.end {
+            bail!(
+                "new path ends at {}, not {}",
+                new_path.orig_req.end,
+                self.orig_req.end
+            );
+        }
+
+        self.total_length = self.crossed_so_far;
+        for step in &new_path.steps {
+            self.total_length += self.dist_crossed_from_step(map, step);
+        }
+        self.steps = new_path.steps;
+        self.uber_turns = new_path.uber_turns;
+        Ok(())
+    }
+
     pub fn is_upcoming_uber_turn_component(&self, t: TurnID) -> bool {
         self.uber_turns
             .front()
@@ -601,7 +631,7 @@ impl PathRequest {
         let (start, end) = match constraints {
             PathConstraints::Pedestrian => (from.sidewalk_pos, to.sidewalk_pos),
             PathConstraints::Bike => (from.biking_connection(map)?.0, to.biking_connection(map)?.0),
-            PathConstraints::Car => (
+            PathConstraints::Car | PathConstraints::Truck => (
                 from.driving_connection(map)?.0,
                 to.driving_connection(map)?.0,
             ),
@@ -610,7 +640,7 @@ impl PathRequest {
             // train to travel between buildings.
             PathConstraints::Bus | PathConstraints::Train => unimplemented!(),
         };
-        if constraints == PathConstraints::Car {
+        if constraints == PathConstraints::Car || constraints == PathConstraints::Truck {
             Some(PathRequest::leave_from_driveway(
                 start,
                 end,
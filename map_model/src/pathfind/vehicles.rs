@@ -1,12 +1,13 @@
-//! Pathfinding for cars, bikes, buses, and trains using contraction hierarchies
+//! Pathfinding for cars, trucks, bikes, buses, and trains using contraction hierarchies
 
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 
 use fast_paths::InputGraph;
 use serde::{Deserialize, Serialize};
 
 use abstutil::MultiMap;
-use geom::Duration;
+use geom::{Distance, Duration, PolyLine};
 
 use crate::pathfind::engine::{CreateEngine, PathfindEngine};
 use crate::pathfind::node_map::{deserialize_nodemap, NodeMap};
@@ -15,9 +16,12 @@ use crate::pathfind::zone_cost;
 use crate::pathfind::{round, unround};
 use crate::{
     osm, DirectedRoadID, Direction, LaneType, Map, MovementID, PathConstraints, PathRequest,
-    PathV2, Position, RoutingParams, Traversable,
+    PathV2, Position, RoutingParams, Traversable, TurnType,
 };
 
+/// Heavy goods vehicles can't make movements curving more tightly than this.
+const MIN_TRUCK_TURN_RADIUS: Distance = Distance::const_meters(5.0);
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct VehiclePathfinder {
     #[serde(deserialize_with = "deserialize_nodemap")]
@@ -93,29 +97,70 @@ impl VehiclePathfinder {
         }
 
         assert!(!map.get_l(req.start.lane()).is_walkable());
-        let mut starts = vec![(
-            self.nodes.get(Node::Road(
-                map.get_l(req.start.lane()).get_directed_parent(),
-            )),
-            0,
-        )];
-        if let Some((pos, cost)) = req.alt_start {
-            starts.push((
-                self.nodes
-                    .get(Node::Road(map.get_l(pos.lane()).get_directed_parent())),
-                round(cost),
-            ));
+        let end_road = map.get_l(req.end.lane()).get_directed_parent();
+
+        // Every edge in the graph assumes a vehicle enters a road at its beginning, but the first
+        // and last road in a path might only be partly used, so modal filters there need special
+        // care.
+        // TODO The vehicle could approach from the other side of the road instead.
+        let end_past_filter = filter_ahead(req.end, self.constraints, map) == Some(false);
+
+        let mut starts = Vec::new();
+        // When a vehicle starts past a filter, it can leave its road, even though the graph
+        // doesn't allow that. Start from the next roads instead, remembering the skipped one.
+        let mut skipped_start_road: HashMap<usize, DirectedRoadID> = HashMap::new();
+        for (pos, cost) in std::iter::once((req.start, Duration::ZERO)).chain(req.alt_start) {
+            let dr = map.get_l(pos.lane()).get_directed_parent();
+            let start_filter = filter_ahead(pos, self.constraints, map);
+            if end_past_filter {
+                // Reaching the end from the beginning of its road means crossing the filter, so
+                // only a vehicle already past the filter on the same road can get there.
+                if dr == end_road && start_filter == Some(false) {
+                    starts.push((self.nodes.get(Node::Road(dr)), round(cost)));
+                }
+                continue;
+            }
+            match start_filter {
+                // The vehicle can't leave its road, but the end might be before the filter too
+                Some(true) => {
+                    if dr == end_road {
+                        starts.push((self.nodes.get(Node::Road(dr)), round(cost)));
+                    }
+                }
+                Some(false) => {
+                    for mvmnt in map.get_movements_for(dr, self.constraints) {
+                        if let Some(mvmnt_cost) =
+                            vehicle_cost_past_filter(dr, mvmnt, self.constraints, &self.params, map)
+                        {
+                            let node = self.nodes.get(Node::Road(mvmnt.to));
+                            if let Entry::Vacant(entry) = skipped_start_road.entry(node) {
+                                entry.insert(dr);
+                                starts.push((node, round(cost + mvmnt_cost)));
+                            }
+                        }
+                    }
+                    starts.push((self.nodes.get(Node::Road(dr)), round(cost)));
+                }
+                None => {
+                    starts.push((self.nodes.get(Node::Road(dr)), round(cost)));
+                }
+            }
+        }
+        if starts.is_empty() {
+            return None;
         }
         let (raw_weight, raw_nodes) = self.engine.calculate_path_multiple_sources_and_targets(
             starts,
-            vec![(
-                self.nodes
-                    .get(Node::Road(map.get_l(req.end.lane()).get_directed_parent())),
-                0,
-            )],
+            vec![(self.nodes.get(Node::Road(end_road)), 0)],
         )?;
 
         let mut road_steps = Vec::new();
+        if let Some(dr) = raw_nodes
+            .first()
+            .and_then(|node| skipped_start_road.get(node))
+        {
+            road_steps.push(*dr);
+        }
         let mut uber_turns = Vec::new();
         for node in raw_nodes.into_iter().map(|id| self.nodes.translate_id(id)) {
             match node {
@@ -137,6 +182,21 @@ impl VehiclePathfinder {
         Some(PathV2::from_roads(road_steps, req, cost, uber_turns, map))
     }
 
+    /// Build a pathfinder for the same vehicles using different routing params. The graph's nodes
+    /// and uber-turns don't depend on the params, and a contraction hierarchy reuses this one's
+    /// node ordering, so this is much faster than starting from scratch.
+    pub fn reweight(&self, params: &RoutingParams, map: &Map) -> VehiclePathfinder {
+        let input_graph =
+            make_input_graph(self.constraints, &self.nodes, &self.uber_turns, params, map);
+        VehiclePathfinder {
+            nodes: self.nodes.clone(),
+            uber_turns: self.uber_turns.clone(),
+            constraints: self.constraints,
+            params: params.clone(),
+            engine: self.engine.reuse_ordering().create(input_graph),
+        }
+    }
+
     pub fn apply_edits(&mut self, map: &Map) {
         if matches!(self.engine, PathfindEngine::Empty) {
             return;
@@ -213,11 +273,19 @@ fn make_input_graph(
         // vehicle.
         // TODO Need to test editing lanes inside an IntersectionCluster very carefully. See Mercer
         // and Dexter.
-        if ut
-            .path
-            .iter()
-            .all(|mvmnt| !mvmnt.to.lanes(constraints, map).is_empty())
-        {
+        if ut.path.iter().all(|mvmnt| {
+            !mvmnt.to.lanes(constraints, map).is_empty()
+                && constraints.allowed_by_diagonal_filter(
+                    mvmnt.parent,
+                    mvmnt.from.road,
+                    mvmnt.to.road,
+                    map,
+                )
+                && !params
+                    .time
+                    .map(|t| map.get_r(mvmnt.from.road).turn_banned_at(mvmnt.to.road, t))
+                    .unwrap_or(false)
+        }) {
             uber_turn_entrances.insert(ut.entry(), idx);
         }
     }
@@ -276,30 +344,59 @@ fn make_input_graph(
 }
 
 /// This returns the pathfinding cost of crossing one road and turn, in units of time. It factors
-/// in the ideal time to cross the space and penalties for entering an access-restricted zone,
-/// taking an unprotected turn, or going up a steep hill for some vehicle types. If this returns
-/// `None`, then the movement isn't actually allowed.
+/// in the ideal time to cross the space (or the observed time, if the params have road travel
+/// times) and penalties for entering an access-restricted zone, taking an unprotected turn, or
+/// going up a steep hill for some vehicle types. If this returns `None`, then the movement isn't
+/// actually allowed.
 pub fn vehicle_cost(
     dr: DirectedRoadID,
     mvmnt: MovementID,
     constraints: PathConstraints,
     params: &RoutingParams,
     map: &Map,
+) -> Option<Duration> {
+    if !constraints.can_cross_road_filter(map.get_r(dr.road)) {
+        return None;
+    }
+    vehicle_cost_past_filter(dr, mvmnt, constraints, params, map)
+}
+
+/// Like `vehicle_cost`, but ignores any modal filter along `dr`, for vehicles starting past it.
+fn vehicle_cost_past_filter(
+    dr: DirectedRoadID,
+    mvmnt: MovementID,
+    constraints: PathConstraints,
+    params: &RoutingParams,
+    map: &Map,
 ) -> Option<Duration> {
     let road = map.get_r(dr.road);
     let movement = &map.get_i(mvmnt.parent).movements[&mvmnt];
     let max_speed = match constraints {
         PathConstraints::Car | PathConstraints::Bus | PathConstraints::Train => None,
+        PathConstraints::Truck => Some(crate::MAX_TRUCK_SPEED),
         PathConstraints::Bike => Some(crate::MAX_BIKE_SPEED),
         PathConstraints::Pedestrian => unreachable!(),
     };
-    let t1 = road.length() / Traversable::max_speed_along_road(dr, max_speed, constraints, map).0;
+    let mut t1 =
+        road.length() / Traversable::max_speed_along_road(dr, max_speed, constraints, map).0;
+    if matches!(
+        constraints,
+        PathConstraints::Car | PathConstraints::Truck | PathConstraints::Bus
+    ) {
+        if let Some(observed) = params
+            .road_travel_times
+            .as_ref()
+            .and_then(|times| times.get(dr, params.time))
+        {
+            t1 = observed;
+        }
+    }
 
     let t2 = movement.geom.length()
         / Traversable::max_speed_along_movement(mvmnt, max_speed, constraints, map);
 
     let base = match constraints {
-        PathConstraints::Car | PathConstraints::Train => t1 + t2,
+        PathConstraints::Car | PathConstraints::Truck | PathConstraints::Train => t1 + t2,
         PathConstraints::Bike => {
             // TODO If we're on a driving lane, higher speed limit is worse.
             // TODO Bike lanes next to parking is dangerous.
@@ -348,6 +445,12 @@ pub fn vehicle_cost(
         multiplier *= params.avoid_high_stress;
     }
 
+    if constraints == PathConstraints::Truck
+        && too_tight_for_trucks(movement.turn_type, &movement.geom)
+    {
+        return None;
+    }
+
     if params.avoid_roads.contains(&dr.road)
         || params
             .avoid_movements_between
@@ -356,11 +459,31 @@ pub fn vehicle_cost(
         return None;
     }
 
-    let mut extra = zone_cost(mvmnt, constraints, map);
+    if let Some(time) = params.time {
+        if road.turn_banned_at(mvmnt.to.road, time) {
+            return None;
+        }
+        // If every lane is a peak-hour bus lane right now, cars can't use the road at all
+        if matches!(constraints, PathConstraints::Car | PathConstraints::Truck)
+            && dr
+                .lanes(constraints, map)
+                .into_iter()
+                .all(|l| map.get_l(l).is_bus_only_at(time, map))
+        {
+            return None;
+        }
+    }
+
+    let mut extra = zone_cost(mvmnt, constraints, params.time, map);
     // Penalize unprotected turns at a stop sign from smaller to larger roads.
     if map.is_unprotected_turn(dr.road, mvmnt.to.road, movement.turn_type) {
         extra += params.unprotected_turn_penalty
     }
+    if matches!(constraints, PathConstraints::Car | PathConstraints::Truck) {
+        if let Some(delay) = params.movement_delays.get(&mvmnt) {
+            extra += *delay;
+        }
+    }
 
     if (params.main_road_penalty - 1.0).abs() > f64::EPSILON
         && road.get_rank() != osm::RoadRank::Local
@@ -370,3 +493,72 @@ pub fn vehicle_cost(
 
     Some(multiplier * base + extra)
 }
+
+/// Trucks can't U-turn, and can't make turns whose curve is sharper than their turning circle.
+fn too_tight_for_trucks(turn_type: TurnType, geom: &PolyLine) -> bool {
+    if turn_type == TurnType::UTurn {
+        return true;
+    }
+    if !matches!(turn_type, TurnType::Left | TurnType::Right) {
+        return false;
+    }
+    let angle = geom
+        .first_line()
+        .angle()
+        .simple_shortest_rotation_towards(geom.last_line().angle())
+        .abs()
+        .to_radians();
+    if angle < f64::EPSILON {
+        return false;
+    }
+    geom.length() / angle < MIN_TRUCK_TURN_RADIUS
+}
+
+/// If a modal filter on this position's road blocks these constraints, is the filter still ahead
+/// of the position, in the lane's direction of travel?
+fn filter_ahead(pos: Position, constraints: PathConstraints, map: &Map) -> Option<bool> {
+    let lane = map.get_l(pos.lane());
+    let road = map.get_r(lane.id.road);
+    let filter = road.modal_filter.as_ref()?;
+    if filter.filter_type.allows(constraints) {
+        return None;
+    }
+    let mut pct = filter.dist / road.length();
+    if lane.dir == Direction::Back {
+        pct = 1.0 - pct;
+    }
+    Some(pos.dist_along() < pct * lane.length())
+}
+
+#[cfg(test)]
+mod tests {
+    use geom::Pt2D;
+
+    use super::*;
+
+    fn corner(size: f64) -> PolyLine {
+        PolyLine::must_new(vec![
+            Pt2D::new(0.0, 0.0),
+            Pt2D::new(size, 0.0),
+            Pt2D::new(size, size),
+        ])
+    }
+
+    #[test]
+    fn test_too_tight_for_trucks() {
+        let straight = PolyLine::must_new(vec![Pt2D::new(0.0, 0.0), Pt2D::new(30.0, 0.0)]);
+
+        // A right angle over 4m is a radius of about 2.5m
+        assert!(too_tight_for_trucks(TurnType::Right, &corner(2.0)));
+        assert!(too_tight_for_trucks(TurnType::Left, &corner(2.0)));
+        // And over 40m, a radius of about 25m
+        assert!(!too_tight_for_trucks(TurnType::Right, &corner(20.0)));
+        assert!(!too_tight_for_trucks(TurnType::Left, &corner(20.0)));
+
+        // Geometry doesn't matter for U-turns and anything that isn't a left or right
+        assert!(too_tight_for_trucks(TurnType::UTurn, &corner(20.0)));
+        assert!(!too_tight_for_trucks(TurnType::Straight, &corner(2.0)));
+        // A turn that doesn't change direction at all can't be too tight
+        assert!(!too_tight_for_trucks(TurnType::Right, &straight));
+    }
+}
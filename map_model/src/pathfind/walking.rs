@@ -311,7 +311,12 @@ fn make_input_graph(
 
             let mut cost = t.geom.length()
                 / PathStep::Turn(t.id).max_speed_along(max_speed, PathConstraints::Pedestrian, map)
-                + zone_cost(t.id.to_movement(map), PathConstraints::Pedestrian, map);
+                + zone_cost(
+                    t.id.to_movement(map),
+                    PathConstraints::Pedestrian,
+                    None,
+                    map,
+                );
 
             if t.turn_type == TurnType::UnmarkedCrossing {
                 // TODO Add to RoutingParams
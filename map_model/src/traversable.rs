@@ -216,7 +216,10 @@ impl Traversable {
             // We assume every pedestrian has a max_speed defined.
             walking_speed_on_incline(max_speed_on_flat_ground.unwrap(), percent_incline)
         } else {
-            debug_assert!(max_speed_on_flat_ground.is_none());
+            // Only trucks have a speed limiter
+            debug_assert!(
+                max_speed_on_flat_ground.is_none() || constraints == PathConstraints::Truck
+            );
             // Incline doesn't affect cars, buses, or trains
             road.speed_limit
         };
@@ -252,6 +255,8 @@ impl Traversable {
 
 // 10 mph
 pub const MAX_BIKE_SPEED: Speed = Speed::const_meters_per_second(4.4704);
+// 90 km/h, the limiter required on heavy goods vehicles in Europe
+pub const MAX_TRUCK_SPEED: Speed = Speed::const_meters_per_second(25.0);
 // 3 mph
 pub const MAX_WALKING_SPEED: Speed = Speed::const_meters_per_second(1.34112);
 
@@ -16,13 +16,14 @@ use serde::{Deserialize, Serialize};
 
 use abstio::{CityName, MapName};
 use abstutil::{deserialize_btreemap, serialize_btreemap, Tags};
-use geom::{Angle, Distance, GPSBounds, PolyLine, Polygon, Pt2D};
+use geom::{Angle, Distance, GPSBounds, PolyLine, Polygon, Pt2D, Time};
 
 pub use self::geometry::{intersection_polygon, InputRoad};
 pub use self::lane_specs::get_lane_specs_ltr;
 pub use self::types::{
-    Amenity, AmenityType, AreaType, BufferType, Direction, DrivingSide, IntersectionType, LaneSpec,
-    LaneType, MapConfig, NamePerLanguage, NORMAL_LANE_THICKNESS, SIDEWALK_THICKNESS,
+    Amenity, AmenityType, AreaType, BufferType, CrossingType, Direction, DrivingSide,
+    IntersectionType, LaneSpec, LaneType, MapConfig, NamePerLanguage, TimeWindow,
+    NORMAL_LANE_THICKNESS, SIDEWALK_THICKNESS,
 };
 
 mod edit;
@@ -349,6 +350,8 @@ pub struct RawRoad {
     pub turn_restrictions: Vec<(RestrictionType, OriginalRoad)>,
     /// (via, to). For turn restrictions where 'via' is an entire road. Only BanTurns.
     pub complicated_turn_restrictions: Vec<(OriginalRoad, OriginalRoad)>,
+    /// (to, windows). Turns that're only banned at some times of day.
+    pub conditional_turn_restrictions: Vec<(OriginalRoad, Vec<TimeWindow>)>,
     pub percent_incline: f64,
     /// Is there a tagged crosswalk near each end of the road?
     pub crosswalk_forward: bool,
@@ -371,6 +374,7 @@ impl RawRoad {
             osm_tags,
             turn_restrictions: Vec::new(),
             complicated_turn_restrictions: Vec::new(),
+            conditional_turn_restrictions: Vec::new(),
             percent_incline: 0.0,
             // Start assuming there's a crosswalk everywhere, and maybe filter it down
             // later
@@ -508,6 +512,9 @@ pub struct RawIntersection {
     pub point: Pt2D,
     pub intersection_type: IntersectionType,
     pub elevation: Distance,
+    /// True if this splits a road only to let pedestrians cross in the middle of a block. These
+    /// shouldn't be collapsed back into one road.
+    pub mid_block_crossing: bool,
 
     // true if src_i matches this intersection (or the deleted/consolidated one, whatever)
     pub trim_roads_for_merging: BTreeMap<(osm::WayID, bool), Pt2D>,
@@ -520,6 +527,7 @@ impl RawIntersection {
             intersection_type,
             // Filled out later
             elevation: Distance::ZERO,
+            mid_block_crossing: false,
             trim_roads_for_merging: BTreeMap::new(),
         }
     }
@@ -564,8 +572,8 @@ pub struct TurnRestriction(pub OriginalRoad, pub RestrictionType, pub OriginalRo
 
 impl RestrictionType {
     pub fn new(restriction: &str) -> Option<RestrictionType> {
-        // TODO There's a huge space of things not represented yet: time conditions, bus-only, no
-        // right turn on red...
+        // TODO There's a huge space of things not represented yet: bus-only, no right turn on
+        // red... Time conditions come separately from restriction:conditional.
 
         // There are so many possibilities:
         // https://taginfo.openstreetmap.org/keys/restriction#values
@@ -590,7 +598,9 @@ pub struct RawTransitRoute {
     /// Entries into transit_stops
     pub stops: Vec<String>,
     pub route_type: RawTransitType,
-    // TODO Schedule
+    /// Each entry is one trip running on the imported service day, listing when the vehicle
+    /// departs each of `stops`, in order. Empty if the feed has no timetable for this route.
+    pub schedule: Vec<Vec<Time>>,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -12,7 +12,11 @@ use crate::{osm, IntersectionType, OriginalRoad, RawMap};
 /// - when the lane specs match and only "unimportant" OSM tags differ
 pub fn collapse(raw: &mut RawMap) {
     let mut merge: Vec<NodeID> = Vec::new();
-    for id in raw.intersections.keys() {
+    for (id, i) in &raw.intersections {
+        // These were split deliberately
+        if i.mid_block_crossing {
+            continue;
+        }
         let roads = raw.roads_per_intersection(*id);
         if roads.len() != 2 {
             continue;
@@ -40,10 +44,16 @@ fn should_collapse(r1: OriginalRoad, r2: OriginalRoad, raw: &RawMap) -> Result<(
     let road2 = &raw.roads[&r2];
 
     // Don't attempt to merge roads with these.
-    if !road1.turn_restrictions.is_empty() || !road1.complicated_turn_restrictions.is_empty() {
+    if !road1.turn_restrictions.is_empty()
+        || !road1.complicated_turn_restrictions.is_empty()
+        || !road1.conditional_turn_restrictions.is_empty()
+    {
         bail!("one road has turn restrictions");
     }
-    if !road2.turn_restrictions.is_empty() || !road2.complicated_turn_restrictions.is_empty() {
+    if !road2.turn_restrictions.is_empty()
+        || !road2.complicated_turn_restrictions.is_empty()
+        || !road2.conditional_turn_restrictions.is_empty()
+    {
         bail!("one road has turn restrictions");
     }
 
@@ -216,6 +226,12 @@ pub fn collapse_intersection(raw: &mut RawMap, i: NodeID) {
                 *id2 = new_r1;
             }
         }
+
+        for (id, _) in &mut road.conditional_turn_restrictions {
+            if rewrite(id) {
+                *id = new_r1;
+            }
+        }
     }
 }
 
@@ -100,6 +100,10 @@ impl RawMap {
             if i1.is_border() || i2.is_border() {
                 continue;
             }
+            // A signalized crossing near a junction isn't part of the junction
+            if i1.mid_block_crossing || i2.mid_block_crossing {
+                continue;
+            }
             if i1.intersection_type != IntersectionType::TrafficSignal
                 && i2.intersection_type != IntersectionType::TrafficSignal
             {
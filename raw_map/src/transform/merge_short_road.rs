@@ -187,6 +187,18 @@ impl RawMap {
             road.turn_restrictions.extend(add);
         }
 
+        // TODO Conditional turn bans into the deleted road should probably apply to its
+        // successors, like simple restrictions. For now, just follow the roads that changed IDs.
+        for road in self.roads.values_mut() {
+            road.conditional_turn_restrictions
+                .retain(|(to, _)| *to != short);
+            for (to, _) in &mut road.conditional_turn_restrictions {
+                if let Some(new_id) = old_to_new.get(to) {
+                    *to = *new_id;
+                }
+            }
+        }
+
         Ok((i1, i2, deleted, created))
     }
 }
@@ -0,0 +1,102 @@
+use anyhow::Result;
+
+use geom::{Distance, PolyLine};
+
+use crate::{osm, CrossingType, IntersectionType, OriginalRoad, RawIntersection, RawMap};
+
+/// Splitting a road any closer to its ends than this produces segments too short to be useful.
+const MIN_DIST_FROM_END: Distance = Distance::const_meters(20.0);
+
+impl RawMap {
+    /// Split a road in two, inserting an intersection `dist` along it where pedestrians can cross
+    /// the road. `id` is the new intersection; use the OSM crossing node if there is one, or
+    /// `new_osm_node_id` otherwise. Returns the two new roads, in the original direction.
+    pub fn add_mid_block_crossing(
+        &mut self,
+        r: OriginalRoad,
+        dist: Distance,
+        crossing: CrossingType,
+        id: osm::NodeID,
+    ) -> Result<(OriginalRoad, OriginalRoad)> {
+        if self.intersections.contains_key(&id) {
+            bail!("{} already exists", id);
+        }
+        if r.i1 == r.i2 {
+            bail!("{} is a loop", r);
+        }
+        let road = &self.roads[&r];
+        if !road.turn_restrictions.is_empty()
+            || !road.complicated_turn_restrictions.is_empty()
+            || !road.conditional_turn_restrictions.is_empty()
+        {
+            bail!("{} has turn restrictions", r);
+        }
+        // Restrictions on other roads referring to this one would need to point to one of the new
+        // pieces. Don't bother handling that.
+        for (other_id, other) in &self.roads {
+            if other.turn_restrictions.iter().any(|(_, to)| *to == r)
+                || other
+                    .complicated_turn_restrictions
+                    .iter()
+                    .any(|(via, to)| *via == r || *to == r)
+                || other
+                    .conditional_turn_restrictions
+                    .iter()
+                    .any(|(to, _)| *to == r)
+            {
+                bail!("{} has a turn restriction involving {}", other_id, r);
+            }
+        }
+
+        let pl = PolyLine::new(road.osm_center_points.clone())?;
+        if dist < MIN_DIST_FROM_END || pl.length() - dist < MIN_DIST_FROM_END {
+            bail!(
+                "A crossing {} along {} would be too close to one end",
+                dist,
+                r
+            );
+        }
+        let before = pl.maybe_exact_slice(Distance::ZERO, dist)?;
+        let after = pl.maybe_exact_slice(dist, pl.length())?;
+        let pt = before.last_pt();
+
+        let mut first = road.clone();
+        first.osm_center_points = before.into_points();
+        first.osm_tags.remove(osm::ENDPT_FWD);
+        let mut second = road.clone();
+        second.osm_center_points = after.into_points();
+        second.osm_tags.remove(osm::ENDPT_BACK);
+        // Unmarked crossings are still valid places to cross, but vehicles don't have to stop
+        let marked = crossing != CrossingType::Unmarked;
+        first.crosswalk_forward = marked;
+        second.crosswalk_backward = marked;
+
+        let mut intersection = RawIntersection::new(
+            pt,
+            if crossing == CrossingType::Signalized {
+                IntersectionType::TrafficSignal
+            } else {
+                IntersectionType::StopSign
+            },
+        );
+        intersection.elevation =
+            (self.intersections[&r.i1].elevation + self.intersections[&r.i2].elevation) / 2.0;
+        intersection.mid_block_crossing = true;
+        self.intersections.insert(id, intersection);
+
+        let r1 = OriginalRoad {
+            osm_way_id: r.osm_way_id,
+            i1: r.i1,
+            i2: id,
+        };
+        let r2 = OriginalRoad {
+            osm_way_id: r.osm_way_id,
+            i1: id,
+            i2: r.i2,
+        };
+        self.roads.remove(&r).unwrap();
+        self.roads.insert(r1, first);
+        self.roads.insert(r2, second);
+        Ok((r1, r2))
+    }
+}
@@ -6,6 +6,7 @@ mod collapse_intersections;
 mod dual_carriageways;
 mod find_short_roads;
 mod merge_short_road;
+mod mid_block_crossings;
 mod remove_disconnected;
 mod shrink_roads;
 mod snappy;
@@ -1,12 +1,13 @@
 use std::collections::BTreeMap;
 use std::fmt;
 
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use strum::IntoEnumIterator;
 use strum_macros::{Display, EnumIter, EnumString};
 
 use abstutil::{deserialize_btreemap, serialize_btreemap, Tags};
-use geom::Distance;
+use geom::{Distance, Duration, Time};
 
 use crate::osm;
 
@@ -302,6 +303,17 @@ pub enum IntersectionType {
     Construction,
 }
 
+/// How pedestrians are controlled at a crossing in the middle of a road.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum CrossingType {
+    /// Pedestrians wait for a traffic signal.
+    Signalized,
+    /// A painted crosswalk, where vehicles yield to pedestrians.
+    Marked,
+    /// No markings; pedestrians have to find a gap in traffic.
+    Unmarked,
+}
+
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum LaneType {
     Driving,
@@ -584,3 +596,173 @@ impl LaneSpec {
         }
     }
 }
+
+/// A recurring daily window of time when some restriction applies, like school drop-off hours.
+/// The simulation only covers one weekday, so days of the week aren't represented.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TimeWindow {
+    /// Measured from midnight
+    pub start: Duration,
+    /// Measured from midnight. If this is before `start`, then the window wraps past midnight.
+    pub end: Duration,
+}
+
+impl TimeWindow {
+    /// Is this time inside the window? Times past the first day wrap around.
+    pub fn contains(self, time: Time) -> bool {
+        let t = time_of_day(time);
+        if self.start <= self.end {
+            self.start <= t && t < self.end
+        } else {
+            t >= self.start || t < self.end
+        }
+    }
+
+    /// Parses a list of windows like "07:30-09:00, 15:00-16:30". Anything else in the input, like
+    /// days of the week from OSM opening hours, is ignored.
+    pub fn parse_all(input: &str) -> Result<Vec<TimeWindow>> {
+        let mut windows = Vec::new();
+        for token in input.split(|c: char| c == ',' || c == ';' || c.is_whitespace()) {
+            if let Some((start, end)) = token.split_once('-') {
+                // Skip day ranges like Mo-Fr
+                if !start.contains(':') {
+                    continue;
+                }
+                if !end.contains(':') {
+                    bail!("{} isn't a time range like 07:30-09:00", token);
+                }
+                windows.push(TimeWindow {
+                    start: Time::parse(start)? - Time::START_OF_DAY,
+                    end: Time::parse(end)? - Time::START_OF_DAY,
+                });
+            }
+        }
+        Ok(windows)
+    }
+
+    /// Parses OSM conditional restrictions like "no @ (Mo-Fr 08:00-09:00,15:00-16:00); delivery
+    /// @ (Sa 07:00-10:00)" into each value and the weekday windows when it applies. Conditions
+    /// that aren't about the time of day, or that only apply on weekends, are skipped.
+    ///
+    /// https://wiki.openstreetmap.org/wiki/Conditional_restrictions
+    pub fn parse_osm_conditional(input: &str) -> Vec<(String, Vec<TimeWindow>)> {
+        let mut results = Vec::new();
+        for restriction in split_outside_parens(input) {
+            let (value, condition) = match restriction.split_once('@') {
+                Some(pair) => pair,
+                None => continue,
+            };
+            let condition = condition
+                .trim()
+                .trim_start_matches('(')
+                .trim_end_matches(')');
+            let mut windows = Vec::new();
+            for rule in condition.split(';') {
+                if !applies_on_weekdays(rule) {
+                    continue;
+                }
+                match TimeWindow::parse_all(rule) {
+                    Ok(list) => windows.extend(list),
+                    Err(err) => {
+                        warn!("Can't parse conditional restriction {}: {}", input, err);
+                    }
+                }
+            }
+            if !windows.is_empty() {
+                results.push((value.trim().to_string(), windows));
+            }
+        }
+        results
+    }
+}
+
+impl fmt::Display for TimeWindow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hh_mm = |d: Duration| {
+            let minutes = (d.inner_seconds() / 60.0).round() as usize;
+            format!("{:02}:{:02}", minutes / 60, minutes % 60)
+        };
+        write!(f, "{}-{}", hh_mm(self.start), hh_mm(self.end))
+    }
+}
+
+fn time_of_day(time: Time) -> Duration {
+    Duration::seconds(time.inner_seconds() % Duration::hours(24).inner_seconds())
+}
+
+// Semicolons separate restrictions, except inside parentheses, where they separate opening hours
+// rules.
+fn split_outside_parens(input: &str) -> Vec<&str> {
+    let mut results = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (idx, c) in input.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ';' if depth == 0 => {
+                results.push(&input[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    results.push(&input[start..]);
+    results
+}
+
+// An opening hours rule without any days applies every day
+fn applies_on_weekdays(rule: &str) -> bool {
+    let mut any_days = false;
+    for token in rule.split(|c: char| c == ',' || c == '-' || c.is_whitespace()) {
+        match token {
+            "Mo" | "Tu" | "We" | "Th" | "Fr" => {
+                return true;
+            }
+            "Sa" | "Su" | "PH" => {
+                any_days = true;
+            }
+            _ => {}
+        }
+    }
+    !any_days
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_osm_conditional() {
+        let hours = |h1: usize, m1: usize, h2: usize, m2: usize| TimeWindow {
+            start: Duration::hours(h1) + Duration::minutes(m1),
+            end: Duration::hours(h2) + Duration::minutes(m2),
+        };
+
+        assert_eq!(
+            TimeWindow::parse_osm_conditional("no @ (Mo-Fr 08:15-09:00,15:00-16:30)"),
+            vec![(
+                "no".to_string(),
+                vec![hours(8, 15, 9, 0), hours(15, 0, 16, 30)]
+            )]
+        );
+        assert_eq!(
+            TimeWindow::parse_osm_conditional(
+                "no @ (Mo-Fr 07:00-09:00; Sa 10:00-12:00); destination @ (weight>7.5)"
+            ),
+            vec![("no".to_string(), vec![hours(7, 0, 9, 0)])]
+        );
+        assert_eq!(
+            TimeWindow::parse_osm_conditional("lane @ 16:00-18:30"),
+            vec![("lane".to_string(), vec![hours(16, 0, 18, 30)])]
+        );
+        assert!(TimeWindow::parse_osm_conditional("no @ (Sa,Su 10:00-14:00)").is_empty());
+
+        assert_eq!(hours(8, 15, 9, 0).to_string(), "08:15-09:00");
+
+        let overnight = hours(22, 0, 6, 0);
+        assert!(overnight.contains(Time::START_OF_DAY + Duration::hours(23)));
+        assert!(overnight.contains(Time::START_OF_DAY + Duration::hours(24 + 5)));
+        assert!(!overnight.contains(Time::START_OF_DAY + Duration::hours(12)));
+    }
+}
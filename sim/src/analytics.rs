@@ -4,14 +4,25 @@ use std::fmt::Write;
 use serde::{Deserialize, Serialize};
 
 use abstutil::Counter;
-use geom::{Duration, Pt2D, Time};
+use geom::{Distance, Duration, Pt2D, Time};
 use map_model::{
-    CompressedMovementID, IntersectionID, LaneID, Map, MovementID, ParkingLotID, Path, PathRequest,
-    RoadID, TransitRouteID, TransitStopID, Traversable, TurnID,
+    CompressedMovementID, DirectedRoadID, IntersectionID, LaneID, Map, MovementID, ParkingLotID,
+    Path, PathRequest, RoadID, RoadTravelTimes, TransitRoute, TransitRouteID, TransitStopID,
+    Traversable, TurnID,
 };
 use synthpop::TripMode;
 
-use crate::{AgentID, AgentType, AlertLocation, CarID, Event, ParkingSpot, TripID, TripPhaseType};
+use crate::{
+    AgentID, AgentType, AlertLocation, CarID, ConflictSummary, Emissions, Event, ParkingSpot,
+    SafetyConflict, TransitPriorityAction, TripID, TripPhaseType, VehicleType,
+};
+
+/// A transit vehicle arriving less than this fraction of the scheduled headway after the previous
+/// vehicle on its route is bunched up with it.
+const BUNCHING_FRACTION: f64 = 0.5;
+/// A transit vehicle departing a stop within this window around the timetable is on time.
+const ON_TIME_EARLIEST: Duration = Duration::const_seconds(-60.0);
+const ON_TIME_LATEST: Duration = Duration::const_seconds(5.0 * 60.0);
 
 /// As a simulation runs, different pieces emit Events. The Analytics object listens to these,
 /// organizing and storing some information from them. The UI queries Analytics to draw time-series
@@ -29,6 +40,10 @@ pub struct Analytics {
     // requires occasionally expensive or complicated summing or merging over all directions of an
     // intersection. So for now, eat the file size cost.
     pub traffic_signal_thruput: TimeSeriesCount<CompressedMovementID>,
+    /// (Road, hour block) -> the total time cars took to cross the road, including waiting to
+    /// leave it, and how many crossings that covers. See `road_travel_times`.
+    /// Only recorded when `SimOptions::record_road_crossing_times` is enabled.
+    pub road_crossing_times: BTreeMap<(DirectedRoadID, usize), (Duration, usize)>,
 
     /// Most fields in Analytics are cumulative over time, but this is just for the current moment
     /// in time.
@@ -39,6 +54,30 @@ pub struct Analytics {
     /// For each passenger boarding, how long did they wait at the stop?
     pub passengers_boarding: BTreeMap<TransitStopID, Vec<(Time, TransitRouteID, Duration)>>,
     pub passengers_alighting: BTreeMap<TransitStopID, Vec<(Time, TransitRouteID)>>,
+    /// How many people waiting couldn't board because the vehicle was full?
+    pub passengers_left_behind: BTreeMap<TransitStopID, Vec<(Time, TransitRouteID, usize)>>,
+    /// Per transit route, how many passengers were aboard each time a vehicle left a stop?
+    pub transit_loads: BTreeMap<TransitRouteID, Vec<(Time, TransitStopID, usize)>>,
+    /// Per transit route, how late did vehicles following the timetable depart each stop?
+    pub transit_schedule_deviations: BTreeMap<TransitRouteID, Vec<(Time, TransitStopID, Duration)>>,
+    /// When was each carpool passenger picked up, and by which car?
+    pub car_passengers: Vec<(Time, TripID, CarID)>,
+    /// When was a ride-hail vehicle assigned to each trip?
+    pub ride_hail_dispatches: Vec<(Time, CarID, TripID)>,
+    /// For each ride-hail pickup, how long did the rider wait, and how far did the vehicle drive
+    /// empty to get there?
+    pub ride_hail_pickups: Vec<(Time, CarID, TripID, Duration, Distance)>,
+    /// For each ride-hail drop-off, how far did the rider travel?
+    pub ride_hail_dropoffs: Vec<(Time, CarID, TripID, Distance)>,
+
+    /// Energy used and emissions by vehicles on each road, since midnight
+    pub road_emissions: BTreeMap<RoadID, Emissions>,
+    /// Energy used and emissions by vehicles turning through each intersection, since midnight
+    pub intersection_emissions: BTreeMap<IntersectionID, Emissions>,
+    /// Energy used and emissions by the vehicle used for each trip. Transit and ride-hail
+    /// vehicles aren't counted, since they aren't tied to one trip.
+    pub trip_emissions: BTreeMap<TripID, Emissions>,
+    pub vehicle_type_emissions: BTreeMap<VehicleType, Emissions>,
 
     pub started_trips: BTreeMap<TripID, Time>,
     /// Finish time, ID, mode, trip duration if successful (or None if cancelled)
@@ -46,6 +85,15 @@ pub struct Analytics {
 
     /// Record different problems that each trip encounters.
     pub problems_per_trip: BTreeMap<TripID, Vec<(Time, Problem)>>,
+    /// (Sidewalk, hour block) -> the most people per square meter measured there. Only filled out
+    /// when pedestrian crowding is simulated. See `sidewalk_level_of_service`.
+    pub sidewalk_peak_density: BTreeMap<(LaneID, usize), f64>,
+    /// Near-misses between agents at intersections. Only recorded when
+    /// `SimOptions::measure_safety_conflicts` is enabled, which prebaking never does.
+    pub safety_conflicts: Vec<(Time, SafetyConflict)>,
+    /// Per road, when did drivers switch to a faster route, and how much time did they expect to
+    /// save? Only recorded when live rerouting is enabled.
+    pub reroutes: BTreeMap<RoadID, Vec<(Time, Duration)>>,
 
     // TODO This subsumes finished_trips
     pub trip_log: Vec<(Time, TripID, Option<PathRequest>, TripPhaseType)>,
@@ -53,10 +101,18 @@ pub struct Analytics {
     // TODO Transit riders aren't represented here yet, just the vehicle they're riding.
     /// Only for traffic signals. The u8 is the movement index from a CompressedMovementID.
     pub intersection_delays: BTreeMap<IntersectionID, Vec<(u8, Time, Duration, AgentType)>>,
+    /// Per transit route, how long did its vehicles wait at each traffic signal?
+    pub transit_signal_delays: BTreeMap<TransitRouteID, Vec<(Time, IntersectionID, Duration)>>,
+    /// Per transit route, when did a traffic signal change its timing for one of its vehicles?
+    pub transit_signal_priority:
+        BTreeMap<TransitRouteID, Vec<(Time, IntersectionID, TransitPriorityAction)>>,
 
     /// Per parking lane or lot, when does a spot become filled (true) or free (false)
     pub parking_lane_changes: BTreeMap<LaneID, Vec<(Time, bool)>>,
     pub parking_lot_changes: BTreeMap<ParkingLotID, Vec<(Time, bool)>>,
+    /// Per driving lane, when did freight vehicles finish unloading there, how long did they
+    /// occupy the curb, and how many vehicles were stuck behind them?
+    pub curb_deliveries: BTreeMap<LaneID, Vec<(Time, Duration, usize)>>,
 
     pub(crate) alerts: Vec<(Time, AlertLocation, String)>,
 
@@ -74,8 +130,16 @@ pub enum Problem {
     ArterialIntersectionCrossing(TurnID),
     /// Another vehicle wanted to over-take this cyclist somewhere on this lane or turn.
     OvertakeDesired(Traversable),
+    /// Another vehicle passed this cyclist with less than the minimum passing distance.
+    ClosePass(Traversable),
     /// Too many people are crossing the same sidewalk or crosswalk at the same time.
     PedestrianOvercrowding(Traversable),
+    /// A pedestrian crossed against the signal.
+    Jaywalking(TurnID),
+    /// A vehicle had to stop for a pedestrian crossing against the signal.
+    JaywalkerConflict(TurnID),
+    /// A vehicle was stuck behind a freight vehicle unloading on this lane.
+    BlockedByDelivery(Traversable),
 }
 
 impl Problem {
@@ -86,10 +150,45 @@ impl Problem {
             Problem::IntersectionDelay(i, _) | Problem::ComplexIntersectionCrossing(i) => {
                 map.get_i(*i).polygon.center()
             }
-            Problem::OvertakeDesired(on) | Problem::PedestrianOvercrowding(on) => {
-                on.get_polyline(map).middle()
-            }
-            Problem::ArterialIntersectionCrossing(t) => map.get_t(*t).geom.middle(),
+            Problem::OvertakeDesired(on)
+            | Problem::ClosePass(on)
+            | Problem::PedestrianOvercrowding(on)
+            | Problem::BlockedByDelivery(on) => on.get_polyline(map).middle(),
+            Problem::ArterialIntersectionCrossing(t)
+            | Problem::Jaywalking(t)
+            | Problem::JaywalkerConflict(t) => map.get_t(*t).geom.middle(),
+        }
+    }
+}
+
+/// The pedestrian level of service on a walkway, from A (free-flowing) to F (people are jammed
+/// together), using the thresholds from the Highway Capacity Manual.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+pub enum LevelOfService {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+}
+
+impl LevelOfService {
+    pub fn from_density(people_per_sq_m: f64) -> LevelOfService {
+        // The HCM defines these by the space per person
+        let sq_m_per_person = 1.0 / people_per_sq_m;
+        if sq_m_per_person > 5.6 {
+            LevelOfService::A
+        } else if sq_m_per_person > 3.7 {
+            LevelOfService::B
+        } else if sq_m_per_person > 2.2 {
+            LevelOfService::C
+        } else if sq_m_per_person > 1.4 {
+            LevelOfService::D
+        } else if sq_m_per_person > 0.75 {
+            LevelOfService::E
+        } else {
+            LevelOfService::F
         }
     }
 }
@@ -99,8 +198,12 @@ pub enum ProblemType {
     IntersectionDelay,
     ComplexIntersectionCrossing,
     OvertakeDesired,
+    ClosePass,
     ArterialIntersectionCrossing,
     PedestrianOvercrowding,
+    Jaywalking,
+    JaywalkerConflict,
+    BlockedByDelivery,
 }
 
 impl From<&Problem> for ProblemType {
@@ -109,8 +212,12 @@ impl From<&Problem> for ProblemType {
             Problem::IntersectionDelay(_, _) => Self::IntersectionDelay,
             Problem::ComplexIntersectionCrossing(_) => Self::ComplexIntersectionCrossing,
             Problem::OvertakeDesired(_) => Self::OvertakeDesired,
+            Problem::ClosePass(_) => Self::ClosePass,
             Problem::ArterialIntersectionCrossing(_) => Self::ArterialIntersectionCrossing,
             Problem::PedestrianOvercrowding(_) => Self::PedestrianOvercrowding,
+            Problem::Jaywalking(_) => Self::Jaywalking,
+            Problem::JaywalkerConflict(_) => Self::JaywalkerConflict,
+            Problem::BlockedByDelivery(_) => Self::BlockedByDelivery,
         }
     }
 }
@@ -131,8 +238,12 @@ impl ProblemType {
             ProblemType::IntersectionDelay,
             ProblemType::ComplexIntersectionCrossing,
             ProblemType::OvertakeDesired,
+            ProblemType::ClosePass,
             ProblemType::ArterialIntersectionCrossing,
             ProblemType::PedestrianOvercrowding,
+            ProblemType::Jaywalking,
+            ProblemType::JaywalkerConflict,
+            ProblemType::BlockedByDelivery,
         ]
     }
 
@@ -143,10 +254,14 @@ impl ProblemType {
                 "where cyclists cross complex intersections"
             }
             ProblemType::OvertakeDesired => "where cars want to overtake cyclists",
+            ProblemType::ClosePass => "where cars pass cyclists too closely",
             ProblemType::ArterialIntersectionCrossing => {
                 "where pedestrians cross arterial intersections"
             }
             ProblemType::PedestrianOvercrowding => "where pedestrians are over-crowded",
+            ProblemType::Jaywalking => "where pedestrians cross against the signal",
+            ProblemType::JaywalkerConflict => "where vehicles stop for jaywalkers",
+            ProblemType::BlockedByDelivery => "where deliveries block traffic",
         }
     }
 }
@@ -157,17 +272,35 @@ impl Analytics {
             road_thruput: TimeSeriesCount::new(),
             intersection_thruput: TimeSeriesCount::new(),
             traffic_signal_thruput: TimeSeriesCount::new(),
+            road_crossing_times: BTreeMap::new(),
             demand: BTreeMap::new(),
             bus_arrivals: Vec::new(),
             passengers_boarding: BTreeMap::new(),
             passengers_alighting: BTreeMap::new(),
+            passengers_left_behind: BTreeMap::new(),
+            transit_loads: BTreeMap::new(),
+            transit_schedule_deviations: BTreeMap::new(),
+            car_passengers: Vec::new(),
+            ride_hail_dispatches: Vec::new(),
+            ride_hail_pickups: Vec::new(),
+            ride_hail_dropoffs: Vec::new(),
+            road_emissions: BTreeMap::new(),
+            intersection_emissions: BTreeMap::new(),
+            trip_emissions: BTreeMap::new(),
+            vehicle_type_emissions: BTreeMap::new(),
             started_trips: BTreeMap::new(),
             finished_trips: Vec::new(),
             problems_per_trip: BTreeMap::new(),
+            sidewalk_peak_density: BTreeMap::new(),
+            safety_conflicts: Vec::new(),
+            reroutes: BTreeMap::new(),
             trip_log: Vec::new(),
             intersection_delays: BTreeMap::new(),
+            transit_signal_delays: BTreeMap::new(),
+            transit_signal_priority: BTreeMap::new(),
             parking_lane_changes: BTreeMap::new(),
             parking_lot_changes: BTreeMap::new(),
+            curb_deliveries: BTreeMap::new(),
             alerts: Vec::new(),
             record_anything,
         }
@@ -245,6 +378,57 @@ impl Analytics {
                 .or_insert_with(Vec::new)
                 .push((time, route));
         }
+        if let Event::PassengersLeftBehind(_, route, stop, count) = ev {
+            self.passengers_left_behind
+                .entry(stop)
+                .or_insert_with(Vec::new)
+                .push((time, route, count));
+        }
+        if let Event::BusDepartedFromStop(_, route, stop, load) = ev {
+            self.transit_loads
+                .entry(route)
+                .or_insert_with(Vec::new)
+                .push((time, stop, load));
+        }
+        if let Event::TransitScheduleDeviation(_, route, stop, deviation) = ev {
+            self.transit_schedule_deviations
+                .entry(route)
+                .or_insert_with(Vec::new)
+                .push((time, stop, deviation));
+        }
+        if let Event::TripPhaseStarting(id, _, _, TripPhaseType::RidingInCar(car)) = ev {
+            self.car_passengers.push((time, id, car));
+        }
+
+        // Ride-hailing
+        if let Event::RideHailDispatched(car, trip) = ev {
+            self.ride_hail_dispatches.push((time, car, trip));
+        }
+        if let Event::RideHailPickup(car, trip, waited, dist) = ev {
+            self.ride_hail_pickups.push((time, car, trip, waited, dist));
+        }
+        if let Event::RideHailDropoff(car, trip, dist) = ev {
+            self.ride_hail_dropoffs.push((time, car, trip, dist));
+        }
+
+        // Emissions
+        if let Event::VehicleEmissions(car, maybe_trip, on, emissions) = ev {
+            match on {
+                Traversable::Lane(l) => {
+                    *self.road_emissions.entry(l.road).or_default() += emissions;
+                }
+                Traversable::Turn(t) => {
+                    *self.intersection_emissions.entry(t.parent).or_default() += emissions;
+                }
+            }
+            if let Some(trip) = maybe_trip {
+                *self.trip_emissions.entry(trip).or_default() += emissions;
+            }
+            *self
+                .vehicle_type_emissions
+                .entry(car.vehicle_type)
+                .or_default() += emissions;
+        }
 
         // Started trips
         if let Event::TripPhaseStarting(id, _, _, _) = ev {
@@ -272,7 +456,7 @@ impl Analytics {
                 AgentID::Car(_) => Duration::seconds(30.0),
                 AgentID::Pedestrian(_) => Duration::seconds(15.0),
                 // Don't record for riders
-                AgentID::BusPassenger(_, _) => Duration::hours(24),
+                AgentID::BusPassenger(_, _) | AgentID::CarPassenger(_, _) => Duration::hours(24),
             };
             if delay > threshold {
                 self.problems_per_trip
@@ -291,6 +475,19 @@ impl Analytics {
             }
         }
 
+        if let Event::TransitDelayedAtSignal(_, route, turn, delay) = ev {
+            self.transit_signal_delays
+                .entry(route)
+                .or_insert_with(Vec::new)
+                .push((time, turn.parent, delay));
+        }
+        if let Event::TransitSignalPriority(_, route, i, action) = ev {
+            self.transit_signal_priority
+                .entry(route)
+                .or_insert_with(Vec::new)
+                .push((time, i, action));
+        }
+
         // Parking spot changes
         if let Event::CarReachedParkingSpot(_, spot) = ev {
             if let ParkingSpot::Onstreet(l, _) = spot {
@@ -319,7 +516,29 @@ impl Analytics {
             }
         }
 
+        // Deliveries
+        if let Event::DeliveryUnloaded(_, l, dwell, blocked) = ev {
+            self.curb_deliveries
+                .entry(l)
+                .or_insert_with(Vec::new)
+                .push((time, dwell, blocked));
+        }
+
+        // Pedestrian crowding
+        if let Event::PedestrianDensityMeasured(Traversable::Lane(l), density) = ev {
+            let peak = self
+                .sidewalk_peak_density
+                .entry((l, time.get_hours()))
+                .or_insert(0.0);
+            if *peak < density {
+                *peak = density;
+            }
+        }
+
         // Safety metrics
+        if let Event::SafetyConflict(ref conflict) = ev {
+            self.safety_conflicts.push((time, conflict.clone()));
+        }
         if let Event::AgentEntersTraversable(a, Some(trip), Traversable::Turn(t), _) = ev {
             if a.to_type() == AgentType::Bike && map.get_i(t.parent).roads.len() > 4 {
                 // Defining a "large intersection" is tricky. If a road is split into two one-ways,
@@ -358,6 +577,26 @@ impl Analytics {
             Event::PathAmended(path) => {
                 self.record_demand(&path, map);
             }
+            Event::Rerouted {
+                road,
+                time_saved,
+                demand_removed,
+                demand_added,
+                ..
+            } => {
+                self.reroutes
+                    .entry(road)
+                    .or_insert_with(Vec::new)
+                    .push((time, time_saved));
+                for id in demand_removed {
+                    if let Some(count) = self.demand.get_mut(&id) {
+                        *count = count.saturating_sub(1);
+                    }
+                }
+                for id in demand_added {
+                    *self.demand.entry(id).or_insert(0) += 1;
+                }
+            }
             Event::Alert(loc, msg) => {
                 self.alerts.push((time, loc, msg));
             }
@@ -371,13 +610,22 @@ impl Analytics {
         }
     }
 
+    /// A car crossed an entire road, starting at some time.
+    pub(crate) fn record_road_crossing(&mut self, dr: DirectedRoadID, entered: Time, time: Time) {
+        if !self.record_anything {
+            return;
+        }
+        let total = self
+            .road_crossing_times
+            .entry((dr, entered.get_hours()))
+            .or_insert((Duration::ZERO, 0));
+        total.0 += time - entered;
+        total.1 += 1;
+    }
+
     pub fn record_demand(&mut self, path: &Path, map: &Map) {
-        for step in path.get_steps() {
-            if let Traversable::Turn(t) = step.as_traversable() {
-                if let Some((id, _)) = map.get_movement_for_traffic_signal(t) {
-                    *self.demand.entry(id).or_insert(0) += 1;
-                }
-            }
+        for id in signal_movements(path, map) {
+            *self.demand.entry(id).or_insert(0) += 1;
         }
     }
 
@@ -425,6 +673,359 @@ impl Analytics {
         results
     }
 
+    /// How many times did vehicles on a transit route cross a traffic signal, and how long did
+    /// they wait there in total, up to some time?
+    pub fn transit_signal_delay(&self, route: TransitRouteID, now: Time) -> (usize, Duration) {
+        let mut count = 0;
+        let mut total = Duration::ZERO;
+        for (t, _, dt) in self
+            .transit_signal_delays
+            .get(&route)
+            .unwrap_or(&Vec::new())
+        {
+            if *t > now {
+                break;
+            }
+            count += 1;
+            total += *dt;
+        }
+        (count, total)
+    }
+
+    /// How many times did traffic signals hold a green and cut a red short for vehicles on a
+    /// transit route, up to some time?
+    pub fn transit_signal_priority_actions(
+        &self,
+        route: TransitRouteID,
+        now: Time,
+    ) -> (usize, usize) {
+        let mut extended = 0;
+        let mut truncated = 0;
+        for (t, _, action) in self
+            .transit_signal_priority
+            .get(&route)
+            .unwrap_or(&Vec::new())
+        {
+            if *t > now {
+                break;
+            }
+            match action {
+                TransitPriorityAction::ExtendedGreen => {
+                    extended += 1;
+                }
+                TransitPriorityAction::TruncatedRed => {
+                    truncated += 1;
+                }
+            }
+        }
+        (extended, truncated)
+    }
+
+    /// For every stop along a transit route, how long after the previous vehicle on the route did
+    /// each vehicle arrive, up to some time?
+    pub fn transit_headways(
+        &self,
+        route: TransitRouteID,
+        now: Time,
+    ) -> BTreeMap<TransitStopID, Vec<(Time, Duration)>> {
+        let mut last_arrival: BTreeMap<TransitStopID, Time> = BTreeMap::new();
+        let mut results: BTreeMap<TransitStopID, Vec<(Time, Duration)>> = BTreeMap::new();
+        for (t, _, r, stop) in &self.bus_arrivals {
+            if *t > now {
+                break;
+            }
+            if *r != route {
+                continue;
+            }
+            if let Some(prev) = last_arrival.insert(*stop, *t) {
+                results
+                    .entry(*stop)
+                    .or_insert_with(Vec::new)
+                    .push((*t, *t - prev));
+            }
+        }
+        results
+    }
+
+    /// Summarizes how regularly and how crowded a transit route ran up to some time.
+    pub fn transit_route_summary(&self, route: &TransitRoute, now: Time) -> TransitRouteSummary {
+        let scheduled_headway = if route.spawn_times.len() >= 2 {
+            let first = route.spawn_times[0];
+            let last = *route.spawn_times.last().unwrap();
+            Some((last - first) / ((route.spawn_times.len() - 1) as f64))
+        } else {
+            None
+        };
+        let mut summary = TransitRouteSummary {
+            route: route.id,
+            scheduled_headway,
+            headways_measured: 0,
+            average_headway: Duration::ZERO,
+            bunched_arrivals: 0,
+            max_load: 0,
+            passengers_left_behind: 0,
+            on_time_departures: None,
+        };
+
+        let mut total = Duration::ZERO;
+        for list in self.transit_headways(route.id, now).values() {
+            for (_, headway) in list {
+                summary.headways_measured += 1;
+                total += *headway;
+                if let Some(scheduled) = scheduled_headway {
+                    if *headway < scheduled * BUNCHING_FRACTION {
+                        summary.bunched_arrivals += 1;
+                    }
+                }
+            }
+        }
+        if summary.headways_measured > 0 {
+            summary.average_headway = total / (summary.headways_measured as f64);
+        }
+
+        for (t, _, load) in self.transit_loads.get(&route.id).unwrap_or(&Vec::new()) {
+            if *t > now {
+                break;
+            }
+            summary.max_load = summary.max_load.max(*load);
+        }
+        // A route may visit the same stop twice
+        let stops: BTreeSet<TransitStopID> = route.stops.iter().cloned().collect();
+        for stop in stops {
+            for (t, r, count) in self
+                .passengers_left_behind
+                .get(&stop)
+                .unwrap_or(&Vec::new())
+            {
+                if *t > now {
+                    break;
+                }
+                if *r == route.id {
+                    summary.passengers_left_behind += *count;
+                }
+            }
+        }
+
+        let mut on_time = 0;
+        let mut departures = 0;
+        for (t, _, deviation) in self
+            .transit_schedule_deviations
+            .get(&route.id)
+            .unwrap_or(&Vec::new())
+        {
+            if *t > now {
+                break;
+            }
+            departures += 1;
+            if *deviation >= ON_TIME_EARLIEST && *deviation <= ON_TIME_LATEST {
+                on_time += 1;
+            }
+        }
+        if departures > 0 {
+            summary.on_time_departures = Some((on_time as f64) / (departures as f64));
+        }
+
+        summary
+    }
+
+    /// Counting the driver, how many people rode in each car on average? Only driving trips that
+    /// successfully finished by some time are counted. None if there aren't any.
+    pub fn average_car_occupancy(&self, now: Time) -> Option<f64> {
+        let passengers: BTreeSet<TripID> =
+            self.car_passengers.iter().map(|(_, id, _)| *id).collect();
+        let mut drivers = 0;
+        let mut riders = 0;
+        for (t, id, mode, maybe_dt) in &self.finished_trips {
+            if *t > now {
+                break;
+            }
+            if *mode != TripMode::Drive || maybe_dt.is_none() {
+                continue;
+            }
+            if passengers.contains(id) {
+                riders += 1;
+            } else {
+                drivers += 1;
+            }
+        }
+        if drivers == 0 {
+            return None;
+        }
+        Some(((drivers + riders) as f64) / (drivers as f64))
+    }
+
+    /// Summarizes how a ride-hail fleet of some size performed up to some time.
+    pub fn ride_hail_summary(&self, now: Time, fleet_size: usize) -> RideHailSummary {
+        let mut summary = RideHailSummary {
+            fleet_size,
+            rides: 0,
+            average_wait: Duration::ZERO,
+            max_wait: Duration::ZERO,
+            deadhead_distance: Distance::ZERO,
+            revenue_distance: Distance::ZERO,
+            utilization: 0.0,
+        };
+
+        let mut total_wait = Duration::ZERO;
+        let mut pickups = 0;
+        for (t, _, _, waited, dist) in &self.ride_hail_pickups {
+            if *t > now {
+                break;
+            }
+            pickups += 1;
+            total_wait += *waited;
+            summary.max_wait = summary.max_wait.max(*waited);
+            summary.deadhead_distance += *dist;
+        }
+        if pickups > 0 {
+            summary.average_wait = total_wait / (pickups as f64);
+        }
+
+        // A vehicle is busy from when it's dispatched until it drops off the rider, or until the
+        // trip is cancelled.
+        let mut busy_until: BTreeMap<TripID, Time> = BTreeMap::new();
+        for (t, _, trip, dist) in &self.ride_hail_dropoffs {
+            if *t > now {
+                break;
+            }
+            summary.rides += 1;
+            summary.revenue_distance += *dist;
+            busy_until.insert(*trip, *t);
+        }
+        for (t, trip, _, maybe_dt) in &self.finished_trips {
+            if *t > now {
+                break;
+            }
+            if maybe_dt.is_none() {
+                busy_until.entry(*trip).or_insert(*t);
+            }
+        }
+        let mut busy = Duration::ZERO;
+        for (t, _, trip) in &self.ride_hail_dispatches {
+            if *t > now {
+                break;
+            }
+            busy += *busy_until.get(trip).unwrap_or(&now) - *t;
+        }
+        let available = (now - Time::START_OF_DAY) * (fleet_size as f64);
+        if available > Duration::ZERO {
+            summary.utilization = busy / available;
+        }
+
+        summary
+    }
+
+    /// The average time cars took to cross each road during each hour of the day, so far. Routing
+    /// can use this to avoid congestion.
+    pub fn road_travel_times(&self) -> RoadTravelTimes {
+        let mut times = RoadTravelTimes::new(Duration::hours(1));
+        for ((dr, hour), (total, count)) in &self.road_crossing_times {
+            times.set(
+                *dr,
+                Time::START_OF_DAY + Duration::hours(*hour),
+                *total / (*count as f64),
+            );
+        }
+        times
+    }
+
+    /// How crowded was a sidewalk during each hour of the day so far, based on the most crowded
+    /// moment? Hours when nobody walked there are omitted.
+    pub fn sidewalk_level_of_service(&self, l: LaneID) -> Vec<(usize, LevelOfService)> {
+        self.sidewalk_peak_density
+            .range((l, 0)..=(l, usize::MAX))
+            .map(|((_, hour), density)| (*hour, LevelOfService::from_density(*density)))
+            .collect()
+    }
+
+    /// Summarize near-misses at each intersection.
+    pub fn safety_conflicts_per_intersection(&self) -> BTreeMap<IntersectionID, ConflictSummary> {
+        let mut per_intersection: BTreeMap<IntersectionID, ConflictSummary> = BTreeMap::new();
+        for (_, conflict) in &self.safety_conflicts {
+            per_intersection
+                .entry(conflict.turn.parent)
+                .or_default()
+                .add(conflict);
+        }
+        per_intersection
+    }
+
+    /// Summarize near-misses by the movement of the agent who entered the conflict second.
+    pub fn safety_conflicts_per_movement(
+        &self,
+        map: &Map,
+    ) -> BTreeMap<MovementID, ConflictSummary> {
+        let mut per_movement: BTreeMap<MovementID, ConflictSummary> = BTreeMap::new();
+        for (_, conflict) in &self.safety_conflicts {
+            per_movement
+                .entry(conflict.turn.to_movement(map))
+                .or_default()
+                .add(conflict);
+        }
+        per_movement
+    }
+
+    /// How many times cyclists were passed with less than the minimum passing distance on each
+    /// road.
+    pub fn close_passes_per_road(&self) -> Counter<RoadID> {
+        let mut cnt = Counter::new();
+        for problems in self.problems_per_trip.values() {
+            for (_, problem) in problems {
+                if let Problem::ClosePass(Traversable::Lane(l)) = problem {
+                    cnt.inc(l.road);
+                }
+            }
+        }
+        cnt
+    }
+
+    /// How long freight vehicles occupied the curb on each road, unloading in the lane, and how
+    /// many vehicles in total were stuck behind them.
+    pub fn curb_occupancy_per_road(&self) -> BTreeMap<RoadID, (Duration, usize)> {
+        let mut per_road = BTreeMap::new();
+        for (l, deliveries) in &self.curb_deliveries {
+            let (total_dwell, total_blocked) =
+                per_road.entry(l.road).or_insert((Duration::ZERO, 0));
+            for (_, dwell, blocked) in deliveries {
+                *total_dwell += *dwell;
+                *total_blocked += *blocked;
+            }
+        }
+        per_road
+    }
+
+    /// Finds every sidewalk that was at least this crowded at some point, along with the worst
+    /// level of service measured there.
+    pub fn overloaded_sidewalks(&self, threshold: LevelOfService) -> Vec<(LaneID, LevelOfService)> {
+        let mut worst: BTreeMap<LaneID, LevelOfService> = BTreeMap::new();
+        for ((l, _), density) in &self.sidewalk_peak_density {
+            let los = LevelOfService::from_density(*density);
+            if los >= threshold && worst.get(l).map(|x| los > *x).unwrap_or(true) {
+                worst.insert(*l, los);
+            }
+        }
+        worst.into_iter().collect()
+    }
+
+    /// Summarizes the energy use and emissions of all vehicles so far.
+    pub fn emissions_summary(&self) -> EmissionsSummary {
+        let mut total = Emissions::ZERO;
+        for emissions in self.vehicle_type_emissions.values() {
+            total += *emissions;
+        }
+        EmissionsSummary {
+            total,
+            per_vehicle_type: self.vehicle_type_emissions.clone(),
+            per_road: self.road_emissions.iter().map(|(r, e)| (*r, *e)).collect(),
+            per_intersection: self
+                .intersection_emissions
+                .iter()
+                .map(|(i, e)| (*i, *e))
+                .collect(),
+            per_trip: self.trip_emissions.iter().map(|(t, e)| (*t, *e)).collect(),
+        }
+    }
+
     /// If calling on prebaked Analytics, be careful to pass in an unedited map, to match how the
     /// simulation was originally run. Otherwise the paths may be nonsense.
     pub fn get_trip_phases(&self, trip: TripID, map: &Map) -> Vec<TripPhase> {
@@ -600,15 +1201,18 @@ impl Analytics {
                     Problem::IntersectionDelay(i, _) | Problem::ComplexIntersectionCrossing(i) => {
                         *i
                     }
-                    Problem::OvertakeDesired(on) | Problem::PedestrianOvercrowding(on) => {
-                        match on {
-                            Traversable::Turn(t) => t.parent,
-                            _ => {
-                                continue;
-                            }
+                    Problem::OvertakeDesired(on)
+                    | Problem::ClosePass(on)
+                    | Problem::PedestrianOvercrowding(on)
+                    | Problem::BlockedByDelivery(on) => match on {
+                        Traversable::Turn(t) => t.parent,
+                        _ => {
+                            continue;
                         }
-                    }
-                    Problem::ArterialIntersectionCrossing(t) => t.parent,
+                    },
+                    Problem::ArterialIntersectionCrossing(t)
+                    | Problem::Jaywalking(t)
+                    | Problem::JaywalkerConflict(t) => t.parent,
                 };
                 if id == i {
                     raw_per_type
@@ -652,14 +1256,15 @@ impl Analytics {
                     break;
                 }
                 let l = match problem {
-                    Problem::OvertakeDesired(on) | Problem::PedestrianOvercrowding(on) => {
-                        match on {
-                            Traversable::Lane(l) => *l,
-                            _ => {
-                                continue;
-                            }
+                    Problem::OvertakeDesired(on)
+                    | Problem::ClosePass(on)
+                    | Problem::PedestrianOvercrowding(on)
+                    | Problem::BlockedByDelivery(on) => match on {
+                        Traversable::Lane(l) => *l,
+                        _ => {
+                            continue;
                         }
-                    }
+                    },
                     _ => {
                         continue;
                     }
@@ -695,6 +1300,93 @@ impl Default for Analytics {
     }
 }
 
+/// Tracks when each car entered its current road, to measure how long crossing roads takes. This
+/// is only needed while cars are driving, so it isn't part of `Analytics`.
+#[derive(Clone, Default)]
+pub(crate) struct RoadCrossings {
+    /// When each car entered its current road, and during what trip
+    entered: BTreeMap<CarID, (TripID, DirectedRoadID, Time)>,
+}
+
+impl RoadCrossings {
+    /// If a car just finished crossing an entire road, returns the road and when the car entered
+    /// it. Buses stop along the way and bikes are slower, so only cars are measured.
+    pub fn handle_event(
+        &mut self,
+        time: Time,
+        ev: &Event,
+        map: &Map,
+    ) -> Option<(DirectedRoadID, Time)> {
+        match ev {
+            Event::AgentEntersTraversable(AgentID::Car(car), Some(trip), on, _)
+                if car.vehicle_type == VehicleType::Car =>
+            {
+                match on {
+                    Traversable::Lane(l) => {
+                        self.entered
+                            .insert(*car, (*trip, map.get_l(*l).get_directed_parent(), time));
+                        None
+                    }
+                    Traversable::Turn(t) => {
+                        // Cars don't produce this event when they first appear partway along a
+                        // lane, so this only measures crossing the full road.
+                        let (prev_trip, dr, entered) = self.entered.remove(car)?;
+                        if prev_trip == *trip && dr == map.get_l(t.src).get_directed_parent() {
+                            Some((dr, entered))
+                        } else {
+                            None
+                        }
+                    }
+                }
+            }
+            // The car didn't cross the rest of the road
+            Event::CarReachedParkingSpot(car, _)
+            | Event::PersonLeavesMap(_, Some(AgentID::Car(car)), _) => {
+                self.entered.remove(car);
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The movements through traffic signals along a path, in order.
+pub(crate) fn signal_movements(path: &Path, map: &Map) -> Vec<MovementID> {
+    path.get_steps()
+        .iter()
+        .filter_map(|step| match step.as_traversable() {
+            Traversable::Turn(t) => map.get_movement_for_traffic_signal(t).map(|(id, _)| id),
+            Traversable::Lane(_) => None,
+        })
+        .collect()
+}
+
+/// Compares the movements through traffic signals on an old and new route, returning the ones
+/// only used by the old route and the ones only used by the new route. A route may use the same
+/// movement more than once.
+pub(crate) fn demand_changes(
+    old: Vec<MovementID>,
+    new: Vec<MovementID>,
+) -> (Vec<MovementID>, Vec<MovementID>) {
+    let mut counts: BTreeMap<MovementID, isize> = BTreeMap::new();
+    for id in old {
+        *counts.entry(id).or_insert(0) -= 1;
+    }
+    for id in new {
+        *counts.entry(id).or_insert(0) += 1;
+    }
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    for (id, count) in counts {
+        if count < 0 {
+            removed.extend(std::iter::repeat(id).take(count.unsigned_abs()));
+        } else {
+            added.extend(std::iter::repeat(id).take(count as usize));
+        }
+    }
+    (removed, added)
+}
+
 #[derive(Debug)]
 pub struct TripPhase {
     pub start_time: Time,
@@ -704,6 +1396,55 @@ pub struct TripPhase {
     pub phase_type: TripPhaseType,
 }
 
+/// How regularly and how crowded did a transit route run?
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TransitRouteSummary {
+    pub route: TransitRouteID,
+    /// The average gap between departures in the route's timetable. None if the route only runs
+    /// once.
+    pub scheduled_headway: Option<Duration>,
+    /// How many times a vehicle arrived at a stop after some earlier vehicle on the route
+    pub headways_measured: usize,
+    pub average_headway: Duration,
+    /// Arrivals less than half of the scheduled headway after the previous vehicle
+    pub bunched_arrivals: usize,
+    /// The most passengers aboard a vehicle leaving any stop
+    pub max_load: usize,
+    /// Somebody passed up by several full vehicles is counted each time
+    pub passengers_left_behind: usize,
+    /// Of the departures from stops following the imported timetable, the fraction between one
+    /// minute early and five minutes late. None if no vehicle has followed the timetable.
+    pub on_time_departures: Option<f64>,
+}
+
+/// How well did a ride-hail fleet serve its riders?
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RideHailSummary {
+    pub fleet_size: usize,
+    /// Riders dropped off
+    pub rides: usize,
+    /// How long riders waited to be picked up
+    pub average_wait: Duration,
+    pub max_wait: Duration,
+    /// Distance driven by the fleet with nobody on board
+    pub deadhead_distance: Distance,
+    /// Distance driven by the fleet with a rider
+    pub revenue_distance: Distance,
+    /// The fraction of the fleet's time spent serving riders, including driving to them
+    pub utilization: f64,
+}
+
+/// How much energy did vehicles use, and what did they emit?
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EmissionsSummary {
+    pub total: Emissions,
+    pub per_vehicle_type: BTreeMap<VehicleType, Emissions>,
+    pub per_road: Vec<(RoadID, Emissions)>,
+    pub per_intersection: Vec<(IntersectionID, Emissions)>,
+    /// Only trips driving their own car
+    pub per_trip: Vec<(TripID, Emissions)>,
+}
+
 /// See https://github.com/a-b-street/abstreet/issues/85
 #[derive(Clone, Serialize, Deserialize)]
 pub struct TimeSeriesCount<X: Ord + Clone> {
@@ -893,3 +1634,258 @@ impl SlidingWindow {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use map_model::{Direction, PathConstraints};
+
+    fn mvmnt(from: usize, to: usize) -> MovementID {
+        MovementID {
+            from: DirectedRoadID {
+                road: RoadID(from),
+                dir: Direction::Fwd,
+            },
+            to: DirectedRoadID {
+                road: RoadID(to),
+                dir: Direction::Fwd,
+            },
+            parent: IntersectionID(0),
+            crosswalk: false,
+        }
+    }
+
+    #[test]
+    fn test_demand_changes() {
+        let (a, b, c, d) = (mvmnt(0, 1), mvmnt(1, 2), mvmnt(2, 3), mvmnt(3, 4));
+
+        // Same route
+        assert_eq!(
+            demand_changes(vec![a, b], vec![a, b]),
+            (Vec::new(), Vec::new())
+        );
+
+        // The routes share a prefix and a suffix
+        assert_eq!(
+            demand_changes(vec![a, b, d], vec![a, c, d]),
+            (vec![b], vec![c])
+        );
+
+        // The old route looped through the same movement twice
+        assert_eq!(
+            demand_changes(vec![a, b, a], vec![a, c]),
+            (vec![a, b], vec![c])
+        );
+
+        assert_eq!(demand_changes(vec![a], Vec::new()), (vec![a], Vec::new()));
+        assert_eq!(demand_changes(Vec::new(), vec![a]), (Vec::new(), vec![a]));
+    }
+
+    #[test]
+    fn test_curb_occupancy_per_road() {
+        let map = Map::blank();
+        let van = CarID {
+            id: 0,
+            vehicle_type: VehicleType::Van,
+        };
+        let lane = |road, offset| LaneID {
+            road: RoadID(road),
+            offset,
+        };
+        let mut analytics = Analytics::new(true);
+        let time = Time::START_OF_DAY + Duration::hours(8);
+        for (l, dwell, blocked) in [
+            (lane(0, 1), Duration::minutes(5), 2),
+            (lane(0, 2), Duration::minutes(3), 0),
+            (lane(1, 1), Duration::minutes(5), 1),
+        ] {
+            analytics.event(Event::DeliveryUnloaded(van, l, dwell, blocked), time, &map);
+        }
+
+        // Both sides of a road are added up
+        let per_road = analytics.curb_occupancy_per_road();
+        assert_eq!(per_road.len(), 2);
+        assert_eq!(per_road[&RoadID(0)], (Duration::minutes(8), 2));
+        assert_eq!(per_road[&RoadID(1)], (Duration::minutes(5), 1));
+
+        // Nothing's recorded when analytics are off
+        let mut analytics = Analytics::new(false);
+        analytics.event(
+            Event::DeliveryUnloaded(van, lane(0, 1), Duration::minutes(5), 2),
+            time,
+            &map,
+        );
+        assert!(analytics.curb_occupancy_per_road().is_empty());
+    }
+
+    #[test]
+    fn test_transit_signal_priority_actions() {
+        let map = Map::blank();
+        let bus = CarID {
+            id: 0,
+            vehicle_type: VehicleType::Bus,
+        };
+        let route = TransitRouteID(3);
+        let start = Time::START_OF_DAY + Duration::hours(8);
+        let mut analytics = Analytics::new(true);
+        for (minutes, action) in [
+            (1, TransitPriorityAction::ExtendedGreen),
+            (2, TransitPriorityAction::TruncatedRed),
+            (3, TransitPriorityAction::TruncatedRed),
+            (10, TransitPriorityAction::ExtendedGreen),
+        ] {
+            analytics.event(
+                Event::TransitSignalPriority(bus, route, IntersectionID(minutes), action),
+                start + Duration::minutes(minutes),
+                &map,
+            );
+        }
+
+        assert_eq!(
+            analytics.transit_signal_priority_actions(route, start + Duration::minutes(5)),
+            (1, 2)
+        );
+        assert_eq!(
+            analytics.transit_signal_priority_actions(route, start + Duration::hours(1)),
+            (2, 2)
+        );
+        assert_eq!(
+            analytics
+                .transit_signal_priority_actions(TransitRouteID(4), start + Duration::hours(1)),
+            (0, 0)
+        );
+    }
+
+    fn stop(road: usize) -> TransitStopID {
+        abstutil::from_json(format!(r#"{{"road": {}, "idx": 0}}"#, road).as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_transit_route_summary() {
+        let map = Map::blank();
+        let bus = |id| CarID {
+            id,
+            vehicle_type: VehicleType::Bus,
+        };
+        let at = |h, m| Time::START_OF_DAY + Duration::hours(h) + Duration::minutes(m);
+        let (stop_a, stop_b) = (stop(1), stop(2));
+        let spawn_times = vec![at(8, 0), at(8, 10), at(8, 20)];
+        let route = TransitRoute {
+            id: TransitRouteID(0),
+            long_name: "Route 0".to_string(),
+            short_name: "0".to_string(),
+            gtfs_id: "0".to_string(),
+            stops: vec![stop_a, stop_b],
+            start: LaneID {
+                road: RoadID(0),
+                offset: 0,
+            },
+            end_border: None,
+            route_type: PathConstraints::Bus,
+            spawn_times: spawn_times.clone(),
+            orig_spawn_times: spawn_times,
+            timetable: Vec::new(),
+        };
+        let other_route = TransitRouteID(1);
+
+        let mut analytics = Analytics::new(true);
+        for (time, ev) in [
+            (at(8, 5), Event::BusArrivedAtStop(bus(0), route.id, stop_a)),
+            (
+                at(8, 6),
+                Event::BusDepartedFromStop(bus(0), route.id, stop_a, 12),
+            ),
+            (at(8, 10), Event::BusArrivedAtStop(bus(0), route.id, stop_b)),
+            (at(8, 15), Event::BusArrivedAtStop(bus(1), route.id, stop_a)),
+            (
+                at(8, 15),
+                Event::PassengersLeftBehind(bus(1), route.id, stop_a, 3),
+            ),
+            (
+                at(8, 16),
+                Event::BusDepartedFromStop(bus(1), route.id, stop_a, 30),
+            ),
+            // Another route serving the same stop doesn't count
+            (
+                at(8, 16),
+                Event::BusArrivedAtStop(bus(9), other_route, stop_a),
+            ),
+            (
+                at(8, 16),
+                Event::PassengersLeftBehind(bus(9), other_route, stop_a, 5),
+            ),
+            // Right behind the previous bus
+            (at(8, 17), Event::BusArrivedAtStop(bus(2), route.id, stop_a)),
+            (at(8, 20), Event::BusArrivedAtStop(bus(1), route.id, stop_b)),
+            (
+                at(8, 21),
+                Event::TransitScheduleDeviation(bus(1), route.id, stop_b, Duration::minutes(1)),
+            ),
+            (
+                at(8, 22),
+                Event::TransitScheduleDeviation(
+                    bus(2),
+                    route.id,
+                    stop_b,
+                    Duration::ZERO - Duration::minutes(2),
+                ),
+            ),
+        ] {
+            analytics.event(ev, time, &map);
+        }
+
+        let headways = analytics.transit_headways(route.id, at(8, 30));
+        assert_eq!(
+            headways[&stop_a],
+            vec![
+                (at(8, 15), Duration::minutes(10)),
+                (at(8, 17), Duration::minutes(2))
+            ]
+        );
+        assert_eq!(headways[&stop_b], vec![(at(8, 20), Duration::minutes(10))]);
+        // Only arrivals up to some time count
+        let headways = analytics.transit_headways(route.id, at(8, 16));
+        assert_eq!(headways[&stop_a], vec![(at(8, 15), Duration::minutes(10))]);
+        assert!(!headways.contains_key(&stop_b));
+        // The first vehicle at each stop has nobody to follow
+        assert!(analytics
+            .transit_headways(other_route, at(8, 30))
+            .is_empty());
+
+        let summary = analytics.transit_route_summary(&route, at(8, 30));
+        assert_eq!(summary.scheduled_headway, Some(Duration::minutes(10)));
+        assert_eq!(summary.headways_measured, 3);
+        assert_eq!(summary.average_headway, Duration::minutes(22) / 3.0);
+        // 2 minutes is less than half of the scheduled 10
+        assert_eq!(summary.bunched_arrivals, 1);
+        assert_eq!(summary.max_load, 30);
+        assert_eq!(summary.passengers_left_behind, 3);
+        // Leaving 2 minutes early isn't on time
+        assert_eq!(summary.on_time_departures, Some(0.5));
+
+        // Earlier, before the bunching
+        let summary = analytics.transit_route_summary(&route, at(8, 16));
+        assert_eq!(summary.headways_measured, 1);
+        assert_eq!(summary.bunched_arrivals, 0);
+        assert_eq!(summary.max_load, 30);
+        assert_eq!(summary.on_time_departures, None);
+    }
+
+    #[test]
+    fn test_level_of_service() {
+        // Nobody there
+        assert_eq!(LevelOfService::from_density(0.0), LevelOfService::A);
+        // 10 square meters per person
+        assert_eq!(LevelOfService::from_density(0.1), LevelOfService::A);
+        // 4
+        assert_eq!(LevelOfService::from_density(0.25), LevelOfService::B);
+        // 2.5
+        assert_eq!(LevelOfService::from_density(0.4), LevelOfService::C);
+        // 2
+        assert_eq!(LevelOfService::from_density(0.5), LevelOfService::D);
+        // 1
+        assert_eq!(LevelOfService::from_density(1.0), LevelOfService::E);
+        // 0.5
+        assert_eq!(LevelOfService::from_density(2.0), LevelOfService::F);
+    }
+}
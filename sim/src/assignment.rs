@@ -0,0 +1,396 @@
+//! Dynamic traffic assignment. Normally drivers pick a route once when their trip starts, using
+//! free-flow travel times, so everybody piles onto the same "fastest" roads. Instead, simulate the
+//! same day repeatedly. After each run, feed the travel times observed for every movement back
+//! into routing, and let some drivers switch to a faster route. Repeat until the routes settle
+//! into an equilibrium, where hardly anybody could do better by switching.
+
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+use serde::Serialize;
+use structopt::StructOpt;
+
+use abstutil::Timer;
+use geom::{Duration, Time};
+use map_model::{
+    vehicle_cost, DirectedRoadID, Map, MovementID, Path, PathConstraints, PathRequest, PathStep,
+    RoutingParams, Traversable,
+};
+use synthpop::Scenario;
+
+use crate::prebake::simulate_day;
+use crate::{AgentID, CarID, Event, PrebakeSummary, Sim, TripID, VehicleType};
+
+/// Options controlling iterative traffic assignment.
+#[derive(Clone, StructOpt)]
+pub struct AssignmentOptions {
+    /// The most times to simulate the day. The first run uses free-flow routes.
+    #[structopt(long, default_value = "5")]
+    pub max_iterations: usize,
+    /// After each run, this fraction of the drivers who could've found a faster route switch to
+    /// it. Must be between 0 and 1. Switching everybody at once just moves the congestion
+    /// somewhere else.
+    #[structopt(long, default_value = "0.2")]
+    pub reroute_fraction: f64,
+    /// Stop once the relative gap between the time drivers spent on their routes and the time
+    /// they would've spent on the best routes falls below this.
+    #[structopt(long, default_value = "0.02")]
+    pub max_gap: f64,
+    /// A seed for picking which drivers switch routes.
+    #[structopt(long, default_value = "42")]
+    pub rng_seed: u64,
+}
+
+impl Default for AssignmentOptions {
+    fn default() -> Self {
+        Self {
+            max_iterations: 5,
+            reroute_fraction: 0.2,
+            max_gap: 0.02,
+            rng_seed: 42,
+        }
+    }
+}
+
+/// What happened during one simulated day of traffic assignment.
+#[derive(Debug, Serialize)]
+pub struct AssignmentIteration {
+    /// Starting from 1
+    pub iteration: usize,
+    /// How many drivers used observed travel times to pick their route during this run
+    pub rerouted_trips: usize,
+    pub finished_trips: usize,
+    pub total_trip_duration_seconds: f64,
+    /// The extra time drivers spent on their routes, compared to the best routes given the
+    /// latest travel times, as a fraction of the total time. 0 means nobody could do better.
+    pub relative_gap: f64,
+}
+
+/// Repeatedly simulate a scenario, re-routing some drivers each time, until the relative gap is
+/// small enough or the maximum iterations are done. Returns the simulation from the final run.
+pub fn assign_traffic(
+    map: &Map,
+    scenario: &Scenario,
+    opts: &AssignmentOptions,
+    timer: &mut Timer,
+) -> (Sim, Vec<AssignmentIteration>) {
+    let mut rng = XorShiftRng::seed_from_u64(opts.rng_seed);
+    let mut choices = RouteChoices::default();
+    let mut delays: BTreeMap<MovementID, Duration> = BTreeMap::new();
+    let mut results = Vec::new();
+
+    let mut iteration = 1;
+    loop {
+        timer.start(format!("traffic assignment iteration {}", iteration));
+        let mut sim = simulate_day(map, scenario, timer, |sim| {
+            sim.set_route_choices(choices.clone());
+            sim.record_movement_times();
+        });
+        choices = sim.take_route_choices();
+        let observed = sim.take_movement_times().unwrap().delays(map);
+
+        // Average the delays over all runs (the method of successive averages). Movements nobody
+        // used in this run weren't congested.
+        let weight = 1.0 / (iteration as f64);
+        let mut averaged = BTreeMap::new();
+        for mvmnt in delays.keys().chain(observed.keys()) {
+            let old = delays.get(mvmnt).cloned().unwrap_or(Duration::ZERO);
+            let new = observed.get(mvmnt).cloned().unwrap_or(Duration::ZERO);
+            let delay = Duration::seconds(
+                old.inner_seconds() + weight * (new.inner_seconds() - old.inner_seconds()),
+            );
+            if delay > Duration::ZERO {
+                averaged.insert(*mvmnt, delay);
+            }
+        }
+        delays = averaged;
+
+        let mut params = map.routing_params().clone();
+        params.movement_delays = delays.clone();
+        let (relative_gap, could_improve) = measure_gap(&choices, &params, map);
+
+        let summary = PrebakeSummary::new(&sim, scenario);
+        info!(
+            "Traffic assignment iteration {}: {} trips re-routed, relative gap {:.4}",
+            iteration,
+            choices.per_trip.len(),
+            relative_gap
+        );
+        results.push(AssignmentIteration {
+            iteration,
+            rerouted_trips: choices.per_trip.len(),
+            finished_trips: summary.finished_trips,
+            total_trip_duration_seconds: summary.total_trip_duration_seconds,
+            relative_gap,
+        });
+        timer.stop(format!("traffic assignment iteration {}", iteration));
+
+        if relative_gap <= opts.max_gap || iteration >= opts.max_iterations {
+            return (sim, results);
+        }
+
+        // Drivers who don't switch keep using whatever costs they used before, so they'll find
+        // the same route again.
+        choices.versions.push(params);
+        let version = choices.versions.len() - 1;
+        for trip in could_improve {
+            if rng.gen_bool(opts.reroute_fraction) {
+                choices.per_trip.insert(trip, version);
+            }
+        }
+        choices.chosen.clear();
+        iteration += 1;
+    }
+}
+
+/// Which costs each driver uses to pick their route, and the routes they picked.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RouteChoices {
+    /// Routing params using the travel times observed through some iteration
+    versions: Vec<RoutingParams>,
+    /// Trips not listed here use the map's own routing params.
+    per_trip: BTreeMap<TripID, usize>,
+    /// The route each car trip picked, and when
+    chosen: BTreeMap<TripID, (PathRequest, Time, Vec<MovementID>)>,
+}
+
+impl RouteChoices {
+    pub fn pathfind(
+        &mut self,
+        trip: TripID,
+        req: PathRequest,
+        now: Time,
+        map: &Map,
+    ) -> Result<Path> {
+        if req.constraints != PathConstraints::Car {
+            return map.pathfind_at(req, now);
+        }
+        let path = match self.per_trip.get(&trip) {
+            Some(version) => {
+                map.pathfind_at_with_params(req.clone(), now, &self.versions[*version])
+            }
+            None => map.pathfind_at(req.clone(), now),
+        }?;
+        // Only the first driving leg of a trip matters
+        self.chosen
+            .entry(trip)
+            .or_insert_with(|| (req, now, path_movements(&path, map)));
+        Ok(path)
+    }
+}
+
+/// Records how long cars take to travel along a road and make a movement at the end of it.
+#[derive(Clone, Default)]
+pub(crate) struct MovementTimes {
+    /// When each car entered its current road
+    entered: HashMap<CarID, (TripID, DirectedRoadID, Time)>,
+    /// The total time and number of observations
+    totals: BTreeMap<MovementID, (Duration, usize)>,
+}
+
+impl MovementTimes {
+    pub fn handle_event(&mut self, time: Time, ev: &Event, map: &Map) {
+        if let Event::AgentEntersTraversable(
+            AgentID::Car(car),
+            Some(trip),
+            Traversable::Lane(l),
+            _,
+        ) = ev
+        {
+            if car.vehicle_type != VehicleType::Car {
+                return;
+            }
+            let dr = map.get_l(*l).get_directed_parent();
+            // Cars don't produce this event when they first appear partway along a lane, so this
+            // always measures the full length of a road. The same car may be used for a later
+            // trip, so don't match up events across trips.
+            if let Some((prev_trip, prev_dr, entered)) =
+                self.entered.insert(*car, (*trip, dr, time))
+            {
+                if prev_trip == *trip && prev_dr != dr && prev_dr.dst_i(map) == dr.src_i(map) {
+                    let mvmnt = MovementID {
+                        from: prev_dr,
+                        to: dr,
+                        parent: dr.src_i(map),
+                        crosswalk: false,
+                    };
+                    let total = self.totals.entry(mvmnt).or_insert((Duration::ZERO, 0));
+                    total.0 += time - entered;
+                    total.1 += 1;
+                }
+            }
+        }
+    }
+
+    /// How much longer each movement took on average, compared to its cost with free-flowing
+    /// traffic.
+    fn delays(&self, map: &Map) -> BTreeMap<MovementID, Duration> {
+        self.delays_compared_to(|mvmnt| {
+            if !map.get_i(mvmnt.parent).movements.contains_key(&mvmnt) {
+                return None;
+            }
+            // The unprotected turn penalty is already a guess at this delay, so only count
+            // anything beyond it.
+            vehicle_cost(
+                mvmnt.from,
+                mvmnt,
+                PathConstraints::Car,
+                map.routing_params(),
+                map,
+            )
+        })
+    }
+
+    /// Like `delays`, but with the free-flow cost of each movement supplied by the caller.
+    /// Movements without a free-flow cost are skipped.
+    fn delays_compared_to<F: Fn(MovementID) -> Option<Duration>>(
+        &self,
+        free_flow_cost: F,
+    ) -> BTreeMap<MovementID, Duration> {
+        let mut delays = BTreeMap::new();
+        for (mvmnt, (total, count)) in &self.totals {
+            if let Some(free_flow) = free_flow_cost(*mvmnt) {
+                let observed = *total / (*count as f64);
+                if observed > free_flow {
+                    delays.insert(*mvmnt, observed - free_flow);
+                }
+            }
+        }
+        delays
+    }
+}
+
+/// Compare the cost of the routes drivers chose against the best routes they could've taken,
+/// both using the latest travel times. Returns the relative gap and the trips that could do
+/// better.
+fn measure_gap(choices: &RouteChoices, params: &RoutingParams, map: &Map) -> (f64, Vec<TripID>) {
+    let mut costs = Vec::new();
+    for (trip, (req, time, movements)) in &choices.chosen {
+        let experienced = match route_cost(movements, params, map) {
+            Some(cost) => cost,
+            None => continue,
+        };
+        let best = match map
+            .pathfind_at_with_params(req.clone(), *time, params)
+            .ok()
+            .and_then(|path| route_cost(&path_movements(&path, map), params, map))
+        {
+            Some(cost) => cost,
+            None => continue,
+        };
+        costs.push((*trip, experienced, best));
+    }
+    relative_gap(costs)
+}
+
+/// Given the cost of each trip's route and the best route it could've taken, returns the relative
+/// gap and the trips that could do better.
+fn relative_gap(costs: Vec<(TripID, Duration, Duration)>) -> (f64, Vec<TripID>) {
+    let mut total_seconds = 0.0;
+    let mut excess_seconds = 0.0;
+    let mut could_improve = Vec::new();
+    for (trip, experienced, best) in costs {
+        total_seconds += experienced.inner_seconds();
+        if best < experienced {
+            excess_seconds += (experienced - best).inner_seconds();
+            // Ignore tiny differences from rounding
+            if experienced - best > Duration::seconds(1.0) {
+                could_improve.push(trip);
+            }
+        }
+    }
+    if total_seconds == 0.0 {
+        return (0.0, could_improve);
+    }
+    (excess_seconds / total_seconds, could_improve)
+}
+
+fn path_movements(path: &Path, map: &Map) -> Vec<MovementID> {
+    path.get_steps()
+        .iter()
+        .filter_map(|step| match step {
+            PathStep::Turn(t) => Some(t.to_movement(map)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn route_cost(movements: &[MovementID], params: &RoutingParams, map: &Map) -> Option<Duration> {
+    let mut cost = Duration::ZERO;
+    for mvmnt in movements {
+        cost += vehicle_cost(mvmnt.from, *mvmnt, PathConstraints::Car, params, map)?;
+    }
+    Some(cost)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use map_model::{Direction, IntersectionID, RoadID};
+
+    fn mvmnt(from: usize, to: usize) -> MovementID {
+        MovementID {
+            from: DirectedRoadID {
+                road: RoadID(from),
+                dir: Direction::Fwd,
+            },
+            to: DirectedRoadID {
+                road: RoadID(to),
+                dir: Direction::Fwd,
+            },
+            parent: IntersectionID(0),
+            crosswalk: false,
+        }
+    }
+
+    fn secs(s: f64) -> Duration {
+        Duration::seconds(s)
+    }
+
+    #[test]
+    fn test_delays() {
+        let mut times = MovementTimes::default();
+        // Two observations, averaging 30s
+        times.totals.insert(mvmnt(0, 1), (secs(60.0), 2));
+        // Faster than free-flow
+        times.totals.insert(mvmnt(0, 2), (secs(5.0), 1));
+        // Exactly free-flow
+        times.totals.insert(mvmnt(0, 3), (secs(10.0), 1));
+        // The movement no longer exists
+        times.totals.insert(mvmnt(0, 4), (secs(100.0), 1));
+
+        let delays = times.delays_compared_to(|m| {
+            if m.to.road == RoadID(4) {
+                None
+            } else {
+                Some(secs(10.0))
+            }
+        });
+        assert_eq!(delays.len(), 1);
+        assert_eq!(delays[&mvmnt(0, 1)], secs(20.0));
+    }
+
+    #[test]
+    fn test_relative_gap() {
+        assert_eq!(relative_gap(Vec::new()), (0.0, Vec::new()));
+
+        // Everybody's already on the best route
+        let (gap, could_improve) = relative_gap(vec![
+            (TripID(0), secs(100.0), secs(100.0)),
+            (TripID(1), secs(50.0), secs(50.0)),
+        ]);
+        assert_eq!(gap, 0.0);
+        assert!(could_improve.is_empty());
+
+        let (gap, could_improve) = relative_gap(vec![
+            (TripID(0), secs(100.0), secs(70.0)),
+            // Within rounding, so this trip won't switch, but still counts towards the gap
+            (TripID(1), secs(100.0), secs(99.5)),
+            (TripID(2), secs(200.0), secs(200.0)),
+        ]);
+        assert!((gap - 30.5 / 400.0).abs() < 1e-9);
+        assert_eq!(could_improve, vec![TripID(0)]);
+    }
+}
@@ -0,0 +1,530 @@
+//! Estimates the energy vehicles use and the pollution they produce, based on how they move
+//! through each road and turn. The factors are rough averages for a modern fleet, not calibrated
+//! for any particular city.
+
+use std::ops;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use geom::{Distance, Duration, Speed};
+
+use crate::{CarID, SimOptions, VehicleType};
+
+const GRAVITY: f64 = 9.81;
+const AIR_DENSITY: f64 = 1.2;
+const JOULES_PER_KWH: f64 = 3.6e6;
+/// Accelerating also spins up the wheels and drivetrain, which takes about this much more energy
+/// than the vehicle's mass alone.
+const ROTATING_MASS_FACTOR: f64 = 1.1;
+/// Electric motors and batteries, from the plug to the wheels
+const ELECTRIC_EFFICIENCY: f64 = 0.85;
+/// The fraction of braking or downhill energy an electric vehicle gets back
+const REGENERATION: f64 = 0.6;
+
+/// How to turn a vehicle's movement along one road or turn into energy use.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum EmissionsModel {
+    /// In the style of COPERT: energy per kilometer is a curve of the average speed, including
+    /// any time spent stopped, plus the work to climb any incline.
+    AverageSpeed,
+    /// In the style of MOVES: the power needed to overcome rolling resistance, drag, and the
+    /// incline at cruising speed, plus accelerating from a stop and idling while stopped.
+    TractivePower,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Powertrain {
+    Petrol,
+    Diesel,
+    Electric,
+}
+
+/// The share of one class of vehicle using each powertrain.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FleetMix {
+    pub petrol: f64,
+    pub diesel: f64,
+    pub electric: f64,
+}
+
+impl FleetMix {
+    /// Parses something like "petrol=0.6,diesel=0.3,electric=0.1". Missing powertrains have no
+    /// share, and the rest are scaled to sum to 1.
+    pub fn parse(x: &str) -> Result<FleetMix> {
+        let mut mix = FleetMix {
+            petrol: 0.0,
+            diesel: 0.0,
+            electric: 0.0,
+        };
+        for part in x.split(',') {
+            let (key, value) = match part.split_once('=') {
+                Some(pair) => pair,
+                None => bail!("Bad fleet mix {}, expected something like petrol=0.5", part),
+            };
+            let share: f64 = value.parse()?;
+            if share < 0.0 {
+                bail!("Bad fleet mix {}, shares can't be negative", part);
+            }
+            match key {
+                "petrol" => mix.petrol = share,
+                "diesel" => mix.diesel = share,
+                "electric" => mix.electric = share,
+                _ => bail!("Bad fleet mix {}. Must be petrol|diesel|electric", key),
+            }
+        }
+        let total = mix.petrol + mix.diesel + mix.electric;
+        if total == 0.0 {
+            bail!("Fleet mix {} doesn't include any vehicles", x);
+        }
+        mix.petrol /= total;
+        mix.diesel /= total;
+        mix.electric /= total;
+        Ok(mix)
+    }
+
+    /// Deterministically picks a powertrain for one vehicle, so that the whole fleet matches the
+    /// shares.
+    fn pick(&self, id: CarID) -> Powertrain {
+        // Multiples of the golden ratio spread consecutive IDs evenly over [0, 1)
+        let x = ((id.id as f64) * 0.618_033_988_749_895).fract();
+        if x < self.petrol {
+            Powertrain::Petrol
+        } else if x < self.petrol + self.diesel {
+            Powertrain::Diesel
+        } else {
+            Powertrain::Electric
+        }
+    }
+}
+
+/// Energy used and pollutants emitted by vehicles. Only tailpipe emissions are counted, except
+/// that PM2.5 also includes brake and tyre wear, which electric vehicles produce too. Electricity
+/// may be negative when an electric vehicle recovers more energy than it uses going downhill.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Emissions {
+    pub fuel_liters: f64,
+    pub electricity_kwh: f64,
+    pub co2_grams: f64,
+    pub nox_grams: f64,
+    pub pm25_grams: f64,
+}
+
+impl Emissions {
+    pub const ZERO: Emissions = Emissions {
+        fuel_liters: 0.0,
+        electricity_kwh: 0.0,
+        co2_grams: 0.0,
+        nox_grams: 0.0,
+        pm25_grams: 0.0,
+    };
+
+    pub fn get(&self, pollutant: Pollutant) -> f64 {
+        match pollutant {
+            Pollutant::Fuel => self.fuel_liters,
+            Pollutant::Electricity => self.electricity_kwh,
+            Pollutant::CO2 => self.co2_grams,
+            Pollutant::NOx => self.nox_grams,
+            Pollutant::PM25 => self.pm25_grams,
+        }
+    }
+}
+
+impl ops::Add for Emissions {
+    type Output = Emissions;
+
+    fn add(self, other: Emissions) -> Emissions {
+        Emissions {
+            fuel_liters: self.fuel_liters + other.fuel_liters,
+            electricity_kwh: self.electricity_kwh + other.electricity_kwh,
+            co2_grams: self.co2_grams + other.co2_grams,
+            nox_grams: self.nox_grams + other.nox_grams,
+            pm25_grams: self.pm25_grams + other.pm25_grams,
+        }
+    }
+}
+
+impl ops::AddAssign for Emissions {
+    fn add_assign(&mut self, other: Emissions) {
+        *self = *self + other;
+    }
+}
+
+/// One of the quantities tracked by Emissions
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Pollutant {
+    Fuel,
+    Electricity,
+    CO2,
+    NOx,
+    PM25,
+}
+
+impl Pollutant {
+    pub fn all() -> Vec<Pollutant> {
+        vec![
+            Pollutant::CO2,
+            Pollutant::NOx,
+            Pollutant::PM25,
+            Pollutant::Fuel,
+            Pollutant::Electricity,
+        ]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Pollutant::Fuel => "fuel",
+            Pollutant::Electricity => "electricity",
+            Pollutant::CO2 => "CO2",
+            Pollutant::NOx => "NOx",
+            Pollutant::PM25 => "PM2.5",
+        }
+    }
+
+    pub fn unit(self) -> &'static str {
+        match self {
+            Pollutant::Fuel => "liters",
+            Pollutant::Electricity => "kWh",
+            Pollutant::CO2 | Pollutant::NOx | Pollutant::PM25 => "grams",
+        }
+    }
+}
+
+/// Physical properties of one class of vehicle.
+struct VehicleClass {
+    mass_kg: f64,
+    rolling_resistance: f64,
+    /// Drag coefficient times frontal area, in m^2
+    drag_area: f64,
+    /// For the average-speed model, the energy at the wheels per km is `a / v + b + c * v^2`, with
+    /// the speed in km/h.
+    speed_curve: (f64, f64, f64),
+    /// While stopped, the power burned by an idling engine, in kW of fuel
+    idle_kw: f64,
+    /// While stopped, the power drawn by an electric vehicle for heating, doors, etc, in kW
+    auxiliary_kw: f64,
+    /// Brake, tyre, and road wear
+    non_exhaust_pm25_per_km: f64,
+}
+
+fn vehicle_class(vehicle_type: VehicleType) -> VehicleClass {
+    match vehicle_type {
+        VehicleType::Car => VehicleClass {
+            mass_kg: 1500.0,
+            rolling_resistance: 0.012,
+            drag_area: 0.7,
+            speed_curve: (1.0, 0.05, 0.000_006),
+            idle_kw: 5.0,
+            auxiliary_kw: 0.5,
+            non_exhaust_pm25_per_km: 0.011,
+        },
+        VehicleType::Bus => VehicleClass {
+            mass_kg: 13_000.0,
+            rolling_resistance: 0.008,
+            drag_area: 6.0,
+            speed_curve: (8.0, 0.4, 0.000_04),
+            idle_kw: 25.0,
+            auxiliary_kw: 5.0,
+            non_exhaust_pm25_per_km: 0.05,
+        },
+        VehicleType::Train => VehicleClass {
+            mass_kg: 45_000.0,
+            rolling_resistance: 0.002,
+            drag_area: 8.0,
+            speed_curve: (10.0, 0.6, 0.000_05),
+            idle_kw: 0.0,
+            auxiliary_kw: 10.0,
+            non_exhaust_pm25_per_km: 0.03,
+        },
+        VehicleType::Truck => VehicleClass {
+            mass_kg: 18_000.0,
+            rolling_resistance: 0.008,
+            drag_area: 5.5,
+            speed_curve: (8.0, 0.4, 0.000_04),
+            idle_kw: 20.0,
+            auxiliary_kw: 2.0,
+            non_exhaust_pm25_per_km: 0.06,
+        },
+        VehicleType::Van => VehicleClass {
+            mass_kg: 2500.0,
+            rolling_resistance: 0.012,
+            drag_area: 1.2,
+            speed_curve: (1.5, 0.08, 0.000_01),
+            idle_kw: 7.0,
+            auxiliary_kw: 0.7,
+            non_exhaust_pm25_per_km: 0.015,
+        },
+        VehicleType::Motorcycle => VehicleClass {
+            mass_kg: 250.0,
+            rolling_resistance: 0.015,
+            drag_area: 0.4,
+            speed_curve: (0.3, 0.02, 0.000_003),
+            idle_kw: 1.5,
+            auxiliary_kw: 0.1,
+            non_exhaust_pm25_per_km: 0.004,
+        },
+        VehicleType::Bike | VehicleType::Scooter => unreachable!(),
+    }
+}
+
+/// Properties of a fuel burned in an engine.
+struct Fuel {
+    /// From the fuel to the wheels, averaged over city driving
+    efficiency: f64,
+    kwh_per_liter: f64,
+    co2_grams_per_liter: f64,
+    nox_grams_per_liter: f64,
+    pm25_grams_per_liter: f64,
+}
+
+fn fuel(powertrain: Powertrain) -> Fuel {
+    match powertrain {
+        Powertrain::Petrol => Fuel {
+            efficiency: 0.22,
+            kwh_per_liter: 8.9,
+            co2_grams_per_liter: 2310.0,
+            nox_grams_per_liter: 1.0,
+            pm25_grams_per_liter: 0.04,
+        },
+        Powertrain::Diesel => Fuel {
+            efficiency: 0.28,
+            kwh_per_liter: 10.0,
+            co2_grams_per_liter: 2650.0,
+            nox_grams_per_liter: 7.0,
+            pm25_grams_per_liter: 0.05,
+        },
+        Powertrain::Electric => unreachable!(),
+    }
+}
+
+/// Assigns powertrains to vehicles and estimates their emissions.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct EmissionsCalculator {
+    model: EmissionsModel,
+    car_mix: FleetMix,
+    bus_mix: FleetMix,
+}
+
+impl EmissionsCalculator {
+    /// None unless the simulation estimates emissions at all.
+    pub fn new(opts: &SimOptions) -> Option<EmissionsCalculator> {
+        Some(EmissionsCalculator {
+            model: opts.emissions_model?,
+            car_mix: opts.car_fleet_mix,
+            bus_mix: opts.bus_fleet_mix,
+        })
+    }
+
+    /// Bikes and e-scooters don't have a powertrain; the scooter's battery is negligible. Trucks
+    /// are diesel, motorcycles petrol, and trains always electric.
+    pub fn powertrain(&self, id: CarID) -> Option<Powertrain> {
+        match id.vehicle_type {
+            VehicleType::Car | VehicleType::Van => Some(self.car_mix.pick(id)),
+            VehicleType::Bus => Some(self.bus_mix.pick(id)),
+            VehicleType::Truck => Some(Powertrain::Diesel),
+            VehicleType::Motorcycle => Some(Powertrain::Petrol),
+            VehicleType::Train => Some(Powertrain::Electric),
+            VehicleType::Bike | VehicleType::Scooter => None,
+        }
+    }
+
+    /// A vehicle covered some distance of a road or turn over some time. Without stopping, it
+    /// would've moved at `speed` the entire time, so the rest of the time was spent idling.
+    /// `incline` is the rise over run, negative for downhill. Returns None for bikes.
+    pub fn calculate(
+        &self,
+        id: CarID,
+        dist: Distance,
+        elapsed: Duration,
+        speed: Speed,
+        incline: f64,
+    ) -> Option<Emissions> {
+        let powertrain = self.powertrain(id)?;
+        let class = vehicle_class(id.vehicle_type);
+        let meters = dist.inner_meters().max(0.0);
+        let seconds = elapsed.inner_seconds().max(0.0);
+        let v = speed.inner_meters_per_second();
+        let moving = if v > 0.0 { meters / v } else { 0.0 };
+        let idle_seconds = (seconds - moving).max(0.0);
+        let climb = class.mass_kg * GRAVITY * incline * meters;
+
+        // Energy needed at the wheels, in joules. Negative when braking or going downhill.
+        let (wheels, idle_seconds) = match self.model {
+            EmissionsModel::AverageSpeed => {
+                if meters == 0.0 || seconds == 0.0 {
+                    return Some(Emissions::ZERO);
+                }
+                let kmh = (3.6 * meters / seconds).max(5.0).min(130.0);
+                let (a, b, c) = class.speed_curve;
+                let per_km = a / kmh + b + c * kmh.powi(2);
+                // Stops are already part of the average speed
+                (per_km * JOULES_PER_KWH * meters / 1000.0 + climb, 0.0)
+            }
+            EmissionsModel::TractivePower => {
+                let rolling = class.mass_kg * GRAVITY * class.rolling_resistance * meters;
+                let drag = 0.5 * AIR_DENSITY * class.drag_area * v.powi(2) * meters;
+                let mut total = rolling + drag + climb;
+                if idle_seconds > 0.0 {
+                    // Assume the vehicle came to a full stop and had to accelerate again
+                    total += ROTATING_MASS_FACTOR * 0.5 * class.mass_kg * v.powi(2);
+                }
+                (total, idle_seconds)
+            }
+        };
+
+        let mut result = Emissions::ZERO;
+        result.pm25_grams = class.non_exhaust_pm25_per_km * meters / 1000.0;
+        if powertrain == Powertrain::Electric {
+            let joules = if wheels > 0.0 {
+                wheels / ELECTRIC_EFFICIENCY
+            } else {
+                wheels * REGENERATION
+            };
+            result.electricity_kwh =
+                joules / JOULES_PER_KWH + class.auxiliary_kw * idle_seconds / 3600.0;
+        } else {
+            let fuel = fuel(powertrain);
+            // Engines can't recover energy, so coasting downhill is free
+            let kwh = wheels.max(0.0) / fuel.efficiency / JOULES_PER_KWH
+                + class.idle_kw * idle_seconds / 3600.0;
+            result.fuel_liters = kwh / fuel.kwh_per_liter;
+            result.co2_grams = result.fuel_liters * fuel.co2_grams_per_liter;
+            result.nox_grams = result.fuel_liters * fuel.nox_grams_per_liter;
+            result.pm25_grams += result.fuel_liters * fuel.pm25_grams_per_liter;
+        }
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calculator(model: EmissionsModel, car_mix: &str) -> EmissionsCalculator {
+        EmissionsCalculator {
+            model,
+            car_mix: FleetMix::parse(car_mix).unwrap(),
+            bus_mix: FleetMix::parse("diesel=1").unwrap(),
+        }
+    }
+
+    fn car(id: usize, vehicle_type: VehicleType) -> CarID {
+        CarID { id, vehicle_type }
+    }
+
+    #[test]
+    fn test_parse_fleet_mix() {
+        let mix = FleetMix::parse("petrol=0.6,diesel=0.3,electric=0.1").unwrap();
+        assert!((mix.petrol - 0.6).abs() < 1e-9);
+        assert!((mix.diesel - 0.3).abs() < 1e-9);
+        assert!((mix.electric - 0.1).abs() < 1e-9);
+
+        // Shares are normalized, and missing powertrains get nothing
+        let mix = FleetMix::parse("petrol=3,electric=1").unwrap();
+        assert!((mix.petrol - 0.75).abs() < 1e-9);
+        assert_eq!(mix.diesel, 0.0);
+        assert!((mix.electric - 0.25).abs() < 1e-9);
+
+        assert!(FleetMix::parse("petrol").is_err());
+        assert!(FleetMix::parse("hydrogen=1").is_err());
+        assert!(FleetMix::parse("petrol=-1,diesel=2").is_err());
+        assert!(FleetMix::parse("petrol=0").is_err());
+        assert!(FleetMix::parse("petrol=lots").is_err());
+    }
+
+    #[test]
+    fn test_pick_matches_shares() {
+        let mix = FleetMix::parse("petrol=0.6,diesel=0.3,electric=0.1").unwrap();
+        let mut counts = [0; 3];
+        for id in 0..1000 {
+            match mix.pick(car(id, VehicleType::Car)) {
+                Powertrain::Petrol => counts[0] += 1,
+                Powertrain::Diesel => counts[1] += 1,
+                Powertrain::Electric => counts[2] += 1,
+            }
+        }
+        assert!((580..=620).contains(&counts[0]), "{:?}", counts);
+        assert!((280..=320).contains(&counts[1]), "{:?}", counts);
+        assert!((80..=120).contains(&counts[2]), "{:?}", counts);
+
+        // The same vehicle always gets the same powertrain
+        let id = car(42, VehicleType::Car);
+        assert_eq!(mix.pick(id), mix.pick(id));
+    }
+
+    #[test]
+    fn test_calculate() {
+        let dist = Distance::meters(1000.0);
+        let speed = Speed::meters_per_second(10.0);
+        let cruise = Duration::seconds(100.0);
+
+        for model in [EmissionsModel::AverageSpeed, EmissionsModel::TractivePower] {
+            let petrol = calculator(model, "petrol=1");
+            assert_eq!(
+                petrol.calculate(car(0, VehicleType::Bike), dist, cruise, speed, 0.0),
+                None
+            );
+            assert_eq!(
+                petrol.calculate(car(0, VehicleType::Scooter), dist, cruise, speed, 0.0),
+                None
+            );
+
+            let flat = petrol
+                .calculate(car(0, VehicleType::Car), dist, cruise, speed, 0.0)
+                .unwrap();
+            assert!(flat.fuel_liters > 0.0);
+            assert_eq!(flat.electricity_kwh, 0.0);
+            assert!((flat.co2_grams - flat.fuel_liters * 2310.0).abs() < 1e-6);
+            // Tailpipe plus brake and tyre wear
+            assert!(flat.pm25_grams > 0.011);
+
+            // Climbing and stopping both burn more fuel
+            let uphill = petrol
+                .calculate(car(0, VehicleType::Car), dist, cruise, speed, 0.05)
+                .unwrap();
+            assert!(uphill.fuel_liters > flat.fuel_liters);
+            let stop_and_go = petrol
+                .calculate(car(0, VehicleType::Car), dist, cruise * 2.0, speed, 0.0)
+                .unwrap();
+            assert!(stop_and_go.fuel_liters > flat.fuel_liters);
+
+            // Heavier vehicles burn more
+            let truck = petrol
+                .calculate(car(0, VehicleType::Truck), dist, cruise, speed, 0.0)
+                .unwrap();
+            assert!(truck.fuel_liters > flat.fuel_liters);
+            assert!(truck.nox_grams > flat.nox_grams);
+
+            // Electric vehicles have no tailpipe, and recover energy going steeply downhill
+            let electric = calculator(model, "electric=1");
+            let ev = electric
+                .calculate(car(0, VehicleType::Car), dist, cruise, speed, 0.0)
+                .unwrap();
+            assert!(ev.electricity_kwh > 0.0);
+            assert_eq!(ev.fuel_liters, 0.0);
+            assert_eq!(ev.co2_grams, 0.0);
+            let downhill = electric
+                .calculate(car(0, VehicleType::Car), dist, cruise, speed, -0.1)
+                .unwrap();
+            assert!(downhill.electricity_kwh < 0.0);
+
+            // But engines can't, so coasting downhill is free
+            let coasting = petrol
+                .calculate(car(0, VehicleType::Car), dist, cruise, speed, -0.1)
+                .unwrap();
+            assert_eq!(coasting.fuel_liters, 0.0);
+        }
+
+        // Not moving at all
+        assert_eq!(
+            calculator(EmissionsModel::AverageSpeed, "petrol=1").calculate(
+                car(0, VehicleType::Car),
+                Distance::ZERO,
+                Duration::ZERO,
+                speed,
+                0.0
+            ),
+            Some(Emissions::ZERO)
+        );
+    }
+}
@@ -1,13 +1,15 @@
 use serde::{Deserialize, Serialize};
 
-use geom::Duration;
+use geom::{Distance, Duration};
 use map_model::{
-    BuildingID, IntersectionID, LaneID, Map, Path, PathRequest, TransitRouteID, TransitStopID,
-    Traversable, TurnID,
+    BuildingID, IntersectionID, LaneID, Map, MovementID, Path, PathRequest, RoadID, TransitRouteID,
+    TransitStopID, Traversable, TurnID,
 };
 use synthpop::TripMode;
 
-use crate::{AgentID, CarID, ParkingSpot, PedestrianID, PersonID, Problem, TripID};
+use crate::{
+    AgentID, CarID, Emissions, ParkingSpot, PedestrianID, PersonID, Problem, SafetyConflict, TripID,
+};
 
 /// As a simulation runs, different systems emit Events. This cleanly separates the internal
 /// mechanics of the simulation from consumers that just want to know what's happening.
@@ -23,10 +25,22 @@ pub enum Event {
     CarLeftParkingSpot(CarID, ParkingSpot),
 
     BusArrivedAtStop(CarID, TransitRouteID, TransitStopID),
-    BusDepartedFromStop(CarID, TransitRouteID, TransitStopID),
+    /// With this many passengers aboard
+    BusDepartedFromStop(CarID, TransitRouteID, TransitStopID, usize),
     /// How long waiting at the stop?
     PassengerBoardsTransit(PersonID, CarID, TransitRouteID, TransitStopID, Duration),
     PassengerAlightsTransit(PersonID, CarID, TransitRouteID, TransitStopID),
+    /// This many people waiting for the route couldn't board, because the vehicle was full
+    PassengersLeftBehind(CarID, TransitRouteID, TransitStopID, usize),
+    /// How late a vehicle following the route's timetable departed a stop. Negative if early.
+    TransitScheduleDeviation(CarID, TransitRouteID, TransitStopID, Duration),
+
+    /// A ride-hail vehicle was assigned to pick somebody up
+    RideHailDispatched(CarID, TripID),
+    /// How long did the rider wait, and how far did the vehicle drive empty to reach them?
+    RideHailPickup(CarID, TripID, Duration, Distance),
+    /// How far did the rider travel?
+    RideHailDropoff(CarID, TripID, Distance),
 
     PersonEntersBuilding(PersonID, BuildingID),
     PersonLeavesBuilding(PersonID, BuildingID),
@@ -37,14 +51,28 @@ pub enum Event {
     PedReachedParkingSpot(PedestrianID, ParkingSpot),
 
     BikeStoppedAtSidewalk(CarID, LaneID),
+    /// A freight vehicle finished unloading on the curb of this lane after this long, with this
+    /// many vehicles stuck behind it
+    DeliveryUnloaded(CarID, LaneID, Duration, usize),
 
     ProblemEncountered(TripID, Problem),
+    /// How many people per square meter were on a sidewalk or crosswalk when somebody started
+    /// walking along it, including them. Only measured when pedestrian crowding is simulated.
+    PedestrianDensityMeasured(Traversable, f64),
+    /// Two agents used conflicting turns very close together in time
+    SafetyConflict(SafetyConflict),
 
     /// If the agent is a transit vehicle, then include a count of how many passengers are on
     /// board.
     AgentEntersTraversable(AgentID, Option<TripID>, Traversable, Option<usize>),
     /// TripID, TurnID (Where the delay was encountered), Time spent waiting at that turn
     IntersectionDelayMeasured(TripID, TurnID, AgentID, Duration),
+    /// Like IntersectionDelayMeasured, but for transit vehicles at traffic signals
+    TransitDelayedAtSignal(CarID, TransitRouteID, TurnID, Duration),
+    /// A traffic signal changed its timing for a transit vehicle with signal priority
+    TransitSignalPriority(CarID, TransitRouteID, IntersectionID, TransitPriorityAction),
+    /// A vehicle left a lane or turn, or vanished partway along it, after using this much energy
+    VehicleEmissions(CarID, Option<TripID>, Traversable, Emissions),
 
     TripFinished {
         trip: TripID,
@@ -58,6 +86,20 @@ pub enum Event {
     /// Just use for parking replanning. Not happy about copying the full path in here, but the way
     /// to plumb info into Analytics is Event.
     PathAmended(Path),
+    /// A driver switched to a faster route partway through their trip, while on this road. Rather
+    /// than copying both paths, only the change in demand at traffic signals is recorded.
+    Rerouted {
+        car: CarID,
+        trip: Option<TripID>,
+        road: RoadID,
+        time_saved: Duration,
+        /// Movements through traffic signals that the rest of the old route used, but the new
+        /// route doesn't
+        demand_removed: Vec<MovementID>,
+        /// Movements through traffic signals that the new route uses, but the rest of the old
+        /// route didn't
+        demand_added: Vec<MovementID>,
+    },
 
     Alert(AlertLocation, String),
 }
@@ -70,6 +112,13 @@ pub enum AlertLocation {
     Building(BuildingID),
 }
 
+/// How a traffic signal responded to an approaching transit vehicle with signal priority
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum TransitPriorityAction {
+    ExtendedGreen,
+    TruncatedRed,
+}
+
 #[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub enum TripPhaseType {
     Driving,
@@ -79,6 +128,10 @@ pub enum TripPhaseType {
     WaitingForBus(TransitRouteID, TransitStopID),
     /// What stop did they board at?
     RidingBus(TransitRouteID, TransitStopID, CarID),
+    WaitingForCarpool,
+    /// Whose car are they riding in?
+    RidingInCar(CarID),
+    WaitingForRideHail,
     Cancelled,
     Finished,
     DelayedStart,
@@ -97,6 +150,9 @@ impl TripPhaseType {
             TripPhaseType::RidingBus(r, _, _) => {
                 format!("Riding route {}", map.get_tr(r).long_name)
             }
+            TripPhaseType::WaitingForCarpool => "Waiting to be picked up".to_string(),
+            TripPhaseType::RidingInCar(car) => format!("Riding in {}", car),
+            TripPhaseType::WaitingForRideHail => "Waiting for a ride-hail vehicle".to_string(),
             TripPhaseType::Cancelled => "Trip was cancelled due to some bug".to_string(),
             TripPhaseType::Finished => "Trip finished".to_string(),
             TripPhaseType::DelayedStart => "Delayed by a previous trip taking too long".to_string(),
@@ -7,7 +7,7 @@
 //!
 //! Helpful terminology:
 //! - sov = single occupancy vehicle, a car with just a driver and no passengers. (Car passengers
-//!   are not currently modelled)
+//!   only exist for trips explicitly grouped into a carpool)
 
 // Disable some noisy clippy warnings
 #![allow(clippy::type_complexity, clippy::too_many_arguments)]
@@ -27,17 +27,26 @@ use map_model::{
     BuildingID, IntersectionID, LaneID, Map, ParkingLotID, Path, PathConstraints, Position,
     TransitRouteID, TransitStopID,
 };
-use synthpop::TripEndpoint;
+use synthpop::{TripEndpoint, VehicleKind};
 
 pub use crate::render::{
     CarStatus, DrawCarInput, DrawPedCrowdInput, DrawPedestrianInput, Intent, PedCrowdLocation,
     UnzoomedAgent,
 };
 
-pub use self::analytics::{Analytics, Problem, ProblemType, SlidingWindow, TripPhase};
+pub(crate) use self::analytics::RoadCrossings;
+pub use self::analytics::{
+    Analytics, EmissionsSummary, LevelOfService, Problem, ProblemType, RideHailSummary,
+    SlidingWindow, TransitRouteSummary, TripPhase,
+};
+pub(crate) use self::assignment::{MovementTimes, RouteChoices};
+pub(crate) use self::emissions::EmissionsCalculator;
+pub use self::emissions::{Emissions, EmissionsModel, FleetMix, Pollutant, Powertrain};
 pub(crate) use self::events::Event;
-pub use self::events::{AlertLocation, TripPhaseType};
-pub use self::make::{fork_rng, BorderSpawnOverTime, ScenarioGenerator, SimFlags, SpawnOverTime};
+pub use self::events::{AlertLocation, TransitPriorityAction, TripPhaseType};
+pub use self::make::{
+    fork_rng, BorderSpawnOverTime, FreightGenerator, ScenarioGenerator, SimFlags, SpawnOverTime,
+};
 pub(crate) use self::make::{StartTripArgs, TripSpec};
 pub(crate) use self::mechanics::{
     DrivingSimState, IntersectionSimState, ParkingSim, ParkingSimState, WalkingSimState,
@@ -45,7 +54,10 @@ pub(crate) use self::mechanics::{
 pub(crate) use self::pandemic::PandemicModel;
 pub use self::prebake::PrebakeSummary;
 pub(crate) use self::recorder::TrafficRecorder;
+pub use self::ride_hail::DispatchPolicy;
+pub(crate) use self::ride_hail::{Dispatched, RideHailSimState, RideHailStop};
 pub(crate) use self::router::{ActionAtEnd, Router};
+pub use self::safety::{ConflictSummary, SafetyConflict};
 pub(crate) use self::scheduler::{Command, Scheduler};
 pub use self::sim::{
     count_parked_cars_per_bldg, rand_dist, AgentProperties, AlertHandler, DelayCause, Sim,
@@ -56,6 +68,8 @@ pub use self::trips::{CommutersVehiclesCounts, Person, PersonState, TripInfo, Tr
 pub(crate) use self::trips::{TripLeg, TripManager};
 
 mod analytics;
+pub mod assignment;
+mod emissions;
 mod events;
 mod make;
 mod mechanics;
@@ -63,7 +77,9 @@ mod pandemic;
 pub mod prebake;
 mod recorder;
 mod render;
+mod ride_hail;
 mod router;
+mod safety;
 mod scheduler;
 mod sim;
 mod transit;
@@ -73,9 +89,19 @@ mod trips;
 pub(crate) const BIKE_LENGTH: Distance = Distance::const_meters(1.8);
 pub(crate) const MIN_CAR_LENGTH: Distance = Distance::const_meters(4.5);
 pub(crate) const MAX_CAR_LENGTH: Distance = Distance::const_meters(6.5);
+pub(crate) const MIN_VAN_LENGTH: Distance = Distance::const_meters(5.0);
+pub(crate) const MAX_VAN_LENGTH: Distance = Distance::const_meters(7.0);
+/// From a rigid delivery truck to an articulated lorry
+pub(crate) const MIN_TRUCK_LENGTH: Distance = Distance::const_meters(10.0);
+pub(crate) const MAX_TRUCK_LENGTH: Distance = Distance::const_meters(16.5);
+pub(crate) const MOTORCYCLE_LENGTH: Distance = Distance::const_meters(2.2);
+pub(crate) const SCOOTER_LENGTH: Distance = Distance::const_meters(1.2);
 // Note this is more than MAX_CAR_LENGTH
 pub(crate) const BUS_LENGTH: Distance = Distance::const_meters(12.5);
 pub(crate) const LIGHT_RAIL_LENGTH: Distance = Distance::const_meters(60.0);
+/// How many passengers fit in a transit vehicle, seated and standing
+pub(crate) const BUS_CAPACITY: usize = 80;
+pub(crate) const LIGHT_RAIL_CAPACITY: usize = 250;
 
 /// At all speeds (including at rest), cars must be at least this far apart, measured from front of
 /// one car to the back of the other.
@@ -105,6 +131,10 @@ impl fmt::Display for CarID {
             VehicleType::Bus => write!(f, "Bus #{}", self.id),
             VehicleType::Train => write!(f, "Train #{}", self.id),
             VehicleType::Bike => write!(f, "Bike #{}", self.id),
+            VehicleType::Truck => write!(f, "Truck #{}", self.id),
+            VehicleType::Van => write!(f, "Van #{}", self.id),
+            VehicleType::Motorcycle => write!(f, "Motorcycle #{}", self.id),
+            VehicleType::Scooter => write!(f, "Scooter #{}", self.id),
         }
     }
 }
@@ -130,6 +160,8 @@ pub enum AgentID {
     Pedestrian(PedestrianID),
     // TODO Rename...
     BusPassenger(PersonID, CarID),
+    /// Riding in a car driven by somebody else, as part of a carpool or ride-hail trip
+    CarPassenger(PersonID, CarID),
 }
 
 impl AgentID {
@@ -140,16 +172,22 @@ impl AgentID {
         }
     }
 
+    /// Vehicles group by how they use the road, like `VehicleType::to_constraints`, except trucks
+    /// still count as cars. Use `to_vehicle_type` to tell them apart.
     pub fn to_type(self) -> AgentType {
         match self {
             AgentID::Car(c) => match c.vehicle_type {
-                VehicleType::Car => AgentType::Car,
-                VehicleType::Bike => AgentType::Bike,
+                VehicleType::Car
+                | VehicleType::Truck
+                | VehicleType::Van
+                | VehicleType::Motorcycle => AgentType::Car,
+                VehicleType::Bike | VehicleType::Scooter => AgentType::Bike,
                 VehicleType::Bus => AgentType::Bus,
                 VehicleType::Train => AgentType::Train,
             },
             AgentID::Pedestrian(_) => AgentType::Pedestrian,
             AgentID::BusPassenger(_, _) => AgentType::TransitRider,
+            AgentID::CarPassenger(_, _) => AgentType::CarPassenger,
         }
     }
 
@@ -157,7 +195,7 @@ impl AgentID {
         match self {
             AgentID::Car(c) => Some(c.vehicle_type),
             AgentID::Pedestrian(_) => None,
-            AgentID::BusPassenger(_, _) => None,
+            AgentID::BusPassenger(_, _) | AgentID::CarPassenger(_, _) => None,
         }
     }
 
@@ -181,6 +219,7 @@ impl fmt::Display for AgentID {
             AgentID::Car(id) => write!(f, "AgentID({})", id),
             AgentID::Pedestrian(id) => write!(f, "AgentID({})", id),
             AgentID::BusPassenger(person, bus) => write!(f, "AgentID({} on {})", person, bus),
+            AgentID::CarPassenger(person, car) => write!(f, "AgentID({} in {})", person, car),
         }
     }
 }
@@ -193,6 +232,7 @@ pub enum AgentType {
     Train,
     Pedestrian,
     TransitRider,
+    CarPassenger,
 }
 
 impl AgentType {
@@ -204,6 +244,7 @@ impl AgentType {
             AgentType::Train,
             AgentType::Pedestrian,
             AgentType::TransitRider,
+            AgentType::CarPassenger,
         ]
     }
 
@@ -215,6 +256,7 @@ impl AgentType {
             AgentType::Train => "Train",
             AgentType::Pedestrian => "Pedestrian",
             AgentType::TransitRider => "Transit rider",
+            AgentType::CarPassenger => "Car passenger",
         }
     }
 
@@ -226,6 +268,7 @@ impl AgentType {
             AgentType::Train => "trains",
             AgentType::Pedestrian => "pedestrians",
             AgentType::TransitRider => "transit riders",
+            AgentType::CarPassenger => "car passengers",
         }
     }
 
@@ -236,6 +279,7 @@ impl AgentType {
             AgentType::Bus | AgentType::Train => unreachable!(),
             AgentType::Pedestrian => "walking",
             AgentType::TransitRider => "riding transit",
+            AgentType::CarPassenger => "riding in a car",
         }
     }
 }
@@ -278,6 +322,13 @@ pub enum VehicleType {
     Bus,
     Train,
     Bike,
+    /// A heavy goods vehicle
+    Truck,
+    /// A delivery van
+    Van,
+    Motorcycle,
+    /// An e-scooter or e-bike
+    Scooter,
 }
 
 impl fmt::Display for VehicleType {
@@ -287,17 +338,28 @@ impl fmt::Display for VehicleType {
             VehicleType::Bus => write!(f, "bus"),
             VehicleType::Train => write!(f, "train"),
             VehicleType::Bike => write!(f, "bike"),
+            VehicleType::Truck => write!(f, "truck"),
+            VehicleType::Van => write!(f, "van"),
+            VehicleType::Motorcycle => write!(f, "motorcycle"),
+            VehicleType::Scooter => write!(f, "scooter"),
         }
     }
 }
 
 impl VehicleType {
+    /// Only trucks have their own lane permissions and road restrictions. Vans and motorcycles
+    /// route and use lanes exactly like cars, and scooters like bikes; they differ only in size,
+    /// speed, acceleration, and emissions. Giving them their own permissions would need new
+    /// `PathConstraints` and a pathfinder for each.
     pub fn to_constraints(self) -> PathConstraints {
         match self {
             VehicleType::Car => PathConstraints::Car,
             VehicleType::Bus => PathConstraints::Bus,
             VehicleType::Train => PathConstraints::Train,
             VehicleType::Bike => PathConstraints::Bike,
+            VehicleType::Truck => PathConstraints::Truck,
+            VehicleType::Van | VehicleType::Motorcycle => PathConstraints::Car,
+            VehicleType::Scooter => PathConstraints::Bike,
         }
     }
 
@@ -307,6 +369,62 @@ impl VehicleType {
             VehicleType::Bus => true,
             VehicleType::Train => true,
             VehicleType::Bike => false,
+            VehicleType::Truck
+            | VehicleType::Van
+            | VehicleType::Motorcycle
+            | VehicleType::Scooter => false,
+        }
+    }
+
+    pub fn from_vehicle_kind(kind: VehicleKind) -> VehicleType {
+        match kind {
+            VehicleKind::Truck => VehicleType::Truck,
+            VehicleKind::Van => VehicleType::Van,
+            VehicleKind::Motorcycle => VehicleType::Motorcycle,
+            VehicleKind::Scooter => VehicleType::Scooter,
+        }
+    }
+
+    /// Ordinary cars and bikes, and transit vehicles, aren't a special kind of vehicle.
+    pub fn to_vehicle_kind(self) -> Option<VehicleKind> {
+        match self {
+            VehicleType::Car | VehicleType::Bus | VehicleType::Train | VehicleType::Bike => None,
+            VehicleType::Truck => Some(VehicleKind::Truck),
+            VehicleType::Van => Some(VehicleKind::Van),
+            VehicleType::Motorcycle => Some(VehicleKind::Motorcycle),
+            VehicleType::Scooter => Some(VehicleKind::Scooter),
+        }
+    }
+
+    /// Is this a privately driven motor vehicle, which parks when it's not being used?
+    pub fn is_car_like(self) -> bool {
+        matches!(
+            self,
+            VehicleType::Car | VehicleType::Truck | VehicleType::Van | VehicleType::Motorcycle
+        )
+    }
+
+    /// Does this vehicle use bike lanes and get locked up at a bike rack?
+    pub fn is_bike_like(self) -> bool {
+        matches!(self, VehicleType::Bike | VehicleType::Scooter)
+    }
+
+    /// Does this vehicle carry goods, stopping on the curb to unload at each destination?
+    pub fn is_freight(self) -> bool {
+        matches!(self, VehicleType::Truck | VehicleType::Van)
+    }
+
+    /// Roughly how wide the vehicle is, including mirrors or handlebars.
+    pub(crate) fn width(self) -> Distance {
+        match self {
+            VehicleType::Car => Distance::meters(1.8),
+            VehicleType::Bus => Distance::meters(2.55),
+            VehicleType::Train => Distance::meters(2.65),
+            VehicleType::Bike => Distance::meters(0.75),
+            VehicleType::Truck => Distance::meters(2.55),
+            VehicleType::Van => Distance::meters(2.1),
+            VehicleType::Motorcycle => Distance::meters(0.9),
+            VehicleType::Scooter => Distance::meters(0.6),
         }
     }
 }
@@ -366,7 +484,7 @@ impl DrivingGoal {
     pub fn goal_pos(&self, constraints: PathConstraints, map: &Map) -> Option<Position> {
         match self {
             DrivingGoal::ParkNear(b) => match constraints {
-                PathConstraints::Car => {
+                PathConstraints::Car | PathConstraints::Truck => {
                     let driving_lane = map.find_driving_lane_near_building(*b);
                     let sidewalk_pos = map.get_b(*b).sidewalk_pos;
                     if driving_lane.road == sidewalk_pos.lane().road {
@@ -387,7 +505,7 @@ impl DrivingGoal {
     pub fn make_router(&self, owner: CarID, path: Path, map: &Map) -> Router {
         match self {
             DrivingGoal::ParkNear(b) => {
-                if owner.vehicle_type == VehicleType::Bike {
+                if owner.vehicle_type.is_bike_like() {
                     Router::bike_then_stop(owner, path, SidewalkSpot::bike_rack(*b, map).unwrap())
                 } else {
                     Router::park_near(owner, path, *b)
@@ -570,9 +688,10 @@ pub(crate) struct CreateCar {
     pub vehicle: Vehicle,
     pub router: Router,
     pub maybe_parked_car: Option<ParkedCar>,
-    /// None for buses
+    /// None for buses and ride-hail vehicles
     pub trip_and_person: Option<(TripID, PersonID)>,
-    pub maybe_route: Option<TransitRouteID>,
+    /// For transit vehicles, the route and when the vehicle was scheduled to start it
+    pub maybe_route: Option<(TransitRouteID, Time)>,
 }
 
 impl CreateCar {
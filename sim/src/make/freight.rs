@@ -0,0 +1,338 @@
+//! Goods movement, separate from the personal trips people make. Delivery vehicles leave a depot,
+//! visit several businesses in turn, and return.
+
+use std::collections::BTreeSet;
+
+use anyhow::{bail, Result};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rand_xorshift::XorShiftRng;
+use serde::{Deserialize, Serialize};
+
+use abstutil::{prettyprint_usize, Timer};
+use geom::{Duration, Pt2D, Speed, Time};
+use map_model::{BuildingID, BuildingType, Map, PathConstraints};
+use synthpop::{
+    IndividTrip, PersonSpec, Scenario, TripEndpoint, TripMode, TripPurpose, VehicleKind,
+};
+
+use crate::make::generator::rand_time;
+
+/// Used to guess when each stop of a tour is reached, including detours and congestion.
+const ESTIMATED_SPEED: Speed = Speed::const_meters_per_second(4.2);
+/// How long the driver spends at each business after arriving, before leaving for the next stop.
+const TIME_PER_STOP: Duration = Duration::const_seconds(10.0 * 60.0);
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct FreightGenerator {
+    pub scenario_name: String,
+    /// Where tours start and end. Goods usually come from warehouses off the map, so this is
+    /// often borders.
+    pub depots: Vec<TripEndpoint>,
+    pub num_tours: usize,
+    /// Each tour visits between this many businesses, inclusive
+    pub min_stops: usize,
+    pub max_stops: usize,
+    /// Between 0 and 1. The rest of the tours use vans.
+    pub fraction_trucks: f64,
+    /// Businesses accept deliveries during one of these periods. Each tour serves one window,
+    /// and skips the remaining stops once it would arrive too late.
+    pub time_windows: Vec<(Time, Time)>,
+}
+
+impl FreightGenerator {
+    /// Tours starting and ending at every border that trucks can use, delivering in the morning
+    /// and early afternoon.
+    pub fn from_borders(map: &Map, name: &str, num_tours: usize) -> FreightGenerator {
+        FreightGenerator {
+            scenario_name: name.to_string(),
+            depots: map
+                .all_intersections()
+                .iter()
+                .filter(|i| {
+                    i.is_border()
+                        && !i.get_outgoing_lanes(map, PathConstraints::Truck).is_empty()
+                        && !i.get_incoming_lanes(map, PathConstraints::Truck).is_empty()
+                })
+                .map(|i| TripEndpoint::Border(i.id))
+                .collect(),
+            num_tours,
+            min_stops: 3,
+            max_stops: 8,
+            fraction_trucks: 0.2,
+            time_windows: vec![
+                (
+                    Time::START_OF_DAY + Duration::hours(6),
+                    Time::START_OF_DAY + Duration::hours(10),
+                ),
+                (
+                    Time::START_OF_DAY + Duration::hours(13),
+                    Time::START_OF_DAY + Duration::hours(16),
+                ),
+            ],
+        }
+    }
+
+    pub fn generate(
+        &self,
+        map: &Map,
+        rng: &mut XorShiftRng,
+        timer: &mut Timer,
+    ) -> Result<Scenario> {
+        self.check()?;
+        let mut scenario = Scenario::empty(map, &self.scenario_name);
+        if self.depots.is_empty() || self.time_windows.is_empty() {
+            warn!("No depots or time windows for {}", self.scenario_name);
+            return Ok(scenario);
+        }
+
+        // Businesses that vehicles can reach, weighted by how many people work there
+        let mut businesses: Vec<(BuildingID, usize, bool)> = Vec::new();
+        for b in map.all_buildings() {
+            let workers = match b.bldg_type {
+                BuildingType::Commercial(n) | BuildingType::ResidentialCommercial(_, n) => n,
+                BuildingType::Residential { .. } | BuildingType::Empty => {
+                    continue;
+                }
+            };
+            if let Some((pos, _)) = b.driving_connection(map) {
+                // Trucks can't use street parking, so they need somewhere off the street to
+                // unload
+                let trucks_ok =
+                    map.get_r(pos.lane().road).allows_trucks() && b.num_parking_spots() > 0;
+                businesses.push((b.id, workers.max(1), trucks_ok));
+            }
+        }
+
+        timer.start_iter("generate delivery tours", self.num_tours);
+        let mut no_stops = 0;
+        for _ in 0..self.num_tours {
+            timer.next();
+            let kind = if rng.gen_bool(self.fraction_trucks) {
+                VehicleKind::Truck
+            } else {
+                VehicleKind::Van
+            };
+            let candidates: Vec<&(BuildingID, usize, bool)> = businesses
+                .iter()
+                .filter(|(_, _, trucks_ok)| kind != VehicleKind::Truck || *trucks_ok)
+                .collect();
+            if let Some(person) = self.make_tour(kind, &candidates, map, rng) {
+                scenario.people.push(person);
+            } else {
+                no_stops += 1;
+            }
+        }
+        if no_stops > 0 {
+            warn!(
+                "{} delivery tours couldn't reach any business in time",
+                prettyprint_usize(no_stops)
+            );
+        }
+
+        Ok(scenario.remove_weird_schedules(true))
+    }
+
+    fn check(&self) -> Result<()> {
+        if !(0.0..=1.0).contains(&self.fraction_trucks) {
+            bail!(
+                "fraction_trucks is {}, but must be between 0 and 1",
+                self.fraction_trucks
+            );
+        }
+        if self.min_stops > self.max_stops {
+            bail!(
+                "min_stops is {}, but max_stops is only {}",
+                self.min_stops,
+                self.max_stops
+            );
+        }
+        for (start, end) in &self.time_windows {
+            if start >= end {
+                bail!("The time window from {} to {} is empty", start, end);
+            }
+        }
+        Ok(())
+    }
+
+    fn make_tour(
+        &self,
+        kind: VehicleKind,
+        candidates: &[&(BuildingID, usize, bool)],
+        map: &Map,
+        rng: &mut XorShiftRng,
+    ) -> Option<PersonSpec> {
+        let depot = *self.depots.choose(rng).unwrap();
+        let (window_start, window_end) = *self.time_windows.choose(rng).unwrap();
+        let num_stops = rng.gen_range(self.min_stops..=self.max_stops);
+
+        let mut stops = BTreeSet::new();
+        // Some businesses may be picked more than once; don't try forever
+        for _ in 0..num_stops * 3 {
+            if stops.len() == num_stops {
+                break;
+            }
+            if let Ok((b, _, _)) = candidates.choose_weighted(rng, |(_, workers, _)| *workers) {
+                stops.insert(*b);
+            }
+        }
+        if let TripEndpoint::Building(b) = depot {
+            stops.remove(&b);
+        }
+
+        // Leave early enough in the window to make a few stops
+        let leave = rand_time(
+            rng,
+            window_start,
+            window_start + (window_end - window_start) / 2.0,
+        );
+        let (legs, return_at) = schedule_tour(
+            depot.pt(map),
+            stops
+                .into_iter()
+                .map(|b| (b, TripEndpoint::Building(b).pt(map)))
+                .collect(),
+            leave,
+            window_end,
+        );
+        if legs.is_empty() {
+            return None;
+        }
+        let mut trips = Vec::new();
+        let mut current = depot;
+        for (depart, b) in legs {
+            let next = TripEndpoint::Building(b);
+            trips.push(delivery_trip(depart, current, next, kind));
+            current = next;
+        }
+        trips.push(delivery_trip(return_at, current, depot, kind));
+
+        Some(PersonSpec {
+            orig_id: None,
+            trips,
+        })
+    }
+}
+
+/// Starting from the depot at some time, visit the closest remaining stop next, until the next one
+/// can't be reached by the end of the time window. Returns when to leave for each stop visited,
+/// and when to head back to the depot.
+fn schedule_tour(
+    depot: Pt2D,
+    mut stops: Vec<(BuildingID, Pt2D)>,
+    leave: Time,
+    window_end: Time,
+) -> (Vec<(Time, BuildingID)>, Time) {
+    let mut legs = Vec::new();
+    let mut pt = depot;
+    let mut depart = leave;
+    while !stops.is_empty() {
+        let idx = (0..stops.len())
+            .min_by_key(|idx| stops[*idx].1.fast_dist(pt))
+            .unwrap();
+        let (b, next_pt) = stops.remove(idx);
+        let arrive = depart + pt.dist_to(next_pt) / ESTIMATED_SPEED;
+        if arrive > window_end {
+            break;
+        }
+        legs.push((depart, b));
+        depart = arrive + TIME_PER_STOP;
+        pt = next_pt;
+    }
+    (legs, depart)
+}
+
+fn delivery_trip(
+    depart: Time,
+    from: TripEndpoint,
+    to: TripEndpoint,
+    kind: VehicleKind,
+) -> IndividTrip {
+    let mut trip = IndividTrip::new(depart, TripPurpose::Delivery, from, to, TripMode::Drive);
+    trip.vehicle = Some(kind);
+    trip
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn test_schedule_tour() {
+        let mut rng = XorShiftRng::seed_from_u64(42);
+        let depot = Pt2D::new(0.0, 0.0);
+        let window_start = Time::START_OF_DAY + Duration::hours(8);
+        let window_end = Time::START_OF_DAY + Duration::hours(10);
+
+        let mut cut_short = 0;
+        let mut finished = 0;
+        for _ in 0..100 {
+            let stops: Vec<(BuildingID, Pt2D)> = (0..rng.gen_range(1..10))
+                .map(|idx| {
+                    (
+                        BuildingID(idx),
+                        Pt2D::new(rng.gen_range(0.0..20_000.0), rng.gen_range(0.0..20_000.0)),
+                    )
+                })
+                .collect();
+            let leave = rand_time(
+                &mut rng,
+                window_start,
+                window_start + (window_end - window_start) / 2.0,
+            );
+            let (legs, return_at) = schedule_tour(depot, stops.clone(), leave, window_end);
+
+            // Every stop visited is reached before the window closes
+            let mut pt = depot;
+            let mut earliest = leave;
+            for (depart, b) in &legs {
+                assert!(*depart >= earliest);
+                let next_pt = stops.iter().find(|(id, _)| id == b).unwrap().1;
+                let arrive = *depart + pt.dist_to(next_pt) / ESTIMATED_SPEED;
+                assert!(arrive <= window_end);
+                earliest = arrive + TIME_PER_STOP;
+                pt = next_pt;
+            }
+            assert_eq!(return_at, earliest);
+            if let Some((depart, _)) = legs.get(0) {
+                assert_eq!(*depart, leave);
+            }
+
+            if legs.len() == stops.len() {
+                finished += 1;
+            } else {
+                cut_short += 1;
+            }
+        }
+        // Make sure both cases happen
+        assert!(finished > 0);
+        assert!(cut_short > 0);
+    }
+
+    #[test]
+    fn test_check() {
+        let map = Map::blank();
+        let mut generator = FreightGenerator::from_borders(&map, "freight", 10);
+        assert!(generator.check().is_ok());
+
+        // Easy to mistake for a percentage
+        generator.fraction_trucks = 20.0;
+        assert!(generator.check().is_err());
+        generator.fraction_trucks = -0.1;
+        assert!(generator.check().is_err());
+        generator.fraction_trucks = 1.0;
+        assert!(generator.check().is_ok());
+
+        generator.min_stops = 5;
+        generator.max_stops = 4;
+        assert!(generator.check().is_err());
+        generator.max_stops = 5;
+        assert!(generator.check().is_ok());
+
+        let noon = Time::START_OF_DAY + Duration::hours(12);
+        generator.time_windows = vec![(noon, noon)];
+        assert!(generator.check().is_err());
+    }
+}
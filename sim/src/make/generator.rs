@@ -193,7 +193,7 @@ impl BorderSpawnOverTime {
     }
 }
 
-fn rand_time(rng: &mut XorShiftRng, low: Time, high: Time) -> Time {
+pub(crate) fn rand_time(rng: &mut XorShiftRng, low: Time, high: Time) -> Time {
     assert!(high > low);
     Time::START_OF_DAY + Duration::seconds(rng.gen_range(low.inner_seconds()..high.inner_seconds()))
 }
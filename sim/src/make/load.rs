@@ -103,7 +103,7 @@ impl SimFlags {
         } else if self.load.contains("/scenarios/") {
             info!("Seeding the simulation from scenario {}", self.load);
 
-            let mut scenario: Scenario = abstio::must_read_object(self.load.clone(), timer);
+            let mut scenario = Scenario::must_load(self.load.clone(), timer);
 
             let map = Map::load_synchronously(scenario.map_name.path(), timer);
 
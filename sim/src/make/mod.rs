@@ -4,11 +4,13 @@
 use rand::{RngCore, SeedableRng};
 use rand_xorshift::XorShiftRng;
 
+pub use self::freight::FreightGenerator;
 pub use self::generator::{BorderSpawnOverTime, ScenarioGenerator, SpawnOverTime};
 pub use self::load::SimFlags;
 pub(crate) use self::spawner::{StartTripArgs, TripSpec};
 
 mod activity_model;
+mod freight;
 mod generator;
 mod load;
 mod spawner;
@@ -4,10 +4,14 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
-use map_model::{BuildingID, Map, PathConstraints, Position, TransitRouteID, TransitStopID};
+use geom::Time;
+use map_model::{
+    BuildingID, Journey, JourneyAccess, JourneyPlanner, Map, PathConstraints, Position,
+    TransitRouteID, TransitStopID,
+};
 use synthpop::{TripEndpoint, TripMode};
 
-use crate::{CarID, DrivingGoal, SidewalkSpot, TripLeg, VehicleType, SPAWN_DIST};
+use crate::{CarID, DrivingGoal, SidewalkPOI, SidewalkSpot, TripLeg, SPAWN_DIST};
 
 /// We need to remember a few things from scenario instantiation that're used for starting the
 /// trip.
@@ -55,6 +59,14 @@ pub(crate) enum TripSpec {
         stop1: TransitStopID,
         maybe_stop2: Option<TransitStopID>,
     },
+    /// Ride public transit, maybe transferring between routes. If the journey starts by driving or
+    /// biking to the first stop, this uses the vehicle.
+    UsingTransitJourney {
+        start: SidewalkSpot,
+        goal: SidewalkSpot,
+        journey: Journey,
+        use_vehicle: Option<CarID>,
+    },
 }
 
 impl TripSpec {
@@ -82,11 +94,7 @@ impl TripSpec {
                     }
                 }
 
-                let constraints = if use_vehicle.vehicle_type == VehicleType::Bike {
-                    PathConstraints::Bike
-                } else {
-                    PathConstraints::Car
-                };
+                let constraints = use_vehicle.vehicle_type.to_constraints();
 
                 legs.push(TripLeg::Drive(*use_vehicle, goal.clone()));
                 if let DrivingGoal::ParkNear(b) = goal {
@@ -201,28 +209,60 @@ impl TripSpec {
                     legs = vec![TripLeg::Walk(walk_to), TripLeg::RideBus(*route, None)];
                 }
             }
+            TripSpec::UsingTransitJourney {
+                start,
+                goal,
+                journey,
+                use_vehicle,
+            } => {
+                match (journey.access, use_vehicle, &start.connection) {
+                    (JourneyAccess::ParkAndRide(b), Some(car), _) => {
+                        legs.push(TripLeg::Walk(SidewalkSpot::deferred_parking_spot()));
+                        legs.push(TripLeg::Drive(*car, DrivingGoal::ParkNear(b)));
+                    }
+                    (JourneyAccess::BikeAndRide(b), Some(bike), SidewalkPOI::Building(home)) => {
+                        // maybe_new checked that there's somewhere to start biking
+                        legs.push(TripLeg::Walk(SidewalkSpot::bike_rack(*home, map).unwrap()));
+                        legs.push(TripLeg::Drive(*bike, DrivingGoal::ParkNear(b)));
+                    }
+                    _ => {}
+                }
+                legs.push(TripLeg::Walk(SidewalkSpot::bus_stop(
+                    journey.rides[0].board,
+                    map,
+                )));
+                for (idx, ride) in journey.rides.iter().enumerate() {
+                    legs.push(TripLeg::RideBus(ride.route, Some(ride.alight)));
+                    legs.push(TripLeg::Walk(match journey.rides.get(idx + 1) {
+                        Some(next) => SidewalkSpot::bus_stop(next.board, map),
+                        None => goal.clone(),
+                    }));
+                }
+            }
         };
 
         (self, legs)
     }
 
     /// Turn an origin/destination pair and mode into a specific plan for instantiating a trip.
-    /// Decisions like how to use public transit happen here.
+    /// Decisions like how to use public transit happen here. If a journey planner is given,
+    /// transit trips use it, starting at some time. A transit trip with a vehicle may use it to
+    /// reach the first stop.
     pub fn maybe_new(
         from: TripEndpoint,
         to: TripEndpoint,
         mode: TripMode,
         use_vehicle: Option<CarID>,
         retry_if_no_room: bool,
+        journey_planner: Option<&JourneyPlanner>,
+        now: Time,
         map: &Map,
     ) -> Result<TripSpec> {
         Ok(match mode {
             TripMode::Drive | TripMode::Bike => {
-                let constraints = if mode == TripMode::Drive {
-                    PathConstraints::Car
-                } else {
-                    PathConstraints::Bike
-                };
+                let constraints = use_vehicle
+                    .map(|v| v.vehicle_type.to_constraints())
+                    .unwrap_or_else(|| mode.to_constraints());
                 let goal = driving_goal(to, constraints, map)?;
                 match from {
                     TripEndpoint::Building(start_bldg) => {
@@ -272,6 +312,38 @@ impl TripSpec {
             TripMode::Transit => {
                 let start = start_sidewalk_spot(from, map)?;
                 let goal = end_sidewalk_spot(to, map)?;
+                if let Some(planner) = journey_planner {
+                    // Vehicles can only be used starting from a building
+                    let access = match (from, use_vehicle) {
+                        (TripEndpoint::Building(_), Some(car))
+                            if car.vehicle_type.is_car_like() =>
+                        {
+                            PathConstraints::Car
+                        }
+                        (TripEndpoint::Building(b), Some(bike))
+                            if bike.vehicle_type.is_bike_like()
+                                && SidewalkSpot::bike_rack(b, map).is_some() =>
+                        {
+                            PathConstraints::Bike
+                        }
+                        _ => PathConstraints::Pedestrian,
+                    };
+                    if let Some(journey) =
+                        planner.plan(map, start.sidewalk_pos, goal.sidewalk_pos, now, access)
+                    {
+                        let use_vehicle = if journey.access == JourneyAccess::Walk {
+                            None
+                        } else {
+                            use_vehicle
+                        };
+                        return Ok(TripSpec::UsingTransitJourney {
+                            start,
+                            goal,
+                            journey,
+                            use_vehicle,
+                        });
+                    }
+                }
                 if let Some((stop1, maybe_stop2, route)) =
                     map.should_use_transit(start.sidewalk_pos, goal.sidewalk_pos)
                 {
@@ -288,6 +360,8 @@ impl TripSpec {
                     TripSpec::JustWalking { start, goal }
                 }
             }
+            // TripManager handles these before ever getting here
+            TripMode::RideHail => bail!("ride-hail trips don't have a TripSpec"),
         })
     }
 }
@@ -2,9 +2,10 @@ use std::collections::{BTreeSet, VecDeque};
 
 use serde::{Deserialize, Serialize};
 
-use geom::{Distance, Duration, PolyLine, Time, EPSILON_DIST};
+use geom::{Distance, Duration, PolyLine, Speed, Time, EPSILON_DIST};
 use map_model::{Direction, LaneID, Map, Traversable};
 
+use crate::mechanics::kinematics::{Kinematics, SpeedProfile};
 use crate::{
     CarID, CarStatus, DistanceInterval, DrawCarInput, Intent, ParkingSpot, PersonID, Router,
     TimeInterval, TransitSimState, TripID, Vehicle, VehicleType,
@@ -21,6 +22,9 @@ pub(crate) struct Car {
     pub trip_and_person: Option<(TripID, PersonID)>,
     pub started_at: Time,
     pub total_blocked_time: Duration,
+    /// When the front of the car entered the current step of its path, and how far along it
+    /// started. Used to estimate emissions once it leaves.
+    pub entered_step: (Time, Distance),
 
     /// In reverse order -- most recently left is first. The sum length of these must be >=
     /// vehicle.length.
@@ -29,6 +33,11 @@ pub(crate) struct Car {
     /// Since lane over-taking isn't implemented yet, a vehicle tends to be stuck behind a slow
     /// leader for a while. Avoid duplicate events.
     pub wants_to_overtake: BTreeSet<CarID>,
+    /// When the driver last looked for a faster route, if live rerouting is enabled
+    pub route_checked_at: Time,
+    /// Only tracked when vehicle kinematics are enabled. How fast the vehicle was going when it
+    /// last finished crossing something.
+    pub speed: Option<Speed>,
 }
 
 impl Car {
@@ -47,7 +56,8 @@ impl Car {
         }
 
         let dist_int = DistanceInterval::new_driving(start_dist, end_dist);
-        self.crossing_state_with_end_dist(dist_int, start_time, map)
+        let stop_at_end = self.router.last_step() && self.router.stops_at_end();
+        self.crossing_state_between(dist_int, start_time, stop_at_end, map)
     }
 
     pub fn crossing_state_with_end_dist(
@@ -55,6 +65,16 @@ impl Car {
         dist_int: DistanceInterval,
         start_time: Time,
         map: &Map,
+    ) -> CarState {
+        self.crossing_state_between(dist_int, start_time, false, map)
+    }
+
+    fn crossing_state_between(
+        &self,
+        dist_int: DistanceInterval,
+        start_time: Time,
+        stop_at_end: bool,
+        map: &Map,
     ) -> CarState {
         let (speed, percent_incline) = self
             .router
@@ -65,11 +85,64 @@ impl Car {
                 self.vehicle.vehicle_type.to_constraints(),
                 map,
             );
+        let steep_uphill = percent_incline >= 0.08;
+        if let Some(last_speed) = self.speed {
+            let kinematics = Kinematics::new(self.vehicle.vehicle_type, percent_incline);
+            let (start_speed, delay) = self.current_motion(last_speed, start_time, kinematics);
+            let profile = SpeedProfile::new(
+                dist_int.end - dist_int.start,
+                delay,
+                start_speed,
+                speed,
+                stop_at_end,
+                kinematics,
+            );
+            return CarState::Crossing {
+                time_int: TimeInterval::new(start_time, start_time + profile.duration()),
+                dist_int,
+                steep_uphill,
+                profile: Some(profile),
+            };
+        }
+
         let dt = (dist_int.end - dist_int.start) / speed;
         CarState::Crossing {
             time_int: TimeInterval::new(start_time, start_time + dt),
             dist_int,
-            steep_uphill: percent_incline >= 0.08,
+            steep_uphill,
+            profile: None,
+        }
+    }
+
+    /// With vehicle kinematics, how fast is the vehicle going right now, and how long must it wait
+    /// before it can speed up?
+    fn current_motion(
+        &self,
+        last_speed: Speed,
+        now: Time,
+        kinematics: Kinematics,
+    ) -> (Speed, Duration) {
+        match self.state {
+            CarState::Crossing {
+                ref time_int,
+                profile: Some(ref profile),
+                ..
+            }
+            | CarState::ChangingLanes {
+                new_time: ref time_int,
+                new_profile: Some(ref profile),
+                ..
+            } => profile.state_after(now - time_int.start),
+            CarState::Queued { blocked_since, .. }
+            | CarState::WaitingToAdvance { blocked_since } => {
+                if blocked_since == now {
+                    // Nothing was in the way after all, so keep going
+                    (last_speed, Duration::ZERO)
+                } else {
+                    (Speed::ZERO, kinematics.braking_delay(last_speed))
+                }
+            }
+            _ => (Speed::ZERO, Duration::ZERO),
         }
     }
 
@@ -325,6 +398,8 @@ pub(crate) enum CarState {
         time_int: TimeInterval,
         dist_int: DistanceInterval,
         steep_uphill: bool,
+        /// Only with vehicle kinematics; otherwise the speed is constant
+        profile: Option<SpeedProfile>,
     },
     ChangingLanes {
         from: LaneID,
@@ -332,12 +407,16 @@ pub(crate) enum CarState {
         // For the most part, act just like a Crossing state with these intervals
         new_time: TimeInterval,
         new_dist: DistanceInterval,
+        new_profile: Option<SpeedProfile>,
         // How long does the lane-changing itself last? This must end before new_time_int does.
         lc_time: TimeInterval,
     },
     Queued {
         blocked_since: Time,
         want_to_change_lanes: Option<LaneID>,
+        /// Overtake this slow cyclist without changing lanes, or for cyclists, filter past this
+        /// stopped vehicle
+        want_to_pass: Option<CarID>,
     },
     WaitingToAdvance {
         blocked_since: Time,
@@ -367,6 +446,28 @@ impl CarState {
         }
     }
 
+    /// While crossing something, where's the front of the vehicle, ignoring anything in the way?
+    pub fn crossing_front(
+        time_int: &TimeInterval,
+        dist_int: &DistanceInterval,
+        profile: &Option<SpeedProfile>,
+        now: Time,
+    ) -> Distance {
+        match profile {
+            Some(profile) => {
+                let dt = if now > time_int.end {
+                    time_int.end - time_int.start
+                } else {
+                    now - time_int.start
+                };
+                (dist_int.start + profile.dist_after(dt)).min(dist_int.end)
+            }
+            // TODO Why percent_clamp_end? We process car updates in any order, so we might
+            // calculate this before moving this car from Crossing to another state.
+            None => dist_int.lerp(time_int.percent_clamp_end(now)),
+        }
+    }
+
     pub fn time_spent_waiting(&self, now: Time) -> Duration {
         match self {
             CarState::Queued { blocked_since, .. }
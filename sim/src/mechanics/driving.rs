@@ -1,28 +1,47 @@
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 
-use abstutil::{deserialize_hashmap, serialize_hashmap, FixedMap, IndexableKey};
-use geom::{Distance, Duration, PolyLine, Time, Pt2D};
-use map_model::{DrivingSide, IntersectionID, LaneID, Map, Path, PathStep, Position, Traversable};
+use abstutil::{deserialize_hashmap, serialize_hashmap, FixedMap, IndexableKey, Timer};
+use geom::{Distance, Duration, PolyLine, Speed, Time, Pt2D};
+use map_model::{
+    DirectedRoadID, DrivingSide, IntersectionID, Lane, LaneID, Map, MovementID, Path,
+    PathConstraints, PathStep, Pathfinder, Position, RoutingParams, Traversable,
+};
 
+use crate::analytics::{demand_changes, signal_movements};
 use crate::mechanics::car::{Car, CarState};
 use crate::mechanics::queue::{Queue, QueueEntry, Queued};
+use crate::safety::Approach;
 use crate::sim::Ctx;
 use crate::{
     ActionAtEnd, AgentID, AgentProperties, CarID, CarStatus, Command, CreateCar, DelayCause,
-    DistanceInterval, DrawCarInput, Event, IntersectionSimState, ParkedCar, ParkingSim,
-    ParkingSpot, PersonID, Problem, SimOptions, TimeInterval, TransitSimState, TripID, TripManager,
-    UnzoomedAgent, Vehicle, VehicleType, WalkingSimState, FOLLOWING_DISTANCE, MAX_CAR_LENGTH,
+    DistanceInterval, DrawCarInput, EmissionsCalculator, Event, IntersectionSimState, ParkedCar,
+    ParkingSim, ParkingSpot, PersonID, Problem, SimOptions, TimeInterval, TransitSimState, TripID,
+    TripManager, UnzoomedAgent, Vehicle, VehicleType, WalkingSimState, FOLLOWING_DISTANCE,
+    MAX_CAR_LENGTH,
 };
 
-const TIME_TO_WAIT_AT_BUS_STOP: Duration = Duration::const_seconds(10.0);
+const TIME_TO_PICK_UP_OR_DROP_OFF: Duration = Duration::const_seconds(30.0);
+/// How long a freight vehicle double-parks in front of its destination
+const TIME_TO_UNLOAD: Duration = Duration::const_seconds(5.0 * 60.0);
 const TIME_TO_CHANGE_LANES: Duration = Duration::const_seconds(1.0);
 
 // TODO Do something else.
 pub const BLIND_RETRY_TO_CREEP_FORWARDS: Duration = Duration::const_seconds(0.1);
 pub const BLIND_RETRY_TO_REACH_END_DIST: Duration = Duration::const_seconds(5.0);
 
+/// Roughly how long each vehicle stuck in a queue takes to clear the intersection once it can go
+const SATURATION_HEADWAY: Duration = Duration::const_seconds(2.0);
+
+/// A driver who's already been stuck behind the same cyclist will squeeze past them with this
+/// much room to spare, even if it's less than the minimum passing distance.
+const MIN_SQUEEZE_CLEARANCE: Distance = Distance::const_meters(0.5);
+/// Cyclists only filter past stopped vehicles if there's at least this much room left over in the
+/// lane.
+const FILTERING_CLEARANCE: Distance = Distance::const_meters(0.25);
+
 /// Simulates vehicles!
 #[derive(Serialize, Deserialize, Clone)]
 pub(crate) struct DrivingSimState {
@@ -45,11 +64,21 @@ pub(crate) struct DrivingSimState {
 
     recalc_lanechanging: bool,
     handle_uber_turns: bool,
+    vehicle_kinematics: bool,
+    /// If vehicles can pass cyclists without changing lanes, how much room drivers try to leave
+    min_passing_distance: Option<Distance>,
+    /// If drivers periodically look for a faster route, how often they check and the least time
+    /// a new route must save
+    live_rerouting: Option<(Duration, Duration)>,
+    /// Reflects the traffic at some recent time
+    live_routing: Option<LiveRouting>,
 
     time_to_unpark_onstreet: Duration,
     time_to_park_onstreet: Duration,
     time_to_unpark_offstreet: Duration,
     time_to_park_offstreet: Duration,
+
+    emissions: Option<EmissionsCalculator>,
 }
 
 // Mutations
@@ -61,12 +90,20 @@ impl DrivingSimState {
             events: Vec::new(),
             recalc_lanechanging: !opts.dont_recalc_lanechanging,
             handle_uber_turns: !opts.dont_handle_uber_turns,
+            vehicle_kinematics: opts.vehicle_kinematics,
+            min_passing_distance: opts.min_passing_distance,
+            live_rerouting: opts
+                .reroute_interval
+                .map(|interval| (interval, opts.reroute_min_gain)),
+            live_routing: None,
             waiting_to_spawn: BTreeMap::new(),
 
             time_to_unpark_onstreet: Duration::seconds(10.0),
             time_to_park_onstreet: Duration::seconds(15.0),
             time_to_unpark_offstreet: Duration::seconds(5.0),
             time_to_park_offstreet: Duration::seconds(5.0),
+
+            emissions: EmissionsCalculator::new(opts),
         };
         if opts.infinite_parking {
             sim.time_to_unpark_offstreet = Duration::seconds(0.1);
@@ -185,12 +222,20 @@ impl DrivingSimState {
                 state: CarState::Queued {
                     blocked_since: now,
                     want_to_change_lanes: None,
+                    want_to_pass: None,
                 },
                 last_steps: VecDeque::new(),
                 started_at: now,
                 total_blocked_time: Duration::ZERO,
+                entered_step: (now, start_dist),
                 trip_and_person: params.trip_and_person,
                 wants_to_overtake: BTreeSet::new(),
+                route_checked_at: now,
+                speed: if self.vehicle_kinematics {
+                    Some(Speed::ZERO)
+                } else {
+                    None
+                },
             };
             let mut start_crossing = false;
             if let Some(p) = params.maybe_parked_car {
@@ -240,7 +285,9 @@ impl DrivingSimState {
                         car.trip_and_person,
                         &mut self.events,
                     ) {
-                        None | Some(ActionAtEnd::GotoLaneEnd) => {}
+                        // A vehicle spawning right in front of its destination doesn't bother to
+                        // unload
+                        None | Some(ActionAtEnd::GotoLaneEnd) | Some(ActionAtEnd::Unload) => {}
                         x => {
                             panic!(
                                 "Car with one-step route {:?} had unexpected result from \
@@ -280,7 +327,7 @@ impl DrivingSimState {
             if start_crossing {
                 // Don't call this earlier where we set crossing_state, because we're not in the
                 // queue yet
-                self.new_crossing_state(ctx, &car);
+                self.new_crossing_state(now, ctx, &car);
             }
 
             self.cars.insert(car.vehicle.id, car);
@@ -381,10 +428,14 @@ impl DrivingSimState {
         transit: &mut TransitSimState,
     ) -> bool {
         match car.state {
-            CarState::Crossing { .. } => {
+            CarState::Crossing { ref profile, .. } => {
+                if let Some(profile) = profile {
+                    car.speed = Some(profile.end_speed());
+                }
                 car.state = CarState::Queued {
                     blocked_since: now,
                     want_to_change_lanes: None,
+                    want_to_pass: None,
                 };
                 if car.router.last_step() {
                     // Immediately run update_car_with_distances.
@@ -394,8 +445,10 @@ impl DrivingSimState {
                 if queue.is_car_at_front(car.vehicle.id) {
                     // Want to re-run, but no urgency about it happening immediately.
                     car.state = CarState::WaitingToAdvance { blocked_since: now };
+                    self.maybe_reroute(car, now, ctx.map);
                     if self.recalc_lanechanging {
                         car.router.opportunistically_lanechange(
+                            now,
                             &self.queues,
                             ctx.map,
                             self.handle_uber_turns,
@@ -409,8 +462,8 @@ impl DrivingSimState {
 
                     // Record when a vehicle wants to pass a bike
                     if first_conflict
-                        && slow_leader.vehicle_type == VehicleType::Bike
-                        && car.vehicle.vehicle_type != VehicleType::Bike
+                        && slow_leader.vehicle_type.is_bike_like()
+                        && !car.vehicle.vehicle_type.is_bike_like()
                     {
                         self.events.push(Event::ProblemEncountered(
                             self.cars[&slow_leader].trip_and_person.unwrap().0,
@@ -425,9 +478,25 @@ impl DrivingSimState {
                         car.state = CarState::Queued {
                             blocked_since: now,
                             want_to_change_lanes: Some(target_lane),
+                            want_to_pass: None,
+                        };
+                        return true;
+                    }
+                    if self.will_pass_in_lane(car, slow_leader, first_conflict, now, ctx.map) {
+                        car.state = CarState::Queued {
+                            blocked_since: now,
+                            want_to_change_lanes: None,
+                            want_to_pass: Some(slow_leader),
                         };
                         return true;
                     }
+                } else if let Some(stopped_leader) = self.wants_to_filter(car, ctx.map) {
+                    car.state = CarState::Queued {
+                        blocked_since: now,
+                        want_to_change_lanes: None,
+                        want_to_pass: Some(stopped_leader),
+                    };
+                    return true;
                 }
             }
             CarState::Unparking {
@@ -481,7 +550,7 @@ impl DrivingSimState {
                 car.state = car.crossing_state(front, now, ctx.map);
                 ctx.scheduler
                     .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
-                self.new_crossing_state(ctx, car);
+                self.new_crossing_state(now, ctx, car);
             }
             CarState::WaitingToAdvance { blocked_since } => {
                 // 'car' is the leader.
@@ -524,6 +593,7 @@ impl DrivingSimState {
                 // We do NOT need to update the follower. If they were Queued, they'll remain that
                 // way, until laggy_head is None.
 
+                self.record_emissions(car, from.get_polyline(ctx.map).length(), now, ctx.map);
                 let last_step = car.router.advance(
                     &car.vehicle,
                     ctx.parking,
@@ -532,6 +602,7 @@ impl DrivingSimState {
                     &mut self.events,
                 );
                 car.total_blocked_time += now - blocked_since;
+                car.entered_step = (now, Distance::ZERO);
                 car.state = car.crossing_state(Distance::ZERO, now, ctx.map);
                 ctx.scheduler
                     .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
@@ -574,6 +645,7 @@ impl DrivingSimState {
                 from,
                 new_time,
                 new_dist,
+                ref new_profile,
                 ..
             } => {
                 // The car is already in the target queue. Just set them in the crossing state; we
@@ -582,6 +654,7 @@ impl DrivingSimState {
                     time_int: new_time,
                     dist_int: new_dist,
                     steep_uphill: false,
+                    profile: new_profile.clone(),
                 };
                 ctx.scheduler
                     .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
@@ -630,13 +703,18 @@ impl DrivingSimState {
             CarState::Queued {
                 blocked_since,
                 want_to_change_lanes,
+                want_to_pass,
             } => {
-                // Two totally different reasons we'll wind up here: we want to lane-change, and
-                // we're on our last step.
+                // Two totally different reasons we'll wind up here: we want to lane-change or pass
+                // somebody, and we're on our last step.
                 if let Some(target_lane) = want_to_change_lanes {
                     self.try_start_lc(car, our_dist, idx, target_lane, now, ctx);
                     return true;
                 }
+                if let Some(other) = want_to_pass {
+                    self.try_pass(car, dists, idx, other, now, ctx);
+                    return true;
+                }
 
                 match car.router.maybe_handle_end(
                     our_dist,
@@ -697,7 +775,7 @@ impl DrivingSimState {
                         car.state = car.crossing_state(our_dist, now, ctx.map);
                         ctx.scheduler
                             .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
-                        self.new_crossing_state(ctx, car);
+                        self.new_crossing_state(now, ctx, car);
                         true
                     }
                     Some(ActionAtEnd::StopBiking(bike_rack)) => {
@@ -714,10 +792,12 @@ impl DrivingSimState {
                     }
                     Some(ActionAtEnd::BusAtStop) => {
                         car.total_blocked_time += now - blocked_since;
-                        if transit.bus_arrived_at_stop(now, car.vehicle.id, trips, walking, ctx) {
+                        if let Some(dwell) =
+                            transit.bus_arrived_at_stop(now, car.vehicle.id, trips, walking, ctx)
+                        {
                             car.state = CarState::IdlingAtStop(
                                 our_dist,
-                                TimeInterval::new(now, now + TIME_TO_WAIT_AT_BUS_STOP),
+                                TimeInterval::new(now, now + dwell),
                             );
                             ctx.scheduler
                                 .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
@@ -727,6 +807,32 @@ impl DrivingSimState {
                             false
                         }
                     }
+                    Some(ActionAtEnd::RideHailAtStop) => {
+                        car.total_blocked_time += now - blocked_since;
+                        trips.ride_hail_vehicle_arrived(
+                            now,
+                            car.vehicle.id,
+                            car.router.get_path().total_length(),
+                            ctx,
+                        );
+                        car.state = CarState::IdlingAtStop(
+                            our_dist,
+                            TimeInterval::new(now, now + TIME_TO_PICK_UP_OR_DROP_OFF),
+                        );
+                        ctx.scheduler
+                            .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                        true
+                    }
+                    Some(ActionAtEnd::Unload) => {
+                        car.total_blocked_time += now - blocked_since;
+                        car.state = CarState::IdlingAtStop(
+                            our_dist,
+                            TimeInterval::new(now, now + TIME_TO_UNLOAD),
+                        );
+                        ctx.scheduler
+                            .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                        true
+                    }
                     None => {
                         ctx.scheduler.push(
                             now + BLIND_RETRY_TO_REACH_END_DIST,
@@ -772,14 +878,35 @@ impl DrivingSimState {
                 );
                 false
             }
-            CarState::IdlingAtStop(dist, _) => {
-                car.router = transit.bus_departed_from_stop(car.vehicle.id, ctx.map);
+            CarState::IdlingAtStop(dist, time_int) => {
+                if car.vehicle.vehicle_type.is_transit() {
+                    car.router = transit.bus_departed_from_stop(car.vehicle.id, ctx.map);
+                } else if car.vehicle.vehicle_type.is_freight() {
+                    self.finished_unloading(car, dists, idx, now - time_int.start);
+                    // Done unloading; now go find a real parking spot. The result will be handled
+                    // once the vehicle reaches its new end distance.
+                    car.router.maybe_handle_end(
+                        dist,
+                        &car.vehicle,
+                        ctx.parking,
+                        ctx.map,
+                        car.trip_and_person,
+                        &mut self.events,
+                    );
+                } else if let Some(router) =
+                    trips.ride_hail_vehicle_departed(now, car.vehicle.id, ctx)
+                {
+                    car.router = router;
+                } else {
+                    // The ride-hail vehicle has nobody to pick up next, so it goes idle
+                    return false;
+                }
                 self.events
                     .push(Event::PathAmended(car.router.get_path().clone()));
                 car.state = car.crossing_state(dist, now, ctx.map);
                 ctx.scheduler
                     .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
-                self.new_crossing_state(ctx, car);
+                self.new_crossing_state(now, ctx, car);
 
                 self.update_follower(idx, dists, now, ctx);
 
@@ -838,6 +965,7 @@ impl DrivingSimState {
         now: Time,
         ctx: &mut Ctx,
     ) {
+        self.record_emissions(car, dists[idx].front, now, ctx.map);
         {
             let queue = self.queues.get_mut(&car.router.head()).unwrap();
             queue.remove_car_from_idx(car.vehicle.id, idx);
@@ -865,6 +993,64 @@ impl DrivingSimState {
         self.update_follower(idx, &dists, now, ctx);
     }
 
+    /// The car's front is about to leave the current step of its path, or the car is vanishing
+    /// partway along it.
+    fn record_emissions(&mut self, car: &Car, front: Distance, now: Time, map: &Map) {
+        let calculator = match self.emissions {
+            Some(ref calculator) => calculator,
+            None => {
+                return;
+            }
+        };
+        let (entered_at, entered_dist) = car.entered_step;
+        let (speed, incline) = car
+            .router
+            .get_path()
+            .current_step()
+            .max_speed_and_incline_along(
+                car.vehicle.max_speed,
+                car.vehicle.vehicle_type.to_constraints(),
+                map,
+            );
+        if let Some(emissions) = calculator.calculate(
+            car.vehicle.id,
+            front - entered_dist,
+            now - entered_at,
+            speed,
+            incline,
+        ) {
+            self.events.push(Event::VehicleEmissions(
+                car.vehicle.id,
+                car.trip_and_person.map(|(t, _)| t),
+                car.router.head(),
+                emissions,
+            ));
+        }
+    }
+
+    /// A freight vehicle finished unloading on the curb. Anybody still stuck right behind them was
+    /// blocked by the delivery.
+    fn finished_unloading(&mut self, car: &Car, dists: &[QueueEntry], idx: usize, dwell: Duration) {
+        let on = car.router.head();
+        let blocked = held_up_behind(dists, idx, |id| {
+            matches!(self.cars[&id].state, CarState::Queued { .. })
+        });
+        for id in &blocked {
+            if let Some((trip, _)) = self.cars[id].trip_and_person {
+                self.events.push(Event::ProblemEncountered(
+                    trip,
+                    Problem::BlockedByDelivery(on),
+                ));
+            }
+        }
+        self.events.push(Event::DeliveryUnloaded(
+            car.vehicle.id,
+            on.as_lane(),
+            dwell,
+            blocked.len(),
+        ));
+    }
+
     /// After a leader (maybe an active vehicle, maybe a static blockage) gets out of the way,
     /// update the follower so that they don't suddenly jump forwards.
     fn update_follower(
@@ -906,7 +1092,7 @@ impl DrivingSimState {
                         Command::UpdateCar(follower_id),
                     );
                     let follower = &self.cars[&follower_id];
-                    self.new_crossing_state(ctx, follower);
+                    self.new_crossing_state(now, ctx, follower);
                 }
                 CarState::Crossing { .. } => {
                     // If the follower was still Crossing, they might not've been blocked by the
@@ -919,7 +1105,7 @@ impl DrivingSimState {
                     );
                     // This'll possibly update the ETA
                     let follower = &self.cars[&follower_id];
-                    self.new_crossing_state(ctx, follower);
+                    self.new_crossing_state(now, ctx, follower);
                 }
                 CarState::ChangingLanes {
                     from, to, lc_time, ..
@@ -931,14 +1117,21 @@ impl DrivingSimState {
                     // finish before the new time interval, because there's no possible way
                     // recalculating this crossing state here will speed things up from the
                     // original estimate.
-                    let (new_time, new_dist) = match follower.crossing_state_with_end_dist(
-                        DistanceInterval::new_driving(follower_dist, ctx.map.get_l(to).length()),
-                        now,
-                        ctx.map,
-                    ) {
+                    let (new_time, new_dist, new_profile) = match follower
+                        .crossing_state_with_end_dist(
+                            DistanceInterval::new_driving(
+                                follower_dist,
+                                ctx.map.get_l(to).length(),
+                            ),
+                            now,
+                            ctx.map,
+                        ) {
                         CarState::Crossing {
-                            time_int, dist_int, ..
-                        } => (time_int, dist_int),
+                            time_int,
+                            dist_int,
+                            profile,
+                            ..
+                        } => (time_int, dist_int, profile),
                         _ => unreachable!(),
                     };
                     assert!(new_time.end >= lc_time.end);
@@ -947,6 +1140,7 @@ impl DrivingSimState {
                         to,
                         new_time,
                         new_dist,
+                        new_profile,
                         lc_time,
                     };
                 }
@@ -1076,6 +1270,7 @@ impl DrivingSimState {
                                     if self.recalc_lanechanging && ctx.handling_live_edits.is_none()
                                     {
                                         follower.router.opportunistically_lanechange(
+                                            now,
                                             &self.queues,
                                             ctx.map,
                                             self.handle_uber_turns,
@@ -1104,6 +1299,118 @@ impl DrivingSimState {
         }
     }
 
+    /// If live rerouting is enabled and it's been long enough since this driver last checked,
+    /// look for a faster route given the current traffic.
+    fn maybe_reroute(&mut self, car: &mut Car, now: Time, map: &Map) {
+        let (interval, min_gain) = match self.live_rerouting {
+            Some(x) => x,
+            None => {
+                return;
+            }
+        };
+        // Buses and bikes stick to their routes
+        if !car.vehicle.vehicle_type.is_car_like()
+            || car.trip_and_person.is_none()
+            || now - car.route_checked_at < interval
+        {
+            return;
+        }
+        car.route_checked_at = now;
+
+        if self
+            .live_routing
+            .as_ref()
+            .map(|live| now - live.built_at >= interval)
+            .unwrap_or(true)
+        {
+            let mut params = map.routing_params().clone();
+            params.time = Some(now);
+            params.movement_delays = self.live_movement_delays(map);
+            self.live_routing = Some(LiveRouting {
+                built_at: now,
+                params,
+                pathfinder: None,
+            });
+        }
+
+        let live = self.live_routing.as_mut().unwrap();
+        // After loading a savestate, the pathfinder has to be rebuilt from the same params
+        let pathfinder = live.pathfinder.get_or_insert_with(|| {
+            // Dijkstra's is much faster to prepare than a contraction hierarchy, and this is
+            // rebuilt often.
+            Arc::new(Pathfinder::new_dijkstra(
+                map,
+                live.params.clone(),
+                vec![PathConstraints::Car, PathConstraints::Truck],
+                &mut Timer::throwaway(),
+            ))
+        });
+        if let Some((old_path, time_saved)) =
+            car.router
+                .maybe_reroute(pathfinder, &live.params, min_gain, map)
+        {
+            let (demand_removed, demand_added) = demand_changes(
+                signal_movements(&old_path, map),
+                signal_movements(car.router.get_path(), map),
+            );
+            self.events.push(Event::Rerouted {
+                car: car.vehicle.id,
+                trip: car.trip_and_person.map(|(t, _)| t),
+                road: car.router.head().as_lane().road,
+                time_saved,
+                demand_removed,
+                demand_added,
+            });
+        }
+    }
+
+    /// Estimate how long vehicles currently wait to leave each road and make some movement, based
+    /// on how many are stuck in the queues.
+    fn live_movement_delays(&self, map: &Map) -> BTreeMap<MovementID, Duration> {
+        // The worst lane determines the delay for the whole road. Using the max keeps this
+        // independent of the order of iterating over the queues.
+        let mut per_road: BTreeMap<DirectedRoadID, Duration> = BTreeMap::new();
+        for queue in self.queues.values() {
+            let lane = match queue.id {
+                Traversable::Lane(l) => l,
+                Traversable::Turn(_) => continue,
+            };
+            let stuck = queue
+                .get_active_cars()
+                .into_iter()
+                // The car currently being updated isn't in self.cars, but it's waiting
+                .filter(|c| {
+                    self.cars
+                        .get(c)
+                        .map(|car| {
+                            matches!(
+                                car.state,
+                                CarState::Queued { .. } | CarState::WaitingToAdvance { .. }
+                            )
+                        })
+                        .unwrap_or(true)
+                })
+                .count();
+            if stuck == 0 {
+                continue;
+            }
+            let delay = (stuck as f64) * SATURATION_HEADWAY;
+            let dr = map.get_l(lane).get_directed_parent();
+            let entry = per_road.entry(dr).or_insert(Duration::ZERO);
+            *entry = (*entry).max(delay);
+        }
+
+        let mut delays = BTreeMap::new();
+        for (dr, delay) in per_road {
+            for mvmnt in map.get_i(dr.dst_i(map)).movements.keys() {
+                if mvmnt.from == dr {
+                    delays.insert(*mvmnt, delay);
+                }
+            }
+        }
+        delays
+    }
+
     /// If the car wants to over-take somebody, what adjacent lane should they use?
     /// - The lane must be in the same direction as the current; no support for crossing the road's
     ///   yellow line yet.
@@ -1182,14 +1489,17 @@ impl DrivingSimState {
 
         // Calculate the crossing state in the target queue. Pass in the DistanceInterval
         // explicitly, because we haven't modified the route yet.
-        let (new_time, new_dist) = match car.crossing_state_with_end_dist(
+        let (new_time, new_dist, new_profile) = match car.crossing_state_with_end_dist(
             DistanceInterval::new_driving(front_target_queue, ctx.map.get_l(target_lane).length()),
             now,
             ctx.map,
         ) {
             CarState::Crossing {
-                time_int, dist_int, ..
-            } => (time_int, dist_int),
+                time_int,
+                dist_int,
+                profile,
+                ..
+            } => (time_int, dist_int, profile),
             _ => unreachable!(),
         };
 
@@ -1241,6 +1551,7 @@ impl DrivingSimState {
                 to: target_lane,
                 new_time,
                 new_dist,
+                new_profile,
                 lc_time,
             };
             ctx.scheduler
@@ -1248,11 +1559,131 @@ impl DrivingSimState {
         }
     }
 
+    /// Pass the vehicle directly in front without changing lanes. If they're moving, this is
+    /// overtaking a slow cyclist; if they're stopped, this is a cyclist filtering past queued
+    /// traffic. The vehicle passing takes over the other's place in the queue, but keeps moving
+    /// from where it is now.
+    fn try_pass(
+        &mut self,
+        car: &mut Car,
+        dists: &[QueueEntry],
+        idx: usize,
+        other: CarID,
+        now: Time,
+        ctx: &mut Ctx,
+    ) {
+        // Double check nothing's changed
+        if idx == 0 || dists[idx - 1].member != Queued::Vehicle(other) {
+            return;
+        }
+        if matches!(self.cars[&other].state, CarState::Queued { .. }) {
+            self.try_filter(car, dists, idx, now, ctx);
+        } else {
+            self.try_overtake_in_lane(car, dists, idx, other, now, ctx);
+        }
+    }
+
+    fn try_overtake_in_lane(
+        &mut self,
+        car: &mut Car,
+        dists: &[QueueEntry],
+        idx: usize,
+        cyclist: CarID,
+        now: Time,
+        ctx: &mut Ctx,
+    ) {
+        let on = car.router.head();
+        let lane_end = if self.queues[&on].laggy_head.is_none() {
+            Some(self.queues[&on].geom_len)
+        } else {
+            None
+        };
+        let new_front = match overtaking_slot(dists, idx, car.vehicle.length, lane_end) {
+            Some(x) => x,
+            None => {
+                return;
+            }
+        };
+
+        // Now that we know exactly where the pass happens, check for a gap in oncoming traffic.
+        // Looking at the oncoming queue might recurse back to this one, so temporarily make the
+        // car visible.
+        self.cars.insert(car.vehicle.id, car.clone());
+        let clearance =
+            self.passing_clearance(car, Some((dists[idx].front, new_front)), now, ctx.map);
+        let expected_clearance = self.passing_clearance(car, None, now, ctx.map);
+        self.cars.remove(&car.vehicle.id);
+        let min_passing_distance = self.min_passing_distance.unwrap();
+        let clearance = match clearance.zip(expected_clearance) {
+            Some((actual, expected))
+                if will_pass_with_clearance(actual, expected, min_passing_distance) =>
+            {
+                actual
+            }
+            // Keep following the cyclist
+            _ => {
+                return;
+            }
+        };
+
+        self.queues
+            .get_mut(&on)
+            .unwrap()
+            .move_car_ahead(car.vehicle.id, idx, idx - 1);
+        // Start from where we are now. The queue keeps us behind whoever's in front of the
+        // cyclist, and the cyclist behind us.
+        car.state = car.crossing_state(dists[idx].front, now, ctx.map);
+        ctx.scheduler
+            .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+        self.new_crossing_state(now, ctx, car);
+
+        if is_close_pass(car.vehicle.vehicle_type, clearance, min_passing_distance) {
+            if let Some((trip, _)) = self.cars[&cyclist].trip_and_person {
+                self.events
+                    .push(Event::ProblemEncountered(trip, Problem::ClosePass(on)));
+            }
+        }
+    }
+
+    fn try_filter(
+        &mut self,
+        bike: &mut Car,
+        dists: &[QueueEntry],
+        idx: usize,
+        now: Time,
+        ctx: &mut Ctx,
+    ) {
+        let on = bike.router.head();
+        let lane = ctx.map.get_l(on.as_lane());
+        let to_idx = filtering_slot(dists, idx, |member| match member {
+            Queued::Vehicle(id) => self.can_filter_past(&self.cars[id], lane),
+            _ => false,
+        });
+        if to_idx == idx {
+            return;
+        }
+
+        // Start from where the bike is now. The vehicles passed wind up behind it.
+        self.queues
+            .get_mut(&on)
+            .unwrap()
+            .move_car_ahead(bike.vehicle.id, idx, to_idx);
+        bike.state = bike.crossing_state(dists[idx].front, now, ctx.map);
+        ctx.scheduler.push(
+            bike.state.get_end_time(),
+            Command::UpdateCar(bike.vehicle.id),
+        );
+        self.new_crossing_state(now, ctx, bike);
+    }
+
     pub fn collect_events(&mut self) -> Vec<Event> {
         std::mem::take(&mut self.events)
     }
 
     pub fn handle_live_edits(&mut self, map: &Map) {
+        // The roads have changed, so rebuild this next time it's needed
+        self.live_routing = None;
+
         // Calculate all queues that should exist now.
         let mut new_queues = HashSet::new();
         for l in map.all_lanes() {
@@ -1291,13 +1722,24 @@ impl DrivingSimState {
         }
     }
 
-    fn new_crossing_state(&self, ctx: &mut Ctx, car: &Car) {
+    fn new_crossing_state(&self, now: Time, ctx: &mut Ctx, car: &Car) {
         if self.queues[&car.router.head()].is_car_at_front(car.vehicle.id) {
             if let Some(Traversable::Turn(turn)) = car.router.maybe_next() {
                 ctx.intersections.approaching_leader(
                     AgentID::Car(car.vehicle.id),
                     turn,
-                    car.state.get_end_time(),
+                    Approach {
+                        start: car.state.get_end_time(),
+                        speed: PathStep::Turn(turn).max_speed_along(
+                            car.vehicle.max_speed,
+                            car.vehicle.vehicle_type.to_constraints(),
+                            ctx.map,
+                        ),
+                        length: car.vehicle.length,
+                    },
+                    now,
+                    ctx.map,
+                    ctx.scheduler,
                 );
             }
         }
@@ -1347,6 +1789,10 @@ impl DrivingSimState {
         result
     }
 
+    pub fn is_estimating_emissions(&self) -> bool {
+        self.emissions.is_some()
+    }
+
     pub fn does_car_exist(&self, id: CarID) -> bool {
         // Because of the shortcut IndexableKey takes with ignoring the VehicleType part of the ID,
         // we have to double-check that it matches!
@@ -1602,6 +2048,14 @@ impl DrivingSimState {
         }
     }
 
+    /// How many vehicles are currently queued on a lane or turn, not counting a laggy head.
+    pub fn queue_length(&self, on: Traversable) -> usize {
+        self.queues
+            .get(&on)
+            .map(|q| q.get_active_cars().len())
+            .unwrap_or(0)
+    }
+
     pub fn debug_queue_lengths(&self, l: LaneID) -> Option<(Distance, Distance)> {
         let queue = self.queues.get(&Traversable::Lane(l))?;
         Some((queue.reserved_length, queue.geom_len))
@@ -1693,6 +2147,246 @@ impl DrivingSimState {
 
         Some(leader.vehicle.id)
     }
+
+    /// Should this vehicle overtake the slow cyclist in front of them, without changing lanes?
+    /// Drivers pass if they can leave the minimum passing distance. If they've already been stuck
+    /// behind the same cyclist earlier, they'll squeeze past with less room. This assumes there'll
+    /// be a gap in oncoming traffic; that's checked when the pass actually starts.
+    fn will_pass_in_lane(
+        &self,
+        car: &Car,
+        slow_leader: CarID,
+        first_conflict: bool,
+        now: Time,
+        map: &Map,
+    ) -> bool {
+        let min_passing_distance = match self.min_passing_distance {
+            Some(x) => x,
+            None => {
+                return false;
+            }
+        };
+        if !slow_leader.vehicle_type.is_bike_like()
+            || car.router.last_step()
+            || !car.last_steps.is_empty()
+        {
+            return false;
+        }
+        match self.passing_clearance(car, None, now, map) {
+            Some(clearance) => {
+                clearance >= min_passing_distance
+                    || (!first_conflict && clearance >= MIN_SQUEEZE_CLEARANCE)
+            }
+            None => false,
+        }
+    }
+
+    /// If this vehicle passes the cyclist in front of them, how much room will they leave? Use
+    /// the oncoming lane if there's a gap in traffic while passing between the two distances along
+    /// the current lane; otherwise the cyclist has to share the current lane. Without the
+    /// distances, just assume there's a gap.
+    fn passing_clearance(
+        &self,
+        car: &Car,
+        stretch: Option<(Distance, Distance)>,
+        now: Time,
+        map: &Map,
+    ) -> Option<Distance> {
+        let lane = map.get_l(car.router.head().maybe_lane()?);
+        let mut width = lane.width;
+        if let Some(oncoming) = self.oncoming_lane(car, lane, map) {
+            if stretch
+                .map(|(from, to)| self.oncoming_gap(oncoming, from, to, now))
+                .unwrap_or(true)
+            {
+                width += map.get_l(oncoming).width;
+            }
+        }
+        Some(width - car.vehicle.vehicle_type.width() - VehicleType::Bike.width())
+    }
+
+    /// Find the lane on the other side of the road's center line, if this vehicle could use it.
+    fn oncoming_lane(&self, car: &Car, lane: &Lane, map: &Map) -> Option<LaneID> {
+        let road = map.get_parent(lane.id);
+        let idx = if map.get_config().driving_side == DrivingSide::Right {
+            lane.id.offset.checked_sub(1)?
+        } else {
+            lane.id.offset + 1
+        };
+        let oncoming = road.lanes.get(idx)?;
+        if oncoming.dir == lane.dir
+            || !car
+                .vehicle
+                .vehicle_type
+                .to_constraints()
+                .can_use(oncoming, map)
+            || !self.queues.contains_key(&Traversable::Lane(oncoming.id))
+        {
+            return None;
+        }
+        Some(oncoming.id)
+    }
+
+    /// Passing between two distances along a lane, is there a gap in traffic on the oncoming lane?
+    /// Oncoming vehicles keep moving during the pass, so they need to be at least as far away as
+    /// the pass is long.
+    fn oncoming_gap(&self, oncoming: LaneID, from: Distance, to: Distance, now: Time) -> bool {
+        let queue = &self.queues[&Traversable::Lane(oncoming)];
+        // The oncoming lane runs the other way
+        let clear_from = queue.geom_len - to - (to - from);
+        let clear_to = queue.geom_len - from;
+
+        let mut occupied: Vec<(Distance, Distance)> = queue
+            .get_car_positions(now, &self.cars, &self.queues)
+            .into_iter()
+            .map(|entry| (entry.back, entry.front))
+            .collect();
+        // Somebody's back might still be sticking out at the end
+        if let Some(id) = queue.laggy_head {
+            occupied.push((
+                queue.geom_len - self.cars[&id].vehicle.length - FOLLOWING_DISTANCE,
+                queue.geom_len,
+            ));
+        }
+        stretch_is_clear(&occupied, clear_from, clear_to)
+    }
+
+    /// Does the given cyclist want to filter past the stopped vehicle in front of them?
+    fn wants_to_filter(&self, bike: &Car, map: &Map) -> Option<CarID> {
+        if self.min_passing_distance.is_none()
+            || !bike.vehicle.vehicle_type.is_bike_like()
+            || bike.router.last_step()
+            || !bike.last_steps.is_empty()
+        {
+            return None;
+        }
+        let lane = map.get_l(bike.router.head().maybe_lane()?);
+        let leader = &self.cars[&self.queues[&bike.router.head()].get_leader(bike.vehicle.id)?];
+        if self.can_filter_past(leader, lane) {
+            Some(leader.vehicle.id)
+        } else {
+            None
+        }
+    }
+
+    fn can_filter_past(&self, car: &Car, lane: &Lane) -> bool {
+        matches!(car.state, CarState::Queued { .. })
+            && !car.router.last_step()
+            && car.last_steps.is_empty()
+            && !car.vehicle.vehicle_type.is_bike_like()
+            && lane.width
+                >= car.vehicle.vehicle_type.width()
+                    + VehicleType::Bike.width()
+                    + FILTERING_CLEARANCE
+    }
+}
+
+/// Where would the vehicle at `idx` pull in, if it overtook the vehicle in front of it? There has
+/// to be room before whatever's in front of that, or the end of the lane, if nothing's in the way
+/// there. The vehicle passed winds up behind, so everything behind has to fit on the lane.
+fn overtaking_slot(
+    dists: &[QueueEntry],
+    idx: usize,
+    vehicle_len: Distance,
+    lane_end: Option<Distance>,
+) -> Option<Distance> {
+    let bound = if idx >= 2 {
+        dists[idx - 2].back - FOLLOWING_DISTANCE
+    } else {
+        lane_end?
+    };
+    let passed = &dists[idx - 1];
+    let new_front = passed.front + FOLLOWING_DISTANCE + vehicle_len;
+    let pushed_back = passed.front - passed.back + FOLLOWING_DISTANCE;
+    if new_front > bound || dists.last().unwrap().back - pushed_back < Distance::ZERO {
+        return None;
+    }
+    Some(new_front)
+}
+
+/// How far up the queue can the cyclist at `idx` filter? They pass as many vehicles as possible,
+/// but never the first one in the queue, which might be waiting at the stop line or about to
+/// turn. The vehicles passed wind up behind the cyclist, so everything behind has to fit on the
+/// lane. Returns the cyclist's new place in the queue.
+fn filtering_slot<F: Fn(&Queued) -> bool>(dists: &[QueueEntry], idx: usize, can_pass: F) -> usize {
+    let last_back = dists.last().unwrap().back;
+    let mut pushed_back = Distance::ZERO;
+    let mut to_idx = idx;
+    while to_idx > 1 {
+        let passed = &dists[to_idx - 1];
+        if !can_pass(&passed.member) {
+            break;
+        }
+        pushed_back += passed.front - passed.back + FOLLOWING_DISTANCE;
+        if last_back - pushed_back < Distance::ZERO {
+            break;
+        }
+        to_idx -= 1;
+    }
+    to_idx
+}
+
+/// Which vehicles are stuck in a line directly behind the member at `idx`? Everybody in the line
+/// is queued right up against the one in front. The line ends at a gap, or at somebody who isn't
+/// queued, or who stopped short for their own reasons, like reaching the end of their trip.
+fn held_up_behind<F: Fn(CarID) -> bool>(
+    dists: &[QueueEntry],
+    idx: usize,
+    is_queued: F,
+) -> Vec<CarID> {
+    let mut held_up = Vec::new();
+    for pair in dists[idx..].windows(2) {
+        let id = match pair[1].member {
+            Queued::Vehicle(id) => id,
+            _ => break,
+        };
+        if !is_queued(id) || pair[1].front < pair[0].back - FOLLOWING_DISTANCE {
+            break;
+        }
+        held_up.push(id);
+    }
+    held_up
+}
+
+/// A driver planned to pass a cyclist expecting to leave some room, assuming a gap in oncoming
+/// traffic. Once they know how much room they'll really leave, should they go ahead? If they
+/// planned on leaving the minimum passing distance, they wait for a gap rather than squeezing
+/// past.
+fn will_pass_with_clearance(
+    actual: Distance,
+    expected: Distance,
+    min_passing_distance: Distance,
+) -> bool {
+    actual >= min_passing_distance
+        || (expected < min_passing_distance && actual >= MIN_SQUEEZE_CLEARANCE)
+}
+
+/// Passing a cyclist with less than the minimum distance is a close pass. Cyclists passing each
+/// other don't count.
+fn is_close_pass(
+    vehicle_type: VehicleType,
+    clearance: Distance,
+    min_passing_distance: Distance,
+) -> bool {
+    !vehicle_type.is_bike_like() && clearance < min_passing_distance
+}
+
+/// Does nothing occupying some (back, front) interval of a lane overlap the stretch between two
+/// distances?
+fn stretch_is_clear(occupied: &[(Distance, Distance)], from: Distance, to: Distance) -> bool {
+    occupied
+        .iter()
+        .all(|(back, front)| *front < from || *back > to)
+}
+
+/// A pathfinder using costs that reflect the traffic at some time
+#[derive(Clone, Serialize, Deserialize)]
+struct LiveRouting {
+    built_at: Time,
+    params: RoutingParams,
+    // Built lazily from the params. Savestates only keep the params, which are much smaller.
+    #[serde(skip_serializing, skip_deserializing)]
+    pathfinder: Option<Arc<Pathfinder>>,
 }
 
 // This implementation relies on the fact that car IDs are unique just by their number. Vehicle
@@ -1702,3 +2396,194 @@ impl IndexableKey for CarID {
         self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: usize, vehicle_type: VehicleType, front: f64, len: f64) -> QueueEntry {
+        QueueEntry {
+            member: Queued::Vehicle(CarID { id, vehicle_type }),
+            front: Distance::meters(front),
+            back: Distance::meters(front - len),
+        }
+    }
+
+    #[test]
+    fn test_overtaking_slot() {
+        let car_len = Distance::meters(4.5);
+        let lane_end = Some(Distance::meters(100.0));
+        let dists = vec![
+            entry(0, VehicleType::Car, 80.0, 4.5),
+            entry(1, VehicleType::Bike, 50.0, 2.0),
+            entry(2, VehicleType::Car, 47.0, 4.5),
+        ];
+        // Pull in just ahead of the cyclist
+        assert_eq!(
+            overtaking_slot(&dists, 2, car_len, lane_end),
+            Some(Distance::meters(55.5))
+        );
+
+        // No room before the next car
+        let dists = vec![
+            entry(0, VehicleType::Car, 56.0, 4.5),
+            entry(1, VehicleType::Bike, 50.0, 2.0),
+            entry(2, VehicleType::Car, 47.0, 4.5),
+        ];
+        assert_eq!(overtaking_slot(&dists, 2, car_len, lane_end), None);
+
+        // The cyclist is first in the queue, so there's room unless somebody's still leaving the
+        // lane
+        let dists = vec![
+            entry(1, VehicleType::Bike, 50.0, 2.0),
+            entry(2, VehicleType::Car, 47.0, 4.5),
+        ];
+        assert_eq!(
+            overtaking_slot(&dists, 1, car_len, lane_end),
+            Some(Distance::meters(55.5))
+        );
+        assert_eq!(overtaking_slot(&dists, 1, car_len, None), None);
+
+        // The cyclist wouldn't fit behind
+        let dists = vec![
+            entry(1, VehicleType::Bike, 8.0, 2.0),
+            entry(2, VehicleType::Car, 5.0, 4.5),
+        ];
+        assert_eq!(overtaking_slot(&dists, 1, car_len, lane_end), None);
+    }
+
+    #[test]
+    fn test_filtering_slot() {
+        let truck = CarID {
+            id: 2,
+            vehicle_type: VehicleType::Truck,
+        };
+        let dists = vec![
+            entry(0, VehicleType::Car, 100.0, 4.5),
+            entry(1, VehicleType::Car, 94.5, 4.5),
+            entry(2, VehicleType::Truck, 89.0, 10.0),
+            entry(3, VehicleType::Car, 78.0, 4.5),
+            entry(4, VehicleType::Bike, 72.5, 2.0),
+            entry(5, VehicleType::Car, 69.5, 4.5),
+        ];
+        // Pass everything except the first vehicle
+        assert_eq!(filtering_slot(&dists, 4, |_| true), 1);
+        // Stop behind the truck
+        assert_eq!(
+            filtering_slot(&dists, 4, |member| *member != Queued::Vehicle(truck)),
+            3
+        );
+        // Nobody to pass
+        assert_eq!(filtering_slot(&dists, 4, |_| false), 4);
+
+        // Everything's close to the start of the lane. Passing the car is fine, but passing the
+        // truck too would push the last car back onto the previous lane.
+        let dists: Vec<QueueEntry> = dists
+            .into_iter()
+            .map(|mut entry| {
+                entry.front -= Distance::meters(58.0);
+                entry.back -= Distance::meters(58.0);
+                entry
+            })
+            .collect();
+        assert_eq!(filtering_slot(&dists, 4, |_| true), 3);
+    }
+
+    #[test]
+    fn test_held_up_behind() {
+        let car = |id| CarID {
+            id,
+            vehicle_type: VehicleType::Car,
+        };
+        let dists = vec![
+            entry(0, VehicleType::Truck, 50.0, 10.0),
+            entry(1, VehicleType::Car, 39.0, 4.5),
+            entry(2, VehicleType::Car, 33.5, 4.5),
+            // Not close enough to be stuck behind anybody
+            entry(3, VehicleType::Car, 20.0, 4.5),
+        ];
+        assert_eq!(held_up_behind(&dists, 0, |_| true), vec![car(1), car(2)]);
+        assert_eq!(held_up_behind(&dists, 1, |_| true), vec![car(2)]);
+        // Somebody still moving ends the line
+        assert_eq!(held_up_behind(&dists, 0, |id| id != car(2)), vec![car(1)]);
+        // Nobody behind
+        assert!(held_up_behind(&dists, 3, |_| true).is_empty());
+
+        // Stopped short of the truck, maybe at the end of their trip
+        let dists = vec![
+            entry(0, VehicleType::Truck, 50.0, 10.0),
+            entry(1, VehicleType::Car, 38.0, 4.5),
+            entry(2, VehicleType::Car, 32.5, 4.5),
+        ];
+        assert!(held_up_behind(&dists, 0, |_| true).is_empty());
+    }
+
+    #[test]
+    fn test_will_pass_with_clearance() {
+        let min = Distance::meters(1.5);
+        // Plenty of room
+        assert!(will_pass_with_clearance(
+            Distance::meters(2.0),
+            Distance::meters(2.0),
+            min
+        ));
+        // The driver expected to use the oncoming lane, but there's no gap, so they wait
+        assert!(!will_pass_with_clearance(
+            Distance::meters(1.0),
+            Distance::meters(3.0),
+            min
+        ));
+        // The driver was always going to squeeze past
+        assert!(will_pass_with_clearance(
+            Distance::meters(1.0),
+            Distance::meters(1.0),
+            min
+        ));
+        // But not that closely
+        assert!(!will_pass_with_clearance(
+            Distance::meters(0.3),
+            Distance::meters(1.0),
+            min
+        ));
+    }
+
+    #[test]
+    fn test_close_pass() {
+        let min = Distance::meters(1.5);
+        assert!(is_close_pass(VehicleType::Car, Distance::meters(1.0), min));
+        assert!(!is_close_pass(VehicleType::Car, Distance::meters(2.0), min));
+        assert!(!is_close_pass(
+            VehicleType::Bike,
+            Distance::meters(1.0),
+            min
+        ));
+    }
+
+    #[test]
+    fn test_stretch_is_clear() {
+        let occupied = vec![
+            (Distance::meters(10.0), Distance::meters(15.0)),
+            (Distance::meters(40.0), Distance::meters(45.0)),
+        ];
+        assert!(stretch_is_clear(
+            &occupied,
+            Distance::meters(16.0),
+            Distance::meters(39.0)
+        ));
+        assert!(!stretch_is_clear(
+            &occupied,
+            Distance::meters(14.0),
+            Distance::meters(20.0)
+        ));
+        assert!(!stretch_is_clear(
+            &occupied,
+            Distance::meters(20.0),
+            Distance::meters(50.0)
+        ));
+        assert!(stretch_is_clear(
+            &[],
+            Distance::meters(0.0),
+            Distance::meters(100.0)
+        ));
+    }
+}
@@ -1,22 +1,40 @@
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
+use rand::{Rng, RngCore, SeedableRng};
+use rand_xorshift::XorShiftRng;
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_btreemap, prettyprint_usize, serialize_btreemap, FixedMap};
 use geom::{Duration, Time};
 use map_model::{
-    ControlStopSign, ControlTrafficSignal, Intersection, IntersectionID, LaneID, Map, StageType,
-    Traversable, TurnID, TurnPriority, TurnType, UberTurn,
+    ControlStopSign, ControlTrafficSignal, Intersection, IntersectionID, LaneID, Map, MovementID,
+    Stage, StageType, TransitRouteID, Traversable, TurnID, TurnPriority, TurnType, UberTurn,
 };
 
 use crate::mechanics::car::{Car, CarState};
 use crate::mechanics::Queue;
+use crate::safety::{self, Approach, ConflictPoint, SafetyConflict};
 use crate::{
-    AgentID, AlertLocation, CarID, Command, DelayCause, Event, Scheduler, SimOptions, Speed,
+    AgentID, AlertLocation, CarID, Command, DelayCause, DrivingSimState, Event, PedestrianID,
+    Problem, Scheduler, SimOptions, Speed, TransitPriorityAction, TripID,
 };
 
 const WAIT_AT_STOP_SIGN: Duration = Duration::const_seconds(0.5);
 const WAIT_BEFORE_YIELD_AT_TRAFFIC_SIGNAL: Duration = Duration::const_seconds(0.2);
+/// When adaptive signals retune splits, never shrink a stage below this.
+const MIN_ADAPTIVE_GREEN: Duration = Duration::const_seconds(5.0);
+/// Transit signal priority never holds a green longer than this past its scheduled end.
+const MAX_TSP_EXTENSION: Duration = Duration::const_seconds(15.0);
+/// When transit signal priority holds a green, leave this long after the vehicle's ETA for it to
+/// cross.
+const TSP_CLEARANCE: Duration = Duration::const_seconds(5.0);
+/// Transit signal priority never cuts a conflicting stage shorter than this.
+const MIN_TSP_GREEN: Duration = Duration::const_seconds(5.0);
+/// A pedestrian waiting for a gap in traffic looks again this often.
+const GAP_RETRY: Duration = Duration::const_seconds(2.0);
+/// A pedestrian crossing in a gap wants an approaching vehicle to arrive at least this long after
+/// they've finished crossing.
+const GAP_SAFETY_MARGIN: Duration = Duration::const_seconds(3.0);
 
 /// Manages conflicts at intersections. When an agent has reached the end of a lane, they call
 /// maybe_start_turn to make a Request. Based on the intersection type (stop sign, traffic signal,
@@ -34,9 +52,36 @@ pub(crate) struct IntersectionSimState {
     break_turn_conflict_cycles: bool,
     handle_uber_turns: bool,
     disable_turn_conflicts: bool,
+    adaptive_signals: bool,
+    measure_safety_conflicts: bool,
     // (x, y) means x is blocked by y. It's a many-to-many relationship. TODO Better data
     // structure.
     blocked_by: BTreeSet<(CarID, CarID)>,
+    // Every active transit vehicle and its route. Used to measure signal delay per route and to
+    // look up which vehicles get transit signal priority.
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    transit_vehicles: BTreeMap<CarID, TransitRouteID>,
+    jaywalking_probability: f64,
+    // The state of an RNG forked from the one used to instantiate the scenario, so a different
+    // seed gives different jaywalkers. Each decision reseeds it.
+    jaywalk_seed: u64,
+    // Whether each pedestrian waiting at a signal decided to jaywalk. Each person makes up their
+    // mind once per crossing, so retrying doesn't make them more likely to go.
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    jaywalk_decisions: BTreeMap<Request, bool>,
+    // Pedestrians crossing against the signal, their trip, and the vehicles that've had to stop
+    // for them
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    jaywalkers: BTreeMap<Request, (TripID, BTreeSet<CarID>)>,
     events: Vec<Event>,
 
     // Count how many calls to maybe_start_turn there are aside from the initial call. Break down
@@ -76,7 +121,9 @@ struct State {
         serialize_with = "serialize_btreemap",
         deserialize_with = "deserialize_btreemap"
     )]
-    leader_eta: BTreeMap<LaneID, (Request, Time)>,
+    leader_eta: BTreeMap<LaneID, (Request, Approach)>,
+    // Turns finished recently, and when. Used to measure post-encroachment time for near-misses.
+    recent_exits: Vec<(Request, Time)>,
 
     signal: Option<SignalState>,
 }
@@ -89,6 +136,26 @@ struct SignalState {
     stage_ends_at: Time,
     // The number of times a variable signal has been extended during the current stage.
     extensions_count: usize,
+    // When the current stage began. Actuated stages measure minimum and maximum green from this.
+    stage_started_at: Time,
+    // Like a loop detector just upstream of the stop line, the last time a vehicle arrived to
+    // make each turn. Only used by actuated stages.
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    last_actuation: BTreeMap<TurnID, Time>,
+    // For actuated stages, is the walk signal showing? Set when the stage begins if a pedestrian
+    // already pushed the call button, or when one pushes it during the stage.
+    walk_called: bool,
+    // When adaptive control is enabled, the retuned duration of every stage for the current
+    // cycle. Empty otherwise, or if the signal doesn't use fixed timing.
+    adaptive_durations: Vec<Duration>,
+    // A transit vehicle with signal priority is approaching to make this turn, arriving at this
+    // time. Only fixed-time stages respond to this.
+    priority_call: Option<(Request, Time)>,
+    // Has the current stage already been held for a priority call?
+    priority_extended: bool,
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Clone, Debug)]
@@ -107,7 +174,14 @@ impl IntersectionSimState {
             break_turn_conflict_cycles: !opts.dont_break_turn_conflict_cycles,
             handle_uber_turns: !opts.dont_handle_uber_turns,
             disable_turn_conflicts: opts.disable_turn_conflicts,
+            adaptive_signals: opts.adaptive_signals,
+            measure_safety_conflicts: opts.measure_safety_conflicts,
             blocked_by: BTreeSet::new(),
+            transit_vehicles: BTreeMap::new(),
+            jaywalking_probability: opts.jaywalking_probability,
+            jaywalk_seed: 0,
+            jaywalk_decisions: BTreeMap::new(),
+            jaywalkers: BTreeMap::new(),
             events: Vec::new(),
 
             total_repeat_requests: 0,
@@ -127,6 +201,7 @@ impl IntersectionSimState {
                 uber_turn_neighbors: Vec::new(),
                 signal: None,
                 leader_eta: BTreeMap::new(),
+                recent_exits: Vec::new(),
             };
             if i.is_traffic_signal() {
                 state.signal = Some(SignalState::new(i.id, Time::START_OF_DAY, map, scheduler));
@@ -153,6 +228,16 @@ impl IntersectionSimState {
         assert!(state.accepted.remove(&Request { agent, turn }));
 
         state.reserved.remove(&Request { agent, turn });
+        if self.measure_safety_conflicts
+            && map.get_t(turn).turn_type != TurnType::SharedSidewalkCorner
+        {
+            state
+                .recent_exits
+                .retain(|(_, t)| now - *t <= safety::MAX_PET);
+            state.recent_exits.push((Request { agent, turn }, now));
+        }
+        self.jaywalkers.remove(&Request { agent, turn });
+        self.jaywalk_decisions.remove(&Request { agent, turn });
         if !handling_live_edits && map.get_t(turn).turn_type != TurnType::SharedSidewalkCorner {
             self.wakeup_waiting(now, turn.parent, scheduler, map);
         }
@@ -175,6 +260,8 @@ impl IntersectionSimState {
     pub fn cancel_request(&mut self, agent: AgentID, turn: TurnID) {
         let state = self.state.get_mut(&turn.parent).unwrap();
         state.waiting.remove(&Request { agent, turn });
+        state.clear_priority_call(agent);
+        self.jaywalk_decisions.remove(&Request { agent, turn });
         if self.break_turn_conflict_cycles {
             if let AgentID::Car(car) = agent {
                 self.blocked_by.retain(|(c1, c2)| *c1 != car && *c2 != car);
@@ -196,11 +283,34 @@ impl IntersectionSimState {
     /// turn.
     pub fn vehicle_gone(&mut self, car: CarID) {
         self.blocked_by.retain(|(c1, c2)| *c1 != car && *c2 != car);
+        if self.transit_vehicles.remove(&car).is_some() {
+            for state in self.state.values_mut() {
+                state.clear_priority_call(AgentID::Car(car));
+            }
+        }
+    }
+
+    /// Jaywalking decisions are drawn from a fork of this RNG. Only call this when jaywalking is
+    /// enabled, so that other scenarios use the RNG the same way as before.
+    pub fn seed_jaywalking(&mut self, mut rng: XorShiftRng) {
+        self.jaywalk_seed = rng.next_u64();
+    }
+
+    pub fn jaywalking_enabled(&self) -> bool {
+        self.jaywalking_probability > 0.0 && !self.use_freeform_policy_everywhere
+    }
+
+    /// Transit vehicles are tracked so their delay at signals can be measured per route, and so
+    /// routes with transit signal priority can request it.
+    pub fn transit_vehicle_created(&mut self, car: CarID, route: TransitRouteID) {
+        self.transit_vehicles.insert(car, route);
     }
 
     pub fn agent_deleted_mid_turn(&mut self, agent: AgentID, turn: TurnID) {
         let state = self.state.get_mut(&turn.parent).unwrap();
         assert!(state.accepted.remove(&Request { agent, turn }));
+        self.jaywalkers.remove(&Request { agent, turn });
+        self.jaywalk_decisions.remove(&Request { agent, turn });
 
         // This agent might have a few more nearby turns reserved, because they're part of an
         // uber-turn. It's a blunt response to just clear them all out, but it should be correct.
@@ -291,47 +401,71 @@ impl IntersectionSimState {
         id: IntersectionID,
         map: &Map,
         scheduler: &mut Scheduler,
+        driving: &DrivingSimState,
     ) {
         let i = map.get_i(id);
+        let adaptive_signals = self.adaptive_signals;
 
-        // trivial function that advances the signal stage and returns duration
-        fn advance(
-            signal_state: &mut SignalState,
-            signal: &ControlTrafficSignal,
-            i: &Intersection,
-            allow_crosswalk_skip: bool,
-        ) -> Duration {
-            signal_state.current_stage = (signal_state.current_stage + 1) % signal.stages.len();
-            let stage = &signal.stages[signal_state.current_stage];
-            // only skip for variable all-walk crosswalk
-            if let StageType::Variable(_, _, _) = stage.stage_type {
-                if allow_crosswalk_skip && stage.max_crosswalk_time(i).is_some() {
-                    // we can skip this stage, as its all walk and we're allowed to skip (no
-                    // pedestrian waiting).
-                    signal_state.current_stage =
-                        (signal_state.current_stage + 1) % signal.stages.len();
-                }
-            }
-            signal.stages[signal_state.current_stage]
-                .stage_type
-                .simple_duration()
-        }
         let state = self.state.get_mut(&id).unwrap();
-        let signal_state = state.signal.as_mut().unwrap();
-        let signal = map.get_traffic_signal(id);
+        let calls = state.movements_with_calls(i);
         let ped_waiting = state.waiting.keys().any(|req| {
             if let AgentID::Pedestrian(_) = req.agent {
                 return true;
             }
             false
         });
-        let duration: Duration;
+        let signal_state = state.signal.as_mut().unwrap();
+        let signal = map.get_traffic_signal(id);
+        let mut duration: Duration;
         // Switch to a new stage?
         assert_eq!(now, signal_state.stage_ends_at);
         let old_stage = &signal.stages[signal_state.current_stage];
         match old_stage.stage_type {
-            StageType::Fixed(_) => {
-                duration = advance(signal_state, signal, i, !ped_waiting);
+            StageType::Fixed(dt) => {
+                let scheduled_end = signal_state.stage_started_at
+                    + signal_state
+                        .adaptive_durations
+                        .get(signal_state.current_stage)
+                        .cloned()
+                        .unwrap_or(dt);
+                // Hold the green for an approaching transit vehicle with priority, if it'll arrive
+                // soon enough.
+                let hold_until = signal_state
+                    .priority_call
+                    .as_ref()
+                    .filter(|(req, _)| {
+                        old_stage.get_priority_of_turn(req.turn, i) != TurnPriority::Banned
+                    })
+                    .and_then(|(req, eta)| {
+                        priority_hold(now, scheduled_end, *eta, signal_state.priority_extended)
+                            .map(|t| (req.agent.as_car(), t))
+                    });
+                if let Some((car, hold_until)) = hold_until {
+                    signal_state.priority_extended = true;
+                    duration = hold_until - now;
+                    self.events.push(Event::TransitSignalPriority(
+                        car,
+                        self.transit_vehicles[&car],
+                        id,
+                        TransitPriorityAction::ExtendedGreen,
+                    ));
+                } else {
+                    duration = signal_state.advance(now, signal, i, !ped_waiting, &calls);
+                    // If the priority vehicle still can't go in the new stage, keep it short.
+                    if let Some((req, eta)) = signal_state.priority_call.as_ref() {
+                        let stage = &signal.stages[signal_state.current_stage];
+                        if matches!(stage.stage_type, StageType::Fixed(_))
+                            && stage.get_priority_of_turn(req.turn, i) == TurnPriority::Banned
+                        {
+                            let min_green = std::cmp::max(
+                                MIN_TSP_GREEN,
+                                signal.get_min_crossing_time(signal_state.current_stage, i),
+                            );
+                            let end = priority_red_end(now, now, min_green, *eta);
+                            duration = std::cmp::min(duration, end - now);
+                        }
+                    }
+                }
             }
             StageType::Variable(min, delay, additional) => {
                 // test if anyone is waiting in current stage, and if so, extend the signal cycle.
@@ -349,7 +483,7 @@ impl IntersectionSimState {
                             min, delay, additional, signal_state.extensions_count
                         ),
                     ));
-                    duration = advance(signal_state, signal, i, !ped_waiting);
+                    duration = signal_state.advance(now, signal, i, !ped_waiting, &calls);
                     signal_state.extensions_count = 0;
                 } else if state.waiting.keys().all(|req| {
                     if let AgentID::Pedestrian(_) = req.agent {
@@ -360,7 +494,7 @@ impl IntersectionSimState {
                     old_stage.get_priority_of_turn(req.turn, i) != TurnPriority::Protected
                 }) {
                     signal_state.extensions_count = 0;
-                    duration = advance(signal_state, signal, i, !ped_waiting);
+                    duration = signal_state.advance(now, signal, i, !ped_waiting, &calls);
                 } else {
                     signal_state.extensions_count += 1;
                     duration = delay;
@@ -373,6 +507,67 @@ impl IntersectionSimState {
                     ));
                 }
             }
+            StageType::Actuated(_, max, passage) => {
+                let conflicting_call =
+                    has_conflicting_call(signal, signal_state.current_stage, &calls);
+                // The most recent vehicle detected on a protected movement
+                let last_actuation = signal_state
+                    .last_actuation
+                    .iter()
+                    .filter(|(turn, _)| {
+                        old_stage.get_priority_of_turn(**turn, i) == TurnPriority::Protected
+                    })
+                    .map(|(_, time)| *time)
+                    .max();
+
+                match actuated_decision(
+                    now,
+                    signal_state.stage_started_at,
+                    max,
+                    passage,
+                    conflicting_call,
+                    last_actuation,
+                ) {
+                    ActuatedDecision::Rest(dt) | ActuatedDecision::Extend(dt) => {
+                        duration = dt;
+                    }
+                    ActuatedDecision::MaxOut => {
+                        self.events.push(Event::Alert(
+                            AlertLocation::Intersection(id),
+                            format!(
+                                "actuated stage maxed out after {}",
+                                now - signal_state.stage_started_at
+                            ),
+                        ));
+                        duration = signal_state.advance(now, signal, i, !ped_waiting, &calls);
+                    }
+                    ActuatedDecision::GapOut => {
+                        duration = signal_state.advance(now, signal, i, !ped_waiting, &calls);
+                    }
+                }
+            }
+        }
+
+        // At the start of every cycle, adaptive signals retune their splits based on the current
+        // queues.
+        if adaptive_signals
+            && signal_state.current_stage == 0
+            && signal_state.stage_started_at == now
+        {
+            signal_state.adaptive_durations = retune_splits(signal, i, driving);
+            if let StageType::Fixed(dt) = signal.stages[0].stage_type {
+                let retuned = signal_state
+                    .adaptive_durations
+                    .first()
+                    .cloned()
+                    .unwrap_or(dt);
+                // Don't undo transit signal priority cutting this stage short
+                duration = if signal_state.priority_call.is_some() {
+                    std::cmp::min(duration, retuned)
+                } else {
+                    retuned
+                };
+            }
         }
 
         signal_state.stage_ends_at = now + duration;
@@ -403,6 +598,14 @@ impl IntersectionSimState {
     ) -> bool {
         #![allow(clippy::logic_bug)] // Remove once TODO below is taken care of
         let req = Request { agent, turn };
+        let approach = Approach {
+            start: now,
+            speed,
+            length: maybe_cars_and_queues
+                .as_ref()
+                .map(|(car, _, _)| car.vehicle.length)
+                .unwrap_or(safety::PEDESTRIAN_LENGTH),
+        };
 
         if let Some(_eta) = self
             .state
@@ -433,13 +636,20 @@ impl IntersectionSimState {
         };
         entry.or_insert((now, urgent));
 
+        let shared_sidewalk_corner =
+            map.get_t(req.turn).turn_type == TurnType::SharedSidewalkCorner;
+
         if repeat_request {
             self.total_repeat_requests += 1;
+        } else if let AgentID::Car(_) = agent {
+            // The vehicle just reached the stop line, so trip the detector.
+            if let Some(ref mut signal) = self.state.get_mut(&turn.parent).unwrap().signal {
+                signal.last_actuation.insert(turn, now);
+            }
+        } else if !shared_sidewalk_corner {
+            self.pedestrian_call(&req, now, map, scheduler);
         }
 
-        let shared_sidewalk_corner =
-            map.get_t(req.turn).turn_type == TurnType::SharedSidewalkCorner;
-
         let readonly_pair = maybe_cars_and_queues.as_ref().map(|(_, c, q)| (*c, &**q));
         let started_uber_turn = |state: &Self, car: &Car| {
             state.handle_uber_turns && car.router.get_path().currently_inside_ut().is_some()
@@ -584,7 +794,24 @@ impl IntersectionSimState {
         // TODO For now, we're only interested in signals, and there's too much raw data to store
         // for stop signs too.
         let state = self.state.get_mut(&turn.parent).unwrap();
-        state.waiting.remove(&req).unwrap();
+        let (waiting_since, _) = state.waiting.remove(&req).unwrap();
+        state.clear_priority_call(agent);
+        if state.signal.is_some() {
+            if let AgentID::Car(car) = agent {
+                if let Some(route) = self.transit_vehicles.get(&car) {
+                    self.events.push(Event::TransitDelayedAtSignal(
+                        car,
+                        *route,
+                        turn,
+                        now - waiting_since,
+                    ));
+                }
+            }
+        }
+        if !shared_sidewalk_corner {
+            self.measure_conflicts(&req, approach, now, map);
+        }
+        let state = self.state.get_mut(&turn.parent).unwrap();
         state.accepted.insert(req);
         if self.break_turn_conflict_cycles {
             if let AgentID::Car(car) = agent {
@@ -594,6 +821,147 @@ impl IntersectionSimState {
         true
     }
 
+    /// A pedestrian who isn't allowed to cross at a traffic signal might cross anyway, if there's
+    /// a gap in traffic. Returns true if they start crossing. Call this only after
+    /// `maybe_start_turn` fails.
+    pub fn maybe_jaywalk(
+        &mut self,
+        ped: PedestrianID,
+        trip: TripID,
+        turn: TurnID,
+        speed: Speed,
+        now: Time,
+        map: &Map,
+        scheduler: &mut Scheduler,
+    ) -> bool {
+        if !self.jaywalking_enabled() {
+            return false;
+        }
+        let t = map.get_t(turn);
+        if !t.turn_type.pedestrian_crossing() {
+            return false;
+        }
+        let signal = match map.maybe_get_traffic_signal(turn.parent) {
+            Some(signal) => signal,
+            None => {
+                return false;
+            }
+        };
+
+        let req = Request {
+            agent: AgentID::Pedestrian(ped),
+            turn,
+        };
+        let probability = self.jaywalking_probability;
+        let seed = &mut self.jaywalk_seed;
+        if !*self
+            .jaywalk_decisions
+            .entry(req.clone())
+            .or_insert_with(|| decide_to_jaywalk(seed, probability))
+        {
+            return false;
+        }
+
+        let state = &self.state[&turn.parent];
+        let stage = &signal.stages[state.signal.as_ref().unwrap().current_stage];
+        let i = map.get_i(turn.parent);
+        // Also wait for anybody about to start a conflicting turn with a green
+        let gap = self.gap_in_traffic(&req, speed, now, map)
+            && !state.waiting.keys().any(|other| {
+                other.agent != req.agent
+                    && stage.get_priority_of_turn(other.turn, i) != TurnPriority::Banned
+                    && map.get_t(other.turn).conflicts_with(t)
+            });
+        if !gap {
+            scheduler.update(now + GAP_RETRY, Command::UpdatePed(ped));
+            return false;
+        }
+
+        // The signal policy may have scheduled a retry; the caller schedules the end of the
+        // crossing instead.
+        scheduler.cancel(Command::UpdatePed(ped));
+        self.measure_conflicts(
+            &req,
+            Approach {
+                start: now,
+                speed,
+                length: safety::PEDESTRIAN_LENGTH,
+            },
+            now,
+            map,
+        );
+        let state = self.state.get_mut(&turn.parent).unwrap();
+        state.waiting.remove(&req);
+        state.accepted.insert(req.clone());
+        self.jaywalkers.insert(req, (trip, BTreeSet::new()));
+        self.events
+            .push(Event::ProblemEncountered(trip, Problem::Jaywalking(turn)));
+        true
+    }
+
+    /// Is nobody in a turn conflicting with a pedestrian's, and will no approaching vehicle arrive
+    /// to start one before they've finished crossing?
+    fn gap_in_traffic(&self, req: &Request, speed: Speed, now: Time, map: &Map) -> bool {
+        let state = &self.state[&req.turn.parent];
+        let t = map.get_t(req.turn);
+        !state
+            .accepted
+            .iter()
+            .chain(state.reserved.iter())
+            .any(|other| map.get_t(other.turn).conflicts_with(t))
+            && gap_before_arrivals(
+                state
+                    .leader_eta
+                    .values()
+                    .filter(|(other, _)| map.get_t(other.turn).conflicts_with(t))
+                    .map(|(_, approach)| approach.start),
+                now,
+                t.geom.length() / speed,
+            )
+    }
+
+    /// An agent is about to start a turn. Record a near-miss with anybody who recently finished a
+    /// conflicting turn, or who's about to start one and would collide with this agent if both
+    /// kept going.
+    fn measure_conflicts(&mut self, req: &Request, approach: Approach, now: Time, map: &Map) {
+        if !self.measure_safety_conflicts {
+            return;
+        }
+        let state = &self.state[&req.turn.parent];
+        let t = map.get_t(req.turn);
+        let conflict_point = |other: &Request| {
+            if other.agent == req.agent {
+                return None;
+            }
+            ConflictPoint::new(t, map.get_t(other.turn))
+        };
+        let conflict = |other: &Request, ttc, pet| {
+            Event::SafetyConflict(SafetyConflict {
+                turn: req.turn,
+                agent_type: req.agent.to_type(),
+                other_turn: other.turn,
+                other_agent_type: other.agent.to_type(),
+                ttc,
+                pet,
+            })
+        };
+
+        for (other, exited) in &state.recent_exits {
+            if let Some(pet) = conflict_point(other)
+                .and_then(|point| safety::post_encroachment_time(point, *exited, now))
+            {
+                self.events.push(conflict(other, None, Some(pet)));
+            }
+        }
+        for (other, other_approach) in state.leader_eta.values() {
+            if let Some(ttc) = conflict_point(other)
+                .and_then(|point| safety::time_to_collision(point, approach, *other_approach, now))
+            {
+                self.events.push(conflict(other, Some(ttc), None));
+            }
+        }
+    }
+
     pub fn collect_events(&mut self) -> Vec<Event> {
         std::mem::take(&mut self.events)
     }
@@ -610,6 +978,11 @@ impl IntersectionSimState {
                 state.signal.as_mut(),
             ) {
                 (Some(ts), Some(signal_state)) => {
+                    // The stages might have changed, so fall back to the configured timing until
+                    // the next cycle.
+                    signal_state.adaptive_durations.clear();
+                    signal_state.last_actuation.clear();
+                    signal_state.priority_call = None;
                     if signal_state.current_stage >= ts.stages.len() {
                         // Just jump back to the first one. Shrug.
                         signal_state.current_stage = 0;
@@ -672,13 +1045,141 @@ impl IntersectionSimState {
     // This is "best effort". If we get something wrong, somebody might start a turn and cut off an
     // approaching vehicle.
     // And it's idempotent -- can call to update an ETA.
-    pub fn approaching_leader(&mut self, agent: AgentID, turn: TurnID, eta: Time) {
+    pub fn approaching_leader(
+        &mut self,
+        agent: AgentID,
+        turn: TurnID,
+        approach: Approach,
+        now: Time,
+        map: &Map,
+        scheduler: &mut Scheduler,
+    ) {
+        let eta = approach.start;
+        let has_priority = match agent {
+            AgentID::Car(car) => self
+                .transit_vehicles
+                .get(&car)
+                .map(|route| map.get_edits().transit_signal_priority.contains(route))
+                .unwrap_or(false),
+            _ => false,
+        };
+        if has_priority && !self.use_freeform_policy_everywhere {
+            self.request_transit_priority(Request { agent, turn }, eta, now, map, scheduler);
+        }
+
         let state = self.state.get_mut(&turn.parent).unwrap();
         // If there was a previous entry here for turn.src, then this leader is spawning in front
         // of the previous leader on a driveway
         state
             .leader_eta
-            .insert(turn.src, (Request { agent, turn }, eta));
+            .insert(turn.src, (Request { agent, turn }, approach));
+        // Approaching vehicles also trip the detector for actuated signals, as of when they'll
+        // arrive.
+        if let Some(ref mut signal) = state.signal {
+            signal.last_actuation.insert(turn, eta);
+        }
+    }
+
+    /// A transit vehicle with signal priority is approaching a signal. If the current stage is
+    /// green for it, update_intersection may hold the green. Otherwise, cut the current stage
+    /// short, as long as it's served a minimum green. The signal doesn't try to recover its
+    /// original offset afterwards.
+    fn request_transit_priority(
+        &mut self,
+        req: Request,
+        eta: Time,
+        now: Time,
+        map: &Map,
+        scheduler: &mut Scheduler,
+    ) {
+        let id = req.turn.parent;
+        let signal = if let Some(signal) = map.maybe_get_traffic_signal(id) {
+            signal
+        } else {
+            return;
+        };
+        let i = map.get_i(id);
+        let signal_state =
+            if let Some(signal_state) = self.state.get_mut(&id).unwrap().signal.as_mut() {
+                signal_state
+            } else {
+                return;
+            };
+        let stage = &signal.stages[signal_state.current_stage];
+        // Actuated and variable stages already respond to demand
+        if !matches!(stage.stage_type, StageType::Fixed(_)) {
+            return;
+        }
+        let agent = req.agent;
+        let banned = stage.get_priority_of_turn(req.turn, i) == TurnPriority::Banned;
+        signal_state.priority_call = Some((req, eta));
+        if !banned {
+            return;
+        }
+
+        let min_green = std::cmp::max(
+            MIN_TSP_GREEN,
+            signal.get_min_crossing_time(signal_state.current_stage, i),
+        );
+        let new_end = priority_red_end(now, signal_state.stage_started_at, min_green, eta);
+        if new_end < signal_state.stage_ends_at {
+            signal_state.stage_ends_at = new_end;
+            scheduler.update(new_end, Command::UpdateIntersection(id));
+            let car = agent.as_car();
+            self.events.push(Event::TransitSignalPriority(
+                car,
+                self.transit_vehicles[&car],
+                id,
+                TransitPriorityAction::TruncatedRed,
+            ));
+        }
+    }
+
+    /// A pedestrian pushed the call button at a signal. Calls for other stages are served when
+    /// the signal advances, but an actuated stage resting in green never advances on its own. So
+    /// if the crosswalk belongs to the current actuated stage and the walk signal isn't showing,
+    /// show it now and hold the stage long enough to cross. If other movements are waiting and
+    /// that would run past the maximum green, leave the call for the next time the stage is
+    /// served.
+    fn pedestrian_call(&mut self, req: &Request, now: Time, map: &Map, scheduler: &mut Scheduler) {
+        let id = req.turn.parent;
+        let signal = if let Some(signal) = map.maybe_get_traffic_signal(id) {
+            signal
+        } else {
+            return;
+        };
+        let i = map.get_i(id);
+        let state = self.state.get_mut(&id).unwrap();
+        let calls = state.movements_with_calls(i);
+        let signal_state = if let Some(signal_state) = state.signal.as_mut() {
+            signal_state
+        } else {
+            return;
+        };
+        let stage = &signal.stages[signal_state.current_stage];
+        let max = if let StageType::Actuated(_, max, _) = stage.stage_type {
+            max
+        } else {
+            return;
+        };
+        let movement = i.turn_to_movement(req.turn).0;
+        if signal_state.walk_called
+            || stage.get_priority_of_movement(movement) != TurnPriority::Protected
+        {
+            return;
+        }
+
+        let walk_until = now + signal.get_min_crossing_time(signal_state.current_stage, i);
+        if has_conflicting_call(signal, signal_state.current_stage, &calls)
+            && walk_until > signal_state.stage_started_at + max
+        {
+            return;
+        }
+        signal_state.walk_called = true;
+        if walk_until > signal_state.stage_ends_at {
+            signal_state.stage_ends_at = walk_until;
+            scheduler.update(walk_until, Command::UpdateIntersection(id));
+        }
     }
 }
 
@@ -881,6 +1382,16 @@ impl IntersectionSimState {
         // TODO Make sure we can optimistically finish this turn before an approaching
         // higher-priority vehicle wants to begin.
 
+        // Vehicles don't have to stop for people at unmarked crossings, including ones in the
+        // middle of a block, so wait for a gap in traffic
+        if req.agent.is_pedestrian()
+            && map.get_t(req.turn).turn_type == TurnType::UnmarkedCrossing
+            && !self.gap_in_traffic(req, speed, now, map)
+        {
+            scheduler.update(now + GAP_RETRY, Command::update_agent(req.agent));
+            return false;
+        }
+
         // If a pedestrian is going to cut off a car, check how long the car has been waiting and
         // maybe yield (regardless of stop sign priority). This is a very rough start to more
         // realistic "batching" of pedestrians to cross a street. Without this, if there's one
@@ -939,6 +1450,14 @@ impl IntersectionSimState {
             return false;
         }
 
+        // Actuated stages only show a walk signal if somebody pushed the button. pedestrian_call
+        // handles arrivals during the stage; otherwise, wait for the next time it's served.
+        if let StageType::Actuated(_, _, _) = stage.stage_type {
+            if req.agent.is_pedestrian() && !signal_state.walk_called {
+                return false;
+            }
+        }
+
         if our_priority == TurnPriority::Yield
             && now < our_time + WAIT_BEFORE_YIELD_AT_TRAFFIC_SIGNAL
         {
@@ -964,8 +1483,13 @@ impl IntersectionSimState {
         // Optimistically if nobody else is in the way, this is how long it'll take to finish the
         // turn. Don't start the turn if we won't finish by the time the light changes. If we get
         // it wrong, that's fine -- block the box a bit.
+        //
+        // Actuated stages keep extending while vehicles are detected, so the time remaining is
+        // unknown. Just go.
         let time_to_cross = turn.geom.length() / speed;
-        if time_to_cross > remaining_stage_time {
+        if time_to_cross > remaining_stage_time
+            && !matches!(stage.stage_type, StageType::Actuated(_, _, _))
+        {
             // Signals enforce a minimum crosswalk time, but some pedestrians are configured to
             // walk very slowly. In that case, allow them to go anyway and wind up in the crosswalk
             // during a red. This matches reality reasonably.
@@ -996,6 +1520,18 @@ impl IntersectionSimState {
             // Never short-circuit; always record all of the dependencies; it might help someone
             // else unstick things.
             if map.get_t(other.turn).conflicts_with(turn) {
+                // Log the first time each vehicle has to wait for a jaywalker
+                if let (AgentID::Car(car), Some((trip, stopped))) =
+                    (req.agent, self.jaywalkers.get_mut(other))
+                {
+                    if stopped.insert(car) {
+                        self.events.push(Event::ProblemEncountered(
+                            *trip,
+                            Problem::JaywalkerConflict(other.turn),
+                        ));
+                    }
+                }
+
                 if self.break_turn_conflict_cycles {
                     if let AgentID::Car(c) = req.agent {
                         if let AgentID::Car(c2) = other.agent {
@@ -1109,6 +1645,12 @@ impl SignalState {
             current_stage: 0,
             stage_ends_at: now,
             extensions_count: 0,
+            stage_started_at: now,
+            last_actuation: BTreeMap::new(),
+            walk_called: false,
+            adaptive_durations: Vec::new(),
+            priority_call: None,
+            priority_extended: false,
         };
 
         let signal = map.get_traffic_signal(id);
@@ -1126,12 +1668,239 @@ impl SignalState {
                 }
             } else {
                 state.stage_ends_at = now + dt - offset;
+                // The offset might reach back before midnight
+                state.stage_started_at = now - std::cmp::min(offset, now - Time::START_OF_DAY);
                 break;
             }
         }
         scheduler.push(state.stage_ends_at, Command::UpdateIntersection(id));
         state
     }
+
+    /// Moves to the next stage, returning how long it should last before being checked again.
+    /// Actuated stages without any calls are skipped.
+    fn advance(
+        &mut self,
+        now: Time,
+        signal: &ControlTrafficSignal,
+        i: &Intersection,
+        allow_crosswalk_skip: bool,
+        calls: &BTreeSet<MovementID>,
+    ) -> Duration {
+        for _ in 0..signal.stages.len() {
+            self.current_stage = (self.current_stage + 1) % signal.stages.len();
+            let stage = &signal.stages[self.current_stage];
+            if let StageType::Actuated(_, _, _) = stage.stage_type {
+                if !stage_has_call(stage, calls) {
+                    continue;
+                }
+            }
+            break;
+        }
+        let stage = &signal.stages[self.current_stage];
+        // only skip for variable all-walk crosswalk
+        if let StageType::Variable(_, _, _) = stage.stage_type {
+            if allow_crosswalk_skip && stage.max_crosswalk_time(i).is_some() {
+                // we can skip this stage, as its all walk and we're allowed to skip (no
+                // pedestrian waiting).
+                self.current_stage = (self.current_stage + 1) % signal.stages.len();
+            }
+        }
+        self.stage_started_at = now;
+        self.priority_extended = false;
+
+        let stage = &signal.stages[self.current_stage];
+        match stage.stage_type {
+            StageType::Fixed(d) => self
+                .adaptive_durations
+                .get(self.current_stage)
+                .cloned()
+                .unwrap_or(d),
+            StageType::Variable(min, _, _) => min,
+            StageType::Actuated(min, _, _) => {
+                // Did somebody push the button for one of the crosswalks?
+                self.walk_called = stage
+                    .protected_movements
+                    .iter()
+                    .any(|m| m.crosswalk && calls.contains(m));
+                if self.walk_called {
+                    std::cmp::max(min, signal.get_min_crossing_time(self.current_stage, i))
+                } else {
+                    min
+                }
+            }
+        }
+    }
+}
+
+impl State {
+    fn clear_priority_call(&mut self, agent: AgentID) {
+        if let Some(ref mut signal) = self.signal {
+            if signal
+                .priority_call
+                .as_ref()
+                .map(|(req, _)| req.agent == agent)
+                .unwrap_or(false)
+            {
+                signal.priority_call = None;
+            }
+        }
+    }
+
+    /// Every movement with somebody waiting to use it. For actuated signals, this is the set of
+    /// calls from vehicle detectors and pedestrian push buttons.
+    fn movements_with_calls(&self, i: &Intersection) -> BTreeSet<MovementID> {
+        self.waiting
+            .keys()
+            .map(|req| i.turn_to_movement(req.turn).0)
+            .collect()
+    }
+}
+
+fn stage_has_call(stage: &Stage, calls: &BTreeSet<MovementID>) -> bool {
+    calls
+        .iter()
+        .any(|m| stage.get_priority_of_movement(*m) != TurnPriority::Banned)
+}
+
+/// Does any stage besides the current one have a call waiting?
+fn has_conflicting_call(
+    signal: &ControlTrafficSignal,
+    current_stage: usize,
+    calls: &BTreeSet<MovementID>,
+) -> bool {
+    signal
+        .stages
+        .iter()
+        .enumerate()
+        .any(|(idx, stage)| idx != current_stage && stage_has_call(stage, calls))
+}
+
+#[derive(Debug, PartialEq)]
+enum ActuatedDecision {
+    /// Nobody else wants to go, so rest in green and check again after this long.
+    Rest(Duration),
+    /// A vehicle arrived recently, so hold the green this much longer.
+    Extend(Duration),
+    /// No vehicle arrived within the passage time, so end the stage.
+    GapOut,
+    /// Other movements have waited through the maximum green, so end the stage.
+    MaxOut,
+}
+
+/// Decides what an actuated stage does when it's checked. `last_actuation` is the most recent
+/// time a vehicle was detected on one of the stage's protected movements.
+fn actuated_decision(
+    now: Time,
+    stage_started_at: Time,
+    max: Duration,
+    passage: Duration,
+    conflicting_call: bool,
+    last_actuation: Option<Time>,
+) -> ActuatedDecision {
+    let passage = std::cmp::max(Duration::const_seconds(1.0), passage);
+    let elapsed = now - stage_started_at;
+    if !conflicting_call {
+        ActuatedDecision::Rest(passage)
+    } else if elapsed >= max {
+        ActuatedDecision::MaxOut
+    } else if let Some(t) = last_actuation.filter(|t| *t + passage > now) {
+        // Never extend past the maximum
+        ActuatedDecision::Extend(std::cmp::min(t + passage - now, max - elapsed))
+    } else {
+        ActuatedDecision::GapOut
+    }
+}
+
+/// Should a stage that's green for an approaching transit vehicle with priority be held past its
+/// scheduled end? Returns when to end it instead, once the vehicle has had time to clear the
+/// intersection. A stage is only held once, and never for more than MAX_TSP_EXTENSION.
+fn priority_hold(
+    now: Time,
+    scheduled_end: Time,
+    eta: Time,
+    already_extended: bool,
+) -> Option<Time> {
+    if already_extended || eta < now || eta + TSP_CLEARANCE > scheduled_end + MAX_TSP_EXTENSION {
+        return None;
+    }
+    Some(eta + TSP_CLEARANCE)
+}
+
+/// When should a fixed stage that's red for an approaching transit vehicle with priority end? Not
+/// before it's served its minimum green, and not so early that the green for the vehicle starts
+/// much before it arrives.
+fn priority_red_end(now: Time, stage_started_at: Time, min_green: Duration, eta: Time) -> Time {
+    let earliest = std::cmp::max(now, stage_started_at + min_green);
+    if eta > earliest + TSP_CLEARANCE {
+        eta - TSP_CLEARANCE
+    } else {
+        earliest
+    }
+}
+
+/// Splits the cycle length of a fixed-time signal between its stages, in proportion to the
+/// "pressure" of each stage -- the number of vehicles queued to use its protected movements, minus
+/// the number already queued downstream. This is a rough version of max-pressure control. Returns
+/// nothing if the signal doesn't use fixed timing or there's no demand.
+fn retune_splits(
+    signal: &ControlTrafficSignal,
+    i: &Intersection,
+    driving: &DrivingSimState,
+) -> Vec<Duration> {
+    if signal
+        .stages
+        .iter()
+        .any(|s| !matches!(s.stage_type, StageType::Fixed(_)))
+    {
+        return Vec::new();
+    }
+
+    let mut pressures = Vec::new();
+    let mut floors = Vec::new();
+    for (idx, stage) in signal.stages.iter().enumerate() {
+        let mut upstream = BTreeSet::new();
+        let mut downstream = BTreeSet::new();
+        for m in &stage.protected_movements {
+            if m.crosswalk {
+                continue;
+            }
+            for t in &i.movements[m].members {
+                upstream.insert(t.src);
+                downstream.insert(t.dst);
+            }
+        }
+        let queued_upstream: usize = upstream
+            .into_iter()
+            .map(|l| driving.queue_length(Traversable::Lane(l)))
+            .sum();
+        let queued_downstream: usize = downstream
+            .into_iter()
+            .map(|l| driving.queue_length(Traversable::Lane(l)))
+            .sum();
+        pressures.push(queued_upstream.saturating_sub(queued_downstream) as f64);
+        floors.push(std::cmp::max(
+            MIN_ADAPTIVE_GREEN,
+            signal.get_min_crossing_time(idx, i),
+        ));
+    }
+
+    split_cycle(signal.simple_cycle_duration(), floors, pressures)
+}
+
+/// Gives every stage its floor, then divides the rest of the cycle in proportion to each stage's
+/// pressure. Returns nothing if there's no pressure or no time to spare.
+fn split_cycle(cycle: Duration, floors: Vec<Duration>, pressures: Vec<f64>) -> Vec<Duration> {
+    let total_pressure: f64 = pressures.iter().sum();
+    let spare = cycle - floors.iter().cloned().sum::<Duration>();
+    if total_pressure == 0.0 || spare <= Duration::ZERO {
+        return Vec::new();
+    }
+    floors
+        .into_iter()
+        .zip(pressures)
+        .map(|(floor, pressure)| floor + spare * (pressure / total_pressure))
+        .collect()
 }
 
 fn allow_block_the_box(i: &Intersection) -> bool {
@@ -1173,3 +1942,227 @@ fn allow_block_the_box(i: &Intersection) -> bool {
     }
     false
 }
+
+/// Will every approaching vehicle arrive long enough after a pedestrian finishes crossing?
+fn gap_before_arrivals(
+    mut etas: impl Iterator<Item = Time>,
+    now: Time,
+    time_to_cross: Duration,
+) -> bool {
+    etas.all(|eta| eta >= now + time_to_cross + GAP_SAFETY_MARGIN)
+}
+
+/// Decide whether somebody jaywalks, then reseed the RNG for the next decision.
+fn decide_to_jaywalk(seed: &mut u64, probability: f64) -> bool {
+    let mut rng = XorShiftRng::seed_from_u64(*seed);
+    let decision = rng.gen_bool(probability.min(1.0));
+    *seed = rng.next_u64();
+    decision
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(s: f64) -> Duration {
+        Duration::seconds(s)
+    }
+
+    #[test]
+    fn test_actuated_decision() {
+        let start = Time::START_OF_DAY + Duration::hours(8);
+        let max = secs(40.0);
+        let passage = secs(3.0);
+
+        // Nobody else is waiting, so rest in green even past the maximum
+        assert_eq!(
+            actuated_decision(start + secs(60.0), start, max, passage, false, None),
+            ActuatedDecision::Rest(passage)
+        );
+
+        // A vehicle arrived 1s ago, so extend until its passage time runs out
+        assert_eq!(
+            actuated_decision(
+                start + secs(20.0),
+                start,
+                max,
+                passage,
+                true,
+                Some(start + secs(19.0))
+            ),
+            ActuatedDecision::Extend(secs(2.0))
+        );
+
+        // Extensions never run past the maximum
+        assert_eq!(
+            actuated_decision(
+                start + secs(39.0),
+                start,
+                max,
+                passage,
+                true,
+                Some(start + secs(39.0))
+            ),
+            ActuatedDecision::Extend(secs(1.0))
+        );
+
+        // Gap out: the last vehicle passed longer ago than the passage time
+        assert_eq!(
+            actuated_decision(
+                start + secs(20.0),
+                start,
+                max,
+                passage,
+                true,
+                Some(start + secs(15.0))
+            ),
+            ActuatedDecision::GapOut
+        );
+        assert_eq!(
+            actuated_decision(start + secs(20.0), start, max, passage, true, None),
+            ActuatedDecision::GapOut
+        );
+
+        // Max out: vehicles keep arriving, but others have waited long enough
+        assert_eq!(
+            actuated_decision(
+                start + secs(40.0),
+                start,
+                max,
+                passage,
+                true,
+                Some(start + secs(39.5))
+            ),
+            ActuatedDecision::MaxOut
+        );
+
+        // A tiny passage time is rounded up, so the signal doesn't check constantly
+        assert_eq!(
+            actuated_decision(start, start, max, Duration::ZERO, false, None),
+            ActuatedDecision::Rest(secs(1.0))
+        );
+    }
+
+    #[test]
+    fn test_split_cycle() {
+        // The 60s cycle leaves 40s to split 3:1 after the floors
+        assert_eq!(
+            split_cycle(secs(60.0), vec![secs(10.0), secs(10.0)], vec![6.0, 2.0]),
+            vec![secs(40.0), secs(20.0)]
+        );
+
+        // A stage with no pressure only gets its floor
+        assert_eq!(
+            split_cycle(
+                secs(60.0),
+                vec![secs(10.0), secs(5.0), secs(15.0)],
+                vec![3.0, 0.0, 0.0]
+            ),
+            vec![secs(40.0), secs(5.0), secs(15.0)]
+        );
+
+        // No demand or no spare time means keep the original timing
+        assert!(split_cycle(secs(60.0), vec![secs(10.0), secs(10.0)], vec![0.0, 0.0]).is_empty());
+        assert!(split_cycle(secs(20.0), vec![secs(10.0), secs(10.0)], vec![1.0, 1.0]).is_empty());
+        assert!(split_cycle(secs(15.0), vec![secs(10.0), secs(10.0)], vec![1.0, 1.0]).is_empty());
+    }
+
+    #[test]
+    fn test_priority_hold() {
+        let now = Time::START_OF_DAY + Duration::hours(8);
+
+        // Hold until the bus has cleared the intersection
+        assert_eq!(
+            priority_hold(now, now, now + secs(5.0), false),
+            Some(now + secs(10.0))
+        );
+        // Holding for exactly the maximum extension is fine
+        assert_eq!(
+            priority_hold(now, now, now + secs(10.0), false),
+            Some(now + secs(15.0))
+        );
+        // But the bus is too far away to hold any longer
+        assert_eq!(priority_hold(now, now, now + secs(12.0), false), None);
+        // The maximum extension counts from when the stage was scheduled to end
+        assert_eq!(
+            priority_hold(now, now + secs(5.0), now + secs(12.0), false),
+            Some(now + secs(17.0))
+        );
+        // Only hold once per stage
+        assert_eq!(priority_hold(now, now, now + secs(5.0), true), None);
+        // The bus should've arrived already
+        assert_eq!(priority_hold(now, now, now - secs(1.0), false), None);
+    }
+
+    #[test]
+    fn test_priority_red_end() {
+        let now = Time::START_OF_DAY + Duration::hours(8);
+        let min_green = secs(5.0);
+
+        // The stage has served its minimum, and the bus is close, so end it now
+        assert_eq!(
+            priority_red_end(now, now - secs(30.0), min_green, now + secs(3.0)),
+            now
+        );
+        // The stage hasn't served its minimum yet
+        assert_eq!(
+            priority_red_end(now, now - secs(2.0), min_green, now + secs(4.0)),
+            now + secs(3.0)
+        );
+        // The bus is far away, so the green starts a little before it arrives
+        assert_eq!(
+            priority_red_end(now, now - secs(30.0), min_green, now + secs(60.0)),
+            now + secs(55.0)
+        );
+        // A stage that just started when the bus can't use it
+        assert_eq!(
+            priority_red_end(now, now, min_green, now + secs(20.0)),
+            now + secs(15.0)
+        );
+        assert_eq!(
+            priority_red_end(now, now, min_green, now + secs(2.0)),
+            now + min_green
+        );
+    }
+
+    #[test]
+    fn test_gap_before_arrivals() {
+        let now = Time::START_OF_DAY + Duration::hours(8);
+        let cross = secs(10.0);
+
+        // Nobody's coming
+        assert!(gap_before_arrivals(Vec::new().into_iter(), now, cross));
+        // Arriving right as the margin runs out is fine
+        assert!(gap_before_arrivals(
+            vec![now + secs(13.0), now + secs(60.0)].into_iter(),
+            now,
+            cross
+        ));
+        // But anybody arriving sooner blocks the crossing
+        assert!(!gap_before_arrivals(
+            vec![now + secs(60.0), now + secs(12.0)].into_iter(),
+            now,
+            cross
+        ));
+    }
+
+    #[test]
+    fn test_decide_to_jaywalk() {
+        let mut seed = 42;
+        assert!(!decide_to_jaywalk(&mut seed, 0.0));
+        assert!(decide_to_jaywalk(&mut seed, 1.0));
+
+        // The same seed makes the same decisions
+        let decisions = |mut seed| {
+            (0..1000)
+                .map(|_| decide_to_jaywalk(&mut seed, 0.3))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(decisions(1), decisions(1));
+        assert_ne!(decisions(1), decisions(2));
+
+        // And the RNG advances, so people don't all decide the same way
+        let yes = decisions(1).into_iter().filter(|x| *x).count();
+        assert!(yes > 200 && yes < 400);
+    }
+}
@@ -0,0 +1,375 @@
+use serde::{Deserialize, Serialize};
+
+use geom::{Distance, Duration, Speed};
+
+use crate::VehicleType;
+
+const GRAVITY: f64 = 9.81;
+/// Even on the steepest hill, a vehicle can still slowly speed up.
+const MIN_ACCELERATION: f64 = 0.1;
+
+/// How quickly a vehicle can speed up and slow down, in m/s^2.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Kinematics {
+    acceleration: f64,
+    deceleration: f64,
+}
+
+impl Kinematics {
+    /// `percent_incline` is the rise over run, negative for downhill. Cars have power to spare on
+    /// hills, but cyclists, e-scooters, and heavy buses and trucks slow down climbing.
+    pub fn new(vehicle_type: VehicleType, percent_incline: f64) -> Kinematics {
+        let (acceleration, deceleration) = match vehicle_type {
+            VehicleType::Car => (2.5, 3.0),
+            VehicleType::Bus => (1.2, 1.5),
+            VehicleType::Train => (1.0, 1.2),
+            VehicleType::Bike => (1.0, 2.0),
+            VehicleType::Truck => (1.0, 1.5),
+            VehicleType::Van => (2.0, 2.5),
+            VehicleType::Motorcycle => (3.5, 3.5),
+            VehicleType::Scooter => (1.2, 2.0),
+        };
+        let acceleration = match vehicle_type {
+            VehicleType::Bike | VehicleType::Scooter | VehicleType::Bus | VehicleType::Truck => {
+                (acceleration - GRAVITY * percent_incline).max(MIN_ACCELERATION)
+            }
+            VehicleType::Car | VehicleType::Van | VehicleType::Motorcycle | VehicleType::Train => {
+                acceleration
+            }
+        };
+        Kinematics {
+            acceleration,
+            deceleration,
+        }
+    }
+
+    /// A vehicle can't know in advance when it'll have to stop for a queue or a red light, so it
+    /// reaches the stop at full speed. This is how much time it would've lost by braking to a stop
+    /// instead, so it can be made up for when the vehicle starts again.
+    pub fn braking_delay(&self, speed: Speed) -> Duration {
+        Duration::seconds(speed.inner_meters_per_second() / (2.0 * self.deceleration))
+    }
+}
+
+/// How a vehicle's speed changes while it crosses a lane or turn: possibly waiting, then speeding
+/// up or slowing down towards a cruising speed, and maybe braking to a stop at the end.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct SpeedProfile {
+    start_speed: Speed,
+    phases: Vec<Phase>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Phase {
+    start_speed: Speed,
+    /// m/s^2, negative when braking
+    acceleration: f64,
+    duration: Duration,
+}
+
+impl Phase {
+    fn dist(&self, dt: Duration) -> f64 {
+        let t = dt.inner_seconds();
+        self.start_speed.inner_meters_per_second() * t + 0.5 * self.acceleration * t * t
+    }
+
+    fn speed(&self, dt: Duration) -> Speed {
+        Speed::meters_per_second(
+            (self.start_speed.inner_meters_per_second() + self.acceleration * dt.inner_seconds())
+                .max(0.0),
+        )
+    }
+}
+
+impl SpeedProfile {
+    /// Cover `dist`, first waiting for `delay`, starting at `start_speed` and never exceeding
+    /// `max_speed`.
+    pub fn new(
+        dist: Distance,
+        delay: Duration,
+        start_speed: Speed,
+        max_speed: Speed,
+        stop_at_end: bool,
+        kinematics: Kinematics,
+    ) -> SpeedProfile {
+        let mut profile = SpeedProfile {
+            start_speed,
+            phases: Vec::new(),
+        };
+        profile.push(Speed::ZERO, 0.0, delay.inner_seconds());
+
+        let d = dist.inner_meters();
+        let v0 = start_speed.inner_meters_per_second();
+        let vmax = max_speed.inner_meters_per_second();
+        let (a, b) = (kinematics.acceleration, kinematics.deceleration);
+        if d <= 0.0 {
+            return profile;
+        }
+
+        if stop_at_end {
+            if v0 * v0 > 2.0 * b * d {
+                // Too fast to stop comfortably in time, so brake harder
+                profile.push(start_speed, -v0 * v0 / (2.0 * d), 2.0 * d / v0);
+                return profile;
+            }
+            // The fastest speed reachable while still leaving room to brake
+            let peak = ((2.0 * a * b * d + b * v0 * v0) / (a + b)).sqrt().min(vmax);
+            let rate = if peak >= v0 { a } else { -b };
+            let change_dist = (peak * peak - v0 * v0) / (2.0 * rate);
+            let brake_dist = peak * peak / (2.0 * b);
+            profile.push(start_speed, rate, (peak - v0) / rate);
+            profile.push(
+                Speed::meters_per_second(peak),
+                0.0,
+                (d - change_dist - brake_dist).max(0.0) / peak,
+            );
+            profile.push(Speed::meters_per_second(peak), -b, peak / b);
+        } else {
+            let rate = if vmax >= v0 { a } else { -b };
+            let change_dist = (vmax * vmax - v0 * v0) / (2.0 * rate);
+            if change_dist >= d {
+                // Never reach the cruising speed
+                let end = (v0 * v0 + 2.0 * rate * d).max(0.0).sqrt();
+                profile.push(start_speed, rate, (end - v0) / rate);
+            } else {
+                profile.push(start_speed, rate, (vmax - v0) / rate);
+                profile.push(max_speed, 0.0, (d - change_dist) / vmax);
+            }
+        }
+        profile
+    }
+
+    fn push(&mut self, start_speed: Speed, acceleration: f64, seconds: f64) {
+        if seconds > 0.0 {
+            self.phases.push(Phase {
+                start_speed,
+                acceleration,
+                duration: Duration::seconds(seconds),
+            });
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.phases.iter().map(|p| p.duration).sum()
+    }
+
+    /// How far the vehicle has gone after some time
+    pub fn dist_after(&self, mut dt: Duration) -> Distance {
+        let mut dist = 0.0;
+        for phase in &self.phases {
+            if dt <= phase.duration {
+                return Distance::meters(dist + phase.dist(dt));
+            }
+            dist += phase.dist(phase.duration);
+            dt -= phase.duration;
+        }
+        Distance::meters(dist)
+    }
+
+    /// After some time, how fast is the vehicle going, and how much longer will it wait before
+    /// moving?
+    pub fn state_after(&self, mut dt: Duration) -> (Speed, Duration) {
+        for phase in &self.phases {
+            if dt < phase.duration {
+                if phase.start_speed == Speed::ZERO && phase.acceleration == 0.0 {
+                    return (Speed::ZERO, phase.duration - dt);
+                }
+                return (phase.speed(dt), Duration::ZERO);
+            }
+            dt -= phase.duration;
+        }
+        (self.end_speed(), Duration::ZERO)
+    }
+
+    pub fn end_speed(&self) -> Speed {
+        self.phases
+            .last()
+            .map(|p| p.speed(p.duration))
+            .unwrap_or(self.start_speed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn car() -> Kinematics {
+        Kinematics::new(VehicleType::Car, 0.0)
+    }
+
+    fn mps(speed: f64) -> Speed {
+        Speed::meters_per_second(speed)
+    }
+
+    /// Distances, durations, and speeds are all rounded a bit
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 0.01,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    fn assert_state(profile: &SpeedProfile, dt: f64, speed: f64, wait: f64) {
+        let (actual_speed, actual_wait) = profile.state_after(Duration::seconds(dt));
+        assert_close(actual_speed.inner_meters_per_second(), speed);
+        assert_close(actual_wait.inner_seconds(), wait);
+    }
+
+    fn assert_dist(profile: &SpeedProfile, dt: f64, dist: f64) {
+        assert_close(
+            profile.dist_after(Duration::seconds(dt)).inner_meters(),
+            dist,
+        );
+    }
+
+    #[test]
+    fn test_accelerate_then_cruise() {
+        let profile = SpeedProfile::new(
+            Distance::meters(100.0),
+            Duration::ZERO,
+            Speed::ZERO,
+            mps(10.0),
+            false,
+            car(),
+        );
+        // 4s to reach 10m/s over 20m, then 8s cruising the other 80m
+        assert_close(profile.duration().inner_seconds(), 12.0);
+        assert_state(&profile, 2.0, 5.0, 0.0);
+        assert_dist(&profile, 2.0, 5.0);
+        assert_dist(&profile, 4.0, 20.0);
+        assert_state(&profile, 8.0, 10.0, 0.0);
+        assert_dist(&profile, 8.0, 60.0);
+        assert_dist(&profile, 12.0, 100.0);
+        // Finished
+        assert_dist(&profile, 20.0, 100.0);
+        assert_state(&profile, 20.0, 10.0, 0.0);
+    }
+
+    #[test]
+    fn test_slow_down_then_cruise() {
+        let profile = SpeedProfile::new(
+            Distance::meters(100.0),
+            Duration::ZERO,
+            mps(20.0),
+            mps(10.0),
+            false,
+            car(),
+        );
+        // 10/3s braking to 10m/s over 50m, then 5s cruising the other 50m
+        assert_close(profile.duration().inner_seconds(), 10.0 / 3.0 + 5.0);
+        assert_dist(&profile, 10.0 / 3.0, 50.0);
+        assert_state(&profile, 1.0, 17.0, 0.0);
+        assert_close(profile.end_speed().inner_meters_per_second(), 10.0);
+    }
+
+    #[test]
+    fn test_never_reach_max_speed() {
+        let profile = SpeedProfile::new(
+            Distance::meters(10.0),
+            Duration::ZERO,
+            Speed::ZERO,
+            mps(30.0),
+            false,
+            car(),
+        );
+        // Reaching 30m/s would take 180m, so just accelerate the whole way
+        let end_speed = 50.0_f64.sqrt();
+        let duration = end_speed / 2.5;
+        assert_close(profile.duration().inner_seconds(), duration);
+        assert_close(profile.end_speed().inner_meters_per_second(), end_speed);
+        assert_dist(&profile, duration, 10.0);
+        assert_state(&profile, 1.0, 2.5, 0.0);
+    }
+
+    #[test]
+    fn test_accelerate_cruise_brake() {
+        let profile = SpeedProfile::new(
+            Distance::meters(100.0),
+            Duration::ZERO,
+            Speed::ZERO,
+            mps(10.0),
+            true,
+            car(),
+        );
+        // 4s accelerating over 20m, cruising, then 10/3s braking over 50/3m
+        let cruise = (100.0 - 20.0 - 50.0 / 3.0) / 10.0;
+        let duration = 4.0 + cruise + 10.0 / 3.0;
+        assert_close(profile.duration().inner_seconds(), duration);
+        assert_state(&profile, 4.0 + cruise / 2.0, 10.0, 0.0);
+        assert_dist(&profile, 4.0 + cruise, 100.0 - 50.0 / 3.0);
+        assert_state(&profile, 4.0 + cruise + 1.0, 7.0, 0.0);
+        assert_dist(&profile, duration, 100.0);
+        assert_close(profile.end_speed().inner_meters_per_second(), 0.0);
+    }
+
+    #[test]
+    fn test_accelerate_brake_without_reaching_max_speed() {
+        let profile = SpeedProfile::new(
+            Distance::meters(10.0),
+            Duration::ZERO,
+            Speed::ZERO,
+            mps(30.0),
+            true,
+            car(),
+        );
+        // Speed up as much as possible while still leaving room to brake, without cruising
+        let peak = (2.0 * 2.5 * 3.0 * 10.0 / 5.5_f64).sqrt();
+        let duration = peak / 2.5 + peak / 3.0;
+        assert_close(profile.duration().inner_seconds(), duration);
+        assert_state(&profile, peak / 2.5, peak, 0.0);
+        assert_dist(&profile, peak / 2.5, peak * peak / 5.0);
+        assert_dist(&profile, duration, 10.0);
+        assert_close(profile.end_speed().inner_meters_per_second(), 0.0);
+    }
+
+    #[test]
+    fn test_brake_hard() {
+        // Going 20m/s, stopping in 10m needs 20m/s^2, much harder than comfortable
+        let profile = SpeedProfile::new(
+            Distance::meters(10.0),
+            Duration::ZERO,
+            mps(20.0),
+            mps(30.0),
+            true,
+            car(),
+        );
+        assert_close(profile.duration().inner_seconds(), 1.0);
+        assert_state(&profile, 0.5, 10.0, 0.0);
+        assert_dist(&profile, 0.5, 7.5);
+        assert_dist(&profile, 1.0, 10.0);
+        assert_close(profile.end_speed().inner_meters_per_second(), 0.0);
+    }
+
+    #[test]
+    fn test_delay() {
+        let profile = SpeedProfile::new(
+            Distance::meters(100.0),
+            Duration::seconds(5.0),
+            Speed::ZERO,
+            mps(10.0),
+            false,
+            car(),
+        );
+        assert_close(profile.duration().inner_seconds(), 17.0);
+        assert_state(&profile, 2.0, 0.0, 3.0);
+        assert_dist(&profile, 2.0, 0.0);
+        assert_dist(&profile, 5.0, 0.0);
+        assert_state(&profile, 7.0, 5.0, 0.0);
+        assert_dist(&profile, 9.0, 20.0);
+
+        // Nowhere to go, just wait
+        let profile = SpeedProfile::new(
+            Distance::ZERO,
+            Duration::seconds(5.0),
+            Speed::ZERO,
+            mps(10.0),
+            true,
+            car(),
+        );
+        assert_close(profile.duration().inner_seconds(), 5.0);
+        assert_dist(&profile, 10.0, 0.0);
+        assert_state(&profile, 10.0, 0.0, 0.0);
+    }
+}
@@ -12,10 +12,12 @@ use abstutil::{
 use geom::{Distance, PolyLine, Pt2D};
 use map_model::{
     BuildingID, Lane, LaneID, LaneType, Map, OffstreetParking, ParkingLotID, PathConstraints,
-    PathStep, Position, Traversable, TurnID,
+    PathStep, Position, Traversable, Turn, TurnID,
 };
 
-use crate::{CarID, CarStatus, DrawCarInput, Event, ParkedCar, ParkingSpot, PersonID, Vehicle};
+use crate::{
+    CarID, CarStatus, DrawCarInput, Event, ParkedCar, ParkingSpot, PersonID, Vehicle, VehicleType,
+};
 
 /// Manages the state of parked cars. There are two implementations:
 /// - NormalParkingSimState allows only one vehicle per ParkingSpot defined in the map
@@ -449,20 +451,25 @@ impl ParkingSim for NormalParkingSimState {
         map: &Map,
     ) -> Vec<(ParkingSpot, Position)> {
         let mut candidates = Vec::new();
-
-        for l in self.driving_to_parking_lanes.get(driving_pos.lane()) {
-            for spot in self.onstreet_lanes[l].spots() {
-                if self.is_free(spot)
-                    && driving_pos.dist_along()
-                        <= self.spot_to_driving_pos(spot, vehicle, map).dist_along()
-                {
-                    candidates.push(spot);
+        // Street spots and lots are sized for cars. A truck can only park at the loading dock of
+        // the building it's visiting.
+        let only_target = vehicle.vehicle_type == VehicleType::Truck;
+
+        if !only_target {
+            for l in self.driving_to_parking_lanes.get(driving_pos.lane()) {
+                for spot in self.onstreet_lanes[l].spots() {
+                    if self.is_free(spot)
+                        && driving_pos.dist_along()
+                            <= self.spot_to_driving_pos(spot, vehicle, map).dist_along()
+                    {
+                        candidates.push(spot);
+                    }
                 }
             }
         }
 
         for (b, bldg_dist) in self.driving_to_offstreet.get(driving_pos.lane()) {
-            if let OffstreetParking::Private(_, _) = map.get_b(*b).parking {
+            if only_target || matches!(map.get_b(*b).parking, OffstreetParking::Private(_, _)) {
                 if target != *b {
                     continue;
                 }
@@ -477,13 +484,15 @@ impl ParkingSim for NormalParkingSimState {
             }
         }
 
-        for pl in self.driving_to_lots.get(driving_pos.lane()) {
-            let lot_dist = map.get_pl(*pl).driving_pos.dist_along();
-            if driving_pos.dist_along() < lot_dist {
-                for idx in 0..self.num_spots_per_lot[pl] {
-                    let spot = ParkingSpot::Lot(*pl, idx);
-                    if self.is_free(spot) {
-                        candidates.push(spot);
+        if !only_target {
+            for pl in self.driving_to_lots.get(driving_pos.lane()) {
+                let lot_dist = map.get_pl(*pl).driving_pos.dist_along();
+                if driving_pos.dist_along() < lot_dist {
+                    for idx in 0..self.num_spots_per_lot[pl] {
+                        let spot = ParkingSpot::Lot(*pl, idx);
+                        if self.is_free(spot) {
+                            candidates.push(spot);
+                        }
                     }
                 }
             }
@@ -607,7 +616,9 @@ impl ParkingSim for NormalParkingSimState {
                     }
                 }
             }
-            for turn in map.get_turns_for(current, PathConstraints::Car) {
+            for turn in
+                turns_for_parking_search(current, start, vehicle.vehicle_type.to_constraints(), map)
+            {
                 if let Entry::Vacant(e) = backrefs.entry(turn.id.dst) {
                     let dist_this_step = turn.geom.length() + map.get_l(current).length();
                     // When vehicles search away from the first lane for a spot, don't all go in
@@ -656,6 +667,33 @@ impl ParkingSim for NormalParkingSimState {
     }
 }
 
+/// Turns a vehicle searching for parking can make from the end of a lane. The search begins with
+/// the vehicle already at the end of `start`, so a modal filter on that road is behind it; only a
+/// diagonal filter can still block it.
+fn turns_for_parking_search(
+    current: LaneID,
+    start: LaneID,
+    constraints: PathConstraints,
+    map: &Map,
+) -> Vec<&Turn> {
+    if current != start {
+        return map.get_turns_for(current, constraints);
+    }
+    map.get_next_turns_and_lanes(current)
+        .into_iter()
+        .filter(|(t, l)| {
+            constraints.can_use(l, map)
+                && constraints.allowed_by_diagonal_filter(
+                    t.id.parent,
+                    t.id.src.road,
+                    t.id.dst.road,
+                    map,
+                )
+        })
+        .map(|(t, _)| t)
+        .collect()
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 struct ParkingLane {
     parking_lane: LaneID,
@@ -1028,7 +1066,9 @@ impl ParkingSim for InfiniteParkingSimState {
                     }
                 }
             }
-            for turn in map.get_turns_for(current, PathConstraints::Car) {
+            for turn in
+                turns_for_parking_search(current, start, vehicle.vehicle_type.to_constraints(), map)
+            {
                 if let Entry::Vacant(e) = backrefs.entry(turn.id.dst) {
                     let dist_this_step = turn.geom.length() + map.get_l(current).length();
                     e.insert(turn.id);
@@ -7,7 +7,7 @@ use geom::{Distance, Time};
 use map_model::{Map, Position, Traversable};
 
 use crate::mechanics::car::{Car, CarState};
-use crate::{CarID, VehicleType, FOLLOWING_DISTANCE};
+use crate::{CarID, FOLLOWING_DISTANCE};
 
 /// A Queue of vehicles on a single lane or turn. This is where
 /// https://a-b-street.github.io/docs/tech/trafficsim/discrete_event.html#exact-positions is
@@ -231,19 +231,18 @@ impl Queue {
                         CarState::Crossing {
                             ref time_int,
                             ref dist_int,
+                            ref profile,
                             ..
-                        } => {
-                            // TODO Why percent_clamp_end? We process car updates in any order, so we might
-                            // calculate this before moving this car from Crossing to another state.
-                            dist_int.lerp(time_int.percent_clamp_end(now)).min(bound)
-                        }
+                        } => CarState::crossing_front(time_int, dist_int, profile, now).min(bound),
                         CarState::ChangingLanes {
                             ref new_time,
                             ref new_dist,
+                            ref new_profile,
                             ..
                         } => {
                             // Same as the Crossing logic
-                            new_dist.lerp(new_time.percent_clamp_end(now)).min(bound)
+                            CarState::crossing_front(new_time, new_dist, new_profile, now)
+                                .min(bound)
                         }
                         CarState::Unparking { front, .. } => front,
                         CarState::Parking(front, _, _) => front,
@@ -425,10 +424,10 @@ impl Queue {
         let bike_cost = if self
             .members
             .iter()
-            .any(|x| matches!(x, Queued::Vehicle(c) if c.vehicle_type == VehicleType::Bike))
+            .any(|x| matches!(x, Queued::Vehicle(c) if c.vehicle_type.is_bike_like()))
             || self
                 .laggy_head
-                .map(|c| c.vehicle_type == VehicleType::Bike)
+                .map(|c| c.vehicle_type.is_bike_like())
                 .unwrap_or(false)
         {
             1
@@ -549,6 +548,14 @@ impl Queue {
         assert_eq!(self.members.remove(idx), Some(Queued::Vehicle(car)));
     }
 
+    /// Record that a car has passed some vehicles in front of it without changing lanes. The space
+    /// reserved in the queue doesn't change.
+    pub fn move_car_ahead(&mut self, car: CarID, from_idx: usize, to_idx: usize) {
+        assert!(to_idx < from_idx);
+        self.remove_car_from_idx(car, from_idx);
+        self.members.insert(to_idx, Queued::Vehicle(car));
+    }
+
     /// If a car thinks it's reached the end of the queue, double check. Blockages or laggy heads
     /// might be in the way.
     pub fn is_car_at_front(&self, car: CarID) -> bool {
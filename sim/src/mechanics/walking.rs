@@ -14,16 +14,23 @@ use crate::{
     pedestrian_body_radius, AgentID, AgentProperties, Command, CommutersVehiclesCounts,
     CreatePedestrian, DistanceInterval, DrawPedCrowdInput, DrawPedestrianInput, Event, Intent,
     IntersectionSimState, ParkedCar, ParkingSpot, PedCrowdLocation, PedestrianID, PersonID,
-    Problem, Scheduler, SidewalkPOI, SidewalkSpot, TimeInterval, TransitSimState, TripID,
-    TripManager, UnzoomedAgent,
+    Problem, Scheduler, SidewalkPOI, SidewalkSpot, SimOptions, TimeInterval, TransitSimState,
+    TripID, TripManager, UnzoomedAgent,
 };
 
 const TIME_TO_START_BIKING: Duration = Duration::const_seconds(30.0);
 const TIME_TO_FINISH_BIKING: Duration = Duration::const_seconds(45.0);
+/// With crowding enabled, people won't step onto a crosswalk already this dense (people per square
+/// meter). They wait at the corner instead.
+const CROSSWALK_CAPACITY: f64 = 2.0;
+/// How often people queueing at a corner check if the crosswalk has room yet
+const CROSSWALK_RETRY: Duration = Duration::const_seconds(1.0);
 
 /// Simulates pedestrians. Unlike vehicles, pedestrians can move bidirectionally on sidewalks and
-/// just "ghost" through each other. There's no queueing or slowdown when many people are
-/// overlapping. They're simply grouped together into a DrawPedCrowdInput for rendering.
+/// just "ghost" through each other. By default, there's no queueing and only a crude slowdown when
+/// many people are overlapping. They're simply grouped together into a DrawPedCrowdInput for
+/// rendering. With crowding enabled, walking speed depends on the density of people, and people
+/// queue at corners when a crosswalk is full.
 #[derive(Serialize, Deserialize, Clone)]
 pub(crate) struct WalkingSimState {
     peds: FixedMap<PedestrianID, Pedestrian>,
@@ -33,14 +40,16 @@ pub(crate) struct WalkingSimState {
     )]
     peds_per_traversable: MultiMap<Traversable, PedestrianID>,
     events: Vec<Event>,
+    crowding: bool,
 }
 
 impl WalkingSimState {
-    pub fn new() -> WalkingSimState {
+    pub fn new(opts: &SimOptions) -> WalkingSimState {
         WalkingSimState {
             peds: FixedMap::new(),
             peds_per_traversable: MultiMap::new(),
             events: Vec::new(),
+            crowding: opts.pedestrian_crowding,
         }
     }
 
@@ -96,6 +105,7 @@ impl WalkingSimState {
             ),
             _ => ped.crossing_state(
                 &self.peds_per_traversable,
+                self.crowding,
                 params.start.sidewalk_pos.dist_along(),
                 now,
                 map,
@@ -239,13 +249,15 @@ impl WalkingSimState {
                         ctx.map,
                         ctx.intersections,
                         &mut self.peds_per_traversable,
+                        self.crowding,
                         &mut self.events,
                         ctx.scheduler,
                     ) {
                         ctx.scheduler
                             .push(ped.state.get_end_time(), Command::UpdatePed(ped.id));
                     } else {
-                        // Must've failed because we can't turn yet. Don't schedule a retry here.
+                        // Must've failed because we can't turn yet. Don't schedule a retry here;
+                        // maybe_transition already did if the crosswalk is full.
                         ped.state = PedState::WaitingToTurn(dist, now);
                     }
                 }
@@ -256,6 +268,7 @@ impl WalkingSimState {
                     ctx.map,
                     ctx.intersections,
                     &mut self.peds_per_traversable,
+                    self.crowding,
                     &mut self.events,
                     ctx.scheduler,
                 ) {
@@ -273,6 +286,7 @@ impl WalkingSimState {
             PedState::LeavingBuilding(b, _) => {
                 ped.state = ped.crossing_state(
                     &self.peds_per_traversable,
+                    self.crowding,
                     ctx.map.get_b(b).sidewalk_pos.dist_along(),
                     now,
                     ctx.map,
@@ -297,6 +311,7 @@ impl WalkingSimState {
             PedState::LeavingParkingLot(pl, _) => {
                 ped.state = ped.crossing_state(
                     &self.peds_per_traversable,
+                    self.crowding,
                     ctx.map.get_pl(pl).sidewalk_pos.dist_along(),
                     now,
                     ctx.map,
@@ -337,6 +352,7 @@ impl WalkingSimState {
             PedState::FinishingBiking(ref spot, _, _) => {
                 ped.state = ped.crossing_state(
                     &self.peds_per_traversable,
+                    self.crowding,
                     spot.sidewalk_pos.dist_along(),
                     now,
                     ctx.map,
@@ -676,6 +692,7 @@ impl Pedestrian {
     fn crossing_state(
         &self,
         peds_per_traversable: &MultiMap<Traversable, PedestrianID>,
+        crowding: bool,
         start_dist: Distance,
         start_time: Time,
         map: &Map,
@@ -693,15 +710,21 @@ impl Pedestrian {
             }
         };
 
-        let speed_penalty = crowdedness_penalty(
-            map,
-            self.path.current_step().as_traversable(),
-            peds_per_traversable,
-        );
-        if speed_penalty != 1.0 {
+        let on = self.path.current_step().as_traversable();
+        let speed_penalty = if crowding {
+            let density = density_with(map, on, peds_per_traversable, self.id);
+            // Zero-length sidewalks and crosswalks have no meaningful density
+            if density.is_finite() {
+                events.push(Event::PedestrianDensityMeasured(on, density));
+            }
+            density_speed_factor(density)
+        } else {
+            crowdedness_penalty(map, on, peds_per_traversable)
+        };
+        if speed_penalty <= 0.5 {
             events.push(Event::ProblemEncountered(
                 self.trip,
-                Problem::PedestrianOvercrowding(self.path.current_step().as_traversable()),
+                Problem::PedestrianOvercrowding(on),
             ));
         }
 
@@ -879,23 +902,37 @@ impl Pedestrian {
         map: &Map,
         intersections: &mut IntersectionSimState,
         peds_per_traversable: &mut MultiMap<Traversable, PedestrianID>,
+        crowding: bool,
         events: &mut Vec<Event>,
         scheduler: &mut Scheduler,
     ) -> bool {
         if let PathStep::Turn(t) | PathStep::ContraflowTurn(t) = self.path.next_step() {
+            // Queue at the corner until there's room on the crosswalk. Nothing else will wake us
+            // up, so check again soon. Always let one person onto even a tiny crosswalk.
+            if crowding
+                && map.get_t(t).turn_type.pedestrian_crossing()
+                && !peds_per_traversable.get(Traversable::Turn(t)).is_empty()
+                && density_with(map, Traversable::Turn(t), peds_per_traversable, self.id)
+                    > CROSSWALK_CAPACITY
+            {
+                scheduler.update(now + CROSSWALK_RETRY, Command::UpdatePed(self.id));
+                return false;
+            }
+            let speed = PathStep::Turn(t).max_speed_along(
+                Some(self.speed),
+                PathConstraints::Pedestrian,
+                map,
+            );
             if !intersections.maybe_start_turn(
                 AgentID::Pedestrian(self.id),
                 t,
-                PathStep::Turn(t).max_speed_along(
-                    Some(self.speed),
-                    PathConstraints::Pedestrian,
-                    map,
-                ),
+                speed,
                 now,
                 map,
                 scheduler,
                 None,
-            ) {
+            ) && !intersections.maybe_jaywalk(self.id, self.trip, t, speed, now, map, scheduler)
+            {
                 return false;
             }
         }
@@ -908,7 +945,8 @@ impl Pedestrian {
             PathStep::Turn(_) => Distance::ZERO,
             PathStep::ContraflowTurn(t) => map.get_t(t).geom.length(),
         };
-        self.state = self.crossing_state(peds_per_traversable, start_dist, now, map, events);
+        self.state =
+            self.crossing_state(peds_per_traversable, crowding, start_dist, now, map, events);
         peds_per_traversable.insert(self.path.current_step().as_traversable(), self.id);
         events.push(Event::AgentEntersTraversable(
             AgentID::Pedestrian(self.id),
@@ -1029,14 +1067,54 @@ fn crowdedness_penalty(
     let people_per_sq_m = (num_people as f64) / area(map, traversable);
     // Based on eyeballing images from
     // https://www.gkstill.com/Support/crowd-density/CrowdDensity-1.html, let's use a fixed
-    // threshold of 1.5 people per square meter as "crowded" and slow them down by half.
-    if people_per_sq_m < 1.5 {
+    // threshold of 1.5 people per square meter as "crowded" and slow them down by half. Like in
+    // `density_speed_factor`, zero-length sidewalks and crosswalks are never crowded.
+    if people_per_sq_m < 1.5 || !people_per_sq_m.is_finite() {
         // Plenty of room, no penalty
         return 1.0;
     }
     0.5
 }
 
+/// People per square meter on a sidewalk or crosswalk, counting one person who may be about to
+/// step onto it.
+fn density_with(
+    map: &Map,
+    traversable: Traversable,
+    peds_per_traversable: &MultiMap<Traversable, PedestrianID>,
+    id: PedestrianID,
+) -> f64 {
+    let peds = peds_per_traversable.get(traversable);
+    let mut num_people = peds.len();
+    if !peds.contains(&id) {
+        num_people += 1;
+    }
+    (num_people as f64) / area(map, traversable)
+}
+
+/// Returns a number in (0, 1] to multiply speed by, using Weidmann's fundamental diagram for
+/// pedestrian flow. People walk freely at low densities, slow down smoothly as it gets more
+/// crowded, and almost stop near the jam density. Since the density depends on the width of the
+/// sidewalk or crosswalk, narrow ones get congested much sooner.
+///
+/// Like `crowdedness_penalty`, people keep this speed for the entire time on a sidewalk.
+fn density_speed_factor(people_per_sq_m: f64) -> f64 {
+    // From Weidmann 1993, "Transporttechnik der Fussgänger"
+    let gamma = 1.913;
+    let jam_density = 5.4;
+    // Even in a jam, people shuffle along slowly
+    let min_factor = 0.1;
+
+    // Zero-length sidewalks and crosswalks have no meaningful density, and nobody spends any time
+    // on them anyway
+    if !people_per_sq_m.is_finite() {
+        return 1.0;
+    }
+
+    let factor = 1.0 - (-gamma * (1.0 / people_per_sq_m - 1.0 / jam_density)).exp();
+    factor.clamp(min_factor, 1.0)
+}
+
 // In m^2
 fn area(map: &Map, traversable: Traversable) -> f64 {
     // The length of the sidewalk or crosswalk
@@ -1050,3 +1128,30 @@ fn area(map: &Map, traversable: Traversable) -> f64 {
     };
     width.inner_meters() * len.inner_meters()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_density_speed_factor() {
+        // Nearly empty sidewalks don't slow anybody down
+        assert!(density_speed_factor(0.01) > 0.99);
+        // Weidmann's curve at one person per square meter
+        assert!((density_speed_factor(1.0) - 0.79).abs() < 0.01);
+        // At and past the jam density, people still shuffle along
+        assert_eq!(density_speed_factor(5.4), 0.1);
+        assert_eq!(density_speed_factor(10.0), 0.1);
+        // Zero-length sidewalks don't count as overcrowded
+        assert_eq!(density_speed_factor(f64::INFINITY), 1.0);
+        assert_eq!(density_speed_factor(f64::NAN), 1.0);
+
+        // More crowded is always slower
+        let mut last = 1.0;
+        for density in [0.5, 1.0, 2.0, 3.0, 4.0] {
+            let factor = density_speed_factor(density);
+            assert!(factor < last);
+            last = factor;
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use serde::Serialize;
 
+use crate::assignment::{assign_traffic, AssignmentOptions};
 use crate::{AlertHandler, Sim, SimFlags, SimOptions};
 use abstutil::{prettyprint_usize, Timer};
 use geom::{Duration, Time};
@@ -15,12 +16,45 @@ pub fn prebake(map: &Map, scenario: Scenario, timer: &mut Timer) -> PrebakeSumma
         scenario.scenario_name
     ));
 
+    let sim = simulate_day(map, &scenario, timer, |_| {});
+    save_prebaked_results(sim, scenario, timer)
+}
+
+/// Like `prebake`, but first iterate traffic assignment, so drivers spread out over routes that
+/// account for congestion. The results of the final iteration are saved.
+pub fn prebake_with_assignment(
+    map: &Map,
+    scenario: Scenario,
+    opts: &AssignmentOptions,
+    timer: &mut Timer,
+) -> PrebakeSummary {
+    timer.start(format!(
+        "prebake for {} / {}",
+        scenario.map_name.describe(),
+        scenario.scenario_name
+    ));
+
+    let (sim, _) = assign_traffic(map, &scenario, opts, timer);
+    save_prebaked_results(sim, scenario, timer)
+}
+
+/// Simulate an entire scenario the way prebaking does. `setup` can configure the simulation
+/// before it starts.
+pub(crate) fn simulate_day<F: FnOnce(&mut Sim)>(
+    map: &Map,
+    scenario: &Scenario,
+    timer: &mut Timer,
+    setup: F,
+) -> Sim {
     let mut opts = SimOptions::new("prebaked");
     opts.alerts = AlertHandler::Silence;
+    // So extract-road-travel-times can use the prebaked results
+    opts.record_road_crossing_times = true;
     let mut sim = Sim::new(map, opts);
+    setup(&mut sim);
     // Bit of an abuse of this, but just need to fix the rng seed.
     let mut rng = SimFlags::for_test("prebaked").make_rng();
-    sim.instantiate(&scenario, map, &mut rng, timer);
+    sim.instantiate(scenario, map, &mut rng, timer);
 
     // Run until a few hours after the end of the day. Some trips start close to midnight, and we
     // want prebaked data for them too.
@@ -30,6 +64,10 @@ pub fn prebake(map: &Map, scenario: Scenario, timer: &mut Timer) -> PrebakeSumma
         &mut None,
         timer,
     );
+    sim
+}
+
+fn save_prebaked_results(sim: Sim, scenario: Scenario, timer: &mut Timer) -> PrebakeSummary {
     abstio::write_binary(
         abstio::path_prebaked_results(&scenario.map_name, &scenario.scenario_name),
         sim.get_analytics(),
@@ -4,7 +4,7 @@ use geom::Time;
 use map_model::{IntersectionID, LaneID, Map, PathStep, Position, Traversable};
 use synthpop::{IndividTrip, PersonSpec, Scenario, TripEndpoint, TripMode, TripPurpose};
 
-use crate::{AgentID, CarID, DrivingSimState, Event, TripID, VehicleType};
+use crate::{AgentID, CarID, DrivingSimState, Event, TripID};
 
 /// Records trips beginning and ending at a specified set of intersections. This can be used to
 /// capture and reproduce behavior in a gridlock-prone chunk of the map, without simulating
@@ -78,17 +78,19 @@ impl TrafficRecorder {
                     None
                 });
         if let Some(exit_intersection) = exit_intersection {
-            self.trips.push(IndividTrip::new(
+            let mut individ_trip = IndividTrip::new(
                 time,
                 TripPurpose::Shopping,
                 TripEndpoint::SuddenlyAppear(Position::start(lane)),
                 TripEndpoint::Border(exit_intersection),
-                if car.vehicle_type == VehicleType::Bike {
+                if car.vehicle_type.is_bike_like() {
                     TripMode::Bike
                 } else {
                     TripMode::Drive
                 },
-            ));
+            );
+            individ_trip.vehicle = car.vehicle_type.to_vehicle_kind();
+            self.trips.push(individ_trip);
             self.seen_trips.insert(trip);
         };
     }
@@ -0,0 +1,533 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use abstutil::{deserialize_btreemap, serialize_btreemap};
+use geom::{Distance, Pt2D, Time};
+use map_model::{Map, Path, PathConstraints, PathRequest, Position};
+
+use crate::{
+    CarID, Command, CreateCar, Event, Router, Scheduler, TripID, Vehicle, VehicleSpec, VehicleType,
+    MIN_CAR_LENGTH,
+};
+
+/// How to pick which idle vehicle serves the next ride request.
+///
+/// Policies are variants rather than implementations of a trait, so they can be picked from the
+/// command line and saved along with the rest of the simulation. To add a new policy:
+///
+/// 1) Add a variant here, with any parameters it needs.
+/// 2) Handle it in `choose`. This sees every idle vehicle, where it's waiting and since when, and
+///    where the oldest request is picked up. It returns the vehicle to send, or None to keep the
+///    request queued until the next time a vehicle becomes idle or another request arrives. If
+///    the vehicle can't reach the pickup, `choose` is asked again without it.
+/// 3) Give it a name in `parse_dispatch_policy`, so `--ride_hail_dispatch` accepts it.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DispatchPolicy {
+    /// The idle vehicle closest to the pickup, as the crow flies
+    Nearest,
+    /// The vehicle that's been idle the longest, spreading work evenly over the fleet
+    LongestIdle,
+}
+
+impl DispatchPolicy {
+    fn choose(self, idle: &[IdleVehicle], pickup: Pt2D) -> Option<CarID> {
+        let best = match self {
+            DispatchPolicy::Nearest => idle.iter().min_by_key(|v| v.pt.dist_to(pickup)),
+            DispatchPolicy::LongestIdle => idle.iter().min_by_key(|v| v.since),
+        };
+        best.map(|v| v.car)
+    }
+
+    /// Keep choosing vehicles until `send` succeeds for one of them. Returns None if the policy
+    /// doesn't choose anybody at first, and the last error if no vehicle could be sent.
+    fn choose_reachable(
+        self,
+        mut idle: Vec<IdleVehicle>,
+        pickup: Pt2D,
+        mut send: impl FnMut(&IdleVehicle) -> Result<()>,
+    ) -> Option<Result<CarID>> {
+        let mut result = None;
+        while let Some(car) = self.choose(&idle, pickup) {
+            let idx = idle.iter().position(|v| v.car == car).unwrap();
+            // Whatever happens, don't choose this vehicle again for this request
+            let vehicle = idle.remove(idx);
+            match send(&vehicle) {
+                Ok(()) => {
+                    return Some(Ok(car));
+                }
+                Err(err) => {
+                    result = Some(Err(err));
+                }
+            }
+        }
+        result
+    }
+}
+
+/// An idle vehicle that could be dispatched
+struct IdleVehicle {
+    car: CarID,
+    at: Position,
+    pt: Pt2D,
+    since: Time,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct RideRequest {
+    trip: TripID,
+    requested_at: Time,
+    pickup: Position,
+    dropoff: Position,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct FleetVehicle {
+    vehicle: Vehicle,
+    state: FleetState,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+enum FleetState {
+    /// Off the map, waiting where the last rider was dropped off
+    Idle { at: Position, since: Time },
+    /// Deadheading to the pickup
+    ToPickup(RideRequest),
+    /// The rider is on board. The path to the drop-off is taken when the vehicle leaves the
+    /// pickup.
+    ToDropoff(RideRequest, Option<Path>),
+    /// The rider just got out, or couldn't be picked up
+    AtDropoff(Position),
+}
+
+/// Whatever happened to a request when it was dispatched.
+pub(crate) enum Dispatched {
+    /// The vehicle was already at the pickup, so the rider gets in immediately.
+    PickedUp(TripID, CarID),
+    /// No idle vehicle can reach the pickup.
+    Unreachable(TripID, String),
+}
+
+/// What a vehicle did when it stopped.
+pub(crate) enum RideHailStop {
+    Pickup(TripID),
+    Dropoff(TripID),
+    /// The rider can't be taken to their destination anymore, so they're left behind.
+    Unreachable(TripID, String),
+}
+
+/// Manages a fleet of on-demand vehicles that aren't tied to any route. Requests are served
+/// first-come, first-served: each waits until some vehicle is idle, then the DispatchPolicy
+/// picks which one. A vehicle carries one rider at a time. Idle vehicles aren't on the map; they
+/// reappear where they last dropped somebody off when they're dispatched again.
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct RideHailSimState {
+    policy: DispatchPolicy,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    vehicles: BTreeMap<CarID, FleetVehicle>,
+    queue: VecDeque<RideRequest>,
+
+    events: Vec<Event>,
+}
+
+impl RideHailSimState {
+    /// The fleet starts spread out evenly over all buildings. `car_ids` must supply one unused ID
+    /// per vehicle.
+    pub fn new(
+        map: &Map,
+        fleet_size: usize,
+        policy: DispatchPolicy,
+        mut car_ids: impl FnMut() -> usize,
+    ) -> RideHailSimState {
+        let mut state = RideHailSimState {
+            policy,
+            vehicles: BTreeMap::new(),
+            queue: VecDeque::new(),
+            events: Vec::new(),
+        };
+
+        let depots: Vec<Position> = map
+            .all_buildings()
+            .iter()
+            .filter_map(|b| b.driving_connection(map).map(|(pos, _)| pos))
+            .collect();
+        if depots.is_empty() {
+            return state;
+        }
+        for idx in 0..fleet_size {
+            let id = CarID {
+                id: car_ids(),
+                vehicle_type: VehicleType::Car,
+            };
+            let vehicle = VehicleSpec {
+                vehicle_type: VehicleType::Car,
+                length: MIN_CAR_LENGTH,
+                max_speed: None,
+            }
+            .make(id, None);
+            state.vehicles.insert(
+                id,
+                FleetVehicle {
+                    vehicle,
+                    state: FleetState::Idle {
+                        at: depots[idx * depots.len() / fleet_size],
+                        since: Time::START_OF_DAY,
+                    },
+                },
+            );
+        }
+        state
+    }
+
+    pub fn fleet_size(&self) -> usize {
+        self.vehicles.len()
+    }
+
+    /// Queue up a request. Fails if the ride itself is impossible. Call `dispatch` afterwards.
+    pub fn request_ride(
+        &mut self,
+        now: Time,
+        trip: TripID,
+        pickup: Position,
+        dropoff: Position,
+        map: &Map,
+    ) -> Result<()> {
+        if self.vehicles.is_empty() {
+            bail!("there's no ride-hail fleet");
+        }
+        if pickup.lane() == dropoff.lane() {
+            bail!("{} is too short to ride-hail", trip);
+        }
+        map.pathfind_at(
+            PathRequest::vehicle(pickup, dropoff, PathConstraints::Car),
+            now,
+        )?;
+        self.queue.push_back(RideRequest {
+            trip,
+            requested_at: now,
+            pickup,
+            dropoff,
+        });
+        Ok(())
+    }
+
+    /// Assign idle vehicles to waiting requests, oldest request first.
+    pub fn dispatch(&mut self, now: Time, map: &Map, scheduler: &mut Scheduler) -> Vec<Dispatched> {
+        let mut results = Vec::new();
+        while let Some(req) = self.queue.front() {
+            let idle: Vec<IdleVehicle> = self
+                .vehicles
+                .iter()
+                .filter_map(|(id, v)| match v.state {
+                    FleetState::Idle { at, since } => Some(IdleVehicle {
+                        car: *id,
+                        at,
+                        pt: at.pt(map),
+                        since,
+                    }),
+                    _ => None,
+                })
+                .collect();
+            if idle.is_empty() {
+                break;
+            }
+
+            // A vehicle might be stranded somewhere it can't reach the pickup from, so the others
+            // are tried before giving up on the rider. If the ride itself has become impossible
+            // since it was requested, a vehicle waiting at the pickup fails right away, and any
+            // other finds out when it gets there.
+            let mut at_pickup = false;
+            let outcome = self.policy.choose_reachable(idle, req.pickup.pt(map), |v| {
+                at_pickup = v.at.lane() == req.pickup.lane();
+                if at_pickup {
+                    self.spawn(now, v.car, req.pickup, req.dropoff, map, scheduler)
+                } else {
+                    self.spawn(now, v.car, v.at, req.pickup, map, scheduler)
+                }
+            });
+            let outcome = match outcome {
+                Some(outcome) => outcome,
+                None => {
+                    break;
+                }
+            };
+            let req = self.queue.pop_front().unwrap();
+
+            match outcome {
+                Ok(car) => {
+                    self.events.push(Event::RideHailDispatched(car, req.trip));
+                    if at_pickup {
+                        // Already waiting right there
+                        self.events.push(Event::RideHailPickup(
+                            car,
+                            req.trip,
+                            now - req.requested_at,
+                            Distance::ZERO,
+                        ));
+                        results.push(Dispatched::PickedUp(req.trip, car));
+                        self.vehicles.get_mut(&car).unwrap().state =
+                            FleetState::ToDropoff(req, None);
+                    } else {
+                        self.vehicles.get_mut(&car).unwrap().state = FleetState::ToPickup(req);
+                    }
+                }
+                Err(err) => {
+                    results.push(Dispatched::Unreachable(req.trip, err.to_string()));
+                }
+            }
+        }
+        results
+    }
+
+    fn spawn(
+        &self,
+        now: Time,
+        car: CarID,
+        from: Position,
+        to: Position,
+        map: &Map,
+        scheduler: &mut Scheduler,
+    ) -> Result<()> {
+        let path = map.pathfind_at(PathRequest::vehicle(from, to, PathConstraints::Car), now)?;
+        scheduler.push(
+            now,
+            Command::SpawnCar(
+                CreateCar {
+                    router: Router::ride_hail_stop(car, path),
+                    vehicle: self.vehicles[&car].vehicle.clone(),
+                    maybe_parked_car: None,
+                    trip_and_person: None,
+                    maybe_route: None,
+                },
+                true,
+            ),
+        );
+        Ok(())
+    }
+
+    /// The vehicle reached the end of its path, after driving some distance.
+    pub fn vehicle_arrived(
+        &mut self,
+        now: Time,
+        car: CarID,
+        dist: Distance,
+        map: &Map,
+    ) -> RideHailStop {
+        let vehicle = self.vehicles.get_mut(&car).unwrap();
+        match vehicle.state {
+            FleetState::ToPickup(ref req) => {
+                let req = req.clone();
+                // This was checked when the ride was requested, but the map may have been edited
+                // since.
+                let path = match map.pathfind_at(
+                    PathRequest::vehicle(req.pickup, req.dropoff, PathConstraints::Car),
+                    now,
+                ) {
+                    Ok(path) => path,
+                    Err(err) => {
+                        vehicle.state = FleetState::AtDropoff(req.pickup);
+                        return RideHailStop::Unreachable(req.trip, err.to_string());
+                    }
+                };
+                self.events.push(Event::RideHailPickup(
+                    car,
+                    req.trip,
+                    now - req.requested_at,
+                    dist,
+                ));
+                let trip = req.trip;
+                vehicle.state = FleetState::ToDropoff(req, Some(path));
+                RideHailStop::Pickup(trip)
+            }
+            FleetState::ToDropoff(ref req, _) => {
+                let trip = req.trip;
+                let at = req.dropoff;
+                self.events.push(Event::RideHailDropoff(car, trip, dist));
+                vehicle.state = FleetState::AtDropoff(at);
+                RideHailStop::Dropoff(trip)
+            }
+            FleetState::Idle { .. } | FleetState::AtDropoff(_) => unreachable!(),
+        }
+    }
+
+    /// The vehicle is done picking up or dropping off. Returns the route to continue on, or None if
+    /// the vehicle is now idle and should vanish. Call `dispatch` afterwards.
+    pub fn vehicle_departed(&mut self, now: Time, car: CarID) -> Option<Router> {
+        let vehicle = self.vehicles.get_mut(&car).unwrap();
+        match vehicle.state {
+            FleetState::ToDropoff(_, ref mut path) => {
+                Some(Router::ride_hail_stop(car, path.take().unwrap()))
+            }
+            FleetState::AtDropoff(at) => {
+                vehicle.state = FleetState::Idle { at, since: now };
+                None
+            }
+            FleetState::Idle { .. } | FleetState::ToPickup(_) => unreachable!(),
+        }
+    }
+
+    pub fn collect_events(&mut self) -> Vec<Event> {
+        self.events.drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geom::Duration;
+    use map_model::{LaneID, RoadID};
+
+    use super::*;
+
+    fn car(id: usize) -> CarID {
+        CarID {
+            id,
+            vehicle_type: VehicleType::Car,
+        }
+    }
+
+    fn pos(road: usize) -> Position {
+        Position::new(
+            LaneID {
+                road: RoadID(road),
+                offset: 0,
+            },
+            Distance::ZERO,
+        )
+    }
+
+    /// Vehicles waiting along a line, idle since some minute
+    fn idle(vehicles: Vec<(f64, usize)>) -> Vec<IdleVehicle> {
+        vehicles
+            .into_iter()
+            .enumerate()
+            .map(|(idx, (x, since))| IdleVehicle {
+                car: car(idx),
+                at: pos(idx),
+                pt: Pt2D::new(x, 0.0),
+                since: Time::START_OF_DAY + Duration::minutes(since),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_choose() {
+        let vehicles = idle(vec![(0.0, 5), (100.0, 1), (30.0, 10)]);
+        let pickup = Pt2D::new(20.0, 0.0);
+        assert_eq!(
+            DispatchPolicy::Nearest.choose(&vehicles, pickup),
+            Some(car(2))
+        );
+        assert_eq!(
+            DispatchPolicy::LongestIdle.choose(&vehicles, pickup),
+            Some(car(1))
+        );
+        assert_eq!(DispatchPolicy::Nearest.choose(&[], pickup), None);
+        assert_eq!(DispatchPolicy::LongestIdle.choose(&[], pickup), None);
+    }
+
+    #[test]
+    fn test_choose_reachable() {
+        let pickup = Pt2D::new(20.0, 0.0);
+        // The vehicle that's been idle longest is stranded, so the next one goes instead
+        let mut tried = Vec::new();
+        let result = DispatchPolicy::LongestIdle.choose_reachable(
+            idle(vec![(0.0, 5), (100.0, 1), (30.0, 10)]),
+            pickup,
+            |v| {
+                tried.push(v.car);
+                if v.car == car(1) {
+                    bail!("stranded");
+                }
+                Ok(())
+            },
+        );
+        assert_eq!(result.unwrap().unwrap(), car(0));
+        assert_eq!(tried, vec![car(1), car(0)]);
+
+        // Every vehicle is tried once before giving up
+        let mut tried = Vec::new();
+        let result = DispatchPolicy::Nearest.choose_reachable(
+            idle(vec![(0.0, 5), (100.0, 1), (30.0, 10)]),
+            pickup,
+            |v| {
+                tried.push(v.car);
+                bail!("{} is stranded", v.car)
+            },
+        );
+        assert_eq!(
+            result.unwrap().unwrap_err().to_string(),
+            format!("{} is stranded", car(1))
+        );
+        assert_eq!(tried, vec![car(2), car(0), car(1)]);
+
+        // With nobody to choose, the request stays queued
+        assert!(DispatchPolicy::Nearest
+            .choose_reachable(Vec::new(), pickup, |_| Ok(()))
+            .is_none());
+    }
+
+    fn fleet(state: FleetState) -> RideHailSimState {
+        let vehicle = VehicleSpec {
+            vehicle_type: VehicleType::Car,
+            length: MIN_CAR_LENGTH,
+            max_speed: None,
+        }
+        .make(car(0), None);
+        let mut vehicles = BTreeMap::new();
+        vehicles.insert(car(0), FleetVehicle { vehicle, state });
+        RideHailSimState {
+            policy: DispatchPolicy::Nearest,
+            vehicles,
+            queue: VecDeque::new(),
+            events: Vec::new(),
+        }
+    }
+
+    fn request(trip: usize) -> RideRequest {
+        RideRequest {
+            trip: TripID(trip),
+            requested_at: Time::START_OF_DAY,
+            pickup: pos(1),
+            dropoff: pos(2),
+        }
+    }
+
+    #[test]
+    fn test_dropoff_then_idle() {
+        let map = Map::blank();
+        let mut state = fleet(FleetState::ToDropoff(request(7), None));
+        // Somebody else is waiting, but the only vehicle is busy
+        state.queue.push_back(request(8));
+        assert!(state
+            .dispatch(Time::START_OF_DAY, &map, &mut Scheduler::new())
+            .is_empty());
+        assert_eq!(state.queue.len(), 1);
+
+        let now = Time::START_OF_DAY + Duration::minutes(10);
+        let dist = Distance::meters(500.0);
+        assert!(matches!(
+            state.vehicle_arrived(now, car(0), dist, &map),
+            RideHailStop::Dropoff(TripID(7))
+        ));
+        assert!(matches!(
+            state.vehicles[&car(0)].state,
+            FleetState::AtDropoff(at) if at == pos(2)
+        ));
+        let events = state.collect_events();
+        assert!(matches!(
+            events[..],
+            [Event::RideHailDropoff(c, TripID(7), d)] if c == car(0) && d == dist
+        ));
+
+        // Dropping somebody off ends the route, and the vehicle waits there
+        assert!(state.vehicle_departed(now, car(0)).is_none());
+        assert!(matches!(
+            state.vehicles[&car(0)].state,
+            FleetState::Idle { at, since } if at == pos(2) && since == now
+        ));
+    }
+}
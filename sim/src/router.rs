@@ -5,16 +5,16 @@ use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 
-use geom::Distance;
+use geom::{Distance, Duration, Time};
 use map_model::{
-    BuildingID, IntersectionID, LaneID, Map, Path, PathConstraints, PathRequest, PathStep,
-    Position, Traversable, Turn, TurnID,
+    vehicle_cost, BuildingID, IntersectionID, LaneID, Map, Path, PathConstraints, PathRequest,
+    PathStep, Pathfinder, Position, RoutingParams, Traversable, Turn, TurnID,
 };
 
 use crate::mechanics::Queue;
 use crate::{
     AlertLocation, CarID, Event, ParkingSim, ParkingSimState, ParkingSpot, PersonID, SidewalkSpot,
-    TripID, TripPhaseType, Vehicle, VehicleType,
+    TripID, TripPhaseType, Vehicle,
 };
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -32,6 +32,9 @@ pub(crate) enum ActionAtEnd {
     GotoLaneEnd,
     StopBiking(SidewalkSpot),
     BusAtStop,
+    RideHailAtStop,
+    /// A freight vehicle double-parking to unload, before looking for a real parking spot
+    Unload,
     GiveUpOnParking,
 }
 
@@ -44,6 +47,8 @@ enum Goal {
         /// No parking available at all!
         stuck_end_dist: Option<Distance>,
         started_looking: bool,
+        /// Freight vehicles first stop in the lane next to the building to unload
+        unload_at: Option<Distance>,
     },
     EndAtBorder {
         end_dist: Distance,
@@ -55,6 +60,10 @@ enum Goal {
     FollowTransitRoute {
         end_dist: Distance,
     },
+    /// A ride-hail vehicle picking up or dropping off somebody
+    RideHailStop {
+        end_dist: Distance,
+    },
 }
 
 impl Router {
@@ -72,6 +81,11 @@ impl Router {
     }
 
     pub fn park_near(owner: CarID, path: Path, bldg: BuildingID) -> Router {
+        let unload_at = if owner.vehicle_type.is_freight() {
+            Some(path.get_req().end.dist_along())
+        } else {
+            None
+        };
         Router {
             path,
             goal: Goal::ParkNearBuilding {
@@ -79,6 +93,7 @@ impl Router {
                 spot: None,
                 stuck_end_dist: None,
                 started_looking: false,
+                unload_at,
             },
             owner,
         }
@@ -102,6 +117,16 @@ impl Router {
         }
     }
 
+    pub fn ride_hail_stop(owner: CarID, path: Path) -> Router {
+        Router {
+            goal: Goal::RideHailStop {
+                end_dist: path.get_req().end.dist_along(),
+            },
+            path,
+            owner,
+        }
+    }
+
     pub fn head(&self) -> Traversable {
         self.path.current_step().as_traversable()
     }
@@ -126,13 +151,21 @@ impl Router {
             Goal::ParkNearBuilding {
                 spot,
                 stuck_end_dist,
+                unload_at,
                 ..
-            } => stuck_end_dist.unwrap_or_else(|| spot.unwrap().1),
+            } => unload_at
+                .or(stuck_end_dist)
+                .unwrap_or_else(|| spot.unwrap().1),
             Goal::BikeThenStop { ref goal } => goal.sidewalk_pos.dist_along(),
-            Goal::FollowTransitRoute { end_dist } => end_dist,
+            Goal::FollowTransitRoute { end_dist } | Goal::RideHailStop { end_dist } => end_dist,
         }
     }
 
+    /// Does the vehicle come to a stop at the end of its path, instead of vanishing at a border?
+    pub fn stops_at_end(&self) -> bool {
+        !matches!(self.goal, Goal::EndAtBorder { .. })
+    }
+
     pub fn get_path(&self) -> &Path {
         &self.path
     }
@@ -200,7 +233,19 @@ impl Router {
                 ref mut stuck_end_dist,
                 target,
                 ref mut started_looking,
+                ref mut unload_at,
             } => {
+                if let Some(d) = *unload_at {
+                    if front < d {
+                        return None;
+                    }
+                    *unload_at = None;
+                    // If the vehicle somehow started past the building, skip unloading
+                    if front == d {
+                        return Some(ActionAtEnd::Unload);
+                    }
+                }
+
                 if let Some(d) = stuck_end_dist {
                     if *d == front {
                         return Some(ActionAtEnd::GiveUpOnParking);
@@ -250,7 +295,7 @@ impl Router {
                                 Some(PathRequest::vehicle(
                                     Position::new(current_lane, front),
                                     new_pos,
-                                    PathConstraints::Car,
+                                    vehicle.vehicle_type.to_constraints(),
                                 )),
                                 TripPhaseType::Parking,
                             ));
@@ -276,7 +321,7 @@ impl Router {
                                     Some(PathRequest::vehicle(
                                         Position::new(current_lane, front),
                                         new_pos,
-                                        PathConstraints::Car,
+                                        vehicle.vehicle_type.to_constraints(),
                                     )),
                                     TripPhaseType::Parking,
                                 ));
@@ -318,11 +363,19 @@ impl Router {
                     None
                 }
             }
+            Goal::RideHailStop { end_dist } => {
+                if end_dist == front {
+                    Some(ActionAtEnd::RideHailAtStop)
+                } else {
+                    None
+                }
+            }
         }
     }
 
     pub fn opportunistically_lanechange(
         &mut self,
+        now: Time,
         queues: &HashMap<Traversable, Queue>,
         map: &Map,
         handle_uber_turns: bool,
@@ -359,6 +412,7 @@ impl Router {
 
                 // The magic happens here. We have different penalties:
                 //
+                // 0) Are we a car headed into a peak-hour bus lane while it's in effect?
                 // 1) Are we headed towards a general purpose lane instead of a dedicated bike/bus
                 //    lane?
                 // 2) Are there any bikes in the target lane? This ONLY matters if we're a car. If
@@ -372,13 +426,18 @@ impl Router {
                 // make our choice based on each penalty in order, breaking ties by moving onto the
                 // next thing. With one exception: To produce more realistic behavior, we combine
                 // `vehicles + lc` as one score to avoid switching lanes just to get around one car.
-                if self.owner.vehicle_type == VehicleType::Bike {
+                if self.owner.vehicle_type.is_bike_like() {
                     bike = 0;
                 } else {
                     slow_lane = 0;
                 }
 
-                (lt, bike, slow_lane, vehicles + lc)
+                let bus_only = usize::from(
+                    matches!(constraints, PathConstraints::Car | PathConstraints::Truck)
+                        && map.get_l(lane).is_bus_only_at(now, map),
+                );
+
+                (bus_only, lt, bike, slow_lane, vehicles + lc)
             };
 
             // Look for other candidates, and assign a cost to each.
@@ -452,6 +511,50 @@ impl Router {
         }
     }
 
+    /// Look for a faster route to the same destination, starting from the end of the current lane.
+    /// Only switch if the new route is expected to save at least `min_gain`. If the route changes,
+    /// returns the abandoned path and the time saved.
+    pub fn maybe_reroute(
+        &mut self,
+        pathfinder: &Pathfinder,
+        params: &RoutingParams,
+        min_gain: Duration,
+        map: &Map,
+    ) -> Option<(Path, Duration)> {
+        // Once a driver starts looking for parking, the path is already being amended.
+        match self.goal {
+            Goal::ParkNearBuilding {
+                started_looking: false,
+                ..
+            }
+            | Goal::EndAtBorder { .. } => {}
+            _ => {
+                return None;
+            }
+        }
+        if self.path.is_last_step() || self.path.currently_inside_ut().is_some() {
+            return None;
+        }
+
+        let current_lane = self.path.current_step().as_lane();
+        let req = PathRequest::vehicle(
+            Position::new(current_lane, map.get_l(current_lane).length()),
+            self.path.get_req().end,
+            self.owner.vehicle_type.to_constraints(),
+        );
+        let new_path = pathfinder.pathfind_v2(req, map)?.into_v1(map).ok()?;
+        let old_cost = remaining_cost(&self.path, params, map)?;
+        let new_cost = remaining_cost(&new_path, params, map)?;
+        if old_cost - new_cost < min_gain {
+            return None;
+        }
+
+        let old_path = self.path.clone();
+        // This fails if the new route needs a turn that can't be made from the current lane
+        self.path.reroute(new_path, map).ok()?;
+        Some((old_path, old_cost - new_cost))
+    }
+
     pub fn can_lanechange(&self, from: LaneID, to: LaneID, map: &Map) -> bool {
         let steps = self.path.get_steps();
         if steps.len() < 3 {
@@ -499,3 +602,16 @@ impl Router {
         }
     }
 }
+
+/// The cost of the movements along the rest of a path, or None if one of them isn't allowed
+/// anymore.
+fn remaining_cost(path: &Path, params: &RoutingParams, map: &Map) -> Option<Duration> {
+    let mut cost = Duration::ZERO;
+    for step in path.get_steps() {
+        if let PathStep::Turn(t) = step {
+            let mvmnt = t.to_movement(map);
+            cost += vehicle_cost(mvmnt.from, mvmnt, path.get_req().constraints, params, map)?;
+        }
+    }
+    Some(cost)
+}
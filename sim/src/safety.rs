@@ -0,0 +1,421 @@
+//! Surrogate safety measures. Agents in the simulation never actually collide, but when two of them
+//! use conflicting turns very close together in time, that's a near-miss. Counting near-misses is
+//! the usual way to estimate crash risk from a traffic simulation, in the style of FHWA's Surrogate
+//! Safety Assessment Model (SSAM).
+//!
+//! Time-to-collision is measured where the two turns' paths cross. Turns merging into the same
+//! lane are rear-end conflicts, measured from the difference in speed once both are in the lane.
+//! Post-encroachment time is only measured for crossing paths; agents entering the same lane one
+//! after another are just following each other.
+
+use serde::{Deserialize, Serialize};
+
+use geom::{Distance, Duration, Speed, Time};
+use map_model::{Map, Turn, TurnID};
+
+use crate::AgentType;
+
+/// Only record near-misses with a time-to-collision under this. This is SSAM's default.
+pub(crate) const MAX_TTC: Duration = Duration::const_seconds(1.5);
+/// Only record near-misses where the post-encroachment time is under this. This is SSAM's default.
+pub(crate) const MAX_PET: Duration = Duration::const_seconds(5.0);
+/// Roughly how much space somebody walking takes up along their path
+pub(crate) const PEDESTRIAN_LENGTH: Distance = Distance::const_meters(0.5);
+
+/// Two agents using conflicting turns through an intersection close together in time.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SafetyConflict {
+    /// The turn of the agent who entered the conflict second
+    pub turn: TurnID,
+    pub agent_type: AgentType,
+    /// The turn of the agent who entered the conflict first, or is about to
+    pub other_turn: TurnID,
+    pub other_agent_type: AgentType,
+    /// When the second agent started their turn, how long until the two would collide if both
+    /// kept going at their current speeds.
+    pub ttc: Option<Duration>,
+    /// How long after the first agent finished their turn the second started theirs.
+    pub pet: Option<Duration>,
+}
+
+impl SafetyConflict {
+    /// The most severe of the measurements; a shorter time is more dangerous.
+    pub fn min_time(&self) -> Duration {
+        match (self.ttc, self.pet) {
+            (Some(ttc), Some(pet)) => ttc.min(pet),
+            (Some(x), None) | (None, Some(x)) => x,
+            (None, None) => unreachable!(),
+        }
+    }
+
+    /// Describe the pair of movements, like "Car (Left) vs Pedestrian (Crosswalk)".
+    pub fn describe(&self, map: &Map) -> String {
+        format!(
+            "{} ({:?}) vs {} ({:?})",
+            self.agent_type.noun(),
+            map.get_t(self.turn).turn_type,
+            self.other_agent_type.noun(),
+            map.get_t(self.other_turn).turn_type
+        )
+    }
+}
+
+/// Near-misses aggregated over some place.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConflictSummary {
+    pub count: usize,
+    pub min_ttc: Option<Duration>,
+    pub min_pet: Option<Duration>,
+}
+
+impl ConflictSummary {
+    pub(crate) fn add(&mut self, conflict: &SafetyConflict) {
+        self.count += 1;
+        if let Some(ttc) = conflict.ttc {
+            self.min_ttc = Some(self.min_ttc.map(|x| x.min(ttc)).unwrap_or(ttc));
+        }
+        if let Some(pet) = conflict.pet {
+            self.min_pet = Some(self.min_pet.map(|x| x.min(pet)).unwrap_or(pet));
+        }
+    }
+}
+
+/// How an agent is starting a turn, or is expected to.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Approach {
+    /// When the agent's front reaches the start of the turn
+    pub start: Time,
+    pub speed: Speed,
+    pub length: Distance,
+}
+
+impl Approach {
+    /// When the agent's front reaches some distance along the turn, and when their back clears it
+    fn occupies(&self, dist: Distance) -> (Time, Time) {
+        let arrive = self.start + dist / self.speed;
+        (arrive, arrive + self.length / self.speed)
+    }
+}
+
+/// Where the paths of two conflicting turns meet.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ConflictPoint {
+    /// The paths cross this far along each turn.
+    Crossing(Distance, Distance),
+    /// Both turns end in the same lane, after this length of each.
+    Merge(Distance, Distance),
+}
+
+impl ConflictPoint {
+    /// None if the turns don't meet, or a sidewalk corner is involved. Turns starting from the
+    /// same place are handled by following behind each other.
+    pub fn new(t1: &Turn, t2: &Turn) -> Option<ConflictPoint> {
+        if !t1.conflicts_with(t2) {
+            return None;
+        }
+        if t1.geom.last_pt() == t2.geom.last_pt() {
+            return Some(ConflictPoint::Merge(t1.geom.length(), t2.geom.length()));
+        }
+        let (pt, _) = t1.geom.intersection(&t2.geom)?;
+        let (dist1, _) = t1.geom.dist_along_of_point(pt)?;
+        let (dist2, _) = t2.geom.dist_along_of_point(pt)?;
+        Some(ConflictPoint::Crossing(dist1, dist2))
+    }
+}
+
+/// If two agents keep going at their current speeds, how long from `now` until they collide? Only
+/// near-misses under `MAX_TTC` count.
+pub(crate) fn time_to_collision(
+    point: ConflictPoint,
+    a: Approach,
+    b: Approach,
+    now: Time,
+) -> Option<Duration> {
+    if a.speed <= Speed::ZERO || b.speed <= Speed::ZERO {
+        return None;
+    }
+    let ttc = match point {
+        ConflictPoint::Crossing(dist_a, dist_b) => {
+            let (a_arrives, a_leaves) = a.occupies(dist_a);
+            let (b_arrives, b_leaves) = b.occupies(dist_b);
+            // The second to arrive hits the first, if the first is still in the way
+            if a_arrives <= b_arrives && b_arrives < a_leaves {
+                b_arrives - now
+            } else if b_arrives < a_arrives && a_arrives < b_leaves {
+                a_arrives - now
+            } else {
+                return None;
+            }
+        }
+        ConflictPoint::Merge(dist_a, dist_b) => {
+            let (a_arrives, a_leaves) = a.occupies(dist_a);
+            let (b_arrives, b_leaves) = b.occupies(dist_b);
+            let ((leader, leader_leaves), (follower, follower_arrives)) = if a_arrives <= b_arrives
+            {
+                ((a, a_leaves), (b, b_arrives))
+            } else {
+                ((b, b_leaves), (a, a_arrives))
+            };
+            if follower_arrives < leader_leaves {
+                // The leader's back hasn't even entered the lane yet
+                follower_arrives - now
+            } else if follower.speed > leader.speed {
+                // A rear-end collision, once the follower closes the gap
+                let gap = leader.speed * (follower_arrives - leader_leaves);
+                follower_arrives - now + gap / (follower.speed - leader.speed)
+            } else {
+                return None;
+            }
+        }
+    };
+    // A prediction from the past is stale; the leader got held up by something else
+    if ttc >= Duration::ZERO && ttc < MAX_TTC {
+        Some(ttc)
+    } else {
+        None
+    }
+}
+
+/// Somebody finished a conflicting turn at `exited`, and somebody else is starting theirs now.
+/// Merging into the same lane afterwards is just following, so only crossing paths count.
+pub(crate) fn post_encroachment_time(
+    point: ConflictPoint,
+    exited: Time,
+    now: Time,
+) -> Option<Duration> {
+    if let ConflictPoint::Merge(_, _) = point {
+        return None;
+    }
+    let pet = now - exited;
+    if pet <= MAX_PET {
+        Some(pet)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geom::{PolyLine, Pt2D};
+    use map_model::{IntersectionID, LaneID, RoadID, TurnType};
+
+    fn secs(s: f64) -> Duration {
+        Duration::seconds(s)
+    }
+
+    fn meters(m: f64) -> Distance {
+        Distance::meters(m)
+    }
+
+    fn approach(start: Time, mps: f64, length: f64) -> Approach {
+        Approach {
+            start,
+            speed: Speed::meters_per_second(mps),
+            length: meters(length),
+        }
+    }
+
+    fn close(actual: Option<Duration>, expected: f64) -> bool {
+        actual
+            .map(|x| (x.inner_seconds() - expected).abs() < 1e-6)
+            .unwrap_or(false)
+    }
+
+    #[test]
+    fn test_post_encroachment_time() {
+        let now = Time::START_OF_DAY + Duration::hours(8);
+        let crossing = ConflictPoint::Crossing(meters(5.0), meters(5.0));
+        assert_eq!(
+            post_encroachment_time(crossing, now - secs(2.0), now),
+            Some(secs(2.0))
+        );
+        assert_eq!(
+            post_encroachment_time(crossing, now - secs(5.0), now),
+            Some(secs(5.0))
+        );
+        assert_eq!(post_encroachment_time(crossing, now - secs(6.0), now), None);
+        // Entering the same lane after somebody else is just following them
+        assert_eq!(
+            post_encroachment_time(
+                ConflictPoint::Merge(meters(10.0), meters(10.0)),
+                now - secs(2.0),
+                now
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_crossing_ttc() {
+        let now = Time::START_OF_DAY + Duration::hours(8);
+        let point = ConflictPoint::Crossing(meters(10.0), meters(5.0));
+        // A 5m car at 10m/s occupies the crossing point 1s to 1.5s from now
+        let car = approach(now, 10.0, 5.0);
+
+        // Somebody else reaches it 1.2s from now, while the car is still there
+        assert!(close(
+            time_to_collision(point, car, approach(now + secs(0.2), 5.0, 5.0), now),
+            1.2
+        ));
+        // The order of the two agents doesn't matter
+        assert!(close(
+            time_to_collision(
+                ConflictPoint::Crossing(meters(5.0), meters(10.0)),
+                approach(now + secs(0.2), 5.0, 5.0),
+                car,
+                now
+            ),
+            1.2
+        ));
+        // Reaching the point after the car is gone
+        assert_eq!(
+            time_to_collision(point, car, approach(now + secs(0.6), 5.0, 5.0), now),
+            None
+        );
+        // Reaching the point first, then the car arrives and hits them
+        assert!(close(
+            time_to_collision(point, car, approach(now - secs(0.5), 5.0, 5.0), now),
+            1.0
+        ));
+        // A collision that's too far away to count
+        let slow = approach(now, 2.0, 5.0);
+        assert_eq!(
+            time_to_collision(point, slow, approach(now + secs(4.0), 5.0, 5.0), now),
+            None
+        );
+        // Stopped agents have no time-to-collision
+        assert_eq!(
+            time_to_collision(point, car, approach(now, 0.0, 5.0), now),
+            None
+        );
+    }
+
+    #[test]
+    fn test_merging_ttc() {
+        let now = Time::START_OF_DAY + Duration::hours(8);
+        let point = ConflictPoint::Merge(meters(10.0), meters(10.0));
+        // A 5m car at 10m/s fully enters the lane 1.5s from now
+        let leader = approach(now, 10.0, 5.0);
+
+        // The other car reaches the lane 1.2s from now, before the leader is out of the way
+        assert!(close(
+            time_to_collision(point, leader, approach(now + secs(0.2), 10.0, 5.0), now),
+            1.2
+        ));
+        // Following at the same speed, never catching up
+        assert_eq!(
+            time_to_collision(point, leader, approach(now + secs(0.6), 10.0, 5.0), now),
+            None
+        );
+        // A faster follower reaches the lane 1.0s from now, when the slow leader's back is 1m
+        // ahead, then closes the gap at 5m/s
+        let slow_leader = approach(now - secs(1.7), 5.0, 2.5);
+        assert!(close(
+            time_to_collision(point, slow_leader, approach(now, 10.0, 5.0), now),
+            1.2
+        ));
+        assert!(close(
+            time_to_collision(point, approach(now, 10.0, 5.0), slow_leader, now),
+            1.2
+        ));
+    }
+
+    fn turn(from: usize, to: usize, pts: Vec<Pt2D>) -> Turn {
+        Turn {
+            id: TurnID {
+                parent: IntersectionID(0),
+                src: LaneID {
+                    road: RoadID(from),
+                    offset: 0,
+                },
+                dst: LaneID {
+                    road: RoadID(to),
+                    offset: 0,
+                },
+            },
+            turn_type: TurnType::Straight,
+            geom: PolyLine::must_new(pts),
+        }
+    }
+
+    #[test]
+    fn test_conflict_point() {
+        let east = turn(0, 1, vec![Pt2D::new(0.0, 10.0), Pt2D::new(20.0, 10.0)]);
+        let south = turn(2, 3, vec![Pt2D::new(5.0, 0.0), Pt2D::new(5.0, 20.0)]);
+        match ConflictPoint::new(&east, &south) {
+            Some(ConflictPoint::Crossing(dist1, dist2)) => {
+                assert!((dist1 - meters(5.0)).abs() < meters(0.01));
+                assert!((dist2 - meters(10.0)).abs() < meters(0.01));
+            }
+            x => panic!("Expected the turns to cross, not {:?}", x),
+        }
+
+        let merge = turn(2, 1, vec![Pt2D::new(5.0, 0.0), Pt2D::new(20.0, 10.0)]);
+        assert_eq!(
+            ConflictPoint::new(&east, &merge),
+            Some(ConflictPoint::Merge(
+                east.geom.length(),
+                merge.geom.length()
+            ))
+        );
+
+        // Two parallel turns never meet
+        let parallel = turn(4, 5, vec![Pt2D::new(0.0, 15.0), Pt2D::new(20.0, 15.0)]);
+        assert_eq!(ConflictPoint::new(&east, &parallel), None);
+    }
+
+    #[test]
+    fn test_summary() {
+        let conflict = |ttc, pet| SafetyConflict {
+            turn: TurnID {
+                parent: IntersectionID(0),
+                src: LaneID {
+                    road: RoadID(0),
+                    offset: 0,
+                },
+                dst: LaneID {
+                    road: RoadID(1),
+                    offset: 0,
+                },
+            },
+            agent_type: AgentType::Car,
+            other_turn: TurnID {
+                parent: IntersectionID(0),
+                src: LaneID {
+                    road: RoadID(2),
+                    offset: 0,
+                },
+                dst: LaneID {
+                    road: RoadID(3),
+                    offset: 0,
+                },
+            },
+            other_agent_type: AgentType::Pedestrian,
+            ttc,
+            pet,
+        };
+
+        let mut summary = ConflictSummary::default();
+        for c in [
+            conflict(Some(secs(1.0)), None),
+            conflict(None, Some(secs(4.0))),
+            conflict(Some(secs(0.5)), None),
+            conflict(None, Some(secs(2.0))),
+        ] {
+            summary.add(&c);
+        }
+        assert_eq!(
+            summary,
+            ConflictSummary {
+                count: 4,
+                min_ttc: Some(secs(0.5)),
+                min_pet: Some(secs(2.0)),
+            }
+        );
+        assert_eq!(
+            conflict(Some(secs(1.0)), Some(secs(0.2))).min_time(),
+            secs(0.2)
+        );
+    }
+}
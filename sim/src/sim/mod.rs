@@ -22,9 +22,10 @@ pub use self::queries::{AgentProperties, DelayCause};
 // TODO Super weird for both of these to wind up here
 pub use self::scenario::{count_parked_cars_per_bldg, rand_dist};
 use crate::{
-    AgentID, AlertLocation, Analytics, CarID, Command, CreateCar, DrivingSimState, Event,
-    IntersectionSimState, PandemicModel, ParkedCar, ParkingSim, ParkingSimState, ParkingSpot,
-    Person, PersonID, Router, Scheduler, SidewalkPOI, SidewalkSpot, StartTripArgs, TrafficRecorder,
+    AgentID, AlertLocation, Analytics, CarID, Command, CreateCar, DispatchPolicy, DrivingSimState,
+    EmissionsModel, Event, FleetMix, IntersectionSimState, MovementTimes, PandemicModel, ParkedCar,
+    ParkingSim, ParkingSimState, ParkingSpot, Person, PersonID, RideHailSimState, RoadCrossings,
+    RouteChoices, Router, Scheduler, SidewalkPOI, SidewalkSpot, StartTripArgs, TrafficRecorder,
     TransitSimState, TripID, TripInfo, TripManager, TripPhaseType, Vehicle, VehicleSpec,
     VehicleType, WalkingSimState, BUS_LENGTH, LIGHT_RAIL_LENGTH, MIN_CAR_LENGTH,
 };
@@ -43,6 +44,7 @@ pub struct Sim {
     walking: WalkingSimState,
     intersections: IntersectionSimState,
     transit: TransitSimState,
+    ride_hail: RideHailSimState,
     trips: TripManager,
     #[serde(skip_serializing, skip_deserializing)]
     pandemic: Option<PandemicModel>,
@@ -62,6 +64,13 @@ pub struct Sim {
     // This is created interactively, and there's no reason to preserve one for savestates.
     #[serde(skip_serializing, skip_deserializing)]
     recorder: Option<TrafficRecorder>,
+    // Only used for traffic assignment
+    #[serde(skip_serializing, skip_deserializing)]
+    movement_times: Option<MovementTimes>,
+    record_road_crossing_times: bool,
+    // Crossings in progress aren't worth preserving for savestates.
+    #[serde(skip_serializing, skip_deserializing)]
+    road_crossings: RoadCrossings,
 
     #[serde(skip_serializing, skip_deserializing)]
     alerts: AlertHandler,
@@ -71,6 +80,7 @@ pub(crate) struct Ctx<'a> {
     pub parking: &'a mut ParkingSimState,
     pub intersections: &'a mut IntersectionSimState,
     pub scheduler: &'a mut Scheduler,
+    pub ride_hail: &'a mut RideHailSimState,
     pub map: &'a Map,
     /// If present, live map edits are being processed, and the agents specified are in the process
     /// of being deleted. Some regular work should maybe be skipped.
@@ -127,6 +137,69 @@ pub struct SimOptions {
     /// quickly.
     #[structopt(long)]
     pub skip_analytics: bool,
+    /// At the start of every cycle, retune the splits of fixed-time traffic signals based on live
+    /// queue lengths, in the style of max-pressure control. The cycle length of each signal is
+    /// preserved.
+    #[structopt(long)]
+    pub adaptive_signals: bool,
+    /// How many on-demand vehicles serve ride-hail trips. They start spread out over the map.
+    #[structopt(long, default_value = "0")]
+    pub ride_hail_fleet_size: usize,
+    /// How to pick which idle ride-hail vehicle serves a request.
+    #[structopt(long, parse(try_from_str = parse_dispatch_policy), default_value = "nearest")]
+    pub ride_hail_dispatch: DispatchPolicy,
+    /// Estimate the energy use and emissions of vehicles using this model. Off by default, since
+    /// it costs time whenever a vehicle finishes a road or turn.
+    #[structopt(long, parse(try_from_str = parse_emissions_model))]
+    pub emissions_model: Option<EmissionsModel>,
+    /// The share of cars using each powertrain, like "petrol=0.6,diesel=0.3,electric=0.1".
+    #[structopt(long, parse(try_from_str = FleetMix::parse), default_value = "petrol=0.7,diesel=0.25,electric=0.05")]
+    pub car_fleet_mix: FleetMix,
+    /// The share of buses using each powertrain. Trains are always electric.
+    #[structopt(long, parse(try_from_str = FleetMix::parse), default_value = "diesel=1")]
+    pub bus_fleet_mix: FleetMix,
+    /// Let drivers periodically look for a faster route to their destination given the current
+    /// traffic, like a navigation app would. This is how often each driver checks, like "5:00".
+    /// Disabled by default.
+    #[structopt(long, parse(try_from_str = Duration::parse))]
+    pub reroute_interval: Option<Duration>,
+    /// With live rerouting, only switch to a new route if it's expected to save at least this
+    /// much time.
+    #[structopt(long, parse(try_from_str = Duration::parse), default_value = "1:00")]
+    pub reroute_min_gain: Duration,
+    /// Plan transit trips using the transit schedule, transferring between routes when that's
+    /// faster. Transit trips made to transfer from a car start by driving to a stop, and people who
+    /// own a bike may bike to one.
+    #[structopt(long)]
+    pub plan_transit_journeys: bool,
+    /// Slow pedestrians down as sidewalks and crosswalks get crowded, based on how wide they are,
+    /// and make people wait at corners when a crosswalk is full. Without this, pedestrians just
+    /// walk at half speed past a fixed density.
+    #[structopt(long)]
+    pub pedestrian_crowding: bool,
+    /// The chance that a pedestrian will cross against a red signal once there's a gap in traffic.
+    /// Each person decides once per crossing, using the scenario's RNG. 0 disables jaywalking.
+    #[structopt(long, default_value = "0.0")]
+    pub jaywalking_probability: f64,
+    /// Make vehicles speed up and brake gradually, based on the type of vehicle and the incline
+    /// for bikes and buses. Without this, vehicles instantly start and stop, moving at a constant
+    /// speed along each lane and turn.
+    #[structopt(long)]
+    pub vehicle_kinematics: bool,
+    /// Let vehicles overtake slower cyclists, either within a wide lane or by using a gap in
+    /// oncoming traffic, and let cyclists filter past stopped vehicles. Drivers try to leave at
+    /// least this many meters of room when passing a cyclist; passes closer than this are recorded
+    /// as problems. Disabled by default.
+    #[structopt(long, parse(try_from_str = parse_meters))]
+    pub min_passing_distance: Option<Distance>,
+    /// Measure how long cars take to cross each road during each hour of the day, so that later
+    /// runs can route using these travel times. See `extract-road-travel-times`.
+    #[structopt(long)]
+    pub record_road_crossing_times: bool,
+    /// Record near-misses between agents using conflicting turns at intersections. See
+    /// `Sim::safety_conflict_dataset`.
+    #[structopt(long)]
+    pub measure_safety_conflicts: bool,
 }
 
 impl SimOptions {
@@ -143,6 +216,29 @@ impl SimOptions {
             infinite_parking: false,
             disable_turn_conflicts: false,
             skip_analytics: false,
+            adaptive_signals: false,
+            ride_hail_fleet_size: 0,
+            ride_hail_dispatch: DispatchPolicy::Nearest,
+            emissions_model: None,
+            car_fleet_mix: FleetMix {
+                petrol: 0.7,
+                diesel: 0.25,
+                electric: 0.05,
+            },
+            bus_fleet_mix: FleetMix {
+                petrol: 0.0,
+                diesel: 1.0,
+                electric: 0.0,
+            },
+            reroute_interval: None,
+            reroute_min_gain: Duration::minutes(1),
+            plan_transit_journeys: false,
+            pedestrian_crowding: false,
+            jaywalking_probability: 0.0,
+            vehicle_kinematics: false,
+            min_passing_distance: None,
+            record_road_crossing_times: false,
+            measure_safety_conflicts: false,
         }
     }
 }
@@ -158,6 +254,10 @@ fn parse_rng(x: &str) -> Result<XorShiftRng> {
     Ok(XorShiftRng::seed_from_u64(seed))
 }
 
+fn parse_meters(x: &str) -> Result<Distance> {
+    Ok(Distance::meters(x.parse()?))
+}
+
 #[derive(Clone)]
 pub enum AlertHandler {
     /// Just print the alert to STDOUT
@@ -183,6 +283,28 @@ fn parse_alert_handler(x: &str) -> Result<AlertHandler> {
     }
 }
 
+fn parse_dispatch_policy(x: &str) -> Result<DispatchPolicy> {
+    match x {
+        "nearest" => Ok(DispatchPolicy::Nearest),
+        "longest_idle" => Ok(DispatchPolicy::LongestIdle),
+        _ => bail!(
+            "Bad --ride_hail_dispatch={}. Must be nearest|longest_idle",
+            x
+        ),
+    }
+}
+
+fn parse_emissions_model(x: &str) -> Result<EmissionsModel> {
+    match x {
+        "average_speed" => Ok(EmissionsModel::AverageSpeed),
+        "tractive_power" => Ok(EmissionsModel::TractivePower),
+        _ => bail!(
+            "Bad --emissions_model={}. Must be average_speed|tractive_power",
+            x
+        ),
+    }
+}
+
 // Setup
 impl Sim {
     pub fn new(map: &Map, mut opts: SimOptions) -> Sim {
@@ -206,13 +328,22 @@ impl Sim {
             opts.allow_block_the_box = true;
         }
 
+        let mut trips = TripManager::new(&opts);
+        let ride_hail = RideHailSimState::new(
+            map,
+            opts.ride_hail_fleet_size,
+            opts.ride_hail_dispatch,
+            || trips.new_car_id(),
+        );
+
         Sim {
             driving: DrivingSimState::new(map, &opts),
             parking: ParkingSimState::new(map, opts.infinite_parking, &mut timer),
-            walking: WalkingSimState::new(),
+            walking: WalkingSimState::new(&opts),
             intersections: IntersectionSimState::new(map, &mut scheduler, &opts),
             transit: TransitSimState::new(map),
-            trips: TripManager::new(),
+            ride_hail,
+            trips,
             pandemic: opts.enable_pandemic_model.map(PandemicModel::new),
             scheduler,
             time: Time::START_OF_DAY,
@@ -226,6 +357,9 @@ impl Sim {
 
             analytics: Analytics::new(!opts.skip_analytics),
             recorder: None,
+            movement_times: None,
+            record_road_crossing_times: opts.record_road_crossing_times,
+            road_crossings: RoadCrossings::default(),
         }
     }
 
@@ -334,7 +468,7 @@ impl Sim {
         }
     }
 
-    fn start_bus(&mut self, route: &TransitRoute, map: &Map) {
+    fn start_bus(&mut self, route: &TransitRoute, scheduled_start: Time, map: &Map) {
         // Spawn one bus for the first leg.
         let path = self.transit.create_empty_route(route, map);
 
@@ -366,7 +500,7 @@ impl Sim {
                     vehicle,
                     maybe_parked_car: None,
                     trip_and_person: None,
-                    maybe_route: Some(route.id),
+                    maybe_route: Some((route.id, scheduled_start)),
                 },
                 true,
             ),
@@ -439,6 +573,7 @@ impl Sim {
             parking: &mut self.parking,
             intersections: &mut self.intersections,
             scheduler: &mut self.scheduler,
+            ride_hail: &mut self.ride_hail,
             map,
             handling_live_edits: None,
         };
@@ -529,7 +664,7 @@ impl Sim {
                                 trip,
                                 person,
                                 Some(req),
-                                if id.vehicle_type == VehicleType::Car {
+                                if id.vehicle_type.is_car_like() {
                                     TripPhaseType::Driving
                                 } else {
                                     TripPhaseType::Biking
@@ -546,8 +681,9 @@ impl Sim {
                             }
                             self.parking.remove_parked_car(parked_car);
                         }
-                        if let Some(route) = maybe_route {
-                            self.transit.bus_created(id, route);
+                        if let Some((route, scheduled_start)) = maybe_route {
+                            self.transit.bus_created(id, route, scheduled_start, map);
+                            self.intersections.transit_vehicle_created(id, route);
                         }
                         self.analytics
                             .record_demand(self.driving.get_path(id).unwrap(), map);
@@ -615,8 +751,13 @@ impl Sim {
                 );
             }
             Command::UpdateIntersection(i) => {
-                self.intersections
-                    .update_intersection(self.time, i, map, &mut self.scheduler);
+                self.intersections.update_intersection(
+                    self.time,
+                    i,
+                    map,
+                    &mut self.scheduler,
+                    &self.driving,
+                );
             }
             Command::Callback(frequency) => {
                 self.scheduler
@@ -631,8 +772,8 @@ impl Sim {
                     .unwrap()
                     .handle_cmd(self.time, cmd, &mut self.scheduler);
             }
-            Command::StartBus(r, _) => {
-                self.start_bus(map.get_tr(r), map);
+            Command::StartBus(r, scheduled_start) => {
+                self.start_bus(map.get_tr(r), scheduled_start, map);
             }
         }
 
@@ -645,6 +786,7 @@ impl Sim {
     fn dispatch_events(&mut self, mut events: Vec<Event>, map: &Map) {
         events.extend(self.trips.collect_events());
         events.extend(self.transit.collect_events());
+        events.extend(self.ride_hail.collect_events());
         events.extend(self.driving.collect_events());
         events.extend(self.walking.collect_events());
         events.extend(self.intersections.collect_events());
@@ -656,6 +798,14 @@ impl Sim {
             if let Some(ref mut r) = self.recorder {
                 r.handle_event(self.time, &ev, map, &self.driving);
             }
+            if let Some(ref mut m) = self.movement_times {
+                m.handle_event(self.time, &ev, map);
+            }
+            if self.record_road_crossing_times {
+                if let Some((dr, entered)) = self.road_crossings.handle_event(self.time, &ev, map) {
+                    self.analytics.record_road_crossing(dr, entered, self.time);
+                }
+            }
 
             self.analytics.event(ev, self.time, map);
         }
@@ -852,6 +1002,7 @@ impl Sim {
             parking: &mut self.parking,
             intersections: &mut self.intersections,
             scheduler: &mut self.scheduler,
+            ride_hail: &mut self.ride_hail,
             map,
             handling_live_edits: Some(affected_agents),
         };
@@ -881,12 +1032,13 @@ impl Sim {
                     self.trips
                         .trip_abruptly_cancelled(trip, AgentID::Pedestrian(ped));
                 }
-                AgentID::BusPassenger(_, _) => unreachable!(),
+                AgentID::BusPassenger(_, _) | AgentID::CarPassenger(_, _) => unreachable!(),
             }
         }
 
         self.driving.handle_live_edits(map);
         self.intersections.handle_live_edits(map);
+        self.trips.handle_live_edits();
 
         (num_trips_cancelled, num_parked_cars)
     }
@@ -912,18 +1064,20 @@ impl Sim {
             }
             for (a, trip) in self.trips.active_agents_and_trips() {
                 if let Some(path) = self.get_path(*a) {
-                    if path
-                        .get_steps()
-                        .iter()
-                        .any(|step| match step.as_traversable() {
+                    let constraints = path.get_req().constraints;
+                    if path.get_steps().iter().enumerate().any(|(idx, step)| {
+                        match step.as_traversable() {
                             Traversable::Lane(l) => edited_lanes.contains(&l),
                             Traversable::Turn(t) => {
                                 closed_intersections.contains(&t.parent)
                                     || edited_lanes.contains(&t.src)
                                     || edited_lanes.contains(&t.dst)
+                                    // The agent may already be past a filter on its current
+                                    // road, so only check later turns
+                                    || (idx > 1 && !constraints.can_cross_filters(t, map))
                             }
-                        })
-                    {
+                        }
+                    }) {
                         affected.insert((*a, *trip));
                     }
                 }
@@ -967,6 +1121,7 @@ impl Sim {
                 parking: &mut self.parking,
                 intersections: &mut self.intersections,
                 scheduler: &mut self.scheduler,
+                ride_hail: &mut self.ride_hail,
                 map,
                 handling_live_edits: None,
             };
@@ -1025,6 +1180,26 @@ impl Sim {
     }
 }
 
+// Traffic assignment
+impl Sim {
+    pub(crate) fn record_movement_times(&mut self) {
+        assert!(self.movement_times.is_none());
+        self.movement_times = Some(MovementTimes::default());
+    }
+
+    pub(crate) fn take_movement_times(&mut self) -> Option<MovementTimes> {
+        self.movement_times.take()
+    }
+
+    pub(crate) fn set_route_choices(&mut self, choices: RouteChoices) {
+        self.trips.route_choices = choices;
+    }
+
+    pub(crate) fn take_route_choices(&mut self) -> RouteChoices {
+        std::mem::take(&mut self.trips.route_choices)
+    }
+}
+
 // Managing highlighted people
 impl Sim {
     pub fn set_highlighted_people(&mut self, people: BTreeSet<PersonID>) {
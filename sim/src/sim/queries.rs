@@ -5,6 +5,7 @@ use serde::Serialize;
 use std::collections::{BTreeMap, BTreeSet};
 
 use abstutil::Counter;
+use collisions::{Conflict, ConflictDataset};
 use geom::{Distance, Duration, PolyLine, Pt2D, Time};
 use map_model::{
     BuildingID, IntersectionID, Lane, LaneID, Map, Path, Position, RoadID, TransitRouteID,
@@ -15,8 +16,9 @@ use synthpop::{OrigPersonID, Scenario, TripMode};
 use crate::analytics::SlidingWindow;
 use crate::{
     AgentID, AgentType, Analytics, CarID, CommutersVehiclesCounts, DrawCarInput, DrawPedCrowdInput,
-    DrawPedestrianInput, PandemicModel, ParkedCar, ParkingSim, PedestrianID, Person, PersonID,
-    PersonState, Sim, TripEndpoint, TripID, TripInfo, TripResult, UnzoomedAgent, VehicleType,
+    DrawPedestrianInput, EmissionsSummary, PandemicModel, ParkedCar, ParkingSim, PedestrianID,
+    Person, PersonID, PersonState, RideHailSummary, Sim, TransitRouteSummary, TripEndpoint, TripID,
+    TripInfo, TripResult, UnzoomedAgent, VehicleType,
 };
 
 // TODO Many of these just delegate to an inner piece. This is unorganized and hard to maintain.
@@ -68,7 +70,9 @@ impl Sim {
         match id {
             AgentID::Car(id) => self.driving.debug_car_json(id),
             AgentID::Pedestrian(id) => self.walking.debug_ped_json(id),
-            AgentID::BusPassenger(_, id) => self.driving.debug_car_json(id),
+            AgentID::BusPassenger(_, id) | AgentID::CarPassenger(_, id) => {
+                self.driving.debug_car_json(id)
+            }
         }
     }
 
@@ -86,7 +90,7 @@ impl Sim {
             AgentID::Pedestrian(id) => self.walking.agent_properties(map, id, self.time),
             AgentID::Car(id) => self.driving.agent_properties(id, self.time),
             // TODO Harder to measure some of this stuff
-            AgentID::BusPassenger(_, _) => AgentProperties {
+            AgentID::BusPassenger(_, _) | AgentID::CarPassenger(_, _) => AgentProperties {
                 total_time: Duration::ZERO,
                 waiting_here: Duration::ZERO,
                 total_waiting: Duration::ZERO,
@@ -204,6 +208,10 @@ impl Sim {
             VehicleType::Bike,
             VehicleType::Bus,
             VehicleType::Train,
+            VehicleType::Truck,
+            VehicleType::Van,
+            VehicleType::Motorcycle,
+            VehicleType::Scooter,
         ] {
             let id = CarID {
                 id: idx,
@@ -214,13 +222,20 @@ impl Sim {
             }
         }
 
-        let id = CarID {
-            id: idx,
-            vehicle_type: VehicleType::Car,
-        };
-        // Only cars can be parked.
-        if self.parking.lookup_parked_car(id).is_some() {
-            return Some(id);
+        // Only car-like vehicles can be parked.
+        for vehicle_type in [
+            VehicleType::Car,
+            VehicleType::Truck,
+            VehicleType::Van,
+            VehicleType::Motorcycle,
+        ] {
+            let id = CarID {
+                id: idx,
+                vehicle_type,
+            };
+            if self.parking.lookup_parked_car(id).is_some() {
+                return Some(id);
+            }
         }
 
         None
@@ -230,7 +245,7 @@ impl Sim {
         match id {
             AgentID::Car(car) => self.driving.get_path(car),
             AgentID::Pedestrian(ped) => self.walking.get_path(ped),
-            AgentID::BusPassenger(_, _) => None,
+            AgentID::BusPassenger(_, _) | AgentID::CarPassenger(_, _) => None,
         }
     }
     pub fn get_all_driving_paths(&self) -> Vec<&Path> {
@@ -241,7 +256,7 @@ impl Sim {
         match id {
             AgentID::Car(car) => self.driving.trace_route(self.time, car, map),
             AgentID::Pedestrian(ped) => self.walking.trace_route(self.time, ped, map),
-            AgentID::BusPassenger(_, _) => None,
+            AgentID::BusPassenger(_, _) | AgentID::CarPassenger(_, _) => None,
         }
     }
 
@@ -272,7 +287,9 @@ impl Sim {
                 .canonical_pt(id, map)
                 .or_else(|| Some(self.get_draw_car(id, map)?.body.last_pt())),
             AgentID::Pedestrian(id) => Some(self.get_draw_ped(id, map)?.pos),
-            AgentID::BusPassenger(_, bus) => Some(self.get_draw_car(bus, map)?.body.last_pt()),
+            AgentID::BusPassenger(_, car) | AgentID::CarPassenger(_, car) => {
+                Some(self.get_draw_car(car, map)?.body.last_pt())
+            }
         }
     }
 
@@ -313,6 +330,53 @@ impl Sim {
         &self.analytics
     }
 
+    /// How well has the ride-hail fleet served its riders so far?
+    pub fn ride_hail_summary(&self) -> RideHailSummary {
+        self.analytics
+            .ride_hail_summary(self.time, self.ride_hail.fleet_size())
+    }
+
+    /// How regularly and how crowded has every transit route run so far?
+    pub fn transit_route_summaries(&self, map: &Map) -> Vec<TransitRouteSummary> {
+        map.all_transit_routes()
+            .iter()
+            .map(|tr| self.analytics.transit_route_summary(tr, self.time))
+            .collect()
+    }
+
+    /// How much energy have vehicles used, and what have they emitted so far?
+    pub fn emissions_summary(&self) -> EmissionsSummary {
+        self.analytics.emissions_summary()
+    }
+
+    /// Near-misses measured so far, in a format comparable to real collision data. Empty unless
+    /// `SimOptions::measure_safety_conflicts` is enabled.
+    pub fn safety_conflict_dataset(&self, map: &Map) -> ConflictDataset {
+        ConflictDataset {
+            source: format!(
+                "{} simulated on {}",
+                self.run_name,
+                map.get_name().describe()
+            ),
+            conflicts: self
+                .analytics
+                .safety_conflicts
+                .iter()
+                .map(|(time, conflict)| Conflict {
+                    location: map
+                        .get_t(conflict.turn)
+                        .geom
+                        .middle()
+                        .to_gps(map.get_gps_bounds()),
+                    time: *time - Time::START_OF_DAY,
+                    ttc: conflict.ttc,
+                    pet: conflict.pet,
+                    description: conflict.describe(map),
+                })
+                .collect(),
+        }
+    }
+
     /// For intersections with an agent waiting beyond some threshold, return when they started
     /// waiting. Sorted by earliest waiting (likely the root cause of gridlock).
     pub fn delayed_intersections(&self, threshold: Duration) -> Vec<(IntersectionID, Time)> {
@@ -328,6 +392,11 @@ impl Sim {
         self.pandemic.as_ref()
     }
 
+    /// Only true when the simulation was started with an emissions model.
+    pub fn is_estimating_emissions(&self) -> bool {
+        self.driving.is_estimating_emissions()
+    }
+
     pub fn get_end_of_day(&self) -> Time {
         // Always count at least 24 hours
         // TODO This should be min()? Also, the end of the day will keep shifting every time we run
@@ -427,13 +496,13 @@ impl Sim {
                 let max_speed = match info.mode {
                     TripMode::Walk | TripMode::Transit => Some(person.ped_speed),
                     // TODO We should really search the vehicles and grab it from there
-                    TripMode::Drive => None,
+                    TripMode::Drive | TripMode::RideHail => None,
                     // Assume just one bike
                     TripMode::Bike => {
                         person
                             .vehicles
                             .iter()
-                            .find(|v| v.vehicle_type == VehicleType::Bike)
+                            .find(|v| v.vehicle_type.is_bike_like())
                             .unwrap()
                             .max_speed
                     }
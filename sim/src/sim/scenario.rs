@@ -7,12 +7,15 @@ use rand_xorshift::XorShiftRng;
 use abstutil::{prettyprint_usize, Counter, Timer};
 use geom::{Distance, Speed};
 use map_model::{BuildingID, Map, OffstreetParking, RoadID};
-use synthpop::{PersonSpec, Scenario, TripEndpoint, TripMode};
+use synthpop::{
+    CarpoolRole, PersonSpec, Scenario, TripEndpoint, TripMode, TripPurpose, VehicleKind,
+};
 
 use crate::make::fork_rng;
 use crate::{
     ParkingSpot, Sim, StartTripArgs, TripInfo, Vehicle, VehicleSpec, VehicleType, BIKE_LENGTH,
-    MAX_CAR_LENGTH, MIN_CAR_LENGTH,
+    MAX_CAR_LENGTH, MAX_TRUCK_LENGTH, MAX_VAN_LENGTH, MIN_CAR_LENGTH, MIN_TRUCK_LENGTH,
+    MIN_VAN_LENGTH, MOTORCYCLE_LENGTH, SCOOTER_LENGTH,
 };
 
 impl Sim {
@@ -41,6 +44,10 @@ impl Sim {
 
         timer.start(format!("Instantiating {}", scenario.scenario_name));
 
+        if self.intersections.jaywalking_enabled() {
+            self.intersections.seed_jaywalking(fork_rng(rng));
+        }
+
         if let Some(ref routes) = scenario.only_seed_buses {
             for route in map.all_transit_routes() {
                 if routes.contains(&route.long_name) {
@@ -65,7 +72,7 @@ impl Sim {
             }
 
             let (vehicle_specs, cars_initially_parked_at, vehicle_foreach_trip) =
-                get_vehicles(p, rng);
+                get_vehicles(p, self.trips.plans_transit_journeys(), rng);
             let person = self.new_person(p.orig_id, rand_ped_speed(rng), vehicle_specs);
             for (idx, b) in cars_initially_parked_at {
                 parked_cars.push((person.vehicles[idx].clone(), b));
@@ -85,6 +92,7 @@ impl Sim {
                         } else {
                             None
                         },
+                        carpool: trip.carpool.clone(),
                     },
                     StartTripArgs {
                         retry_if_no_room,
@@ -103,8 +111,10 @@ impl Sim {
     }
 }
 
+/// With `park_and_ride`, transit trips made to transfer from a car start by driving to a stop.
 fn get_vehicles(
     person: &PersonSpec,
+    park_and_ride: bool,
     rng: &mut XorShiftRng,
 ) -> (
     Vec<VehicleSpec>,
@@ -116,13 +126,43 @@ fn get_vehicles(
     let mut vehicle_foreach_trip = Vec::new();
 
     let mut bike_idx = None;
+    let mut scooter_idx = None;
     // For each indexed car, is it parked somewhere, or off-map?
     let mut car_locations: Vec<(usize, Option<BuildingID>)> = Vec::new();
 
     // TODO If the trip is cancelled, this should be affected...
     for trip in &person.trips {
         let use_for_trip = match trip.mode {
-            TripMode::Walk | TripMode::Transit => None,
+            TripMode::Transit
+                if park_and_ride && trip.purpose == TripPurpose::ParkAndRideTransfer =>
+            {
+                match trip.origin {
+                    TripEndpoint::Building(b) => {
+                        let idx = find_car(
+                            Some(b),
+                            VehicleType::Car,
+                            &car_locations,
+                            &mut vehicle_specs,
+                            &mut cars_initially_parked_at,
+                            rng,
+                        );
+                        // The car is left near some stop, so nothing else can use it
+                        car_locations.retain(|(i, _)| idx != *i);
+                        Some(idx)
+                    }
+                    TripEndpoint::Border(_) | TripEndpoint::SuddenlyAppear(_) => None,
+                }
+            }
+            TripMode::Walk | TripMode::Transit | TripMode::RideHail => None,
+            // Carpool passengers ride in somebody else's car
+            TripMode::Drive if matches!(trip.carpool, Some((_, CarpoolRole::Passenger))) => None,
+            TripMode::Bike if trip.vehicle == Some(VehicleKind::Scooter) => {
+                if scooter_idx.is_none() {
+                    scooter_idx = Some(vehicle_specs.len());
+                    vehicle_specs.push(rand_vehicle(VehicleKind::Scooter, rng));
+                }
+                scooter_idx
+            }
             TripMode::Bike => {
                 if bike_idx.is_none() {
                     bike_idx = Some(vehicle_specs.len());
@@ -136,22 +176,18 @@ fn get_vehicles(
                     _ => None,
                 };
 
-                // Any available cars in the right spot?
-                let idx = if let Some(idx) = car_locations
-                    .iter()
-                    .find(|(_, parked_at)| *parked_at == need_parked_at)
-                    .map(|(idx, _)| *idx)
-                {
-                    idx
-                } else {
-                    // Need a new car, starting in the right spot
-                    let idx = vehicle_specs.len();
-                    vehicle_specs.push(rand_car(rng));
-                    if let Some(b) = need_parked_at {
-                        cars_initially_parked_at.push((idx, b));
-                    }
-                    idx
-                };
+                let vehicle_type = trip
+                    .vehicle
+                    .map(VehicleType::from_vehicle_kind)
+                    .unwrap_or(VehicleType::Car);
+                let idx = find_car(
+                    need_parked_at,
+                    vehicle_type,
+                    &car_locations,
+                    &mut vehicle_specs,
+                    &mut cars_initially_parked_at,
+                    rng,
+                );
 
                 // Where does this car wind up?
                 car_locations.retain(|(i, _)| idx != *i);
@@ -176,6 +212,9 @@ fn get_vehicles(
         if bike_idx.is_some() {
             n -= 1;
         }
+        if scooter_idx.is_some() {
+            n -= 1;
+        }
         if n > 1 {
             println!("Someone needs {} cars", n);
         }
@@ -188,6 +227,36 @@ fn get_vehicles(
     )
 }
 
+fn find_car(
+    need_parked_at: Option<BuildingID>,
+    vehicle_type: VehicleType,
+    car_locations: &[(usize, Option<BuildingID>)],
+    vehicle_specs: &mut Vec<VehicleSpec>,
+    cars_initially_parked_at: &mut Vec<(usize, BuildingID)>,
+    rng: &mut XorShiftRng,
+) -> usize {
+    // Any available vehicles of the right type in the right spot?
+    if let Some(idx) = car_locations
+        .iter()
+        .find(|(idx, parked_at)| {
+            *parked_at == need_parked_at && vehicle_specs[*idx].vehicle_type == vehicle_type
+        })
+        .map(|(idx, _)| *idx)
+    {
+        return idx;
+    }
+    // Need a new vehicle, starting in the right spot
+    let idx = vehicle_specs.len();
+    vehicle_specs.push(match vehicle_type.to_vehicle_kind() {
+        Some(kind) => rand_vehicle(kind, rng),
+        None => rand_car(rng),
+    });
+    if let Some(b) = need_parked_at {
+        cars_initially_parked_at.push((idx, b));
+    }
+    idx
+}
+
 fn rand_car(rng: &mut XorShiftRng) -> VehicleSpec {
     let length = rand_dist(rng, MIN_CAR_LENGTH, MAX_CAR_LENGTH);
     VehicleSpec {
@@ -210,6 +279,31 @@ fn rand_bike(rng: &mut XorShiftRng) -> VehicleSpec {
     }
 }
 
+fn rand_vehicle(kind: VehicleKind, rng: &mut XorShiftRng) -> VehicleSpec {
+    let (length, max_speed) = match kind {
+        VehicleKind::Truck => (
+            rand_dist(rng, MIN_TRUCK_LENGTH, MAX_TRUCK_LENGTH),
+            Some(map_model::MAX_TRUCK_SPEED),
+        ),
+        VehicleKind::Van => (rand_dist(rng, MIN_VAN_LENGTH, MAX_VAN_LENGTH), None),
+        VehicleKind::Motorcycle => (MOTORCYCLE_LENGTH, None),
+        // Most e-scooters are limited to around 25km/h
+        VehicleKind::Scooter => (
+            SCOOTER_LENGTH,
+            Some(rand_speed(
+                rng,
+                Speed::km_per_hour(15.0),
+                Speed::km_per_hour(25.0),
+            )),
+        ),
+    };
+    VehicleSpec {
+        vehicle_type: VehicleType::from_vehicle_kind(kind),
+        length,
+        max_speed,
+    }
+}
+
 pub fn rand_dist(rng: &mut XorShiftRng, low: Distance, high: Distance) -> Distance {
     assert!(high > low);
     Distance::meters(rng.gen_range(low.inner_meters()..high.inner_meters()))
@@ -353,10 +447,10 @@ fn find_spot_near_building(
 
 pub fn count_parked_cars_per_bldg(scenario: &Scenario) -> Counter<BuildingID> {
     let mut per_bldg = Counter::new();
-    // Pass in a dummy RNG
+    // Pass in a dummy RNG, and assume the default SimOptions
     let mut rng = XorShiftRng::seed_from_u64(0);
     for p in &scenario.people {
-        let (_, cars_initially_parked_at, _) = get_vehicles(p, &mut rng);
+        let (_, cars_initially_parked_at, _) = get_vehicles(p, false, &mut rng);
         for (_, b) in cars_initially_parked_at {
             per_bldg.inc(b);
         }
@@ -3,17 +3,27 @@ use std::collections::{BTreeMap, BTreeSet};
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_btreemap, serialize_btreemap};
-use geom::Time;
+use geom::{Duration, Time};
 use map_model::{Map, Path, TransitRoute, TransitRouteID, TransitStopID};
 
 use crate::sim::Ctx;
 use crate::{
     AgentID, CarID, DrivingSimState, Event, PedestrianID, PersonID, Router, TripID, TripManager,
-    TripPhaseType, UnzoomedAgent, VehicleType, WalkingSimState,
+    TripPhaseType, UnzoomedAgent, VehicleType, WalkingSimState, BUS_CAPACITY, LIGHT_RAIL_CAPACITY,
 };
 
 // These index stops along a route, not stops along a single sidewalk.
 type StopIdx = usize;
+// (ped, route, bound for, started waiting)
+type Waiting = (PedestrianID, TransitRouteID, Option<TransitStopID>, Time);
+
+/// Vehicles wait this long at every stop, even if nobody gets on or off.
+const BASE_DWELL_TIME: Duration = Duration::const_seconds(10.0);
+const TIME_PER_BOARDING: Duration = Duration::const_seconds(3.0);
+const TIME_PER_ALIGHTING: Duration = Duration::const_seconds(2.0);
+/// Every few stops along a route is a timepoint, where a vehicle running early holds until its
+/// scheduled departure. The first stop is always a timepoint.
+const TIMEPOINT_EVERY_N_STOPS: usize = 5;
 
 #[derive(Serialize, Deserialize, Clone)]
 struct Route {
@@ -21,6 +31,11 @@ struct Route {
     // the place where the vehicle vanishes.
     paths: Vec<Path>,
     stops: Vec<TransitStopID>,
+    /// Entry i is when a vehicle should depart stop i, relative to when it started the route.
+    /// This assumes free-flow driving and nobody boarding or alighting. Only used for vehicles
+    /// that don't follow the imported timetable. Since a vehicle can't beat this schedule, it
+    /// almost never holds at timepoints; that only matters for imported timetables.
+    schedule: Vec<Duration>,
     active_vehicles: BTreeSet<CarID>,
 }
 
@@ -30,9 +45,21 @@ struct Bus {
     route: TransitRouteID,
     /// Where does each passenger want to deboard?
     passengers: Vec<(PersonID, Option<TransitStopID>)>,
+    capacity: usize,
+    /// When the vehicle was scheduled to start the route
+    scheduled_start: Time,
+    /// If the vehicle follows a trip from the route's imported timetable, when it should depart
+    /// each stop
+    timetable: Option<Vec<Time>>,
     state: BusState,
 }
 
+impl Bus {
+    fn is_full(&self) -> bool {
+        self.passengers.len() >= self.capacity
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 enum BusState {
     DrivingToStop(StopIdx),
@@ -44,6 +71,10 @@ enum BusState {
 
 /// Manages public transit vehicles (buses and trains) that follow a route. The transit model is
 /// currently kind of broken, so not describing the state machine yet.
+///
+/// Each vehicle has a limited capacity, so riders may be left behind at a stop when a full
+/// vehicle arrives. Vehicles dwell at stops longer when more people get on or off, and at
+/// timepoints, vehicles running ahead of schedule wait.
 #[derive(Serialize, Deserialize, Clone)]
 pub(crate) struct TransitSimState {
     #[serde(
@@ -56,13 +87,11 @@ pub(crate) struct TransitSimState {
         deserialize_with = "deserialize_btreemap"
     )]
     routes: BTreeMap<TransitRouteID, Route>,
-    /// waiting at => (ped, route, bound for, started waiting)
     #[serde(
         serialize_with = "serialize_btreemap",
         deserialize_with = "deserialize_btreemap"
     )]
-    peds_waiting:
-        BTreeMap<TransitStopID, Vec<(PedestrianID, TransitRouteID, Option<TransitStopID>, Time)>>,
+    peds_waiting: BTreeMap<TransitStopID, Vec<Waiting>>,
 
     events: Vec<Event>,
 }
@@ -91,9 +120,16 @@ impl TransitSimState {
                 Ok(paths) => {
                     let stops = bus_route.stops.clone();
                     assert_eq!(paths.len(), stops.len() + 1);
+                    let mut schedule = Vec::new();
+                    let mut offset = Duration::ZERO;
+                    for path in &paths[0..stops.len()] {
+                        offset += path.estimate_duration(map, None) + BASE_DWELL_TIME;
+                        schedule.push(offset);
+                    }
                     Route {
                         stops,
                         paths,
+                        schedule,
                         active_vehicles: BTreeSet::new(),
                     }
                 }
@@ -105,22 +141,30 @@ impl TransitSimState {
         self.routes[&bus_route.id].paths[0].clone()
     }
 
-    pub fn bus_created(&mut self, bus: CarID, r: TransitRouteID) {
+    pub fn bus_created(&mut self, bus: CarID, r: TransitRouteID, scheduled_start: Time, map: &Map) {
         let route = self.routes.get_mut(&r).unwrap();
         route.active_vehicles.insert(bus);
+        let capacity = match bus.vehicle_type {
+            VehicleType::Bus => BUS_CAPACITY,
+            VehicleType::Train => LIGHT_RAIL_CAPACITY,
+            _ => unreachable!(),
+        };
         self.buses.insert(
             bus,
             Bus {
                 car: bus,
                 route: r,
                 passengers: Vec::new(),
+                capacity,
+                scheduled_start,
+                timetable: map.get_tr(r).scheduled_stop_times(scheduled_start).cloned(),
                 state: BusState::DrivingToStop(0),
             },
         );
     }
 
-    /// If true, the bus is idling. If false, the bus actually arrived at a border and should now
-    /// vanish.
+    /// If Some, the bus is idling at a stop for that long. If None, the bus actually arrived at a
+    /// border and should now vanish.
     ///
     /// TODO Misnomer -- callback from Router::follow_bus_route
     pub fn bus_arrived_at_stop(
@@ -130,7 +174,7 @@ impl TransitSimState {
         trips: &mut TripManager,
         walking: &mut WalkingSimState,
         ctx: &mut Ctx,
-    ) -> bool {
+    ) -> Option<Duration> {
         let mut bus = self.buses.get_mut(&id).unwrap();
         match bus.state {
             BusState::DrivingToStop(stop_idx) => {
@@ -141,8 +185,10 @@ impl TransitSimState {
 
                 // Deboard existing passengers.
                 let mut still_riding = Vec::new();
+                let mut alighted = 0;
                 for (person, maybe_stop2) in bus.passengers.drain(..) {
                     if Some(stop1) == maybe_stop2 {
+                        alighted += 1;
                         trips.person_left_bus(now, person, bus.car, ctx);
                         self.events.push(Event::PassengerAlightsTransit(
                             person, bus.car, bus.route, stop1,
@@ -153,43 +199,58 @@ impl TransitSimState {
                 }
                 bus.passengers = still_riding;
 
-                // Board new passengers.
-                let mut still_waiting = Vec::new();
-                for (ped, route, maybe_stop2, started_waiting) in
-                    self.peds_waiting.remove(&stop1).unwrap()
-                {
-                    if bus.route == route {
-                        let (trip, person) = trips.ped_boarded_bus(
-                            now,
-                            ped,
-                            bus.car,
-                            now - started_waiting,
-                            walking,
-                        );
-                        self.events.push(Event::PassengerBoardsTransit(
-                            person,
-                            bus.car,
-                            bus.route,
-                            stop1,
-                            now - started_waiting,
-                        ));
-                        // TODO Recording the PathRequest for the passenger is actually hard. We
-                        // don't want to route directly between their first and last stop, because
-                        // there might be a much shorter path there. Should we record a leg per leg
-                        // of the transit route being followed?
-                        self.events.push(Event::TripPhaseStarting(
-                            trip,
-                            person,
-                            None,
-                            TripPhaseType::RidingBus(route, stop1, bus.car),
-                        ));
-                        bus.passengers.push((person, maybe_stop2));
-                    } else {
-                        still_waiting.push((ped, route, maybe_stop2, started_waiting));
-                    }
+                let (boarding, still_waiting, left_behind) = board_in_order(
+                    self.peds_waiting.remove(&stop1).unwrap(),
+                    bus.route,
+                    bus.capacity.saturating_sub(bus.passengers.len()),
+                );
+                let boarded = boarding.len();
+                for (ped, route, maybe_stop2, started_waiting) in boarding {
+                    let (trip, person) =
+                        trips.ped_boarded_bus(now, ped, bus.car, now - started_waiting, walking);
+                    self.events.push(Event::PassengerBoardsTransit(
+                        person,
+                        bus.car,
+                        bus.route,
+                        stop1,
+                        now - started_waiting,
+                    ));
+                    // TODO Recording the PathRequest for the passenger is actually hard. We
+                    // don't want to route directly between their first and last stop, because
+                    // there might be a much shorter path there. Should we record a leg per leg
+                    // of the transit route being followed?
+                    self.events.push(Event::TripPhaseStarting(
+                        trip,
+                        person,
+                        None,
+                        TripPhaseType::RidingBus(route, stop1, bus.car),
+                    ));
+                    bus.passengers.push((person, maybe_stop2));
                 }
                 self.peds_waiting.insert(stop1, still_waiting);
-                true
+                if left_behind > 0 {
+                    self.events.push(Event::PassengersLeftBehind(
+                        id,
+                        bus.route,
+                        stop1,
+                        left_behind,
+                    ));
+                }
+
+                let scheduled_departure = match bus.timetable {
+                    Some(ref times) => times[stop_idx],
+                    None => bus.scheduled_start + self.routes[&bus.route].schedule[stop_idx],
+                };
+                let dwell = dwell_time(stop_idx, boarded, alighted, scheduled_departure, now);
+                if bus.timetable.is_some() {
+                    self.events.push(Event::TransitScheduleDeviation(
+                        id,
+                        bus.route,
+                        stop1,
+                        now + dwell - scheduled_departure,
+                    ));
+                }
+                Some(dwell)
             }
             BusState::DrivingOffMap => {
                 self.routes
@@ -208,7 +269,7 @@ impl TransitSimState {
                     trips.transit_rider_reached_border(now, person, id, ctx);
                 }
                 bus.state = BusState::Finished;
-                false
+                None
             }
             BusState::AtStop(_) | BusState::Finished => unreachable!(),
         }
@@ -223,6 +284,7 @@ impl TransitSimState {
                     id,
                     bus.route,
                     route.stops[stop_idx],
+                    bus.passengers.len(),
                 ));
 
                 if stop_idx == route.stops.len() - 1 {
@@ -238,7 +300,8 @@ impl TransitSimState {
         }
     }
 
-    /// Returns the bus if the pedestrian boarded immediately.
+    /// Returns the bus if the pedestrian boarded immediately. They can't board a full bus idling at
+    /// the stop, and instead wait for the next one.
     pub fn ped_waiting_for_bus(
         &mut self,
         now: Time,
@@ -255,6 +318,11 @@ impl TransitSimState {
             for bus in &route.active_vehicles {
                 if let BusState::AtStop(idx) = self.buses[bus].state {
                     if route.stops[idx] == stop1 {
+                        if self.buses[bus].is_full() {
+                            self.events
+                                .push(Event::PassengersLeftBehind(*bus, route_id, stop1, 1));
+                            continue;
+                        }
                         self.buses
                             .get_mut(bus)
                             .unwrap()
@@ -340,10 +408,7 @@ impl TransitSimState {
         (buses, trains)
     }
 
-    pub fn get_people_waiting_at_stop(
-        &self,
-        at: TransitStopID,
-    ) -> &Vec<(PedestrianID, TransitRouteID, Option<TransitStopID>, Time)> {
+    pub fn get_people_waiting_at_stop(&self, at: TransitStopID) -> &Vec<Waiting> {
         &self.peds_waiting[&at]
     }
 
@@ -379,3 +444,122 @@ impl TransitSimState {
         results
     }
 }
+
+/// Split the people waiting at a stop into those boarding a vehicle on some route, in the order
+/// they started waiting, and everybody else. Also returns how many waiting for the route were left
+/// behind, because the vehicle filled up.
+fn board_in_order(
+    waiting: Vec<Waiting>,
+    route: TransitRouteID,
+    mut free_spots: usize,
+) -> (Vec<Waiting>, Vec<Waiting>, usize) {
+    let mut boarding = Vec::new();
+    let mut still_waiting = Vec::new();
+    let mut left_behind = 0;
+    for x in waiting {
+        if x.1 != route {
+            still_waiting.push(x);
+        } else if free_spots == 0 {
+            left_behind += 1;
+            still_waiting.push(x);
+        } else {
+            free_spots -= 1;
+            boarding.push(x);
+        }
+    }
+    (boarding, still_waiting, left_behind)
+}
+
+/// How long a vehicle stays at a stop. At a timepoint, a vehicle running early also holds until
+/// its scheduled departure.
+fn dwell_time(
+    stop_idx: StopIdx,
+    boarded: usize,
+    alighted: usize,
+    scheduled_departure: Time,
+    now: Time,
+) -> Duration {
+    let dwell = BASE_DWELL_TIME
+        + TIME_PER_BOARDING * (boarded as f64)
+        + TIME_PER_ALIGHTING * (alighted as f64);
+    if stop_idx % TIMEPOINT_EVERY_N_STOPS == 0 {
+        dwell.max(scheduled_departure - now)
+    } else {
+        dwell
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn waiting(ped: usize, route: usize) -> Waiting {
+        (
+            PedestrianID(ped),
+            TransitRouteID(route),
+            None,
+            Time::START_OF_DAY + Duration::minutes(ped),
+        )
+    }
+
+    fn peds(list: &[Waiting]) -> Vec<usize> {
+        list.iter().map(|(ped, _, _, _)| ped.0).collect()
+    }
+
+    #[test]
+    fn test_board_in_order() {
+        let route = TransitRouteID(1);
+        let all = vec![
+            waiting(0, 1),
+            waiting(1, 2),
+            waiting(2, 1),
+            waiting(3, 1),
+            waiting(4, 2),
+            waiting(5, 1),
+        ];
+
+        // Room for everybody
+        let (boarding, still_waiting, left_behind) = board_in_order(all.clone(), route, 10);
+        assert_eq!(peds(&boarding), vec![0, 2, 3, 5]);
+        assert_eq!(peds(&still_waiting), vec![1, 4]);
+        assert_eq!(left_behind, 0);
+
+        // The first to arrive at the stop get on
+        let (boarding, still_waiting, left_behind) = board_in_order(all.clone(), route, 2);
+        assert_eq!(peds(&boarding), vec![0, 2]);
+        assert_eq!(peds(&still_waiting), vec![1, 3, 4, 5]);
+        assert_eq!(left_behind, 2);
+
+        // A full vehicle
+        let (boarding, still_waiting, left_behind) = board_in_order(all, route, 0);
+        assert!(boarding.is_empty());
+        assert_eq!(peds(&still_waiting), vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(left_behind, 4);
+    }
+
+    #[test]
+    fn test_dwell_time() {
+        let now = Time::START_OF_DAY + Duration::hours(8);
+        let secs = Duration::seconds;
+
+        // 10s, plus 3s per boarding and 2s per alighting
+        assert_eq!(dwell_time(1, 0, 0, now, now), secs(10.0));
+        assert_eq!(dwell_time(1, 2, 3, now, now), secs(22.0));
+
+        // Running early at a timepoint means holding until the scheduled departure
+        let early = now + secs(60.0);
+        assert_eq!(dwell_time(0, 2, 3, early, now), secs(60.0));
+        assert_eq!(
+            dwell_time(TIMEPOINT_EVERY_N_STOPS, 2, 3, early, now),
+            secs(60.0)
+        );
+        // But not at other stops
+        for stop_idx in 1..TIMEPOINT_EVERY_N_STOPS {
+            assert_eq!(dwell_time(stop_idx, 2, 3, early, now), secs(22.0));
+        }
+        // Running a little early, but boarding takes longer anyway
+        assert_eq!(dwell_time(0, 2, 3, now + secs(15.0), now), secs(22.0));
+        // Running late
+        assert_eq!(dwell_time(0, 2, 3, now - secs(60.0), now), secs(22.0));
+    }
+}
@@ -1,23 +1,25 @@
-use std::collections::{BTreeMap, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_btreemap, serialize_btreemap, Counter};
 use geom::{Distance, Duration, Speed, Time};
 use map_model::{
-    BuildingID, IntersectionID, Map, PathConstraints, PathRequest, Position, TransitRouteID,
-    TransitStopID,
+    BuildingID, IntersectionID, JourneyAccess, JourneyPlanner, Map, PathConstraints, PathRequest,
+    Position, TransitRouteID, TransitStopID,
 };
 use synthpop::{
-    IndividTrip, OrigPersonID, PersonSpec, Scenario, TripEndpoint, TripMode, TripPurpose,
+    CarpoolID, CarpoolRole, IndividTrip, OrigPersonID, PersonSpec, Scenario, TripEndpoint,
+    TripMode, TripPurpose,
 };
 
 use crate::sim::Ctx;
 use crate::{
-    AgentID, AgentType, AlertLocation, CarID, Command, CreateCar, CreatePedestrian, DrivingGoal,
-    Event, ParkedCar, ParkingSim, ParkingSpot, PedestrianID, PersonID, SidewalkPOI, SidewalkSpot,
-    StartTripArgs, TransitSimState, TripID, TripPhaseType, TripSpec, Vehicle, VehicleSpec,
-    VehicleType, WalkingSimState,
+    AgentID, AgentType, AlertLocation, CarID, Command, CreateCar, CreatePedestrian, Dispatched,
+    DrivingGoal, Event, ParkedCar, ParkingSim, ParkingSpot, PedestrianID, PersonID, RideHailStop,
+    RouteChoices, Router, SidewalkPOI, SidewalkSpot, SimOptions, StartTripArgs, TransitSimState,
+    TripID, TripPhaseType, TripSpec, Vehicle, VehicleSpec, VehicleType, WalkingSimState,
 };
 
 /// Manages people, each of which executes some trips through the day. Each trip is further broken
@@ -37,25 +39,47 @@ pub(crate) struct TripManager {
     )]
     active_trip_mode: BTreeMap<AgentID, TripID>,
     unfinished_trips: usize,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    carpools: BTreeMap<CarpoolID, Carpool>,
 
     car_id_counter: usize,
 
     events: Vec<Event>,
+
+    // Only used for traffic assignment
+    #[serde(skip_serializing, skip_deserializing)]
+    pub route_choices: RouteChoices,
+
+    plan_transit_journeys: bool,
+    // Lazily built when the first transit trip starts
+    #[serde(skip_serializing, skip_deserializing)]
+    journey_planner: Option<JourneyPlanner>,
 }
 
 // Initialization
 impl TripManager {
-    pub fn new() -> TripManager {
+    pub fn new(opts: &SimOptions) -> TripManager {
         TripManager {
             trips: Vec::new(),
             people: Vec::new(),
             active_trip_mode: BTreeMap::new(),
             unfinished_trips: 0,
+            carpools: BTreeMap::new(),
             car_id_counter: 0,
             events: Vec::new(),
+            route_choices: RouteChoices::default(),
+            plan_transit_journeys: opts.plan_transit_journeys,
+            journey_planner: None,
         }
     }
 
+    pub fn plans_transit_journeys(&self) -> bool {
+        self.plan_transit_journeys
+    }
+
     // TODO assert the specs are correct yo
     pub fn new_person(
         &mut self,
@@ -85,6 +109,7 @@ impl TripManager {
             vehicles,
             delayed_trips: Vec::new(),
             on_bus: None,
+            in_car: None,
         });
         self.get_person(id).unwrap()
     }
@@ -129,6 +154,25 @@ impl TripManager {
             }
         }
         person.trips.push(id);
+        if let Some((carpool, ref role)) = trip.info.carpool {
+            if trip.info.mode == TripMode::Drive {
+                let carpool = self
+                    .carpools
+                    .entry(carpool)
+                    .or_insert_with(Carpool::default);
+                match role {
+                    // If there are multiple drivers, the extra ones just drive alone
+                    CarpoolRole::Driver { .. } => {
+                        if carpool.driver.is_none() {
+                            carpool.driver = Some(id);
+                        }
+                    }
+                    CarpoolRole::Passenger => {
+                        carpool.passengers.push(id);
+                    }
+                }
+            }
+        }
         self.trips.push(trip);
         id
     }
@@ -159,13 +203,41 @@ impl TripManager {
         }
         self.trips[trip.0].started = true;
 
+        if let Some(id) = self.carpool_passenger(trip) {
+            self.start_carpool_passenger(now, trip, id, ctx);
+            return;
+        }
+        if self.trips[trip.0].info.mode == TripMode::RideHail {
+            self.start_ride_hail(now, trip, ctx);
+            return;
+        }
+        let carpool_driver = self.carpool_driver_stops(trip);
+
+        let mut use_vehicle = args.use_vehicle;
+        if self.plan_transit_journeys && self.trips[trip.0].info.mode == TripMode::Transit {
+            if self.journey_planner.is_none() {
+                self.journey_planner = Some(JourneyPlanner::new(ctx.map));
+            }
+            // Anybody with a bike might bike to the first stop
+            if use_vehicle.is_none() {
+                use_vehicle = self.people[self.trips[trip.0].person.0]
+                    .vehicles
+                    .iter()
+                    .find(|v| v.vehicle_type.is_bike_like())
+                    .map(|v| v.id);
+            }
+        }
+
+        let person = &mut self.people[self.trips[trip.0].person.0];
         let info = &self.trips[trip.0].info;
         let spec = match TripSpec::maybe_new(
             info.start,
             info.end,
             info.mode,
-            args.use_vehicle,
+            use_vehicle,
             args.retry_if_no_room,
+            self.journey_planner.as_ref(),
+            now,
             ctx.map,
         ) {
             Ok(spec) => spec,
@@ -178,13 +250,64 @@ impl TripManager {
         let (spec, legs) = spec.into_plan(ctx.map);
         assert!(self.trips[trip.0].legs.is_empty());
         self.trips[trip.0].legs.extend(legs);
+        // A carpool driver parks at each stop along the way.
+        let mut carpool_driver_id = None;
+        if let Some((id, stops)) = carpool_driver {
+            let legs = &mut self.trips[trip.0].legs;
+            if let Some(idx) = legs
+                .iter()
+                .position(|leg| matches!(leg, TripLeg::Drive(_, _)))
+            {
+                let car = match legs[idx] {
+                    TripLeg::Drive(car, _) => car,
+                    _ => unreachable!(),
+                };
+                for (offset, b) in stops.into_iter().enumerate() {
+                    legs.insert(idx + offset, TripLeg::Drive(car, DrivingGoal::ParkNear(b)));
+                }
+            }
+            carpool_driver_id = Some(id);
+        }
+
+        // A transit journey starts just like walking to a stop, or driving or biking to one
+        let spec = match spec {
+            TripSpec::UsingTransitJourney {
+                start,
+                goal,
+                journey,
+                use_vehicle,
+            } => match (journey.access, use_vehicle, start.connection.clone()) {
+                (JourneyAccess::ParkAndRide(b), Some(car), SidewalkPOI::Building(start_bldg)) => {
+                    TripSpec::UsingParkedCar {
+                        car,
+                        start_bldg,
+                        goal: DrivingGoal::ParkNear(b),
+                    }
+                }
+                (JourneyAccess::BikeAndRide(b), Some(bike), SidewalkPOI::Building(start)) => {
+                    TripSpec::UsingBike {
+                        bike,
+                        start,
+                        goal: DrivingGoal::ParkNear(b),
+                    }
+                }
+                _ => TripSpec::UsingTransit {
+                    start,
+                    goal,
+                    route: journey.rides[0].route,
+                    stop1: journey.rides[0].board,
+                    maybe_stop2: Some(journey.rides[0].alight),
+                },
+            },
+            spec => spec,
+        };
 
         match spec {
             TripSpec::VehicleAppearing {
                 start_pos,
-                goal,
                 retry_if_no_room,
                 use_vehicle,
+                ..
             } => {
                 assert_eq!(person.state, PersonState::OffMap);
                 self.events.push(Event::PersonEntersMap(
@@ -194,13 +317,19 @@ impl TripManager {
                 ));
                 person.state = PersonState::Trip(trip);
 
+                // Carpool drivers head for their first stop. There's nobody to pick up where they
+                // appear.
+                let goal = match self.trips[trip.0].legs[0] {
+                    TripLeg::Drive(_, ref goal) => goal.clone(),
+                    _ => unreachable!(),
+                };
+                if let Some(id) = carpool_driver_id {
+                    self.carpools.get_mut(&id).unwrap().next_stop = 1;
+                }
+
                 let vehicle = person.get_vehicle(use_vehicle);
                 assert!(ctx.parking.lookup_parked_car(vehicle.id).is_none());
-                let constraints = if use_vehicle.vehicle_type == VehicleType::Bike {
-                    PathConstraints::Bike
-                } else {
-                    PathConstraints::Car
-                };
+                let constraints = use_vehicle.vehicle_type.to_constraints();
                 let req = PathRequest::vehicle(
                     start_pos,
                     goal.goal_pos(constraints, ctx.map).unwrap(),
@@ -208,7 +337,7 @@ impl TripManager {
                 );
                 let person = person.id;
 
-                match ctx.map.pathfind(req) {
+                match self.route_choices.pathfind(trip, req, now, ctx.map) {
                     Ok(path) => {
                         let router = goal.make_router(vehicle.id, path, ctx.map);
                         ctx.scheduler.push(
@@ -432,12 +561,18 @@ impl TripManager {
                     }
                 }
             }
+            TripSpec::UsingTransitJourney { .. } => unreachable!(),
         }
     }
 
     pub fn collect_events(&mut self) -> Vec<Event> {
         std::mem::take(&mut self.events)
     }
+
+    pub fn handle_live_edits(&mut self) {
+        // Transit routes may have changed, so rebuild this next time it's needed
+        self.journey_planner = None;
+    }
 }
 
 // Transitions between different legs of a trip
@@ -471,7 +606,21 @@ impl TripManager {
             }
             _ => unreachable!(),
         };
+        let id = trip.id;
 
+        if let Some(TripLeg::Drive(_, _)) = trip.legs.front() {
+            let parked_car = ctx.parking.get_car_at_spot(spot).unwrap().clone();
+            if self.carpool_driver_stops(id).is_some() {
+                // A carpool driver is stopping to pick up or drop off passengers
+                self.carpool_driver_stopped(now, id, parked_car, ctx);
+            } else {
+                self.drive_parked_car(now, id, parked_car, ctx);
+            }
+            return;
+        }
+        self.carpool_driver_arrived(now, id, ctx);
+
+        let trip = &mut self.trips[id.0];
         match &trip.legs[0] {
             TripLeg::Walk(to) => match (spot, &to.connection) {
                 (ParkingSpot::Offstreet(b1, _), SidewalkPOI::Building(b2)) if b1 == *b2 => {
@@ -490,7 +639,6 @@ impl TripManager {
             _ => unreachable!(),
         };
 
-        let id = trip.id;
         self.spawn_ped(
             now,
             id,
@@ -519,7 +667,23 @@ impl TripManager {
 
         trip.assert_walking_leg(SidewalkSpot::deferred_parking_spot());
         let parked_car = ctx.parking.get_car_at_spot(spot).unwrap().clone();
-        let drive_to = match trip.legs[0] {
+        if let ParkingSpot::Offstreet(b, _) = spot {
+            self.events
+                .push(Event::PersonEntersBuilding(trip.person, b));
+        }
+
+        let id = trip.id;
+        if self.carpool_driver_stops(id).is_some() {
+            // Pick up anybody starting from here
+            self.carpool_driver_stopped(now, id, parked_car, ctx);
+        } else {
+            self.drive_parked_car(now, id, parked_car, ctx);
+        }
+    }
+
+    /// Start driving a parked car towards the goal of the trip's next leg.
+    fn drive_parked_car(&mut self, now: Time, trip: TripID, parked_car: ParkedCar, ctx: &mut Ctx) {
+        let drive_to = match self.trips[trip.0].legs[0] {
             TripLeg::Drive(c, ref to) => {
                 assert_eq!(c, parked_car.vehicle.id);
                 to.clone()
@@ -530,24 +694,17 @@ impl TripManager {
         let base_start =
             ctx.parking
                 .spot_to_driving_pos(parked_car.spot, &parked_car.vehicle, ctx.map);
-        let end = drive_to.goal_pos(PathConstraints::Car, ctx.map).unwrap();
-        let req = match spot {
-            ParkingSpot::Onstreet(_, _) => {
-                PathRequest::vehicle(base_start, end, PathConstraints::Car)
-            }
-            ParkingSpot::Offstreet(b, _) => {
-                self.events
-                    .push(Event::PersonEntersBuilding(trip.person, b));
-                PathRequest::leave_from_driveway(base_start, end, PathConstraints::Car, ctx.map)
-            }
-            ParkingSpot::Lot(_, _) => {
-                PathRequest::leave_from_driveway(base_start, end, PathConstraints::Car, ctx.map)
+        let constraints = parked_car.vehicle.vehicle_type.to_constraints();
+        let end = drive_to.goal_pos(constraints, ctx.map).unwrap();
+        let req = match parked_car.spot {
+            ParkingSpot::Onstreet(_, _) => PathRequest::vehicle(base_start, end, constraints),
+            ParkingSpot::Offstreet(_, _) | ParkingSpot::Lot(_, _) => {
+                PathRequest::leave_from_driveway(base_start, end, constraints, ctx.map)
             }
         };
 
-        let person = trip.person;
-        let trip = trip.id;
-        match ctx.map.pathfind(req) {
+        let person = self.trips[trip.0].person;
+        match self.route_choices.pathfind(trip, req, now, ctx.map) {
             Ok(path) => {
                 let router = drive_to.make_router(parked_car.vehicle.id, path, ctx.map);
                 ctx.scheduler.push(
@@ -615,8 +772,8 @@ impl TripManager {
                 req.start.lane()
             ))
         } else {
-            ctx.map
-                .pathfind(req)
+            self.route_choices
+                .pathfind(trip.id, req, now, ctx.map)
                 .map(|path| drive_to.make_router(bike, path, ctx.map))
         };
         match maybe_router {
@@ -869,7 +1026,10 @@ impl TripManager {
         distance_crossed: Distance,
         ctx: &mut Ctx,
     ) {
-        let trip = &mut self.trips[self.active_trip_mode.remove(&AgentID::Car(car)).unwrap().0];
+        let id = self.active_trip_mode.remove(&AgentID::Car(car)).unwrap();
+        // Passengers ride off the map too
+        self.carpool_driver_arrived(now, id, ctx);
+        let trip = &mut self.trips[id.0];
         trip.total_blocked_time += blocked_time;
         trip.total_distance += distance_crossed;
 
@@ -890,7 +1050,6 @@ impl TripManager {
             ));
         }
 
-        let id = trip.id;
         self.trip_finished(now, id, ctx);
     }
 
@@ -1008,7 +1167,7 @@ impl TripManager {
 
         // Don't forget the car!
         if let Some(vehicle) = abandoned_vehicle {
-            if vehicle.vehicle_type == VehicleType::Car {
+            if vehicle.vehicle_type.is_car_like() {
                 // First remove the parked car, if needed. Maybe the trip was cancelled while the
                 // car was parked in the starting building.
                 if let Some(parked_car) = ctx.parking.lookup_parked_car(vehicle.id).cloned() {
@@ -1064,6 +1223,7 @@ impl TripManager {
             }
         }
 
+        self.carpool_trip_cancelled(now, id, ctx);
         self.start_delayed_trip(now, person, ctx);
     }
 
@@ -1072,6 +1232,408 @@ impl TripManager {
     }
 }
 
+// Carpools. Pickups and drop-offs happen at the curb and are instantaneous; the car just has to
+// park somewhere near the stop.
+impl TripManager {
+    /// If this trip is a carpool passenger, returns the carpool.
+    fn carpool_passenger(&self, trip: TripID) -> Option<CarpoolID> {
+        let info = &self.trips[trip.0].info;
+        match info.carpool {
+            Some((id, CarpoolRole::Passenger)) if info.mode == TripMode::Drive => Some(id),
+            _ => None,
+        }
+    }
+
+    /// If this trip is driving a carpool, returns the carpool and the stops between the trip's
+    /// start and end.
+    fn carpool_driver_stops(&self, trip: TripID) -> Option<(CarpoolID, Vec<BuildingID>)> {
+        let info = &self.trips[trip.0].info;
+        match info.carpool {
+            Some((id, CarpoolRole::Driver { ref stops }))
+                if self.carpools.get(&id).and_then(|c| c.driver) == Some(trip) =>
+            {
+                Some((id, stops.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Every place the carpool stops, including the driver's start and end.
+    fn all_carpool_stops(&self, id: CarpoolID) -> Vec<TripEndpoint> {
+        let driver = self.carpools[&id].driver.unwrap();
+        let info = &self.trips[driver.0].info;
+        let mut stops = vec![info.start];
+        if let Some((_, stops_between)) = self.carpool_driver_stops(driver) {
+            stops.extend(stops_between.into_iter().map(TripEndpoint::Building));
+        }
+        stops.push(info.end);
+        stops
+    }
+
+    /// Returns the index of the passenger's pickup and drop-off stops.
+    fn carpool_passenger_stops(&self, trip: TripID) -> Result<(usize, usize)> {
+        let id = self.carpool_passenger(trip).unwrap();
+        if self.carpools[&id].driver.is_none() {
+            bail!("carpool {:?} has no driver", id);
+        }
+        let info = &self.trips[trip.0].info;
+        let stops = self.all_carpool_stops(id);
+        let pickup = stops
+            .iter()
+            .position(|stop| *stop == info.start && matches!(stop, TripEndpoint::Building(_)))
+            .ok_or_else(|| anyhow!("carpool {:?} doesn't stop at {:?}", id, info.start))?;
+        let dropoff = stops
+            .iter()
+            .skip(pickup + 1)
+            .position(|stop| *stop == info.end)
+            .map(|idx| pickup + 1 + idx)
+            .ok_or_else(|| {
+                anyhow!(
+                    "carpool {:?} doesn't stop at {:?} after {:?}",
+                    id,
+                    info.end,
+                    info.start
+                )
+            })?;
+        Ok((pickup, dropoff))
+    }
+
+    fn start_carpool_passenger(&mut self, now: Time, trip: TripID, id: CarpoolID, ctx: &mut Ctx) {
+        // cancel_trip expects some leg to exist
+        self.trips[trip.0].legs.push_back(TripLeg::RideCar(id));
+
+        let pickup = match self.carpool_passenger_stops(trip) {
+            Ok((pickup, _)) => pickup,
+            Err(err) => {
+                self.cancel_trip(now, trip, err.to_string(), None, ctx);
+                return;
+            }
+        };
+        let carpool = &self.carpools[&id];
+        let driver = &self.trips[carpool.driver.unwrap().0];
+        if driver.finished_at.is_some()
+            || driver.info.cancellation_reason.is_some()
+            || carpool.next_stop > pickup
+        {
+            self.cancel_trip(
+                now,
+                trip,
+                format!("missed the ride with carpool {:?}", id),
+                None,
+                ctx,
+            );
+            return;
+        }
+
+        let person = self.trips[trip.0].person;
+        assert_eq!(
+            self.people[person.0].state,
+            PersonState::Inside(match self.trips[trip.0].info.start {
+                TripEndpoint::Building(b) => b,
+                _ => unreachable!(),
+            })
+        );
+        self.people[person.0].state = PersonState::Trip(trip);
+        self.events.push(Event::TripPhaseStarting(
+            trip,
+            person,
+            None,
+            TripPhaseType::WaitingForCarpool,
+        ));
+
+        let carpool = self.carpools.get_mut(&id).unwrap();
+        carpool.waiting.insert(trip);
+        // Maybe the driver is already here, waiting for us
+        if carpool.parked.is_some() && carpool.next_stop == pickup {
+            self.carpool_stop(now, id, ctx);
+        }
+    }
+
+    /// The driver has parked at the next stop.
+    fn carpool_driver_stopped(
+        &mut self,
+        now: Time,
+        driver: TripID,
+        parked_car: ParkedCar,
+        ctx: &mut Ctx,
+    ) {
+        let (id, _) = self.carpool_driver_stops(driver).unwrap();
+        self.carpools.get_mut(&id).unwrap().parked = Some(parked_car);
+        self.carpool_stop(now, id, ctx);
+    }
+
+    /// Drop off and pick up passengers at the current stop, then continue to the next stop if
+    /// nobody else is expected here.
+    fn carpool_stop(&mut self, now: Time, id: CarpoolID, ctx: &mut Ctx) {
+        let carpool = &self.carpools[&id];
+        let here = carpool.next_stop;
+        let car = carpool.parked.as_ref().unwrap().vehicle.id;
+
+        let mut drop_off = Vec::new();
+        let mut pick_up = Vec::new();
+        let mut still_expected = false;
+        for t in &carpool.passengers {
+            let (pickup, dropoff) = match self.carpool_passenger_stops(*t) {
+                Ok(pair) => pair,
+                Err(_) => {
+                    continue;
+                }
+            };
+            if carpool.riding.contains(t) {
+                if dropoff == here {
+                    drop_off.push(*t);
+                }
+            } else if carpool.waiting.contains(t) {
+                if pickup == here {
+                    pick_up.push(*t);
+                }
+            } else if pickup == here
+                && !self.trips[t.0].started
+                && self.trips[t.0].info.cancellation_reason.is_none()
+            {
+                still_expected = true;
+            }
+        }
+
+        for t in drop_off {
+            self.carpool_passenger_alights(now, id, t, ctx);
+        }
+        for t in pick_up {
+            self.carpool_passenger_boards(id, t, car);
+        }
+        if still_expected {
+            return;
+        }
+
+        // Finishing a passenger's trip might've started another trip that already sent the car on
+        // its way.
+        let carpool = self.carpools.get_mut(&id).unwrap();
+        if let Some(parked_car) = carpool.parked.take() {
+            carpool.next_stop += 1;
+            let driver = carpool.driver.unwrap();
+            self.drive_parked_car(now, driver, parked_car, ctx);
+        }
+    }
+
+    fn carpool_passenger_boards(&mut self, id: CarpoolID, trip: TripID, car: CarID) {
+        let carpool = self.carpools.get_mut(&id).unwrap();
+        if !carpool.waiting.remove(&trip) {
+            return;
+        }
+        carpool.riding.insert(trip);
+        self.passenger_gets_in(trip, car);
+    }
+
+    fn carpool_passenger_alights(&mut self, now: Time, id: CarpoolID, trip: TripID, ctx: &mut Ctx) {
+        self.carpools.get_mut(&id).unwrap().riding.remove(&trip);
+        self.passenger_gets_out(now, trip, ctx);
+    }
+
+    /// The driver has reached their final destination, so everybody still riding gets out.
+    fn carpool_driver_arrived(&mut self, now: Time, driver: TripID, ctx: &mut Ctx) {
+        if let Some((id, _)) = self.carpool_driver_stops(driver) {
+            let riding: Vec<TripID> = self.carpools[&id].riding.iter().cloned().collect();
+            for t in riding {
+                self.carpool_passenger_alights(now, id, t, ctx);
+            }
+        }
+    }
+
+    fn carpool_trip_cancelled(&mut self, now: Time, trip: TripID, ctx: &mut Ctx) {
+        let id = match self.trips[trip.0].info.carpool {
+            Some((id, _)) => id,
+            None => {
+                return;
+            }
+        };
+        let carpool = match self.carpools.get_mut(&id) {
+            Some(carpool) => carpool,
+            None => {
+                return;
+            }
+        };
+
+        if carpool.driver == Some(trip) {
+            // Nobody else is getting a ride
+            carpool.parked = None;
+            let stranded: Vec<TripID> = carpool
+                .waiting
+                .iter()
+                .chain(carpool.riding.iter())
+                .cloned()
+                .collect();
+            carpool.waiting.clear();
+            for t in stranded {
+                if self.carpools[&id].riding.contains(&t) {
+                    self.carpools.get_mut(&id).unwrap().riding.remove(&t);
+                    let person = self.trips[t.0].person;
+                    let car = self.people[person.0].in_car.take().unwrap();
+                    self.active_trip_mode
+                        .remove(&AgentID::CarPassenger(person, car));
+                }
+                self.cancel_trip(
+                    now,
+                    t,
+                    format!("the driver of carpool {:?} had their trip cancelled", id),
+                    None,
+                    ctx,
+                );
+            }
+        } else if carpool.waiting.remove(&trip) {
+            // The driver might be waiting for this passenger
+            if carpool.parked.is_some() {
+                self.carpool_stop(now, id, ctx);
+            }
+        } else if carpool.riding.remove(&trip) {
+            let person = self.trips[trip.0].person;
+            let car = self.people[person.0].in_car.take().unwrap();
+            self.active_trip_mode
+                .remove(&AgentID::CarPassenger(person, car));
+        }
+    }
+
+    /// Somebody gets into a car driven by somebody else at the start of their trip. Also used for
+    /// ride-hailing.
+    fn passenger_gets_in(&mut self, trip: TripID, car: CarID) {
+        let person = self.trips[trip.0].person;
+        if let TripEndpoint::Building(b) = self.trips[trip.0].info.start {
+            self.events.push(Event::PersonLeavesBuilding(person, b));
+        }
+        self.people[person.0].in_car = Some(car);
+        self.active_trip_mode
+            .insert(AgentID::CarPassenger(person, car), trip);
+        self.events.push(Event::TripPhaseStarting(
+            trip,
+            person,
+            None,
+            TripPhaseType::RidingInCar(car),
+        ));
+    }
+
+    /// A passenger gets out of the car at the end of their trip. Also used for ride-hailing.
+    fn passenger_gets_out(&mut self, now: Time, trip: TripID, ctx: &mut Ctx) {
+        let person = self.trips[trip.0].person;
+        let car = self.people[person.0].in_car.take().unwrap();
+        let agent = AgentID::CarPassenger(person, car);
+        assert_eq!(self.active_trip_mode.remove(&agent), Some(trip));
+
+        match self.trips[trip.0].legs.pop_front() {
+            Some(TripLeg::RideCar(_)) | Some(TripLeg::RideHail) => {}
+            _ => unreachable!(),
+        }
+        self.people[person.0].state = match self.trips[trip.0].info.end {
+            TripEndpoint::Building(b) => {
+                self.events.push(Event::PersonEntersBuilding(person, b));
+                PersonState::Inside(b)
+            }
+            TripEndpoint::Border(i) => {
+                self.events
+                    .push(Event::PersonLeavesMap(person, Some(agent), i));
+                PersonState::OffMap
+            }
+            TripEndpoint::SuddenlyAppear(_) => unreachable!(),
+        };
+        self.trip_finished(now, trip, ctx);
+    }
+}
+
+// Ride-hailing. Like carpools, riders get in and out at the curb. RideHailSimState manages the
+// fleet itself.
+impl TripManager {
+    fn start_ride_hail(&mut self, now: Time, trip: TripID, ctx: &mut Ctx) {
+        // cancel_trip expects some leg to exist
+        self.trips[trip.0].legs.push_back(TripLeg::RideHail);
+
+        if let Err(err) = self
+            .ride_hail_curbs(trip, ctx.map)
+            .and_then(|(pickup, dropoff)| {
+                ctx.ride_hail
+                    .request_ride(now, trip, pickup, dropoff, ctx.map)
+            })
+        {
+            self.cancel_trip(now, trip, err.to_string(), None, ctx);
+            return;
+        }
+
+        let person = self.trips[trip.0].person;
+        self.people[person.0].state = PersonState::Trip(trip);
+        self.events.push(Event::TripPhaseStarting(
+            trip,
+            person,
+            None,
+            TripPhaseType::WaitingForRideHail,
+        ));
+        self.dispatch_ride_hail(now, ctx);
+    }
+
+    /// Where is the rider picked up and dropped off?
+    fn ride_hail_curbs(&self, trip: TripID, map: &Map) -> Result<(Position, Position)> {
+        let curb = |endpt: TripEndpoint| -> Result<Position> {
+            match endpt {
+                TripEndpoint::Building(b) => map
+                    .get_b(b)
+                    .driving_connection(map)
+                    .map(|(pos, _)| pos)
+                    .ok_or_else(|| anyhow!("{} isn't reachable by car", b)),
+                _ => bail!(
+                    "ride-hail trips must start and end at buildings, not {:?}",
+                    endpt
+                ),
+            }
+        };
+        let info = &self.trips[trip.0].info;
+        Ok((curb(info.start)?, curb(info.end)?))
+    }
+
+    fn dispatch_ride_hail(&mut self, now: Time, ctx: &mut Ctx) {
+        for result in ctx.ride_hail.dispatch(now, ctx.map, ctx.scheduler) {
+            match result {
+                Dispatched::PickedUp(trip, car) => {
+                    self.passenger_gets_in(trip, car);
+                }
+                Dispatched::Unreachable(trip, err) => {
+                    self.cancel_trip(now, trip, err, None, ctx);
+                }
+            }
+        }
+    }
+
+    pub fn ride_hail_vehicle_arrived(
+        &mut self,
+        now: Time,
+        car: CarID,
+        dist: Distance,
+        ctx: &mut Ctx,
+    ) {
+        match ctx.ride_hail.vehicle_arrived(now, car, dist, ctx.map) {
+            RideHailStop::Pickup(trip) => {
+                self.passenger_gets_in(trip, car);
+            }
+            RideHailStop::Dropoff(trip) => {
+                self.passenger_gets_out(now, trip, ctx);
+            }
+            RideHailStop::Unreachable(trip, err) => {
+                self.cancel_trip(now, trip, err, None, ctx);
+            }
+        }
+    }
+
+    /// Returns the vehicle's route to the drop-off, or None if the vehicle should vanish.
+    pub fn ride_hail_vehicle_departed(
+        &mut self,
+        now: Time,
+        car: CarID,
+        ctx: &mut Ctx,
+    ) -> Option<Router> {
+        let router = ctx.ride_hail.vehicle_departed(now, car);
+        if router.is_none() {
+            // Somebody might've been waiting for a vehicle to free up
+            self.dispatch_ride_hail(now, ctx);
+        }
+        router
+    }
+}
+
 // Queries
 impl TripManager {
     pub fn active_agents(&self) -> Vec<AgentID> {
@@ -1105,6 +1667,13 @@ impl TripManager {
             TripLeg::Walk(_) => AgentID::Pedestrian(person.ped),
             TripLeg::Drive(c, _) => AgentID::Car(*c),
             TripLeg::RideBus(_, _) => AgentID::BusPassenger(person.id, person.on_bus.unwrap()),
+            TripLeg::RideCar(_) | TripLeg::RideHail => match person.in_car {
+                Some(car) => AgentID::CarPassenger(person.id, car),
+                // Still waiting to be picked up
+                None => {
+                    return TripResult::ModeChange;
+                }
+            },
         };
         if self.active_trip_mode.get(&a) == Some(&id) {
             TripResult::Ok(a)
@@ -1159,6 +1728,8 @@ impl TripManager {
             cyclists: 0,
 
             sov_drivers: 0,
+            carpool_drivers: 0,
+            car_passengers: 0,
 
             buses,
             trains,
@@ -1166,13 +1737,29 @@ impl TripManager {
             train_riders: 0,
         };
 
+        let occupied_cars: BTreeSet<CarID> = self
+            .active_trip_mode
+            .keys()
+            .filter_map(|a| match a {
+                AgentID::CarPassenger(_, c) => Some(*c),
+                _ => None,
+            })
+            .collect();
+
         for a in self.active_trip_mode.keys() {
             match a {
                 AgentID::Car(c) => match c.vehicle_type {
-                    VehicleType::Car => {
-                        cnt.sov_drivers += 1;
+                    VehicleType::Car
+                    | VehicleType::Truck
+                    | VehicleType::Van
+                    | VehicleType::Motorcycle => {
+                        if occupied_cars.contains(c) {
+                            cnt.carpool_drivers += 1;
+                        } else {
+                            cnt.sov_drivers += 1;
+                        }
                     }
-                    VehicleType::Bike => {
+                    VehicleType::Bike | VehicleType::Scooter => {
                         cnt.cyclists += 1;
                     }
                     VehicleType::Bus | VehicleType::Train => unreachable!(),
@@ -1184,8 +1771,16 @@ impl TripManager {
                     VehicleType::Train => {
                         cnt.train_riders += 1;
                     }
-                    VehicleType::Car | VehicleType::Bike => unreachable!(),
+                    VehicleType::Car
+                    | VehicleType::Truck
+                    | VehicleType::Van
+                    | VehicleType::Motorcycle
+                    | VehicleType::Bike
+                    | VehicleType::Scooter => unreachable!(),
                 },
+                AgentID::CarPassenger(_, _) => {
+                    cnt.car_passengers += 1;
+                }
                 // These're counted separately
                 AgentID::Pedestrian(_) => {}
             }
@@ -1270,6 +1865,8 @@ impl TripManager {
                         // TODO Not true for long. People will be able to spawn at borders already
                         // on a bus.
                         TripMode::Transit => AgentType::Pedestrian,
+                        // Ride-hail trips don't start at borders
+                        TripMode::RideHail => AgentType::CarPassenger,
                     };
                     times.push((t.info.departure, agent_type));
                 }
@@ -1291,13 +1888,15 @@ impl TripManager {
                     .iter()
                     .map(|t| {
                         let trip = &self.trips[t.0];
-                        IndividTrip::new(
+                        let mut individ = IndividTrip::new(
                             trip.info.departure,
                             trip.info.purpose,
                             trip.info.start,
                             trip.info.end,
                             trip.info.mode,
-                        )
+                        );
+                        individ.carpool = trip.info.carpool.clone();
+                        individ
                     })
                     .collect(),
             });
@@ -1330,6 +1929,7 @@ pub struct TripInfo {
     /// Did a ScenarioModifier apply to this?
     pub modified: bool,
     pub cancellation_reason: Option<String>,
+    pub carpool: Option<(CarpoolID, CarpoolRole)>,
 }
 
 impl Trip {
@@ -1352,6 +1952,10 @@ pub(crate) enum TripLeg {
     Drive(CarID, DrivingGoal),
     /// Maybe get off at a stop, maybe ride off-map
     RideBus(TransitRouteID, Option<TransitStopID>),
+    /// Wait to be picked up, then get dropped off
+    RideCar(CarpoolID),
+    /// Wait for a ride-hail vehicle, then get dropped off
+    RideHail,
 }
 
 pub enum TripResult<T> {
@@ -1397,6 +2001,7 @@ pub struct Person {
 
     delayed_trips: Vec<(TripID, StartTripArgs)>,
     on_bus: Option<CarID>,
+    in_car: Option<CarID>,
 }
 
 impl Person {
@@ -1405,6 +2010,21 @@ impl Person {
     }
 }
 
+/// Progress of one carpool through the day
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+struct Carpool {
+    driver: Option<TripID>,
+    passengers: Vec<TripID>,
+    /// Passengers whose trip has started, but who haven't been picked up yet
+    waiting: BTreeSet<TripID>,
+    riding: BTreeSet<TripID>,
+    /// An index into the driver's start, stops, and end. The car is either parked at this stop or
+    /// heading towards it.
+    next_stop: usize,
+    /// Set while the car is parked at a stop, waiting for passengers
+    parked: Option<ParkedCar>,
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub enum PersonState {
     Trip(TripID),
@@ -1422,9 +2042,364 @@ pub struct CommutersVehiclesCounts {
     pub cyclists: usize,
 
     pub sov_drivers: usize,
+    /// Drivers with at least one passenger
+    pub carpool_drivers: usize,
+    pub car_passengers: usize,
 
     pub buses: usize,
     pub trains: usize,
     pub bus_riders: usize,
     pub train_riders: usize,
 }
+
+#[cfg(test)]
+mod tests {
+    use abstutil::Timer;
+
+    use super::*;
+    use crate::{
+        DispatchPolicy, IntersectionSimState, ParkingSimState, RideHailSimState, Scheduler,
+    };
+
+    /// Owns everything a Ctx borrows
+    struct World {
+        map: Map,
+        parking: ParkingSimState,
+        intersections: IntersectionSimState,
+        scheduler: Scheduler,
+        ride_hail: RideHailSimState,
+    }
+
+    impl World {
+        fn new() -> World {
+            let map = Map::blank();
+            let mut scheduler = Scheduler::new();
+            let parking = ParkingSimState::new(&map, false, &mut Timer::throwaway());
+            let intersections =
+                IntersectionSimState::new(&map, &mut scheduler, &SimOptions::new("test"));
+            let ride_hail = RideHailSimState::new(&map, 0, DispatchPolicy::Nearest, || 0);
+            World {
+                map,
+                parking,
+                intersections,
+                scheduler,
+                ride_hail,
+            }
+        }
+
+        fn ctx(&mut self) -> Ctx {
+            Ctx {
+                parking: &mut self.parking,
+                intersections: &mut self.intersections,
+                scheduler: &mut self.scheduler,
+                ride_hail: &mut self.ride_hail,
+                map: &self.map,
+                handling_live_edits: None,
+            }
+        }
+    }
+
+    fn b(id: usize) -> TripEndpoint {
+        TripEndpoint::Building(BuildingID(id))
+    }
+
+    /// Each trip belongs to a different person
+    fn add_trip(trips: &mut TripManager, start: usize, end: usize, role: CarpoolRole) -> TripID {
+        let person = trips
+            .new_person(None, Speed::meters_per_second(1.3), Vec::new())
+            .id;
+        trips.new_trip(
+            person,
+            TripInfo {
+                departure: Time::START_OF_DAY,
+                mode: TripMode::Drive,
+                start: b(start),
+                end: b(end),
+                purpose: TripPurpose::Work,
+                modified: false,
+                cancellation_reason: None,
+                carpool: Some((CarpoolID(0), role)),
+            },
+        )
+    }
+
+    /// The driver goes from building 0 to 3, stopping at 1 and 2 along the way.
+    fn add_driver(trips: &mut TripManager) -> TripID {
+        add_trip(
+            trips,
+            0,
+            3,
+            CarpoolRole::Driver {
+                stops: vec![BuildingID(1), BuildingID(2)],
+            },
+        )
+    }
+
+    fn car() -> CarID {
+        CarID {
+            id: 0,
+            vehicle_type: VehicleType::Car,
+        }
+    }
+
+    fn parked_car(at: usize) -> ParkedCar {
+        ParkedCar {
+            vehicle: Vehicle {
+                id: car(),
+                owner: Some(PersonID(0)),
+                vehicle_type: VehicleType::Car,
+                length: Distance::meters(4.5),
+                max_speed: None,
+            },
+            spot: ParkingSpot::Offstreet(BuildingID(at), 0),
+            parked_since: Time::START_OF_DAY,
+        }
+    }
+
+    /// Start a passenger's trip, without going through StartTripArgs
+    fn start_passenger(trips: &mut TripManager, trip: TripID, ctx: &mut Ctx) {
+        trips.trips[trip.0].started = true;
+        trips.start_carpool_passenger(Time::START_OF_DAY, trip, CarpoolID(0), ctx);
+    }
+
+    fn riding(trips: &TripManager, trip: TripID) -> bool {
+        let person = trips.trips[trip.0].person;
+        trips.carpools[&CarpoolID(0)].riding.contains(&trip)
+            && trips
+                .active_trip_mode
+                .get(&AgentID::CarPassenger(person, car()))
+                == Some(&trip)
+    }
+
+    #[test]
+    fn test_carpool_passenger_stops() {
+        let mut trips = TripManager::new(&SimOptions::new("test"));
+        let driver = add_driver(&mut trips);
+        let from_start = add_trip(&mut trips, 0, 2, CarpoolRole::Passenger);
+        let to_end = add_trip(&mut trips, 1, 3, CarpoolRole::Passenger);
+        let not_a_stop = add_trip(&mut trips, 5, 3, CarpoolRole::Passenger);
+        let backwards = add_trip(&mut trips, 2, 1, CarpoolRole::Passenger);
+
+        assert_eq!(
+            trips.all_carpool_stops(CarpoolID(0)),
+            vec![b(0), b(1), b(2), b(3)]
+        );
+        assert_eq!(trips.carpool_passenger_stops(from_start).unwrap(), (0, 2));
+        assert_eq!(trips.carpool_passenger_stops(to_end).unwrap(), (1, 3));
+        assert!(trips.carpool_passenger_stops(not_a_stop).is_err());
+        assert!(trips.carpool_passenger_stops(backwards).is_err());
+
+        // The driver isn't a passenger
+        assert_eq!(trips.carpool_passenger(driver), None);
+        assert_eq!(trips.carpool_passenger(to_end), Some(CarpoolID(0)));
+    }
+
+    #[test]
+    fn test_carpool_stop() {
+        let mut world = World::new();
+        let mut ctx = world.ctx();
+        let mut trips = TripManager::new(&SimOptions::new("test"));
+        let driver = add_driver(&mut trips);
+        let short = add_trip(&mut trips, 1, 2, CarpoolRole::Passenger);
+        let long = add_trip(&mut trips, 1, 3, CarpoolRole::Passenger);
+        // Picked up at the second stop, so the driver waits there
+        let later = add_trip(&mut trips, 2, 3, CarpoolRole::Passenger);
+        // Also picked up at the first stop, but hasn't left home yet
+        let late = add_trip(&mut trips, 1, 3, CarpoolRole::Passenger);
+
+        start_passenger(&mut trips, short, &mut ctx);
+        assert!(trips.carpools[&CarpoolID(0)].waiting.contains(&short));
+        assert_eq!(
+            trips.people[trips.trips[short.0].person.0].state,
+            PersonState::Trip(short)
+        );
+
+        // The driver reaches the first stop. One passenger is waiting and gets in, but the driver
+        // waits for everybody else being picked up here.
+        trips.carpools.get_mut(&CarpoolID(0)).unwrap().next_stop = 1;
+        trips.carpool_driver_stopped(Time::START_OF_DAY, driver, parked_car(1), &mut ctx);
+        assert!(riding(&trips, short));
+        assert!(trips.carpools[&CarpoolID(0)].parked.is_some());
+
+        // The next passenger shows up while the car is parked and gets in right away
+        start_passenger(&mut trips, long, &mut ctx);
+        assert!(riding(&trips, long));
+        assert!(trips.carpools[&CarpoolID(0)].waiting.is_empty());
+
+        // Pretend the driver moved on without the last passenger, then reached the second stop
+        {
+            let carpool = trips.carpools.get_mut(&CarpoolID(0)).unwrap();
+            carpool.next_stop = 2;
+            carpool.parked = None;
+        }
+        trips.carpool_driver_stopped(
+            Time::START_OF_DAY + Duration::minutes(5),
+            driver,
+            parked_car(2),
+            &mut ctx,
+        );
+        // Only one passenger gets out here
+        let trip = &trips.trips[short.0];
+        assert_eq!(
+            trip.finished_at,
+            Some(Time::START_OF_DAY + Duration::minutes(5))
+        );
+        assert_eq!(
+            trips.people[trip.person.0].state,
+            PersonState::Inside(BuildingID(2))
+        );
+        assert_eq!(trips.people[trip.person.0].in_car, None);
+        assert!(!riding(&trips, short));
+        assert!(riding(&trips, long));
+        // Still waiting for somebody to be picked up here
+        assert!(trips.carpools[&CarpoolID(0)].parked.is_some());
+        assert!(trips.trips[later.0].finished_at.is_none());
+
+        // Too late to get picked up at the first stop
+        start_passenger(&mut trips, late, &mut ctx);
+        assert!(trips.trips[late.0]
+            .info
+            .cancellation_reason
+            .as_ref()
+            .unwrap()
+            .starts_with("missed the ride"));
+        assert_eq!(
+            trips.people[trips.trips[late.0].person.0].state,
+            PersonState::Inside(BuildingID(3))
+        );
+    }
+
+    #[test]
+    fn test_passenger_gets_in_and_out() {
+        let mut world = World::new();
+        let mut ctx = world.ctx();
+        let mut trips = TripManager::new(&SimOptions::new("test"));
+        add_driver(&mut trips);
+        let passenger = add_trip(&mut trips, 1, 2, CarpoolRole::Passenger);
+        let person = trips.trips[passenger.0].person;
+        trips.trips[passenger.0]
+            .legs
+            .push_back(TripLeg::RideCar(CarpoolID(0)));
+        trips.people[person.0].state = PersonState::Trip(passenger);
+        trips.events.clear();
+
+        trips.passenger_gets_in(passenger, car());
+        assert_eq!(trips.people[person.0].in_car, Some(car()));
+        assert_eq!(
+            trips
+                .active_trip_mode
+                .get(&AgentID::CarPassenger(person, car())),
+            Some(&passenger)
+        );
+        assert!(matches!(
+            trips.events[0],
+            Event::PersonLeavesBuilding(p, BuildingID(1)) if p == person
+        ));
+        assert!(matches!(
+            trips.events[1],
+            Event::TripPhaseStarting(t, _, None, TripPhaseType::RidingInCar(c)) if t == passenger && c == car()
+        ));
+        trips.events.clear();
+
+        let unfinished = trips.unfinished_trips;
+        let now = Time::START_OF_DAY + Duration::minutes(10);
+        trips.passenger_gets_out(now, passenger, &mut ctx);
+        assert_eq!(trips.people[person.0].in_car, None);
+        assert!(trips.active_trip_mode.is_empty());
+        assert!(trips.trips[passenger.0].legs.is_empty());
+        assert_eq!(trips.trips[passenger.0].finished_at, Some(now));
+        assert_eq!(trips.unfinished_trips, unfinished - 1);
+        assert_eq!(
+            trips.people[person.0].state,
+            PersonState::Inside(BuildingID(2))
+        );
+        assert!(matches!(
+            trips.events[0],
+            Event::PersonEntersBuilding(p, BuildingID(2)) if p == person
+        ));
+        assert!(matches!(trips.events[1], Event::TripFinished { trip, .. } if trip == passenger));
+    }
+
+    #[test]
+    fn test_carpool_trip_cancelled() {
+        let mut world = World::new();
+        let mut ctx = world.ctx();
+        let mut trips = TripManager::new(&SimOptions::new("test"));
+        let driver = add_driver(&mut trips);
+        let rider = add_trip(&mut trips, 1, 3, CarpoolRole::Passenger);
+        let quitter = add_trip(&mut trips, 2, 3, CarpoolRole::Passenger);
+        let stranded = add_trip(&mut trips, 2, 3, CarpoolRole::Passenger);
+        // Nobody's started this trip yet, so the driver waits for it at the second stop
+        let expected = add_trip(&mut trips, 2, 3, CarpoolRole::Passenger);
+
+        trips.trips[driver.0]
+            .legs
+            .push_back(TripLeg::Drive(car(), DrivingGoal::ParkNear(BuildingID(3))));
+        start_passenger(&mut trips, rider, &mut ctx);
+        trips.carpool_passenger_boards(CarpoolID(0), rider, car());
+        assert!(riding(&trips, rider));
+
+        // Two passengers wait at the second stop before the car gets there
+        trips.carpools.get_mut(&CarpoolID(0)).unwrap().next_stop = 2;
+        start_passenger(&mut trips, quitter, &mut ctx);
+        start_passenger(&mut trips, stranded, &mut ctx);
+        assert_eq!(trips.carpools[&CarpoolID(0)].waiting.len(), 2);
+
+        // One of them gives up just as the car parks. The other gets in, and the driver keeps
+        // waiting for somebody who hasn't left home yet.
+        trips.carpools.get_mut(&CarpoolID(0)).unwrap().parked = Some(parked_car(2));
+        trips.cancel_trip(
+            Time::START_OF_DAY,
+            quitter,
+            "gave up".to_string(),
+            None,
+            &mut ctx,
+        );
+        let carpool = &trips.carpools[&CarpoolID(0)];
+        assert!(carpool.waiting.is_empty());
+        assert!(riding(&trips, stranded));
+        assert!(carpool.parked.is_some());
+        assert_eq!(
+            trips.people[trips.trips[quitter.0].person.0].state,
+            PersonState::Inside(BuildingID(3))
+        );
+        assert!(trips.trips[expected.0].info.cancellation_reason.is_none());
+
+        // When the driver's trip is cancelled, everybody riding or waiting is too
+        trips.cancel_trip(
+            Time::START_OF_DAY,
+            driver,
+            "broke down".to_string(),
+            None,
+            &mut ctx,
+        );
+        let carpool = &trips.carpools[&CarpoolID(0)];
+        assert!(carpool.parked.is_none());
+        assert!(carpool.waiting.is_empty());
+        assert!(carpool.riding.is_empty());
+        assert!(trips.active_trip_mode.is_empty());
+        for t in [rider, stranded] {
+            let trip = &trips.trips[t.0];
+            assert!(trip
+                .info
+                .cancellation_reason
+                .as_ref()
+                .unwrap()
+                .contains("driver"));
+            assert_eq!(trips.people[trip.person.0].in_car, None);
+            assert_eq!(
+                trips.people[trip.person.0].state,
+                PersonState::Inside(BuildingID(3))
+            );
+        }
+        // Somebody who hasn't left yet finds out when they try to start
+        assert!(trips.trips[expected.0].info.cancellation_reason.is_none());
+        start_passenger(&mut trips, expected, &mut ctx);
+        assert!(trips.trips[expected.0]
+            .info
+            .cancellation_reason
+            .as_ref()
+            .unwrap()
+            .starts_with("missed the ride"));
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use abstio::MapName;
+use geom::Time;
+
+use crate::{
+    CarpoolID, CarpoolRole, IndividTrip, OrigPersonID, PersonSpec, Scenario, TripEndpoint,
+    TripMode, TripPurpose,
+};
+
+/// Scenarios are stored in bincode, which can't skip over fields that a file doesn't have. When
+/// the Scenario format changes, keep a copy of the old structs here, so files written with the old
+/// format can still be read and upgraded.
+pub fn from_binary(bytes: &[u8]) -> Result<Scenario> {
+    let err = match read_all::<Scenario>(bytes) {
+        Ok(scenario) => {
+            return Ok(scenario);
+        }
+        Err(err) => err,
+    };
+    // Before trips could use a special vehicle
+    if let Ok(scenario) = read_all::<ScenarioV1>(bytes) {
+        return Ok(scenario.upgrade());
+    }
+    // Before trips could be part of a carpool
+    if let Ok(scenario) = read_all::<ScenarioV0>(bytes) {
+        return Ok(scenario.upgrade().upgrade());
+    }
+    Err(err)
+}
+
+/// bincode happily reads an object from the start of some bytes and ignores the rest, so a file in
+/// one format might be misread as another. Make sure every byte is used up.
+fn read_all<T: DeserializeOwned + Serialize>(bytes: &[u8]) -> Result<T> {
+    let obj: T = abstutil::from_binary(bytes)?;
+    let size = abstutil::serialized_size_bytes(&obj);
+    if size != bytes.len() {
+        bail!("Only read {} of {} bytes", size, bytes.len());
+    }
+    Ok(obj)
+}
+
+#[derive(Serialize, Deserialize)]
+struct ScenarioV0 {
+    scenario_name: String,
+    map_name: MapName,
+    people: Vec<PersonSpecV0>,
+    only_seed_buses: Option<BTreeSet<String>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PersonSpecV0 {
+    orig_id: Option<OrigPersonID>,
+    trips: Vec<IndividTripV0>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct IndividTripV0 {
+    depart: Time,
+    origin: TripEndpoint,
+    destination: TripEndpoint,
+    mode: TripMode,
+    purpose: TripPurpose,
+    cancelled: bool,
+    modified: bool,
+}
+
+impl ScenarioV0 {
+    fn upgrade(self) -> ScenarioV1 {
+        ScenarioV1 {
+            scenario_name: self.scenario_name,
+            map_name: self.map_name,
+            people: self
+                .people
+                .into_iter()
+                .map(|person| PersonSpecV1 {
+                    orig_id: person.orig_id,
+                    trips: person
+                        .trips
+                        .into_iter()
+                        .map(|trip| IndividTripV1 {
+                            depart: trip.depart,
+                            origin: trip.origin,
+                            destination: trip.destination,
+                            mode: trip.mode,
+                            purpose: trip.purpose,
+                            cancelled: trip.cancelled,
+                            modified: trip.modified,
+                            carpool: None,
+                        })
+                        .collect(),
+                })
+                .collect(),
+            only_seed_buses: self.only_seed_buses,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ScenarioV1 {
+    scenario_name: String,
+    map_name: MapName,
+    people: Vec<PersonSpecV1>,
+    only_seed_buses: Option<BTreeSet<String>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PersonSpecV1 {
+    orig_id: Option<OrigPersonID>,
+    trips: Vec<IndividTripV1>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct IndividTripV1 {
+    depart: Time,
+    origin: TripEndpoint,
+    destination: TripEndpoint,
+    mode: TripMode,
+    purpose: TripPurpose,
+    cancelled: bool,
+    modified: bool,
+    carpool: Option<(CarpoolID, CarpoolRole)>,
+}
+
+impl ScenarioV1 {
+    fn upgrade(self) -> Scenario {
+        Scenario {
+            scenario_name: self.scenario_name,
+            map_name: self.map_name,
+            people: self
+                .people
+                .into_iter()
+                .map(|person| PersonSpec {
+                    orig_id: person.orig_id,
+                    trips: person
+                        .trips
+                        .into_iter()
+                        .map(|trip| {
+                            let mut new = IndividTrip::new(
+                                trip.depart,
+                                trip.purpose,
+                                trip.origin,
+                                trip.destination,
+                                trip.mode,
+                            );
+                            new.cancelled = trip.cancelled;
+                            new.modified = trip.modified;
+                            new.carpool = trip.carpool;
+                            new
+                        })
+                        .collect(),
+                })
+                .collect(),
+            only_seed_buses: self.only_seed_buses,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use map_model::BuildingID;
+
+    use super::*;
+    use crate::VehicleKind;
+
+    fn old_scenario() -> ScenarioV0 {
+        ScenarioV0 {
+            scenario_name: "weekday".to_string(),
+            map_name: MapName::seattle("montlake"),
+            people: vec![PersonSpecV0 {
+                orig_id: None,
+                trips: vec![IndividTripV0 {
+                    depart: Time::START_OF_DAY,
+                    origin: TripEndpoint::Building(BuildingID(1)),
+                    destination: TripEndpoint::Building(BuildingID(2)),
+                    mode: TripMode::Drive,
+                    purpose: TripPurpose::Work,
+                    cancelled: false,
+                    modified: true,
+                }],
+            }],
+            only_seed_buses: None,
+        }
+    }
+
+    #[test]
+    fn test_upgrade_old_format() {
+        let bytes = abstutil::to_binary(&old_scenario());
+        let scenario = from_binary(&bytes).unwrap();
+        assert_eq!(scenario.scenario_name, "weekday");
+        assert_eq!(scenario.people.len(), 1);
+        let trip = &scenario.people[0].trips[0];
+        assert_eq!(trip.mode, TripMode::Drive);
+        assert!(trip.modified);
+        assert!(trip.carpool.is_none());
+        assert!(trip.vehicle.is_none());
+    }
+
+    #[test]
+    fn test_upgrade_carpool_format() {
+        let mut old = old_scenario().upgrade();
+        old.people[0].trips[0].carpool = Some((CarpoolID(3), CarpoolRole::Passenger));
+        let bytes = abstutil::to_binary(&old);
+        let scenario = from_binary(&bytes).unwrap();
+        let trip = &scenario.people[0].trips[0];
+        assert_eq!(trip.carpool, Some((CarpoolID(3), CarpoolRole::Passenger)));
+        assert!(trip.vehicle.is_none());
+    }
+
+    #[test]
+    fn test_current_format() {
+        let mut scenario = old_scenario().upgrade().upgrade();
+        scenario.people[0].trips[0].carpool = Some((CarpoolID(3), CarpoolRole::Passenger));
+        scenario.people[0].trips[0].vehicle = Some(VehicleKind::Van);
+        let bytes = abstutil::to_binary(&scenario);
+        let scenario = from_binary(&bytes).unwrap();
+        let trip = &scenario.people[0].trips[0];
+        assert_eq!(trip.carpool, Some((CarpoolID(3), CarpoolRole::Passenger)));
+        assert_eq!(trip.vehicle, Some(VehicleKind::Van));
+
+        // Garbage is still an error
+        assert!(from_binary(&bytes[0..bytes.len() - 1]).is_err());
+    }
+}
@@ -38,6 +38,7 @@ impl TripEndpoint {
         Some(match mode {
             TripMode::Walk | TripMode::Transit => PathRequest::walking(start, end),
             TripMode::Bike => PathRequest::vehicle(start, end, PathConstraints::Bike),
+            TripMode::RideHail => PathRequest::vehicle(start, end, PathConstraints::Car),
             // Only cars leaving from a building might turn out from the driveway in a special way
             TripMode::Drive => {
                 if matches!(from, TripEndpoint::Building(_)) {
@@ -52,7 +53,7 @@ impl TripEndpoint {
     fn pos(self, mode: TripMode, from: bool, map: &Map) -> Option<Position> {
         match mode {
             TripMode::Walk | TripMode::Transit => self.sidewalk_pos(map, from),
-            TripMode::Drive | TripMode::Bike => {
+            TripMode::Drive | TripMode::Bike | TripMode::RideHail => {
                 let constraints = mode.to_constraints();
                 if from {
                     match self {
@@ -73,7 +74,7 @@ impl TripEndpoint {
 
                 match self {
                     TripEndpoint::Building(b) => match constraints {
-                        PathConstraints::Car => {
+                        PathConstraints::Car | PathConstraints::Truck => {
                             let driving_lane = map.find_driving_lane_near_building(b);
                             let sidewalk_pos = map.get_b(b).sidewalk_pos;
                             if driving_lane.road == sidewalk_pos.lane().road {
@@ -7,7 +7,9 @@ use serde::Deserialize;
 use geom::{Distance, FindClosest, LonLat, Time};
 use map_model::Map;
 
-use crate::{IndividTrip, MapBorders, PersonSpec, TripEndpoint, TripMode, TripPurpose};
+use crate::{
+    IndividTrip, MapBorders, PersonSpec, TripEndpoint, TripMode, TripPurpose, VehicleKind,
+};
 
 #[derive(Deserialize)]
 pub struct ExternalPerson {
@@ -21,6 +23,9 @@ pub struct ExternalTrip {
     pub destination: ExternalTripEndpoint,
     pub mode: TripMode,
     pub purpose: TripPurpose,
+    /// Use a special kind of vehicle, instead of an ordinary car or bike. It must match the mode.
+    #[serde(default)]
+    pub vehicle: Option<VehicleKind>,
 }
 
 #[derive(Deserialize)]
@@ -89,7 +94,7 @@ impl ExternalPerson {
                     }
                 }
 
-                spec.trips.push(IndividTrip::new(
+                let mut individ_trip = IndividTrip::new(
                     trip.departure,
                     trip.purpose,
                     match lookup_pt(trip.origin, true, trip.mode) {
@@ -115,7 +120,9 @@ impl ExternalPerson {
                         }
                     },
                     trip.mode,
-                ));
+                );
+                individ_trip.vehicle = trip.vehicle;
+                spec.trips.push(individ_trip);
             }
             results.push(spec);
         }
@@ -20,9 +20,10 @@ pub use self::counts::TrafficCounts;
 pub use self::endpoint::TripEndpoint;
 pub use self::external::{ExternalPerson, ExternalTrip, ExternalTripEndpoint};
 pub use self::modifier::ScenarioModifier;
-pub use self::scenario::{IndividTrip, PersonSpec, Scenario, TripPurpose};
+pub use self::scenario::{CarpoolID, CarpoolRole, IndividTrip, PersonSpec, Scenario, TripPurpose};
 
 mod borders;
+mod compat;
 mod counts;
 mod endpoint;
 mod external;
@@ -39,6 +40,8 @@ pub enum TripMode {
     Bike,
     Transit,
     Drive,
+    /// Summoning an on-demand vehicle from a ride-hail or microtransit fleet
+    RideHail,
 }
 
 impl TripMode {
@@ -48,6 +51,7 @@ impl TripMode {
             TripMode::Bike,
             TripMode::Transit,
             TripMode::Drive,
+            TripMode::RideHail,
         ]
     }
 
@@ -57,6 +61,7 @@ impl TripMode {
             TripMode::Bike => "bike",
             TripMode::Transit => "use transit",
             TripMode::Drive => "drive",
+            TripMode::RideHail => "hail a ride",
         }
     }
 
@@ -67,6 +72,7 @@ impl TripMode {
             TripMode::Bike => "biking",
             TripMode::Transit => "using transit",
             TripMode::Drive => "driving",
+            TripMode::RideHail => "riding in a ride-hail vehicle",
         }
     }
 
@@ -76,6 +82,7 @@ impl TripMode {
             TripMode::Bike => "Bike",
             TripMode::Transit => "Bus",
             TripMode::Drive => "Car",
+            TripMode::RideHail => "Ride-hail",
         }
     }
 
@@ -85,7 +92,7 @@ impl TripMode {
             TripMode::Bike => PathConstraints::Bike,
             // TODO WRONG
             TripMode::Transit => PathConstraints::Bus,
-            TripMode::Drive => PathConstraints::Car,
+            TripMode::Drive | TripMode::RideHail => PathConstraints::Car,
         }
     }
 
@@ -95,7 +102,56 @@ impl TripMode {
             PathConstraints::Bike => TripMode::Bike,
             // TODO The bijection breaks down... transit rider vs train vs bus...
             PathConstraints::Bus | PathConstraints::Train => TripMode::Transit,
-            PathConstraints::Car => TripMode::Drive,
+            PathConstraints::Car | PathConstraints::Truck => TripMode::Drive,
+        }
+    }
+}
+
+/// Which kind of vehicle does a trip use? Trips that don't specify one drive an ordinary car or
+/// ride an ordinary bike.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, PartialOrd, Ord)]
+pub enum VehicleKind {
+    /// A heavy goods vehicle. It can't use roads with weight or height limits, or make tight turns.
+    Truck,
+    /// A delivery van, which double-parks to unload at its destination before parking
+    Van,
+    Motorcycle,
+    /// An e-scooter or e-bike, which uses bike lanes
+    Scooter,
+}
+
+impl VehicleKind {
+    pub fn all() -> Vec<VehicleKind> {
+        vec![
+            VehicleKind::Truck,
+            VehicleKind::Van,
+            VehicleKind::Motorcycle,
+            VehicleKind::Scooter,
+        ]
+    }
+
+    /// The only mode a trip using this vehicle can have
+    pub fn mode(self) -> TripMode {
+        match self {
+            VehicleKind::Truck | VehicleKind::Van | VehicleKind::Motorcycle => TripMode::Drive,
+            VehicleKind::Scooter => TripMode::Bike,
+        }
+    }
+
+    pub fn to_constraints(self) -> PathConstraints {
+        match self {
+            VehicleKind::Truck => PathConstraints::Truck,
+            VehicleKind::Van | VehicleKind::Motorcycle => PathConstraints::Car,
+            VehicleKind::Scooter => PathConstraints::Bike,
+        }
+    }
+
+    pub fn noun(self) -> &'static str {
+        match self {
+            VehicleKind::Truck => "Truck",
+            VehicleKind::Van => "Van",
+            VehicleKind::Motorcycle => "Motorcycle",
+            VehicleKind::Scooter => "Scooter",
         }
     }
 }
@@ -71,6 +71,10 @@ impl ScenarioModifier {
                         if let Some(to_mode) = *to_mode {
                             trip.mode = to_mode;
                             trip.modified = true;
+                            // The special vehicle can't be used for the new mode
+                            if trip.vehicle.map(|v| v.mode()) != Some(to_mode) {
+                                trip.vehicle = None;
+                            }
                         } else {
                             trip.modified = true;
                             trip.cancelled = true;
@@ -84,7 +88,7 @@ impl ScenarioModifier {
             }
             // TODO This doesn't work on web!
             ScenarioModifier::AddExtraTrips(name) => {
-                let other: Scenario = abstio::must_read_object(
+                let other = Scenario::must_load(
                     abstio::path_scenario(map.get_name(), name),
                     &mut Timer::throwaway(),
                 );
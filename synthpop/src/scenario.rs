@@ -5,11 +5,11 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
 use abstio::{CityName, MapName};
-use abstutil::prettyprint_usize;
+use abstutil::{prettyprint_usize, Timer};
 use geom::Time;
-use map_model::Map;
+use map_model::{BuildingID, Map};
 
-use crate::{OrigPersonID, TripEndpoint, TripMode};
+use crate::{OrigPersonID, TripEndpoint, TripMode, VehicleKind};
 
 /// A Scenario describes all the input to a simulation. Usually a scenario covers one day.
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -42,6 +42,30 @@ pub struct IndividTrip {
     pub cancelled: bool,
     /// Did a ScenarioModifier affect this?
     pub modified: bool,
+    /// Is this trip a shared ride? Only driving trips can be part of a carpool.
+    #[serde(default)]
+    pub carpool: Option<(CarpoolID, CarpoolRole)>,
+    /// A special kind of vehicle used for this trip, instead of an ordinary car or bike. Its mode
+    /// must match the trip's.
+    #[serde(default)]
+    pub vehicle: Option<VehicleKind>,
+}
+
+/// Several people sharing one car. Every trip in the same carpool has the same ID; exactly one of
+/// them is the driver.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Debug)]
+pub struct CarpoolID(pub usize);
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub enum CarpoolRole {
+    /// Drives from the trip's origin to its destination, stopping at each of these buildings in
+    /// order to pick up or drop off passengers. The driver waits at each stop until everybody
+    /// being picked up there is ready.
+    Driver { stops: Vec<BuildingID> },
+    /// Rides from the trip's origin to its destination. The origin must be one of the driver's
+    /// stops or the building they start from. The destination must be a later stop or the
+    /// driver's destination.
+    Passenger,
 }
 
 impl IndividTrip {
@@ -60,6 +84,8 @@ impl IndividTrip {
             purpose,
             cancelled: false,
             modified: false,
+            carpool: None,
+            vehicle: None,
         }
     }
 }
@@ -78,6 +104,8 @@ pub enum TripPurpose {
     Recreation,
     Medical,
     ParkAndRideTransfer,
+    /// Carrying goods to a business, not a personal trip
+    Delivery,
 }
 
 impl fmt::Display for TripPurpose {
@@ -98,12 +126,38 @@ impl fmt::Display for TripPurpose {
                 TripPurpose::Recreation => "recreation",
                 TripPurpose::Medical => "medical",
                 TripPurpose::ParkAndRideTransfer => "park-and-ride transfer",
+                TripPurpose::Delivery => "delivery",
             }
         )
     }
 }
 
 impl Scenario {
+    /// Loads a scenario from a JSON or binary file. Files written in an older format are upgraded.
+    pub fn load(path: String, timer: &mut Timer) -> Result<Scenario> {
+        if path.ends_with(".bin") {
+            timer.start(format!("read {}", path));
+            let result = abstio::slurp_file(&path).and_then(|bytes| Scenario::from_binary(&bytes));
+            timer.stop(format!("read {}", path));
+            result
+        } else {
+            abstio::maybe_read_json(path, timer)
+        }
+    }
+
+    /// Like `load`, but panics on failure
+    pub fn must_load(path: String, timer: &mut Timer) -> Scenario {
+        match Scenario::load(path.clone(), timer) {
+            Ok(scenario) => scenario,
+            Err(err) => panic!("Couldn't load scenario {}: {}", path, err),
+        }
+    }
+
+    /// Deserializes a bincoded scenario. Scenarios written in an older format are upgraded.
+    pub fn from_binary(bytes: &[u8]) -> Result<Scenario> {
+        crate::compat::from_binary(bytes)
+    }
+
     pub fn save(&self) {
         abstio::write_binary(
             abstio::path_scenario(&self.map_name, &self.scenario_name),
@@ -202,6 +256,16 @@ impl PersonSpec {
                     trip.origin
                 );
             }
+            if let Some(vehicle) = trip.vehicle {
+                if vehicle.mode() != trip.mode {
+                    bail!(
+                        "Person ({:?}) has a {:?} trip using a {:?}",
+                        self.orig_id,
+                        trip.mode,
+                        vehicle
+                    );
+                }
+            }
         }
 
         Ok(())
@@ -2,14 +2,17 @@
 
 use std::io::Write;
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 use fs_err::File;
 use rand::seq::SliceRandom;
 
 use abstio::{CityName, MapName};
 use abstutil::Timer;
 use geom::{Duration, Time};
-use map_model::{IntersectionID, LaneType, Map, Perimeter, RoadID};
+use map_model::{
+    DiagonalFilter, Direction, EditCmd, FilterType, IntersectionID, LaneID, LaneType, Map, Path,
+    PathConstraints, PathRequest, PathStep, Perimeter, Position, RoadFilter, RoadID,
+};
 use sim::{AlertHandler, PrebakeSummary, Sim, SimFlags, SimOptions};
 use synthpop::{IndividTrip, PersonSpec, Scenario, TripEndpoint, TripMode, TripPurpose};
 
@@ -19,11 +22,14 @@ fn main() -> Result<()> {
     test_lane_changing(&import_map(abstio::path(
         "../tests/input/lane_selection.osm",
     )))?;
+    test_truck_restrictions(&import_map(abstio::path("../tests/input/truck_ban.osm")))?;
+    test_modal_filters(import_map(abstio::path("../tests/input/modal_filter.osm")))?;
     test_map_importer()?;
     check_proposals()?;
     ab_test_spurious_diff()?;
     bus_test()?;
     bus_route_test()?;
+    gtfs_export_test()?;
     smoke_test()?;
     Ok(())
 }
@@ -81,7 +87,7 @@ fn smoke_test() -> Result<()> {
     for name in MapName::list_all_maps_locally() {
         let map = map_model::Map::load_synchronously(name.path(), &mut timer);
         let scenario = if map.get_city_name() == &CityName::seattle() {
-            abstio::read_binary(abstio::path_scenario(&name, "weekday"), &mut timer)
+            Scenario::must_load(abstio::path_scenario(&name, "weekday"), &mut timer)
         } else {
             let mut rng = sim::SimFlags::for_test("smoke_test").make_rng();
             sim::ScenarioGenerator::proletariat_robot(&map, &mut rng, &mut timer)
@@ -198,6 +204,214 @@ fn test_lane_changing(map: &Map) -> Result<()> {
     Ok(())
 }
 
+/// Verify trucks never route along roads that ban them, even when the ban makes them take a detour
+/// that cars don't.
+fn test_truck_restrictions(map: &Map) -> Result<()> {
+    let uses_banned_road = |path: &Path| {
+        path.get_steps().iter().any(|step| match step {
+            PathStep::Lane(l) | PathStep::ContraflowLane(l) => !map.get_r(l.road).allows_trucks(),
+            _ => false,
+        })
+    };
+
+    let lanes: Vec<LaneID> = map
+        .all_lanes()
+        .filter(|l| l.is_driving())
+        .map(|l| l.id)
+        .collect();
+    let mut truck_detours = 0;
+    for from in &lanes {
+        for to in &lanes {
+            if from == to {
+                continue;
+            }
+            let start = Position::new(*from, map.get_l(*from).length() / 2.0);
+            let end = Position::new(*to, map.get_l(*to).length() / 2.0);
+            let car_shortcut = map
+                .pathfind(PathRequest::vehicle(start, end, PathConstraints::Car))
+                .map(|path| uses_banned_road(&path))
+                .unwrap_or(false);
+            if let Ok(path) = map.pathfind(PathRequest::vehicle(start, end, PathConstraints::Truck))
+            {
+                if uses_banned_road(&path) {
+                    bail!(
+                        "A truck going from {} to {} uses a road that bans trucks",
+                        from,
+                        to
+                    );
+                }
+                if car_shortcut {
+                    truck_detours += 1;
+                }
+            }
+        }
+    }
+    // Otherwise the test isn't checking anything
+    if truck_detours == 0 {
+        bail!("No truck had to detour around the road that bans them; the test map is broken");
+    }
+    Ok(())
+}
+
+/// Check routing around a modal filter halfway along a road, including trips that start or end
+/// partway along it, and then around a diagonal filter.
+fn test_modal_filters(mut map: Map) -> Result<()> {
+    let mut timer = Timer::new("test modal filters");
+    let far_west = road_named(&map, "Far West Street")?;
+    let filtered = road_named(&map, "Filtered Street")?;
+    let east = road_named(&map, "East Street")?;
+    let north = road_named(&map, "North Street")?;
+    let south = road_named(&map, "South Street")?;
+    let crossroads = map.get_r(filtered).dst_i;
+
+    let mut edits = map.get_edits().clone();
+    edits.commands.push(map.edit_road_cmd(filtered, |new| {
+        new.modal_filter = Some(RoadFilter {
+            dist: map.get_r(filtered).length() / 2.0,
+            filter_type: FilterType::WalkCycleOnly,
+        });
+    }));
+    map.must_apply_edits(edits, &mut timer);
+    map.recalculate_pathfinding_after_edits(&mut timer);
+
+    // Everything here heads east along the filtered road, towards the crossroads
+    let filtered_lane = driving_lane(&map, filtered, Direction::Fwd)?;
+    let before_filter = pos_along(&map, filtered_lane, 0.4);
+    let past_filter = pos_along(&map, filtered_lane, 0.6);
+    let far_west_start = pos_along(&map, driving_lane(&map, far_west, Direction::Fwd)?, 0.5);
+    let east_end = pos_along(&map, driving_lane(&map, east, Direction::Fwd)?, 0.5);
+
+    let car = PathConstraints::Car;
+    // Starting just past the filter, a car can leave the road at the crossroads
+    let path = map.pathfind(PathRequest::vehicle(past_filter, east_end, car))?;
+    if path.get_steps()[0] != PathStep::Lane(filtered_lane) {
+        bail!("A car starting past the filter doesn't begin on the filtered road");
+    }
+    expect_path(&map, before_filter, east_end, car, false)?;
+    // Ending just before the filter is fine, but just past it can't be reached from behind
+    expect_path(&map, far_west_start, before_filter, car, true)?;
+    expect_path(&map, far_west_start, past_filter, car, false)?;
+    // Trips staying on the filtered road
+    expect_path(
+        &map,
+        past_filter,
+        pos_along(&map, filtered_lane, 0.9),
+        car,
+        true,
+    )?;
+    expect_path(
+        &map,
+        pos_along(&map, filtered_lane, 0.1),
+        before_filter,
+        car,
+        true,
+    )?;
+    expect_path(
+        &map,
+        pos_along(&map, filtered_lane, 0.1),
+        past_filter,
+        car,
+        false,
+    )?;
+    // Cyclists get through
+    expect_path(&map, before_filter, east_end, PathConstraints::Bike, true)?;
+    expect_path(
+        &map,
+        far_west_start,
+        past_filter,
+        PathConstraints::Bike,
+        true,
+    )?;
+    // Reaching a turn means driving the whole lane, so the filter blocks them all
+    if !map.get_turns_for(filtered_lane, car).is_empty() {
+        bail!("Cars can turn from the end of a filtered road");
+    }
+    if map
+        .get_turns_for(filtered_lane, PathConstraints::Bike)
+        .is_empty()
+    {
+        bail!("Cyclists can't turn from the end of a filtered road");
+    }
+
+    // Replace the road filter with a diagonal filter that only lets cars turn between the filtered
+    // road and the north one
+    let mut edits = map.new_edits();
+    edits.commands.push(EditCmd::ChangeDiagonalFilter {
+        i: crossroads,
+        old: None,
+        new: Some(DiagonalFilter::new(
+            &map,
+            crossroads,
+            filtered,
+            north,
+            FilterType::WalkCycleOnly,
+        )?),
+    });
+    map.must_apply_edits(edits, &mut timer);
+    map.recalculate_pathfinding_after_edits(&mut timer);
+
+    let north_end = pos_along(&map, driving_lane(&map, north, Direction::Fwd)?, 0.5);
+    let south_end = pos_along(&map, driving_lane(&map, south, Direction::Fwd)?, 0.5);
+    expect_path(&map, before_filter, north_end, car, true)?;
+    expect_path(&map, before_filter, east_end, car, false)?;
+    expect_path(&map, before_filter, south_end, car, false)?;
+    expect_path(&map, before_filter, east_end, PathConstraints::Bike, true)?;
+    let turns = map.get_turns_for(filtered_lane, car);
+    if turns.is_empty() || turns.iter().any(|t| t.id.dst.road != north) {
+        bail!("The diagonal filter should only allow cars to turn north");
+    }
+    // Heading west into the crossroads from the other group of roads
+    let from_east = pos_along(&map, driving_lane(&map, east, Direction::Back)?, 0.5);
+    let filtered_west = pos_along(&map, driving_lane(&map, filtered, Direction::Back)?, 0.5);
+    expect_path(&map, from_east, filtered_west, car, false)?;
+    expect_path(&map, from_east, south_end, car, true)?;
+
+    Ok(())
+}
+
+fn road_named(map: &Map, name: &str) -> Result<RoadID> {
+    map.all_roads()
+        .find(|r| r.get_name(None) == name)
+        .map(|r| r.id)
+        .ok_or_else(|| anyhow!("No road named {}", name))
+}
+
+fn driving_lane(map: &Map, r: RoadID, dir: Direction) -> Result<LaneID> {
+    map.get_r(r)
+        .lanes
+        .iter()
+        .find(|l| l.is_driving() && l.dir == dir)
+        .map(|l| l.id)
+        .ok_or_else(|| anyhow!("{} has no {:?} driving lane", r, dir))
+}
+
+fn pos_along(map: &Map, l: LaneID, pct: f64) -> Position {
+    Position::new(l, pct * map.get_l(l).length())
+}
+
+fn expect_path(
+    map: &Map,
+    start: Position,
+    end: Position,
+    constraints: PathConstraints,
+    exists: bool,
+) -> Result<()> {
+    if map
+        .pathfind(PathRequest::vehicle(start, end, constraints))
+        .is_ok()
+        != exists
+    {
+        bail!(
+            "Expected a {:?} path from {} to {} to {}",
+            constraints,
+            start,
+            end,
+            if exists { "exist" } else { "not exist" }
+        );
+    }
+    Ok(())
+}
+
 /// Generate single blocks and merged LTN-style blocks for some maps, counting the number of
 /// failures. Store in a goldenfile, so somebody can manually do a visual diff if anything changes.
 fn test_blockfinding() -> Result<()> {
@@ -263,8 +477,8 @@ fn ab_test_spurious_diff() -> Result<()> {
     let mut timer = Timer::new("A/B test spurious diff");
     let mut map =
         map_model::Map::load_synchronously(MapName::seattle("montlake").path(), &mut timer);
-    let scenario: Scenario =
-        abstio::read_binary(abstio::path_scenario(map.get_name(), "weekday"), &mut timer);
+    let scenario =
+        Scenario::must_load(abstio::path_scenario(map.get_name(), "weekday"), &mut timer);
 
     let no_map_edits = run_sim(&map, &scenario, &mut timer);
 
@@ -355,6 +569,101 @@ fn bus_route_test() -> Result<()> {
     Ok(())
 }
 
+/// Edit the schedule of a route with an imported timetable, export the map as GTFS, and check the
+/// edited schedule is what gets read back.
+fn gtfs_export_test() -> Result<()> {
+    let mut timer = Timer::new("GTFS export test");
+    let mut map =
+        map_model::Map::load_synchronously(MapName::seattle("arboretum").path(), &mut timer);
+    let route = map
+        .all_transit_routes()
+        .iter()
+        .find(|tr| tr.timetable.len() >= 2 && tr.all_paths(&map).is_ok())
+        .ok_or_else(|| anyhow!("no route with a timetable"))?;
+    let id = route.id;
+    let gtfs_id = route.gtfs_id.clone();
+    // Keep one trip from the timetable, drop the rest, and add a trip after midnight that has to
+    // assume free-flow driving
+    let kept = route.timetable[1].clone();
+    let late_night = Time::START_OF_DAY + Duration::hours(24) + Duration::minutes(15);
+    let new = vec![kept.spawn_time, late_night];
+
+    let mut edits = map.get_edits().clone();
+    edits.commands.push(EditCmd::ChangeRouteSchedule {
+        id,
+        old: route.spawn_times.clone(),
+        new: new.clone(),
+    });
+    map.must_apply_edits(edits, &mut timer);
+    let num_stops = map.get_tr(id).stops.len();
+
+    let files = map_model::gtfs::export(&map, "America/Los_Angeles")?;
+    let file = |name: &str| -> Result<csv::Reader<&[u8]>> {
+        let (_, contents) = files
+            .iter()
+            .find(|(filename, _)| *filename == name)
+            .ok_or_else(|| anyhow!("no {}", name))?;
+        Ok(csv::Reader::from_reader(contents.as_bytes()))
+    };
+
+    let mut trip_ids = Vec::new();
+    for rec in file("trips.txt")?.records() {
+        let rec = rec?;
+        if &rec[0] == gtfs_id {
+            trip_ids.push(rec[2].to_string());
+        }
+    }
+    if trip_ids.len() != new.len() {
+        bail!(
+            "Route {} exported {} trips, but the edit has {}",
+            gtfs_id,
+            trip_ids.len(),
+            new.len()
+        );
+    }
+
+    let mut stop_times: Vec<Vec<Time>> = vec![Vec::new(); trip_ids.len()];
+    for rec in file("stop_times.txt")?.records() {
+        let rec = rec?;
+        if let Some(idx) = trip_ids.iter().position(|id| id == &rec[0]) {
+            if rec[1] != rec[2] {
+                bail!("Trip {} arrives and departs at different times", &rec[0]);
+            }
+            stop_times[idx].push(Time::parse(&rec[2])?);
+        }
+    }
+
+    // The trip kept from the timetable has its original times, to the second
+    let rounded = |times: &[Time]| -> Vec<usize> {
+        times
+            .iter()
+            .map(|t| t.inner_seconds().round() as usize)
+            .collect()
+    };
+    if rounded(&stop_times[0]) != rounded(&kept.stop_times) {
+        bail!(
+            "Trip {} was exported as {:?}, but the timetable has {:?}",
+            trip_ids[0],
+            stop_times[0],
+            kept.stop_times
+        );
+    }
+    // The new trip serves every stop in order, starting no earlier than it spawns
+    let late = &stop_times[1];
+    if late.len() != num_stops
+        || late[0] < late_night
+        || late.windows(2).any(|pair| pair[0] > pair[1])
+    {
+        bail!(
+            "Trip {} spawning at {} was exported as {:?}",
+            trip_ids[1],
+            late_night,
+            late
+        );
+    }
+    Ok(())
+}
+
 /// On set maps with bus routes imported, simulate an hour to flush out crashes.
 fn bus_test() -> Result<()> {
     let mut timer = Timer::new("bus smoke test");
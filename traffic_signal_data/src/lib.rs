@@ -60,6 +60,11 @@ pub enum StageType {
     /// is 20, and additional is 40, the maximum cycle duration is 60.
     /// If there are crosswalks, the minimum is the minimum for the maximum crosswalks
     Variable(usize, usize, usize),
+    /// Minimum green, maximum green, passage time
+    /// The stage is only served when some movement in it has a call from a detector or a
+    /// pedestrian push button. It lasts at least the minimum green, then each new vehicle arriving
+    /// on a protected movement extends it by the passage time, up to the maximum green.
+    Actuated(usize, usize, usize),
 }
 
 /// A movement through an intersection.